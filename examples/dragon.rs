@@ -3,12 +3,13 @@ use voxgen::l_system::{LSystem, RenderOptions};
 
 fn main() {
     // Define an L System
-    let l_system = LSystem::new("dragon", "L", vec!["L→L+R+", "R→-L-R"]);
+    let l_system = LSystem::try_new("dragon", "L", vec!["L→L+R+", "R→-L-R"]).unwrap();
     // Render the L System as a MagicaVoxel .vox file.
     RenderOptions::new()
         .derivation_length(8)
         .offset_x(10.0)
         .offset_y(-15.0)
         .rainbow(true)
-        .render(l_system);
+        .save(&l_system, "test/volumes/dragon_8.vox")
+        .unwrap();
 }
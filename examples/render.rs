@@ -0,0 +1,75 @@
+// Render an L System definition file to a .vox file, optionally re-rendering
+// it whenever it changes.
+//
+//     cargo run --example render -- test/systems/koch.toml koch.vox
+//     cargo run --example render -- test/systems/koch.toml koch.vox --watch
+//
+// With the viewer feature, --view shows the render in a window instead, and
+// with --watch refreshes it on every change.
+//
+//     cargo run --example render --features viewer -- test/systems/koch.toml --view --watch
+use std::ops::ControlFlow;
+use voxgen::l_system::watch::Watch;
+
+fn main() {
+    let mut watch = false;
+    let mut view = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--watch" => watch = true,
+            "--view" => view = true,
+            _ => paths.push(arg),
+        }
+    }
+    let (input, output) = match paths.as_slice() {
+        [input] if view => (input, None),
+        [input, output] => (input, Some(output)),
+        _ => {
+            eprintln!("usage: render <definition> [<output.vox>] [--watch] [--view]");
+            std::process::exit(2);
+        }
+    };
+    let mut runner = Watch::new(input);
+    if let Some(output) = output {
+        runner.output(output);
+    }
+    if view {
+        view_renders(runner, watch);
+        return;
+    }
+    runner.run(|result| {
+        match result {
+            Ok(_) => println!("rendered {}", input),
+            Err(e) => eprintln!("{}: {}", input, e),
+        }
+        if watch {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    });
+}
+
+#[cfg(feature = "viewer")]
+fn view_renders(mut runner: Watch, watch: bool) {
+    let result = if watch {
+        voxgen::viewer::watch(runner)
+    } else {
+        match runner.poll() {
+            Some(Ok(buf)) => voxgen::viewer::show(&buf),
+            Some(Err(e)) => Err(std::io::Error::other(e)),
+            None => Ok(()),
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "viewer"))]
+fn view_renders(_runner: Watch, _watch: bool) {
+    eprintln!("--view needs the viewer feature");
+    std::process::exit(2);
+}
@@ -0,0 +1,335 @@
+#ifndef VOXGEN_H
+#define VOXGEN_H
+
+/* Generated by cbindgen from src/lib.rs on every build, don't edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The result of a fallible call.
+ */
+typedef enum VoxgenStatus {
+  /**
+   * The call succeeded.
+   */
+  VOXGEN_STATUS_OK = 0,
+  /**
+   * A pointer was null or a string wasn't valid UTF-8.
+   */
+  VOXGEN_STATUS_INVALID_ARGUMENT,
+  /**
+   * Reading or writing a file failed.
+   */
+  VOXGEN_STATUS_IO,
+  /**
+   * An L System rule couldn't be parsed.
+   */
+  VOXGEN_STATUS_PARSE,
+  /**
+   * An L System render went over a limit.
+   */
+  VOXGEN_STATUS_LIMIT,
+  /**
+   * A voxel position was outside the buffer.
+   */
+  VOXGEN_STATUS_OUT_OF_BOUNDS,
+  /**
+   * A buffer was too large to create.
+   */
+  VOXGEN_STATUS_CAPACITY,
+  /**
+   * The library panicked.
+   */
+  VOXGEN_STATUS_PANIC,
+  /**
+   * Any other error.
+   */
+  VOXGEN_STATUS_OTHER,
+} VoxgenStatus;
+
+/**
+ * A buffer of RGBA voxels.
+ */
+typedef struct VoxgenBuffer VoxgenBuffer;
+
+/**
+ * The faces between the visible and empty voxels of a buffer, as an
+ * indexed triangle mesh.
+ */
+typedef struct VoxgenMesh VoxgenMesh;
+
+/**
+ * An RGBA voxel color, where an alpha of 0 is empty.
+ */
+typedef struct VoxgenColor {
+  uint8_t r;
+  uint8_t g;
+  uint8_t b;
+  uint8_t a;
+} VoxgenColor;
+
+/**
+ * The settings of an L System render, see `voxgen_render_options_default`.
+ */
+typedef struct VoxgenRenderOptions {
+  /**
+   * The number of derivation steps.
+   */
+  uint32_t derivation_length;
+  /**
+   * The number of voxels moved by `F` and `f`.
+   */
+  float step_size;
+  /**
+   * The radians turned by `+` and `-`.
+   */
+  float angle_increment;
+  /**
+   * The size of the buffer, ignored if `auto_fit` is set.
+   */
+  uint32_t size_x;
+  uint32_t size_y;
+  uint32_t size_z;
+  /**
+   * Size the buffer to fit the drawing.
+   */
+  bool auto_fit;
+  /**
+   * The seed of stochastic productions.
+   */
+  uint64_t seed;
+} VoxgenRenderOptions;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Get the message of the last failed call on this thread, or null if no
+ * call has failed.
+ *
+ * The message stays valid until the next failed call on this thread.
+ */
+const char *voxgen_last_error(void);
+
+/**
+ * Create an empty buffer, writing it to `out`.
+ *
+ * # Safety
+ *
+ * `out` must be valid for writes.
+ */
+enum VoxgenStatus voxgen_buffer_new(uint32_t size_x,
+                                    uint32_t size_y,
+                                    uint32_t size_z,
+                                    struct VoxgenBuffer **out);
+
+/**
+ * Load a buffer from the MagicaVoxel `.vox` file at `path`, writing it to
+ * `out`.
+ *
+ * # Safety
+ *
+ * `path` must be a nul terminated string and `out` must be valid for
+ * writes.
+ */
+enum VoxgenStatus voxgen_buffer_load(const char *path, struct VoxgenBuffer **out);
+
+/**
+ * Free a buffer. Does nothing if `buf` is null.
+ *
+ * # Safety
+ *
+ * `buf` must be null or a buffer created by this library that hasn't been
+ * freed.
+ */
+void voxgen_buffer_free(struct VoxgenBuffer *buf);
+
+/**
+ * Get the dimensions of `buf`. Null out parameters are skipped.
+ *
+ * # Safety
+ *
+ * `buf` must be a valid buffer, and the out parameters null or valid for
+ * writes.
+ */
+enum VoxgenStatus voxgen_buffer_dimensions(const struct VoxgenBuffer *buf,
+                                           uint32_t *size_x,
+                                           uint32_t *size_y,
+                                           uint32_t *size_z);
+
+/**
+ * Get the color of the voxel at `(x, y, z)`, writing it to `out`.
+ *
+ * # Safety
+ *
+ * `buf` must be a valid buffer and `out` must be valid for writes.
+ */
+enum VoxgenStatus voxgen_buffer_get_voxel(const struct VoxgenBuffer *buf,
+                                          uint32_t x,
+                                          uint32_t y,
+                                          uint32_t z,
+                                          struct VoxgenColor *out);
+
+/**
+ * Set the color of the voxel at `(x, y, z)`.
+ *
+ * # Safety
+ *
+ * `buf` must be a valid buffer.
+ */
+enum VoxgenStatus voxgen_buffer_set_voxel(struct VoxgenBuffer *buf,
+                                          uint32_t x,
+                                          uint32_t y,
+                                          uint32_t z,
+                                          struct VoxgenColor color);
+
+/**
+ * Save `buf` as a MagicaVoxel `.vox` file to `path`.
+ *
+ * # Safety
+ *
+ * `buf` must be a valid buffer and `path` a nul terminated string.
+ */
+enum VoxgenStatus voxgen_buffer_save_vox(const struct VoxgenBuffer *buf, const char *path);
+
+/**
+ * Get the default render options: 2 derivation steps, a step size of 2
+ * voxels, an angle increment of pi/2 radians, a 64 voxel cube buffer and
+ * a seed of 0.
+ */
+struct VoxgenRenderOptions voxgen_render_options_default(void);
+
+/**
+ * Render the L System with `axiom` and `count` `productions` into a new
+ * buffer, writing it to `out`.
+ *
+ * Rules use the syntax of `LSystem::try_new` of the Rust crate, such as
+ * `"F → F[+F]F[-F]F"`.
+ *
+ * # Safety
+ *
+ * `axiom` must be a nul terminated string, `productions` must point to
+ * `count` nul terminated strings, `options` must be null or point to
+ * render options, and `out` must be valid for writes. Null `options` use
+ * `voxgen_render_options_default`.
+ */
+enum VoxgenStatus voxgen_lsystem_render(const char *axiom,
+                                        const char *const *productions,
+                                        size_t count,
+                                        const struct VoxgenRenderOptions *options,
+                                        struct VoxgenBuffer **out);
+
+/**
+ * Render the L System definition file at `path` with the options of it's
+ * `render` section into a new buffer, writing it to `out`.
+ *
+ * # Safety
+ *
+ * `path` must be a nul terminated string and `out` must be valid for
+ * writes.
+ */
+enum VoxgenStatus voxgen_lsystem_render_file(const char *path, struct VoxgenBuffer **out);
+
+/**
+ * Mesh the faces between the visible and empty voxels of `buf`, writing
+ * the mesh to `out`.
+ *
+ * Every face is a quad of 4 vertices split into 2 triangles wound counter
+ * clockwise seen from outside, in the Z up voxel coordinates of the
+ * buffer.
+ *
+ * # Safety
+ *
+ * `buf` must be null or a valid buffer, and `out` must be valid for
+ * writes.
+ */
+enum VoxgenStatus voxgen_buffer_mesh(const struct VoxgenBuffer *buf, struct VoxgenMesh **out);
+
+/**
+ * Free a mesh. Does nothing if `mesh` is null.
+ *
+ * # Safety
+ *
+ * `mesh` must be null or a mesh created by this library that hasn't been
+ * freed.
+ */
+void voxgen_mesh_free(struct VoxgenMesh *mesh);
+
+/**
+ * Get the number of vertices of `mesh`.
+ *
+ * # Safety
+ *
+ * `mesh` must be a valid mesh.
+ */
+size_t voxgen_mesh_vertex_count(const struct VoxgenMesh *mesh);
+
+/**
+ * Get the positions of the vertices of `mesh`, 3 floats each.
+ *
+ * # Safety
+ *
+ * `mesh` must be a valid mesh, and the positions are only valid until
+ * it's freed.
+ */
+const float *voxgen_mesh_positions(const struct VoxgenMesh *mesh);
+
+/**
+ * Get the normals of the vertices of `mesh`, 3 floats each.
+ *
+ * # Safety
+ *
+ * `mesh` must be a valid mesh, and the normals are only valid until it's
+ * freed.
+ */
+const float *voxgen_mesh_normals(const struct VoxgenMesh *mesh);
+
+/**
+ * Get the colors of the vertices of `mesh`.
+ *
+ * # Safety
+ *
+ * `mesh` must be a valid mesh, and the colors are only valid until it's
+ * freed.
+ */
+const struct VoxgenColor *voxgen_mesh_colors(const struct VoxgenMesh *mesh);
+
+/**
+ * Get the number of triangle vertex indices of `mesh`, 3 per triangle.
+ *
+ * # Safety
+ *
+ * `mesh` must be a valid mesh.
+ */
+size_t voxgen_mesh_index_count(const struct VoxgenMesh *mesh);
+
+/**
+ * Get the triangle vertex indices of `mesh`.
+ *
+ * # Safety
+ *
+ * `mesh` must be a valid mesh, and the indices are only valid until it's
+ * freed.
+ */
+const uint32_t *voxgen_mesh_indices(const struct VoxgenMesh *mesh);
+
+/**
+ * Save `mesh` as a Wavefront `.obj` file with vertex colors to `path`, in
+ * the Y up axes of OBJ files.
+ *
+ * # Safety
+ *
+ * `mesh` must be a valid mesh and `path` a nul terminated string.
+ */
+enum VoxgenStatus voxgen_mesh_save_obj(const struct VoxgenMesh *mesh, const char *path);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VOXGEN_H */
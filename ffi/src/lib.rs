@@ -0,0 +1,558 @@
+//! C bindings for voxgen, so game engines written in C and C++ can embed
+//! the generators.
+//!
+//! The header `include/voxgen.h` is generated from this file by cbindgen
+//! on every build. Link against the `voxgen_ffi` static or dynamic library.
+//!
+//! Fallible functions return a `VoxgenStatus`, and on failure leave a
+//! message for `voxgen_last_error`. Objects created by the library are
+//! freed with their matching `_free` function. Panics are caught and
+//! reported as `VOXGEN_STATUS_PANIC` instead of unwinding into C.
+//!
+//! ```c
+//! #include "voxgen.h"
+//!
+//! const char *productions[] = {"F → F[+F]F[-F]F"};
+//! VoxgenRenderOptions options = voxgen_render_options_default();
+//! options.derivation_length = 3;
+//! options.auto_fit = true;
+//! VoxgenBuffer *buf = NULL;
+//! if (voxgen_lsystem_render("F", productions, 1, &options, &buf) != VOXGEN_STATUS_OK) {
+//!     fprintf(stderr, "%s\n", voxgen_last_error());
+//! }
+//! voxgen_buffer_save_vox(buf, "plant.vox");
+//! VoxgenMesh *mesh = NULL;
+//! voxgen_buffer_mesh(buf, &mesh);
+//! upload(voxgen_mesh_positions(mesh), voxgen_mesh_vertex_count(mesh));
+//! voxgen_mesh_free(mesh);
+//! voxgen_buffer_free(buf);
+//! ```
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use voxgen::l_system::{LSystem, RenderOptions};
+use voxgen::mesh::SurfaceMesh;
+use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+
+/// The result of a fallible call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxgenStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A pointer was null or a string wasn't valid UTF-8.
+    InvalidArgument,
+    /// Reading or writing a file failed.
+    Io,
+    /// An L System rule couldn't be parsed.
+    Parse,
+    /// An L System render went over a limit.
+    Limit,
+    /// A voxel position was outside the buffer.
+    OutOfBounds,
+    /// A buffer was too large to create.
+    Capacity,
+    /// The library panicked.
+    Panic,
+    /// Any other error.
+    Other,
+}
+
+/// An RGBA voxel color, where an alpha of 0 is empty.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VoxgenColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl From<Rgba> for VoxgenColor {
+    fn from(Rgba([r, g, b, a]): Rgba) -> VoxgenColor {
+        VoxgenColor { r, g, b, a }
+    }
+}
+
+impl From<VoxgenColor> for Rgba {
+    fn from(color: VoxgenColor) -> Rgba {
+        Rgba([color.r, color.g, color.b, color.a])
+    }
+}
+
+/// The settings of an L System render, see `voxgen_render_options_default`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct VoxgenRenderOptions {
+    /// The number of derivation steps.
+    pub derivation_length: u32,
+    /// The number of voxels moved by `F` and `f`.
+    pub step_size: f32,
+    /// The radians turned by `+` and `-`.
+    pub angle_increment: f32,
+    /// The size of the buffer, ignored if `auto_fit` is set.
+    pub size_x: u32,
+    pub size_y: u32,
+    pub size_z: u32,
+    /// Size the buffer to fit the drawing.
+    pub auto_fit: bool,
+    /// The seed of stochastic productions.
+    pub seed: u64,
+}
+
+/// A buffer of RGBA voxels.
+pub struct VoxgenBuffer(ArrayVoxelBuffer<Rgba>);
+
+/// The faces between the visible and empty voxels of a buffer, as an
+/// indexed triangle mesh.
+pub struct VoxgenMesh {
+    mesh: SurfaceMesh,
+    colors: Vec<VoxgenColor>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A failed call, with it's status and message.
+struct Failure {
+    status: VoxgenStatus,
+    message: String,
+}
+
+impl Failure {
+    fn new(status: VoxgenStatus, message: impl Display) -> Failure {
+        Failure {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<voxgen::Error> for Failure {
+    fn from(error: voxgen::Error) -> Failure {
+        let status = match error {
+            voxgen::Error::Io(_) => VoxgenStatus::Io,
+            voxgen::Error::Parse(_) => VoxgenStatus::Parse,
+            voxgen::Error::Limit(_) => VoxgenStatus::Limit,
+            voxgen::Error::OutOfBounds { .. } => VoxgenStatus::OutOfBounds,
+            voxgen::Error::Capacity(_) => VoxgenStatus::Capacity,
+            _ => VoxgenStatus::Other,
+        };
+        Failure::new(status, error)
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(error: std::io::Error) -> Failure {
+        Failure::new(VoxgenStatus::Io, error)
+    }
+}
+
+/// Run `f`, turning it's errors and panics into a status and recording
+/// their message for `voxgen_last_error`.
+fn guard<F>(f: F) -> VoxgenStatus
+where
+    F: FnOnce() -> Result<(), Failure>,
+{
+    let failure = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return VoxgenStatus::Ok,
+        Ok(Err(failure)) => failure,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panicked".to_string());
+            Failure::new(VoxgenStatus::Panic, message)
+        }
+    };
+    let message = CString::new(failure.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+    failure.status
+}
+
+/// Borrow the value `ptr` points to, failing if it's null.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a valid value for `'a`.
+unsafe fn borrow<'a, T>(ptr: *const T, name: &str) -> Result<&'a T, Failure> {
+    ptr.as_ref()
+        .ok_or_else(|| Failure::new(VoxgenStatus::InvalidArgument, format!("`{}` is null", name)))
+}
+
+/// Read the UTF-8 string `ptr` points to, failing if it's null or invalid.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a nul terminated string valid for `'a`.
+unsafe fn string<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if ptr.is_null() {
+        return Err(Failure::new(
+            VoxgenStatus::InvalidArgument,
+            format!("`{}` is null", name),
+        ));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|_| {
+        Failure::new(
+            VoxgenStatus::InvalidArgument,
+            format!("`{}` isn't valid UTF-8", name),
+        )
+    })
+}
+
+/// Write `value` to the out parameter `out`, failing if it's null.
+///
+/// # Safety
+///
+/// `out` must be null or valid for writes.
+unsafe fn write<T>(out: *mut T, value: T) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(Failure::new(
+            VoxgenStatus::InvalidArgument,
+            "the output pointer is null",
+        ));
+    }
+    out.write(value);
+    Ok(())
+}
+
+/// Get the message of the last failed call on this thread, or null if no
+/// call has failed.
+///
+/// The message stays valid until the next failed call on this thread.
+#[no_mangle]
+pub extern "C" fn voxgen_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |m| m.as_ptr()))
+}
+
+/// Create an empty buffer, writing it to `out`.
+///
+/// # Safety
+///
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_new(
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    out: *mut *mut VoxgenBuffer,
+) -> VoxgenStatus {
+    guard(|| {
+        let buf = ArrayVoxelBuffer::try_new(size_x, size_y, size_z).map_err(voxgen::Error::from)?;
+        write(out, Box::into_raw(Box::new(VoxgenBuffer(buf))))
+    })
+}
+
+/// Load a buffer from the MagicaVoxel `.vox` file at `path`, writing it to
+/// `out`.
+///
+/// # Safety
+///
+/// `path` must be a nul terminated string and `out` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_load(
+    path: *const c_char,
+    out: *mut *mut VoxgenBuffer,
+) -> VoxgenStatus {
+    guard(|| {
+        let buf = ArrayVoxelBuffer::load(string(path, "path")?)?;
+        write(out, Box::into_raw(Box::new(VoxgenBuffer(buf))))
+    })
+}
+
+/// Free a buffer. Does nothing if `buf` is null.
+///
+/// # Safety
+///
+/// `buf` must be null or a buffer created by this library that hasn't been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_free(buf: *mut VoxgenBuffer) {
+    if !buf.is_null() {
+        drop(Box::from_raw(buf));
+    }
+}
+
+/// Get the dimensions of `buf`. Null out parameters are skipped.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer, and the out parameters null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_dimensions(
+    buf: *const VoxgenBuffer,
+    size_x: *mut u32,
+    size_y: *mut u32,
+    size_z: *mut u32,
+) -> VoxgenStatus {
+    guard(|| {
+        let (x, y, z) = borrow(buf, "buf")?.0.dimensions();
+        for (out, size) in [(size_x, x), (size_y, y), (size_z, z)] {
+            if !out.is_null() {
+                out.write(size);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Get the color of the voxel at `(x, y, z)`, writing it to `out`.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_get_voxel(
+    buf: *const VoxgenBuffer,
+    x: u32,
+    y: u32,
+    z: u32,
+    out: *mut VoxgenColor,
+) -> VoxgenStatus {
+    guard(|| {
+        let voxel = *borrow(buf, "buf")?.0.try_voxel(x, y, z)?;
+        write(out, voxel.into())
+    })
+}
+
+/// Set the color of the voxel at `(x, y, z)`.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_set_voxel(
+    buf: *mut VoxgenBuffer,
+    x: u32,
+    y: u32,
+    z: u32,
+    color: VoxgenColor,
+) -> VoxgenStatus {
+    guard(|| {
+        let buf = buf
+            .as_mut()
+            .ok_or_else(|| Failure::new(VoxgenStatus::InvalidArgument, "`buf` is null"))?;
+        *buf.0.try_voxel_mut(x, y, z)? = color.into();
+        Ok(())
+    })
+}
+
+/// Save `buf` as a MagicaVoxel `.vox` file to `path`.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer and `path` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_save_vox(
+    buf: *const VoxgenBuffer,
+    path: *const c_char,
+) -> VoxgenStatus {
+    guard(|| Ok(borrow(buf, "buf")?.0.save(string(path, "path")?)?))
+}
+
+/// Get the default render options: 2 derivation steps, a step size of 2
+/// voxels, an angle increment of pi/2 radians, a 64 voxel cube buffer and
+/// a seed of 0.
+#[no_mangle]
+pub extern "C" fn voxgen_render_options_default() -> VoxgenRenderOptions {
+    VoxgenRenderOptions {
+        derivation_length: 2,
+        step_size: 2.0,
+        angle_increment: std::f32::consts::FRAC_PI_2,
+        size_x: 64,
+        size_y: 64,
+        size_z: 64,
+        auto_fit: false,
+        seed: 0,
+    }
+}
+
+/// Render the L System with `axiom` and `count` `productions` into a new
+/// buffer, writing it to `out`.
+///
+/// Rules use the syntax of `LSystem::try_new` of the Rust crate, such as
+/// `"F → F[+F]F[-F]F"`.
+///
+/// # Safety
+///
+/// `axiom` must be a nul terminated string, `productions` must point to
+/// `count` nul terminated strings, `options` must be null or point to
+/// render options, and `out` must be valid for writes. Null `options` use
+/// `voxgen_render_options_default`.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_lsystem_render(
+    axiom: *const c_char,
+    productions: *const *const c_char,
+    count: usize,
+    options: *const VoxgenRenderOptions,
+    out: *mut *mut VoxgenBuffer,
+) -> VoxgenStatus {
+    guard(|| {
+        let axiom = string(axiom, "axiom")?;
+        let mut rules = Vec::with_capacity(count);
+        if count > 0 {
+            let productions = borrow(productions, "productions")?;
+            for &rule in std::slice::from_raw_parts(productions, count) {
+                rules.push(string(rule, "productions")?);
+            }
+        }
+        let l_system = LSystem::try_new("ffi", axiom, rules).map_err(voxgen::Error::from)?;
+        let options = options
+            .as_ref()
+            .copied()
+            .unwrap_or_else(|| voxgen_render_options_default());
+        let buf = RenderOptions::new()
+            .derivation_length(options.derivation_length)
+            .step_size(options.step_size)
+            .angle_increment(options.angle_increment)
+            .size_x(options.size_x)
+            .size_y(options.size_y)
+            .size_z(options.size_z)
+            .auto_fit(options.auto_fit)
+            .seed(options.seed)
+            .render(&l_system)
+            .map_err(voxgen::Error::from)?;
+        write(out, Box::into_raw(Box::new(VoxgenBuffer(buf))))
+    })
+}
+
+/// Render the L System definition file at `path` with the options of it's
+/// `render` section into a new buffer, writing it to `out`.
+///
+/// # Safety
+///
+/// `path` must be a nul terminated string and `out` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_lsystem_render_file(
+    path: *const c_char,
+    out: *mut *mut VoxgenBuffer,
+) -> VoxgenStatus {
+    guard(|| {
+        let path = string(path, "path")?;
+        let l_system = LSystem::from_file(path)?;
+        let buf = RenderOptions::from_file(path)?
+            .render(&l_system)
+            .map_err(voxgen::Error::from)?;
+        write(out, Box::into_raw(Box::new(VoxgenBuffer(buf))))
+    })
+}
+
+/// Mesh the faces between the visible and empty voxels of `buf`, writing
+/// the mesh to `out`.
+///
+/// Every face is a quad of 4 vertices split into 2 triangles wound counter
+/// clockwise seen from outside, in the Z up voxel coordinates of the
+/// buffer.
+///
+/// # Safety
+///
+/// `buf` must be null or a valid buffer, and `out` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_buffer_mesh(
+    buf: *const VoxgenBuffer,
+    out: *mut *mut VoxgenMesh,
+) -> VoxgenStatus {
+    guard(|| {
+        let mesh = SurfaceMesh::from_buffer(&borrow(buf, "buf")?.0);
+        let colors = mesh.colors().iter().map(|&c| c.into()).collect();
+        write(out, Box::into_raw(Box::new(VoxgenMesh { mesh, colors })))
+    })
+}
+
+/// Free a mesh. Does nothing if `mesh` is null.
+///
+/// # Safety
+///
+/// `mesh` must be null or a mesh created by this library that hasn't been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_free(mesh: *mut VoxgenMesh) {
+    if !mesh.is_null() {
+        drop(Box::from_raw(mesh));
+    }
+}
+
+/// Get the number of vertices of `mesh`.
+///
+/// # Safety
+///
+/// `mesh` must be a valid mesh.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_vertex_count(mesh: *const VoxgenMesh) -> usize {
+    (*mesh).mesh.positions().len()
+}
+
+/// Get the positions of the vertices of `mesh`, 3 floats each.
+///
+/// # Safety
+///
+/// `mesh` must be a valid mesh, and the positions are only valid until
+/// it's freed.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_positions(mesh: *const VoxgenMesh) -> *const f32 {
+    (*mesh).mesh.positions().as_ptr().cast()
+}
+
+/// Get the normals of the vertices of `mesh`, 3 floats each.
+///
+/// # Safety
+///
+/// `mesh` must be a valid mesh, and the normals are only valid until it's
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_normals(mesh: *const VoxgenMesh) -> *const f32 {
+    (*mesh).mesh.normals().as_ptr().cast()
+}
+
+/// Get the colors of the vertices of `mesh`.
+///
+/// # Safety
+///
+/// `mesh` must be a valid mesh, and the colors are only valid until it's
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_colors(mesh: *const VoxgenMesh) -> *const VoxgenColor {
+    (*mesh).colors.as_ptr()
+}
+
+/// Get the number of triangle vertex indices of `mesh`, 3 per triangle.
+///
+/// # Safety
+///
+/// `mesh` must be a valid mesh.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_index_count(mesh: *const VoxgenMesh) -> usize {
+    (*mesh).mesh.indices().len()
+}
+
+/// Get the triangle vertex indices of `mesh`.
+///
+/// # Safety
+///
+/// `mesh` must be a valid mesh, and the indices are only valid until it's
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_indices(mesh: *const VoxgenMesh) -> *const u32 {
+    (*mesh).mesh.indices().as_ptr()
+}
+
+/// Save `mesh` as a Wavefront `.obj` file with vertex colors to `path`, in
+/// the Y up axes of OBJ files.
+///
+/// # Safety
+///
+/// `mesh` must be a valid mesh and `path` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn voxgen_mesh_save_obj(
+    mesh: *const VoxgenMesh,
+    path: *const c_char,
+) -> VoxgenStatus {
+    guard(|| Ok(borrow(mesh, "mesh")?.mesh.save_obj(string(path, "path")?)?))
+}
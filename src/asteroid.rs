@@ -0,0 +1,208 @@
+use crate::noise::{Generator, NoiseParams};
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+/// A crater, as the direction of it's center from the middle of the body,
+/// it's angular radius and it's depth.
+#[derive(Clone, Copy, Debug)]
+struct Crater {
+    direction: [f32; 3],
+    angle: f32,
+    depth: f32,
+}
+
+/// An asteroid and rock generator, displacing the surface of an ellipsoid
+/// with noise and stamping craters into it.
+///
+/// Voxels are colored in layers by their depth below the surface, as a
+/// fraction of the distance from the surface to the center.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::asteroid::Asteroid;
+/// Asteroid::new()
+///     .radii(16.0, 12.0, 10.0)
+///     .roughness(0.3)
+///     .craters(8)
+///     .seed(4)
+///     .render()
+///     .save("test/volumes/asteroid.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Asteroid {
+    radii: [f32; 3],
+    roughness: f32,
+    frequency: f64,
+    craters: usize,
+    crater_size: (f32, f32),
+    layers: Vec<(f32, Rgba)>,
+    seed: u64,
+}
+
+impl Asteroid {
+    pub fn new() -> Asteroid {
+        Asteroid {
+            radii: [12.0, 12.0, 12.0],
+            roughness: 0.2,
+            frequency: 1.5,
+            craters: 6,
+            crater_size: (0.15, 0.4),
+            layers: vec![
+                (0.0, Rgba([120, 115, 110, 255])),
+                (0.1, Rgba([95, 85, 80, 255])),
+                (0.5, Rgba([150, 90, 60, 255])),
+            ],
+            seed: 0,
+        }
+    }
+
+    /// Set the radii of the ellipsoid along each axis, before displacement.
+    pub fn radii(&mut self, x: f32, y: f32, z: f32) -> &mut Self {
+        self.radii = [x.max(1.0), y.max(1.0), z.max(1.0)];
+        self
+    }
+
+    /// Set how far the noise displaces the surface, as a fraction of the
+    /// radii, from a smooth ellipsoid at 0.0 to a jagged lump near 1.0.
+    pub fn roughness(&mut self, roughness: f32) -> &mut Self {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the frequency of the displacement noise over the surface, where
+    /// higher frequencies give more, smaller bumps.
+    pub fn frequency(&mut self, frequency: f64) -> &mut Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Set the number of craters stamped into the surface.
+    pub fn craters(&mut self, n: usize) -> &mut Self {
+        self.craters = n;
+        self
+    }
+
+    /// Set the smallest and largest crater radius, in radians seen from the
+    /// middle of the body.
+    pub fn crater_size(&mut self, min: f32, max: f32) -> &mut Self {
+        self.crater_size = (min.max(0.01), max.max(min.max(0.01)));
+        self
+    }
+
+    /// Set the colors of the layers, each starting at the given depth below
+    /// the surface as a fraction of the distance to the center.
+    pub fn layers(&mut self, layers: Vec<(f32, Rgba)>) -> &mut Self {
+        self.layers = layers;
+        self.layers.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    /// Set the `seed` of the displacement noise and crater placement.
+    ///
+    /// The same seed always gives the same body.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Generate the body into a new buffer fitting it, with the center of
+    /// the ellipsoid in the middle.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let mut rng = Seed::new(self.seed).rng();
+        let craters: Vec<Crater> = (0..self.craters)
+            .map(|_| {
+                let angle = rng.gen_range(self.crater_size.0..=self.crater_size.1);
+                Crater {
+                    direction: random_direction(&mut rng),
+                    angle,
+                    // Bigger craters are deeper, up to a third of the radius.
+                    depth: angle * rng.gen_range(0.3..=0.6),
+                }
+            })
+            .collect();
+        let mut params = NoiseParams::new();
+        params
+            .generator(Generator::Fbm)
+            .octaves(4)
+            .frequency(self.frequency)
+            .seed(Seed::new(self.seed).noise());
+        let displacement = params.sampler();
+        let strata = params
+            .seed(Seed::new(self.seed).derive("strata").noise())
+            .sampler();
+        let reach = 1.0 + self.roughness;
+        let size = self.radii.map(|r| (2.0 * r * reach).ceil() as u32 + 2);
+        let center = size.map(|s| s as f32 / 2.0);
+        let mut buf = ArrayVoxelBuffer::new(size[0], size[1], size[2]);
+        for z in 0..size[2] {
+            for y in 0..size[1] {
+                for x in 0..size[0] {
+                    // Position in the unit sphere the ellipsoid is stretched
+                    // from.
+                    let p = [x, y, z].map(|c| c as f32 + 0.5);
+                    let q: [f32; 3] = std::array::from_fn(|k| (p[k] - center[k]) / self.radii[k]);
+                    let rho = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2]).sqrt();
+                    if rho > reach {
+                        continue;
+                    }
+                    let d = q.map(|c| c / rho.max(f32::EPSILON));
+                    let (dx, dy, dz) = (d[0] as f64, d[1] as f64, d[2] as f64);
+                    let mut surface = 1.0 + self.roughness * displacement(dx, dy, dz) as f32;
+                    for crater in &craters {
+                        surface += crater_profile(crater, d);
+                    }
+                    if rho > surface {
+                        continue;
+                    }
+                    let depth = 1.0 - rho / surface + 0.05 * strata(dx, dy, dz) as f32;
+                    let layer = self
+                        .layers
+                        .iter()
+                        .rev()
+                        .find(|(start, _)| depth >= *start)
+                        .or(self.layers.first());
+                    if let Some((_, color)) = layer {
+                        *buf.voxel_mut(x, y, z) = *color;
+                    }
+                }
+            }
+        }
+        buf
+    }
+}
+
+impl Default for Asteroid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get a uniformly distributed random unit vector.
+fn random_direction(rng: &mut ChaCha8Rng) -> [f32; 3] {
+    loop {
+        let v: [f32; 3] = std::array::from_fn(|_| rng.gen_range(-1.0..=1.0));
+        let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        if length > 1e-3 && length <= 1.0 {
+            return v.map(|c| c / length);
+        }
+    }
+}
+
+/// Get the change in surface height a crater makes in direction `d`: a bowl
+/// inside the crater, and a raised rim around it.
+fn crater_profile(crater: &Crater, d: [f32; 3]) -> f32 {
+    let cos = d[0] * crater.direction[0] + d[1] * crater.direction[1] + d[2] * crater.direction[2];
+    let t = cos.clamp(-1.0, 1.0).acos() / crater.angle;
+    if t < 1.0 {
+        -crater.depth * (1.0 - t * t)
+    } else if t < 1.4 {
+        let rim = 1.0 - ((t - 1.2) / 0.2).powi(2);
+        crater.depth * 0.25 * rim
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,322 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+
+/// The cells counted as neighbors of a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// The 26 cells sharing a face, edge or corner.
+    Moore,
+    /// The 6 cells sharing a face.
+    VonNeumann,
+}
+
+impl Neighborhood {
+    /// Get the offsets of the neighbors.
+    fn offsets(self) -> Vec<(i32, i32, i32)> {
+        let mut offsets = Vec::new();
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let distance = i32::abs(x) + i32::abs(y) + i32::abs(z);
+                    match self {
+                        Neighborhood::Moore if distance > 0 => offsets.push((x, y, z)),
+                        Neighborhood::VonNeumann if distance == 1 => offsets.push((x, y, z)),
+                        _ => (),
+                    }
+                }
+            }
+        }
+        offsets
+    }
+}
+
+/// A life-like rule deciding which cells are alive after a step from the
+/// number of alive neighbors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    birth: [bool; 27],
+    survival: [bool; 27],
+    neighborhood: Neighborhood,
+}
+
+impl Rule {
+    /// Create a rule where dead cells with a neighbor count in `birth` come
+    /// alive and alive cells with a neighbor count in `survival` stay alive.
+    ///
+    /// Counts above the size of the `neighborhood` are ignored.
+    pub fn new(birth: &[usize], survival: &[usize], neighborhood: Neighborhood) -> Rule {
+        let mut rule = Rule {
+            birth: [false; 27],
+            survival: [false; 27],
+            neighborhood,
+        };
+        for &n in birth.iter().filter(|&&n| n < 27) {
+            rule.birth[n] = true;
+        }
+        for &n in survival.iter().filter(|&&n| n < 27) {
+            rule.survival[n] = true;
+        }
+        rule
+    }
+
+    /// Smooth random noise into connected caves, filling cells surrounded by
+    /// mostly alive neighbors and clearing isolated ones.
+    pub fn cave_smoothing() -> Rule {
+        let majority: Vec<usize> = (14..=26).collect();
+        let survival: Vec<usize> = (13..=26).collect();
+        Rule::new(&majority, &survival, Neighborhood::Moore)
+    }
+
+    /// Carter Bays' 3D Life 4555, where cells are born with 5 neighbors and
+    /// survive with 4 or 5.
+    pub fn life_4555() -> Rule {
+        Rule::new(&[5], &[4, 5], Neighborhood::Moore)
+    }
+
+    /// Grow blobby clouds from random noise.
+    pub fn clouds() -> Rule {
+        let survival: Vec<usize> = (13..=26).collect();
+        Rule::new(&[13, 14, 17, 18, 19], &survival, Neighborhood::Moore)
+    }
+
+    /// Get the cells counted as neighbors.
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    /// Get the birth and survival neighbor counts as bit masks, where bit n
+    /// is set if n alive neighbors give birth or survival.
+    #[cfg(feature = "gpu")]
+    pub(crate) fn masks(&self) -> (u32, u32) {
+        let mask = |counts: &[bool; 27]| {
+            counts
+                .iter()
+                .enumerate()
+                .filter(|(_, &on)| on)
+                .fold(0u32, |mask, (n, _)| mask | 1 << n)
+        };
+        (mask(&self.birth), mask(&self.survival))
+    }
+
+    fn next(&self, alive: bool, neighbors: usize) -> bool {
+        if alive {
+            self.survival[neighbors]
+        } else {
+            self.birth[neighbors]
+        }
+    }
+}
+
+/// A three dimensional cellular automaton over a grid of alive and dead
+/// cells.
+///
+/// Steps are double buffered, so every cell of a step sees the cells of the
+/// previous step.
+///
+/// # Examples
+///
+/// Smooth random noise into caves.
+/// ```
+/// # use voxgen::automata::{Automaton, Rule};
+/// # use voxgen::voxel_buffer::Rgba;
+/// let mut caves = Automaton::random(32, 32, 32, 0.55, 7, Rule::cave_smoothing());
+/// caves.boundary(true).steps(4);
+/// caves.to_buffer(Rgba([110, 100, 90, 255])).save("test/volumes/automata_caves.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Automaton {
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    cells: Vec<bool>,
+    back: Vec<bool>,
+    rule: Rule,
+    boundary: bool,
+}
+
+impl Automaton {
+    /// Create an automaton of dead cells.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32, rule: Rule) -> Automaton {
+        let len = size_x as usize * size_y as usize * size_z as usize;
+        Automaton {
+            size_x,
+            size_y,
+            size_z,
+            cells: vec![false; len],
+            back: vec![false; len],
+            rule,
+            boundary: false,
+        }
+    }
+
+    /// Create an automaton with each cell alive with probability `density`,
+    /// picked with a random number generator seeded with `seed`.
+    pub fn random(
+        size_x: u32,
+        size_y: u32,
+        size_z: u32,
+        density: f64,
+        seed: u64,
+        rule: Rule,
+    ) -> Automaton {
+        let mut rng = Seed::new(seed).rng();
+        let mut automaton = Automaton::new(size_x, size_y, size_z, rule);
+        for cell in automaton.cells.iter_mut() {
+            *cell = rng.gen_bool(density.clamp(0.0, 1.0));
+        }
+        automaton
+    }
+
+    /// Create an automaton with the voxels of `buf` that aren't transparent
+    /// alive.
+    pub fn from_buffer(buf: &ArrayVoxelBuffer<Rgba>, rule: Rule) -> Automaton {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let mut automaton = Automaton::new(size_x, size_y, size_z, rule);
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let i = automaton.index(x, y, z);
+                    automaton.cells[i] = buf.voxel(x, y, z).0[3] != 0;
+                }
+            }
+        }
+        automaton
+    }
+
+    /// Set whether cells outside the grid count as alive neighbors.
+    ///
+    /// Alive boundaries close caves off at the edges of the grid.
+    pub fn boundary(&mut self, alive: bool) -> &mut Self {
+        self.boundary = alive;
+        self
+    }
+
+    /// Set the `rule` of the following steps.
+    pub fn rule(&mut self, rule: Rule) -> &mut Self {
+        self.rule = rule;
+        self
+    }
+
+    /// Check whether the cell at `(x, y, z)` is alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y, z)` is outside the grid.
+    pub fn alive(&self, x: u32, y: u32, z: u32) -> bool {
+        assert!(x < self.size_x && y < self.size_y && z < self.size_z);
+        self.cells[self.index(x, y, z)]
+    }
+
+    /// Set whether the cell at `(x, y, z)` is alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y, z)` is outside the grid.
+    pub fn set(&mut self, x: u32, y: u32, z: u32, alive: bool) -> &mut Self {
+        assert!(x < self.size_x && y < self.size_y && z < self.size_z);
+        let i = self.index(x, y, z);
+        self.cells[i] = alive;
+        self
+    }
+
+    /// Count the alive cells.
+    pub fn population(&self) -> usize {
+        self.cells.iter().filter(|&&alive| alive).count()
+    }
+
+    /// Advance every cell by one step of the rule.
+    pub fn step(&mut self) -> &mut Self {
+        let offsets = self.rule.neighborhood.offsets();
+        for z in 0..self.size_z {
+            for y in 0..self.size_y {
+                for x in 0..self.size_x {
+                    let neighbors = offsets
+                        .iter()
+                        .filter(|(dx, dy, dz)| self.neighbor(x, y, z, *dx, *dy, *dz))
+                        .count();
+                    let i = self.index(x, y, z);
+                    self.back[i] = self.rule.next(self.cells[i], neighbors);
+                }
+            }
+        }
+        std::mem::swap(&mut self.cells, &mut self.back);
+        self
+    }
+
+    /// Advance every cell by `n` steps of the rule.
+    pub fn steps(&mut self, n: usize) -> &mut Self {
+        for _ in 0..n {
+            self.step();
+        }
+        self
+    }
+
+    /// Advance every cell by `n` steps of the rule on `gpu`, with the same
+    /// result as `steps`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::automata::{Automaton, Rule};
+    /// # use voxgen::gpu::Gpu;
+    /// // Machines without a GPU have nothing to compare.
+    /// if let Some(gpu) = Gpu::new() {
+    ///     let mut cpu = Automaton::random(40, 30, 20, 0.55, 7, Rule::cave_smoothing());
+    ///     let mut gpu_caves = cpu.clone();
+    ///     cpu.boundary(true).steps(4);
+    ///     gpu_caves.boundary(true).steps_gpu(&gpu, 4);
+    ///     for z in 0..20 {
+    ///         for y in 0..30 {
+    ///             for x in 0..40 {
+    ///                 assert_eq!(cpu.alive(x, y, z), gpu_caves.alive(x, y, z));
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "gpu")]
+    pub fn steps_gpu(&mut self, gpu: &crate::gpu::Gpu, n: usize) -> &mut Self {
+        gpu.automaton_steps(
+            [self.size_x, self.size_y, self.size_z],
+            &mut self.cells,
+            &self.rule,
+            self.boundary,
+            n,
+        );
+        self
+    }
+
+    /// Draw the alive cells into a new buffer with `color`.
+    pub fn to_buffer(&self, color: Rgba) -> ArrayVoxelBuffer<Rgba> {
+        let mut buf = ArrayVoxelBuffer::new(self.size_x, self.size_y, self.size_z);
+        for z in 0..self.size_z {
+            for y in 0..self.size_y {
+                for x in 0..self.size_x {
+                    if self.cells[self.index(x, y, z)] {
+                        *buf.voxel_mut(x, y, z) = color;
+                    }
+                }
+            }
+        }
+        buf
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        x as usize
+            + y as usize * self.size_x as usize
+            + z as usize * self.size_x as usize * self.size_y as usize
+    }
+
+    /// Check whether the neighbor of `(x, y, z)` at an offset is alive.
+    fn neighbor(&self, x: u32, y: u32, z: u32, dx: i32, dy: i32, dz: i32) -> bool {
+        let x = x.checked_add_signed(dx).filter(|&x| x < self.size_x);
+        let y = y.checked_add_signed(dy).filter(|&y| y < self.size_y);
+        let z = z.checked_add_signed(dz).filter(|&z| z < self.size_z);
+        match (x, y, z) {
+            (Some(x), Some(y), Some(z)) => self.cells[self.index(x, y, z)],
+            _ => self.boundary,
+        }
+    }
+}
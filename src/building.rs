@@ -0,0 +1,307 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+
+/// The shape of a building roof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Roof {
+    /// A flat roof with a low parapet around it's edge.
+    Flat,
+    /// Two slopes meeting at a ridge along the longer side, with gable
+    /// walls at the ends.
+    Gable,
+    /// Slopes on all four sides, meeting at a ridge or a point.
+    Hip,
+}
+
+/// The parameters of a building generator.
+///
+/// Buildings are boxes of stacked floors with windows spaced along every
+/// wall, a door in the middle of the front wall facing -y, and a roof.
+/// The seed decides which window positions get a window.
+///
+/// # Examples
+///
+/// Line a street with houses.
+/// ```
+/// # use voxgen::building::{BuildingParams, Roof};
+/// # use voxgen::voxel_buffer::ArrayVoxelBuffer;
+/// let mut street = ArrayVoxelBuffer::new(64, 16, 24);
+/// let mut params = BuildingParams::new();
+/// params.size(12, 10).window_chance(0.8);
+/// for (i, roof) in [Roof::Flat, Roof::Gable, Roof::Hip].into_iter().enumerate() {
+///     params.floors(i as u32 + 1).roof(roof).seed(i as u64);
+///     street.stamp(&params.render(), 4 + 20 * i as i32, 3, 0);
+/// }
+/// street.save("test/volumes/buildings.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct BuildingParams {
+    width: u32,
+    depth: u32,
+    floors: u32,
+    floor_height: u32,
+    window_width: u32,
+    window_height: u32,
+    window_spacing: u32,
+    window_chance: f64,
+    door_width: u32,
+    door_height: u32,
+    roof: Roof,
+    seed: u64,
+    wall_color: Rgba,
+    trim_color: Rgba,
+    window_color: Rgba,
+    door_color: Rgba,
+    roof_color: Rgba,
+}
+
+impl BuildingParams {
+    pub fn new() -> BuildingParams {
+        BuildingParams {
+            width: 12,
+            depth: 10,
+            floors: 2,
+            floor_height: 5,
+            window_width: 2,
+            window_height: 2,
+            window_spacing: 4,
+            window_chance: 1.0,
+            door_width: 2,
+            door_height: 3,
+            roof: Roof::Gable,
+            seed: 0,
+            wall_color: Rgba([210, 190, 160, 255]),
+            trim_color: Rgba([150, 130, 110, 255]),
+            window_color: Rgba([120, 170, 210, 255]),
+            door_color: Rgba([100, 60, 30, 255]),
+            roof_color: Rgba([150, 60, 40, 255]),
+        }
+    }
+
+    /// Set the outer `width` along x and `depth` along y of the walls.
+    pub fn size(&mut self, width: u32, depth: u32) -> &mut Self {
+        self.width = width.max(3);
+        self.depth = depth.max(3);
+        self
+    }
+
+    pub fn floors(&mut self, floors: u32) -> &mut Self {
+        self.floors = floors.max(1);
+        self
+    }
+
+    /// Set the `height` of every floor, including the slab under it.
+    pub fn floor_height(&mut self, height: u32) -> &mut Self {
+        self.floor_height = height.max(2);
+        self
+    }
+
+    /// Set the `width` and `height` of windows, and the `spacing` between
+    /// their left edges along a wall.
+    pub fn windows(&mut self, width: u32, height: u32, spacing: u32) -> &mut Self {
+        self.window_width = width.max(1);
+        self.window_height = height.max(1);
+        self.window_spacing = spacing.max(self.window_width + 1);
+        self
+    }
+
+    /// Set the probability of each window position getting a window.
+    pub fn window_chance(&mut self, chance: f64) -> &mut Self {
+        self.window_chance = chance.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn door(&mut self, width: u32, height: u32) -> &mut Self {
+        self.door_width = width.max(1);
+        self.door_height = height.max(1);
+        self
+    }
+
+    pub fn roof(&mut self, roof: Roof) -> &mut Self {
+        self.roof = roof;
+        self
+    }
+
+    /// Set the `seed` of the random number generator placing windows.
+    ///
+    /// The same parameters and seed always give the same building.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the colors of the walls, and the trim along the floor slabs.
+    pub fn wall_colors(&mut self, wall: Rgba, trim: Rgba) -> &mut Self {
+        self.wall_color = wall;
+        self.trim_color = trim;
+        self
+    }
+
+    pub fn window_color(&mut self, color: Rgba) -> &mut Self {
+        self.window_color = color;
+        self
+    }
+
+    pub fn door_color(&mut self, color: Rgba) -> &mut Self {
+        self.door_color = color;
+        self
+    }
+
+    pub fn roof_color(&mut self, color: Rgba) -> &mut Self {
+        self.roof_color = color;
+        self
+    }
+
+    /// Get the height of the roof above the walls.
+    fn roof_height(&self) -> u32 {
+        match self.roof {
+            Roof::Flat => 1,
+            Roof::Gable | Roof::Hip => self.width.min(self.depth).div_ceil(2),
+        }
+    }
+
+    /// Build the building into a new buffer fitting it exactly, with the
+    /// ground floor slab in the bottom layer.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let (width, depth) = (self.width, self.depth);
+        let top = self.floors * self.floor_height;
+        let mut buf = ArrayVoxelBuffer::new(width, depth, top + 1 + self.roof_height());
+        // Floor slabs with trim around them, and the walls between them.
+        for z in 0..=top {
+            let slab = z % self.floor_height == 0;
+            for y in 0..depth {
+                for x in 0..width {
+                    let edge = x == 0 || y == 0 || x == width - 1 || y == depth - 1;
+                    if slab {
+                        let color = if edge {
+                            self.trim_color
+                        } else {
+                            self.wall_color
+                        };
+                        *buf.voxel_mut(x, y, z) = color;
+                    } else if edge {
+                        *buf.voxel_mut(x, y, z) = self.wall_color;
+                    }
+                }
+            }
+        }
+        self.windows_into(&mut buf);
+        // The door, in the middle of the front wall.
+        let door_x = width.saturating_sub(self.door_width) / 2;
+        let door_top = self.door_height.min(self.floor_height - 1);
+        for z in 1..=door_top {
+            for x in door_x.max(1)..(door_x + self.door_width).min(width - 1) {
+                *buf.voxel_mut(x, 0, z) = self.door_color;
+            }
+        }
+        self.roof_into(&mut buf, top + 1);
+        buf
+    }
+
+    /// Put windows along every wall of every floor.
+    fn windows_into(&self, buf: &mut ArrayVoxelBuffer<Rgba>) {
+        let mut rng = Seed::new(self.seed).rng();
+        let (width, depth) = (self.width, self.depth);
+        // Every wall as it's length, first column and direction along it,
+        // going around the building counter clockwise.
+        let (right, back) = (width as i32 - 1, depth as i32 - 1);
+        let walls = [
+            (width, (0, 0), (1, 0)),
+            (depth, (right, 0), (0, 1)),
+            (width, (right, back), (-1, 0)),
+            (depth, (0, back), (0, -1)),
+        ];
+        let sill = 2.min(self.floor_height - 1);
+        let window_top = (sill + self.window_height).min(self.floor_height);
+        for floor in 0..self.floors {
+            let base = floor * self.floor_height;
+            for &(length, (x0, y0), (dx, dy)) in &walls {
+                // Keep windows off the corners, centering them on the wall.
+                let room = length.saturating_sub(2);
+                if room < self.window_width {
+                    continue;
+                }
+                let count = (room - self.window_width) / self.window_spacing + 1;
+                let span = (count - 1) * self.window_spacing + self.window_width;
+                let start = 1 + (room - span) / 2;
+                for i in 0..count {
+                    if !rng.gen_bool(self.window_chance) {
+                        continue;
+                    }
+                    let left = start + i * self.window_spacing;
+                    for t in left..left + self.window_width {
+                        let (x, y) = ((x0 + dx * t as i32) as u32, (y0 + dy * t as i32) as u32);
+                        for z in base + sill..base + window_top {
+                            *buf.voxel_mut(x, y, z) = self.window_color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Put the roof on top of the walls, starting at `base`.
+    fn roof_into(&self, buf: &mut ArrayVoxelBuffer<Rgba>, base: u32) {
+        let (width, depth) = (self.width, self.depth);
+        match self.roof {
+            Roof::Flat => {
+                for y in 0..depth {
+                    for x in 0..width {
+                        if x == 0 || y == 0 || x == width - 1 || y == depth - 1 {
+                            *buf.voxel_mut(x, y, base) = self.trim_color;
+                        }
+                    }
+                }
+            }
+            Roof::Gable => {
+                // Slope across the shorter side, with the ridge along the
+                // longer one.
+                let along_x = width >= depth;
+                let (across, along) = if along_x {
+                    (depth, width)
+                } else {
+                    (width, depth)
+                };
+                for k in 0..self.roof_height() {
+                    let (low, high) = (k, across - 1 - k);
+                    for a in low..=high {
+                        for b in 0..along {
+                            let slope = a - low < 2 || high - a < 2;
+                            let end = b == 0 || b == along - 1;
+                            let color = if slope {
+                                self.roof_color
+                            } else if end {
+                                self.wall_color
+                            } else {
+                                continue;
+                            };
+                            let (x, y) = if along_x { (b, a) } else { (a, b) };
+                            *buf.voxel_mut(x, y, base + k) = color;
+                        }
+                    }
+                }
+            }
+            Roof::Hip => {
+                for k in 0..self.roof_height() {
+                    let (x_high, y_high) = (width - 1 - k, depth - 1 - k);
+                    for y in k..=y_high {
+                        for x in k..=x_high {
+                            let ring = x - k < 2 || x_high - x < 2 || y - k < 2 || y_high - y < 2;
+                            if ring {
+                                *buf.voxel_mut(x, y, base + k) = self.roof_color;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for BuildingParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
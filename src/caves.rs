@@ -0,0 +1,390 @@
+use crate::noise::{Generator, NoiseParams};
+use crate::seed::{self, Seed};
+use crate::terrain::WATER;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use std::f32::consts::{FRAC_PI_3, FRAC_PI_4, PI};
+
+/// The directions a drunkard steps in, in the order of the step bias.
+const STEPS: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// A cave generator carving winding tunnels and chambers out of solid
+/// terrain with Perlin worms.
+///
+/// Every worm crawls through the terrain one voxel at a time, turning as
+/// the Perlin noise at it's position says, and clears a sphere around
+/// itself whose radius also follows the noise. Now and then a worm hollows
+/// out a chamber of overlapping blobs. Entrance worms start on the surface
+/// heading down into the ground, the others start anywhere underground.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::caves::CaveCarver;
+/// # use voxgen::terrain::DiamondSquare;
+/// let mut buf = DiamondSquare::new().seed(8).render(64, 64, 32);
+/// let entrances = CaveCarver::new().worms(6).entrances(2).seed(8).carve(&mut buf);
+/// assert_eq!(entrances.len(), 2);
+/// buf.save("test/volumes/caves_worms.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct CaveCarver {
+    worms: usize,
+    entrances: usize,
+    length: usize,
+    radius: f32,
+    radius_variation: f32,
+    frequency: f64,
+    chamber_chance: f64,
+    chamber_radius: f32,
+    seed: u64,
+}
+
+impl CaveCarver {
+    pub fn new() -> CaveCarver {
+        CaveCarver {
+            worms: 4,
+            entrances: 1,
+            length: 120,
+            radius: 2.0,
+            radius_variation: 0.75,
+            frequency: 1.0 / 24.0,
+            chamber_chance: 0.01,
+            chamber_radius: 5.0,
+            seed: 0,
+        }
+    }
+
+    /// Set the number of worms carving tunnels, including the entrances.
+    pub fn worms(&mut self, n: usize) -> &mut Self {
+        self.worms = n;
+        self
+    }
+
+    /// Set the number of worms starting on the surface, opening the caves
+    /// to it.
+    pub fn entrances(&mut self, n: usize) -> &mut Self {
+        self.entrances = n;
+        self
+    }
+
+    /// Set the number of voxels every worm crawls.
+    pub fn length(&mut self, length: usize) -> &mut Self {
+        self.length = length;
+        self
+    }
+
+    /// Set the tunnel `radius`, and the `variation` of it along the tunnels
+    /// as a fraction of it.
+    pub fn radius(&mut self, radius: f32, variation: f32) -> &mut Self {
+        self.radius = radius.max(0.5);
+        self.radius_variation = variation.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the frequency of the noise steering the worms, where higher
+    /// frequencies give twistier tunnels.
+    pub fn frequency(&mut self, frequency: f64) -> &mut Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Set the probability of hollowing out a chamber on every voxel
+    /// crawled, and the chamber `radius`.
+    pub fn chambers(&mut self, chance: f64, radius: f32) -> &mut Self {
+        self.chamber_chance = chance.clamp(0.0, 1.0);
+        self.chamber_radius = radius.max(1.0);
+        self
+    }
+
+    /// Set the `seed` of the random number generator placing the worms and
+    /// chambers, and of the steering noise.
+    ///
+    /// The same seed always gives the same caves.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Carve caves out of the terrain in `buf`, returning the surface
+    /// voxels where the entrances start.
+    ///
+    /// Only terrain voxels are cleared, leaving `WATER` in place. Columns
+    /// without terrain can't hold entrances, so fewer entrances than asked
+    /// for are returned for buffers without any.
+    pub fn carve(&self, buf: &mut ArrayVoxelBuffer<Rgba>) -> Vec<(u32, u32, u32)> {
+        let mut rng = Seed::new(self.seed).rng();
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let mut noise = NoiseParams::new();
+        noise.generator(Generator::Perlin).frequency(self.frequency);
+        let seed = Seed::new(self.seed);
+        let yaw_noise = noise.seed(seed.noise()).sampler();
+        let pitch_noise = noise.seed(seed.derive("pitch").noise()).sampler();
+        let radius_noise = noise.seed(seed.derive("radius").noise()).sampler();
+        let surface: Vec<(u32, u32, u32)> = (0..size_y)
+            .flat_map(|y| (0..size_x).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let top = (0..size_z).rev().find(|&z| buf.voxel(x, y, z).0[3] != 0)?;
+                (*buf.voxel(x, y, top) != WATER).then_some((x, y, top))
+            })
+            .collect();
+        let mut entrances = Vec::new();
+        for worm in 0..self.worms {
+            let (mut p, mut pitch) = if worm < self.entrances && !surface.is_empty() {
+                let start = surface[seed::index(&mut rng, surface.len())];
+                entrances.push(start);
+                ([start.0 as f32, start.1 as f32, start.2 as f32], -FRAC_PI_4)
+            } else {
+                let p = [
+                    rng.gen_range(0.0..size_x as f32),
+                    rng.gen_range(0.0..size_y as f32),
+                    rng.gen_range(0.0..size_z as f32 * 0.6),
+                ];
+                (p, 0.0)
+            };
+            let mut yaw = rng.gen_range(0.0..2.0 * PI);
+            for _ in 0..self.length {
+                let (x, y, z) = (p[0] as f64, p[1] as f64, p[2] as f64);
+                let variation = radius_noise(x, y, z) as f32 * self.radius_variation;
+                carve_sphere(buf, p, self.radius * (1.0 + variation));
+                if rng.gen_bool(self.chamber_chance) {
+                    self.carve_chamber(buf, p, &mut rng);
+                }
+                yaw += yaw_noise(x, y, z) as f32 * FRAC_PI_4;
+                pitch = (pitch + pitch_noise(x, y, z) as f32 * 0.2).clamp(-FRAC_PI_3, FRAC_PI_4);
+                p[0] += yaw.cos() * pitch.cos();
+                p[1] += yaw.sin() * pitch.cos();
+                p[2] += pitch.sin();
+                let outside = p[0] < 0.0
+                    || p[1] < 0.0
+                    || p[2] < 0.0
+                    || p[0] >= size_x as f32
+                    || p[1] >= size_y as f32
+                    || p[2] >= size_z as f32;
+                if outside {
+                    break;
+                }
+            }
+        }
+        entrances
+    }
+
+    /// Hollow out a chamber of a few overlapping blobs around `center`.
+    fn carve_chamber(
+        &self,
+        buf: &mut ArrayVoxelBuffer<Rgba>,
+        center: [f32; 3],
+        rng: &mut ChaCha8Rng,
+    ) {
+        let r = self.chamber_radius;
+        for _ in 0..rng.gen_range(3..=5) {
+            let blob = [
+                center[0] + rng.gen_range(-r..=r) * 0.6,
+                center[1] + rng.gen_range(-r..=r) * 0.6,
+                // Keep chambers flatter than they are wide.
+                center[2] + rng.gen_range(-r..=r) * 0.2,
+            ];
+            carve_sphere(buf, blob, r * rng.gen_range(0.6..=1.0));
+        }
+    }
+}
+
+impl Default for CaveCarver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A drunkard's walk digger, carving organic cave pockets and ant nests
+/// with random walks.
+///
+/// Every walker steps to a random face neighbor one voxel at a time, picked by the
+/// step bias, and clears a sphere around itself. Now and then a walker
+/// branches, starting another walker where it stands, until the number of
+/// walkers reaches the limit. Walkers never step out of the buffer.
+///
+/// # Examples
+///
+/// Dig an ant nest down into a block of dirt.
+/// ```
+/// # use voxgen::caves::DrunkardWalk;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let mut buf = ArrayVoxelBuffer::new(48, 48, 32);
+/// for z in 0..28 {
+///     for y in 0..48 {
+///         for x in 0..48 {
+///             *buf.voxel_mut(x, y, z) = Rgba([120, 85, 50, 255]);
+///         }
+///     }
+/// }
+/// let ends = DrunkardWalk::new()
+///     .start(24, 24, 27)
+///     .steps(150)
+///     .bias([1.0, 1.0, 1.0, 1.0, 0.3, 1.5])
+///     .branching(0.02, 8)
+///     .seed(3)
+///     .carve(&mut buf);
+/// assert!(!ends.is_empty());
+/// buf.save("test/volumes/caves_drunkard.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DrunkardWalk {
+    start: Option<(u32, u32, u32)>,
+    steps: usize,
+    bias: [f32; 6],
+    radius: f32,
+    branch_chance: f64,
+    walkers: usize,
+    seed: u64,
+}
+
+impl DrunkardWalk {
+    pub fn new() -> DrunkardWalk {
+        DrunkardWalk {
+            start: None,
+            steps: 400,
+            bias: [1.0; 6],
+            radius: 1.5,
+            branch_chance: 0.0,
+            walkers: 1,
+            seed: 0,
+        }
+    }
+
+    /// Set the voxel the first walker starts on, instead of the middle of
+    /// the buffer.
+    pub fn start(&mut self, x: u32, y: u32, z: u32) -> &mut Self {
+        self.start = Some((x, y, z));
+        self
+    }
+
+    /// Set the number of steps every walker takes.
+    pub fn steps(&mut self, steps: usize) -> &mut Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Set the weights of stepping towards +x, -x, +y, -y, +z and -z.
+    ///
+    /// Negative weights count as 0.0.
+    pub fn bias(&mut self, bias: [f32; 6]) -> &mut Self {
+        self.bias = bias.map(|w| w.max(0.0));
+        self
+    }
+
+    /// Set the `radius` of the brush every walker clears around itself.
+    pub fn radius(&mut self, radius: f32) -> &mut Self {
+        self.radius = radius.max(0.5);
+        self
+    }
+
+    /// Set the probability of every walker branching on every step, and
+    /// the most `walkers` there can be, including the first.
+    pub fn branching(&mut self, chance: f64, walkers: usize) -> &mut Self {
+        self.branch_chance = chance.clamp(0.0, 1.0);
+        self.walkers = walkers.max(1);
+        self
+    }
+
+    /// Set the `seed` of the random number generator steering the walkers.
+    ///
+    /// The same seed always gives the same tunnels.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Dig the tunnels out of `buf`, returning the voxels where the walkers
+    /// stopped, in the order they started, for placing chambers and
+    /// treasure at the ends of tunnels.
+    ///
+    /// Only terrain voxels are cleared, leaving `WATER` in place.
+    pub fn carve(&self, buf: &mut ArrayVoxelBuffer<Rgba>) -> Vec<(u32, u32, u32)> {
+        let mut rng = Seed::new(self.seed).rng();
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let total: f32 = self.bias.iter().sum();
+        let start = self.start.unwrap_or((size_x / 2, size_y / 2, size_z / 2));
+        let mut walkers = vec![[
+            start.0.min(size_x - 1) as i64,
+            start.1.min(size_y - 1) as i64,
+            start.2.min(size_z - 1) as i64,
+        ]];
+        let mut ends = Vec::new();
+        // Walkers walk one after another, so branches start where their
+        // parent was when it branched.
+        let mut next = 0;
+        while next < walkers.len() {
+            let mut p = walkers[next];
+            for _ in 0..self.steps {
+                let center = [p[0] as f32 + 0.5, p[1] as f32 + 0.5, p[2] as f32 + 0.5];
+                carve_sphere(buf, center, self.radius);
+                if walkers.len() < self.walkers && rng.gen_bool(self.branch_chance) {
+                    walkers.push(p);
+                }
+                if total <= 0.0 {
+                    break;
+                }
+                let mut pick = rng.gen::<f32>() * total;
+                let mut step = STEPS[5];
+                for (d, w) in STEPS.iter().zip(self.bias) {
+                    if pick < w {
+                        step = *d;
+                        break;
+                    }
+                    pick -= w;
+                }
+                let q = [p[0] + step[0], p[1] + step[1], p[2] + step[2]];
+                let inside = q[0] >= 0
+                    && q[1] >= 0
+                    && q[2] >= 0
+                    && q[0] < size_x as i64
+                    && q[1] < size_y as i64
+                    && q[2] < size_z as i64;
+                if inside {
+                    p = q;
+                }
+            }
+            ends.push((p[0] as u32, p[1] as u32, p[2] as u32));
+            next += 1;
+        }
+        ends
+    }
+}
+
+impl Default for DrunkardWalk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clear the terrain voxels of `buf` within `radius` of `center`.
+fn carve_sphere(buf: &mut ArrayVoxelBuffer<Rgba>, center: [f32; 3], radius: f32) {
+    let (size_x, size_y, size_z) = buf.dimensions();
+    let range = |c: f32, size: u32| {
+        let low = (c - radius).floor().max(0.0) as u32;
+        let high = ((c + radius).ceil().max(0.0) as u32).min(size);
+        low..high
+    };
+    for z in range(center[2], size_z) {
+        for y in range(center[1], size_y) {
+            for x in range(center[0], size_x) {
+                let d2 = (x as f32 + 0.5 - center[0]).powi(2)
+                    + (y as f32 + 0.5 - center[1]).powi(2)
+                    + (z as f32 + 0.5 - center[2]).powi(2);
+                if d2 <= radius * radius && *buf.voxel(x, y, z) != WATER {
+                    *buf.voxel_mut(x, y, z) = Rgba([0, 0, 0, 0]);
+                }
+            }
+        }
+    }
+}
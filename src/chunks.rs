@@ -0,0 +1,465 @@
+use crate::progress::{Progress, Stage};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, Voxel, VoxelBuffer};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Generate a buffer of `dims` one chunk at a time across all cores, and
+/// stitch the chunks together.
+///
+/// The buffer is split into chunks of `chunk_size`, and `generate` is called
+/// once per chunk with the chunk's coordinates in the grid of chunks and an
+/// empty buffer to fill. Chunk (`cx`, `cy`, `cz`) covers the voxels from
+/// (`cx * chunk_size.0`, `cy * chunk_size.1`, `cz * chunk_size.2`), and the
+/// chunks along the far edges are cut short to fit `dims`.
+///
+/// Chunks are generated in parallel, so `generate` must only depend on the
+/// chunk it's given for the result to be the same on any machine. Every
+/// chunk is held until stitching, so generation needs about twice the
+/// memory of the buffer.
+///
+/// # Panics
+///
+/// Panics if any dimension of `chunk_size` is 0.
+///
+/// # Examples
+///
+/// Generate rolling hills in chunks of 32 by 32 columns.
+/// ```
+/// # use voxgen::chunks::generate_chunks;
+/// # use voxgen::voxel_buffer::{Rgba, VoxelBuffer};
+/// let buf = generate_chunks((128, 96, 32), (32, 32, 32), |(cx, cy, _), chunk| {
+///     let (size_x, size_y, _) = chunk.dimensions();
+///     for y in 0..size_y {
+///         for x in 0..size_x {
+///             let (wx, wy) = ((cx * 32 + x) as f32, (cy * 32 + y) as f32);
+///             let height = 12.0 + 6.0 * (wx / 9.0).sin() + 6.0 * (wy / 13.0).cos();
+///             for z in 0..height as u32 {
+///                 let green = 120 + (z * 4) as u8;
+///                 *chunk.voxel_mut(x, y, z) = Rgba([70, green, 60, 255]);
+///             }
+///         }
+///     }
+/// });
+/// assert_eq!(buf.dimensions(), (128, 96, 32));
+/// buf.save("test/volumes/chunks.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn generate_chunks<T, F>(
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+    generate: F,
+) -> ArrayVoxelBuffer<T>
+where
+    T: Voxel + Copy + Send,
+    F: Fn((u32, u32, u32), &mut ArrayVoxelBuffer<T>) + Sync,
+{
+    generate_chunks_with_progress(dims, chunk_size, generate, |_| ())
+}
+
+/// Generate a buffer like `generate_chunks`, reporting every chunk
+/// generated to `progress` as `Stage::Generation`.
+///
+/// Chunks are generated in parallel, so `progress` is called from several
+/// threads, and reports can arrive out of order.
+///
+/// # Panics
+///
+/// Panics if any dimension of `chunk_size` is 0.
+pub fn generate_chunks_with_progress<T, F, R>(
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+    generate: F,
+    progress: R,
+) -> ArrayVoxelBuffer<T>
+where
+    T: Voxel + Copy + Send,
+    F: Fn((u32, u32, u32), &mut ArrayVoxelBuffer<T>) + Sync,
+    R: Fn(Progress) + Sync,
+{
+    let coords = chunk_coords(dims, chunk_size);
+    let total = coords.len();
+    let done = AtomicUsize::new(0);
+    let chunks: Vec<_> = coords
+        .into_par_iter()
+        .map(|coords| {
+            let chunk = generate_chunk(dims, chunk_size, coords, &generate);
+            progress(Progress {
+                stage: Stage::Generation,
+                done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                total: Some(total),
+            });
+            chunk
+        })
+        .collect();
+    let mut buf = ArrayVoxelBuffer::new(dims.0, dims.1, dims.2);
+    for (origin, chunk) in chunks {
+        buf.paste(&chunk, origin);
+    }
+    buf
+}
+
+/// Generate chunks like `generate_chunks`, writing every chunk to a chunk
+/// file at `path` instead of stitching them into one buffer, so volumes
+/// larger than memory can be generated.
+///
+/// Chunks are generated in parallel batches of one chunk per thread and
+/// written in order, so only a batch of chunks is held at a time.
+///
+/// # Panics
+///
+/// Panics if any dimension of `chunk_size` is 0.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::chunks::{generate_chunks_to, ChunkFile};
+/// # use voxgen::voxel_buffer::{Rgba, VoxelBuffer};
+/// let path = std::env::temp_dir().join("voxgen_chunks_example.chunks");
+/// generate_chunks_to(&path, (64, 64, 16), (32, 32, 16), |(cx, cy, _), chunk| {
+///     if (cx + cy) % 2 == 0 {
+///         chunk.fill(Rgba([90, 140, 80, 255]));
+///     }
+/// })?;
+/// let mut file = ChunkFile::open(&path)?;
+/// assert_eq!(file.dimensions(), (64, 64, 16));
+/// let chunks: Vec<_> = file.chunks().collect::<Result<_, _>>()?;
+/// assert_eq!(chunks.len(), 4);
+/// assert_eq!(*chunks[1].1.voxel(0, 0, 0), Rgba([0, 0, 0, 0]));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn generate_chunks_to<P, F>(
+    path: P,
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+    generate: F,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: Fn((u32, u32, u32), &mut ArrayVoxelBuffer<Rgba>) + Sync,
+{
+    generate_chunks_to_with_progress(path, dims, chunk_size, generate, |_| ())
+}
+
+/// Generate chunks to a chunk file like `generate_chunks_to`, reporting
+/// every chunk written to `progress` as `Stage::Generation`.
+///
+/// # Panics
+///
+/// Panics if any dimension of `chunk_size` is 0.
+pub fn generate_chunks_to_with_progress<P, F, R>(
+    path: P,
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+    generate: F,
+    progress: R,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: Fn((u32, u32, u32), &mut ArrayVoxelBuffer<Rgba>) + Sync,
+    R: Fn(Progress),
+{
+    let mut writer = ChunkWriter::create(path, dims, chunk_size)?;
+    let coords = chunk_coords(dims, chunk_size);
+    let total = coords.len();
+    let mut done = 0;
+    for batch in coords.chunks(rayon::current_num_threads().max(1)) {
+        let chunks: Vec<_> = batch
+            .par_iter()
+            .map(|&coords| {
+                (
+                    coords,
+                    generate_chunk(dims, chunk_size, coords, &generate).1,
+                )
+            })
+            .collect();
+        for (coords, chunk) in chunks {
+            writer.write(coords, &chunk)?;
+            done += 1;
+            progress(Progress {
+                stage: Stage::Generation,
+                done,
+                total: Some(total),
+            });
+        }
+    }
+    writer.finish()
+}
+
+/// A chunk with it's coordinates in the grid of chunks.
+pub type Chunk = ((u32, u32, u32), ArrayVoxelBuffer<Rgba>);
+
+/// The magic bytes at the start of a chunk file.
+const MAGIC: &[u8; 4] = b"VXCH";
+
+/// The version of the chunk file format.
+const VERSION: u32 = 1;
+
+/// A writer appending chunks of a volume to a chunk file as they're
+/// generated.
+///
+/// A chunk file starts with the bytes `VXCH`, the format version, and the
+/// dimensions and chunk size of the volume, followed by chunk records in
+/// the order they were written. Every record holds the chunk coordinates,
+/// the number of visible voxels, and every visible voxel as it's index in
+/// the chunk, x first, then y, then z, followed by it's color. All numbers
+/// are little-endian `u32`s.
+///
+/// Read chunk files back with `ChunkFile`.
+pub struct ChunkWriter {
+    file: BufWriter<File>,
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+}
+
+impl ChunkWriter {
+    /// Create a chunk file at `path` for a volume of `dims` split into
+    /// chunks of `chunk_size`, replacing any file there.
+    ///
+    /// Voxels are indexed with `u32`s, so chunks over 2³² voxels return an
+    /// `InvalidInput` error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any dimension of `chunk_size` is 0.
+    pub fn create<P>(
+        path: P,
+        dims: (u32, u32, u32),
+        chunk_size: (u32, u32, u32),
+    ) -> io::Result<ChunkWriter>
+    where
+        P: AsRef<Path>,
+    {
+        assert_chunk_size(chunk_size);
+        if chunk_volume(dims, chunk_size) > 1 << 32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunks over 2^32 voxels",
+            ));
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        for n in [
+            VERSION,
+            dims.0,
+            dims.1,
+            dims.2,
+            chunk_size.0,
+            chunk_size.1,
+            chunk_size.2,
+        ] {
+            file.write_all(&n.to_le_bytes())?;
+        }
+        Ok(ChunkWriter {
+            file,
+            dims,
+            chunk_size,
+        })
+    }
+
+    /// Append the chunk at `coords` in the grid of chunks.
+    ///
+    /// Chunks larger than the chunk size are cut short, as are chunks
+    /// running past the far edges of the volume.
+    pub fn write(
+        &mut self,
+        coords: (u32, u32, u32),
+        chunk: &ArrayVoxelBuffer<Rgba>,
+    ) -> io::Result<()> {
+        let (size_x, size_y, size_z) = chunk_dimensions(self.dims, self.chunk_size, coords)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "chunk outside the volume")
+            })?;
+        let visible: Vec<_> = chunk
+            .enumerate_occupied()
+            .filter(|((x, y, z), _)| *x < size_x && *y < size_y && *z < size_z)
+            .collect();
+        for n in [coords.0, coords.1, coords.2, visible.len() as u32] {
+            self.file.write_all(&n.to_le_bytes())?;
+        }
+        for ((x, y, z), rgba) in visible {
+            let index = x + y * size_x + z * size_x * size_y;
+            self.file.write_all(&index.to_le_bytes())?;
+            self.file.write_all(&rgba.0)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the written chunks to disk.
+    pub fn finish(mut self) -> io::Result<()> {
+        // Flush explicitly, since dropping the writer ignores errors.
+        self.file.flush()
+    }
+}
+
+/// A chunk file written by `ChunkWriter`, read one chunk at a time.
+pub struct ChunkFile {
+    file: BufReader<File>,
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+}
+
+impl ChunkFile {
+    /// Open the chunk file at `path`, reading it's header.
+    pub fn open<P>(path: P) -> io::Result<ChunkFile>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("missing VXCH header"));
+        }
+        let mut header = [0; 7];
+        for n in &mut header {
+            *n = read_u32(&mut file)?;
+        }
+        let [version, x, y, z, cx, cy, cz] = header;
+        if version != VERSION {
+            return Err(invalid("unsupported chunk file version"));
+        }
+        if cx == 0 || cy == 0 || cz == 0 {
+            return Err(invalid("chunk size is 0"));
+        }
+        if chunk_volume((x, y, z), (cx, cy, cz)) > 1 << 32 {
+            return Err(invalid("chunks over 2^32 voxels"));
+        }
+        Ok(ChunkFile {
+            file,
+            dims: (x, y, z),
+            chunk_size: (cx, cy, cz),
+        })
+    }
+
+    /// Get the volume dimensions.
+    pub fn dimensions(&self) -> (u32, u32, u32) {
+        self.dims
+    }
+
+    /// Get the chunk size.
+    pub fn chunk_size(&self) -> (u32, u32, u32) {
+        self.chunk_size
+    }
+
+    /// Iterate over the remaining chunks in the order they were written,
+    /// with their coordinates in the grid of chunks.
+    pub fn chunks(&mut self) -> impl Iterator<Item = io::Result<Chunk>> + '_ {
+        std::iter::from_fn(move || self.read_chunk().transpose())
+    }
+
+    /// Read the next chunk, or `None` at the end of the file.
+    fn read_chunk(&mut self) -> io::Result<Option<Chunk>> {
+        if self.file.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let coords = (
+            read_u32(&mut self.file)?,
+            read_u32(&mut self.file)?,
+            read_u32(&mut self.file)?,
+        );
+        let (size_x, size_y, size_z) = chunk_dimensions(self.dims, self.chunk_size, coords)
+            .ok_or_else(|| invalid("chunk outside the volume"))?;
+        let count = read_u32(&mut self.file)?;
+        if count as u64 > size_x as u64 * size_y as u64 * size_z as u64 {
+            return Err(invalid("more voxels than the chunk holds"));
+        }
+        let mut chunk = ArrayVoxelBuffer::try_new(size_x, size_y, size_z)
+            .map_err(|error| invalid(&error.to_string()))?;
+        for _ in 0..count {
+            let index = read_u32(&mut self.file)?;
+            let mut rgba = [0; 4];
+            self.file.read_exact(&mut rgba)?;
+            let (x, y, z) = (
+                index % size_x,
+                index / size_x % size_y,
+                index / size_x / size_y,
+            );
+            if z >= size_z {
+                return Err(invalid("voxel outside the chunk"));
+            }
+            *chunk.voxel_mut(x, y, z) = Rgba(rgba);
+        }
+        Ok(Some((coords, chunk)))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn assert_chunk_size(chunk_size: (u32, u32, u32)) {
+    assert!(
+        chunk_size.0 > 0 && chunk_size.1 > 0 && chunk_size.2 > 0,
+        "chunk size must not be 0"
+    );
+}
+
+/// Get the coordinates of every chunk of a volume of `dims`, x first, then
+/// y, then z.
+fn chunk_coords(dims: (u32, u32, u32), chunk_size: (u32, u32, u32)) -> Vec<(u32, u32, u32)> {
+    assert_chunk_size(chunk_size);
+    let counts = (
+        dims.0.div_ceil(chunk_size.0),
+        dims.1.div_ceil(chunk_size.1),
+        dims.2.div_ceil(chunk_size.2),
+    );
+    (0..counts.2)
+        .flat_map(|cz| (0..counts.1).flat_map(move |cy| (0..counts.0).map(move |cx| (cx, cy, cz))))
+        .collect()
+}
+
+/// Get the number of voxels of the largest chunk of a volume of `dims`.
+fn chunk_volume(dims: (u32, u32, u32), chunk_size: (u32, u32, u32)) -> u64 {
+    chunk_size.0.min(dims.0) as u64
+        * chunk_size.1.min(dims.1) as u64
+        * chunk_size.2.min(dims.2) as u64
+}
+
+/// Get the dimensions of the chunk at `coords`, cut short at the far edges
+/// of the volume, or `None` if it's outside the volume.
+fn chunk_dimensions(
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+    coords: (u32, u32, u32),
+) -> Option<(u32, u32, u32)> {
+    let extent = |dim: u32, size: u32, c: u32| {
+        let origin = c.checked_mul(size).filter(|&origin| origin < dim)?;
+        Some(size.min(dim - origin))
+    };
+    Some((
+        extent(dims.0, chunk_size.0, coords.0)?,
+        extent(dims.1, chunk_size.1, coords.1)?,
+        extent(dims.2, chunk_size.2, coords.2)?,
+    ))
+}
+
+/// Generate the chunk at `coords`, returning it with it's origin.
+fn generate_chunk<T, F>(
+    dims: (u32, u32, u32),
+    chunk_size: (u32, u32, u32),
+    coords: (u32, u32, u32),
+    generate: &F,
+) -> ((u32, u32, u32), ArrayVoxelBuffer<T>)
+where
+    T: Voxel + Copy,
+    F: Fn((u32, u32, u32), &mut ArrayVoxelBuffer<T>),
+{
+    let origin = (
+        coords.0 * chunk_size.0,
+        coords.1 * chunk_size.1,
+        coords.2 * chunk_size.2,
+    );
+    let (size_x, size_y, size_z) = chunk_dimensions(dims, chunk_size, coords).unwrap();
+    let mut chunk = ArrayVoxelBuffer::new(size_x, size_y, size_z);
+    generate(coords, &mut chunk);
+    (origin, chunk)
+}
@@ -0,0 +1,332 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+
+/// A point on the ground, in voxel coordinates.
+pub type Point = [f32; 2];
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn cross(a: Point, b: Point) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length(a: Point) -> f32 {
+    (a[0] * a[0] + a[1] * a[1]).sqrt()
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+/// A building lot, a convex quadrilateral with it's corners in counter
+/// clockwise order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lot {
+    pub corners: [Point; 4],
+}
+
+impl Lot {
+    /// Get the average of the corners.
+    pub fn center(&self) -> Point {
+        let sum = self
+            .corners
+            .iter()
+            .fold([0.0, 0.0], |s, c| [s[0] + c[0], s[1] + c[1]]);
+        [sum[0] / 4.0, sum[1] / 4.0]
+    }
+
+    /// Check whether `p` is inside the lot.
+    pub fn contains(&self, p: Point) -> bool {
+        (0..4).all(|k| {
+            let (a, b) = (self.corners[k], self.corners[(k + 1) % 4]);
+            cross(sub(b, a), sub(p, a)) >= 0.0
+        })
+    }
+
+    /// Get the smallest and largest corner coordinates, as `(min, max)`.
+    pub fn bounds(&self) -> (Point, Point) {
+        self.corners.iter().fold(
+            ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+            |(min, max), c| {
+                (
+                    [min[0].min(c[0]), min[1].min(c[1])],
+                    [max[0].max(c[0]), max[1].max(c[1])],
+                )
+            },
+        )
+    }
+}
+
+/// A road network generator laying out a perturbed grid of city blocks.
+///
+/// Roads run between the corners of a grid of blocks, with every corner
+/// moved randomly by up to `jitter` of the block size, so blocks become
+/// irregular quadrilaterals. Each block is lined with sidewalks and divided
+/// into one or two rows of lots facing the streets.
+///
+/// # Examples
+///
+/// Lay out a town on a grass field.
+/// ```
+/// # use voxgen::city::RoadNetwork;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let mut buf = ArrayVoxelBuffer::new(96, 96, 2);
+/// for y in 0..96 {
+///     for x in 0..96 {
+///         *buf.voxel_mut(x, y, 0) = Rgba([100, 80, 60, 255]);
+///     }
+/// }
+/// let lots = RoadNetwork::new().seed(4).rasterize(&mut buf);
+/// assert!(!lots.is_empty());
+/// buf.save("test/volumes/city.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct RoadNetwork {
+    block_size: f32,
+    jitter: f32,
+    road_width: f32,
+    sidewalk_width: f32,
+    lot_width: f32,
+    seed: u64,
+    road_color: Rgba,
+    sidewalk_color: Rgba,
+    lot_color: Rgba,
+}
+
+impl RoadNetwork {
+    pub fn new() -> RoadNetwork {
+        RoadNetwork {
+            block_size: 32.0,
+            jitter: 0.15,
+            road_width: 4.0,
+            sidewalk_width: 1.0,
+            lot_width: 8.0,
+            seed: 0,
+            road_color: Rgba([50, 50, 55, 255]),
+            sidewalk_color: Rgba([170, 170, 165, 255]),
+            lot_color: Rgba([80, 130, 60, 255]),
+        }
+    }
+
+    /// Set the distance between the corners of blocks before perturbation.
+    pub fn block_size(&mut self, size: f32) -> &mut Self {
+        self.block_size = size.max(1.0);
+        self
+    }
+
+    /// Set how far block corners move, as a fraction of the block size.
+    ///
+    /// The jitter is capped at 0.25 to keep blocks convex.
+    pub fn jitter(&mut self, jitter: f32) -> &mut Self {
+        self.jitter = jitter.clamp(0.0, 0.25);
+        self
+    }
+
+    pub fn road_width(&mut self, width: f32) -> &mut Self {
+        self.road_width = width.max(0.0);
+        self
+    }
+
+    pub fn sidewalk_width(&mut self, width: f32) -> &mut Self {
+        self.sidewalk_width = width.max(0.0);
+        self
+    }
+
+    /// Set the street frontage of lots.
+    pub fn lot_width(&mut self, width: f32) -> &mut Self {
+        self.lot_width = width.max(1.0);
+        self
+    }
+
+    /// Set the `seed` of the random number generator perturbing the grid.
+    ///
+    /// The same seed always gives the same layout.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the ground colors of roads, sidewalks and lots.
+    pub fn colors(&mut self, road: Rgba, sidewalk: Rgba, lot: Rgba) -> &mut Self {
+        self.road_color = road;
+        self.sidewalk_color = sidewalk;
+        self.lot_color = lot;
+        self
+    }
+
+    /// Get the corners of the blocks of an area `size_x` by `size_y`, in
+    /// rows along x.
+    fn corners(&self, size_x: f32, size_y: f32) -> (usize, usize, Vec<Point>) {
+        let mut rng = Seed::new(self.seed).rng();
+        let nx = ((size_x / self.block_size).round() as usize).max(1);
+        let ny = ((size_y / self.block_size).round() as usize).max(1);
+        let (dx, dy) = (size_x / nx as f32, size_y / ny as f32);
+        let mut corners = Vec::with_capacity((nx + 1) * (ny + 1));
+        for j in 0..=ny {
+            for i in 0..=nx {
+                let mut offset = |n: usize, i: usize, d: f32| {
+                    if i == 0 || i == n || self.jitter == 0.0 {
+                        0.0
+                    } else {
+                        rng.gen_range(-self.jitter..=self.jitter) * d
+                    }
+                };
+                let x = i as f32 * dx + offset(nx, i, dx);
+                let y = j as f32 * dy + offset(ny, j, dy);
+                corners.push([x, y]);
+            }
+        }
+        (nx, ny, corners)
+    }
+
+    /// Lay out roads over an area `size_x` by `size_y`, returning the road
+    /// center lines and the lots.
+    fn layout(&self, size_x: f32, size_y: f32) -> (Vec<(Point, Point)>, Vec<Lot>) {
+        let (nx, ny, corners) = self.corners(size_x, size_y);
+        let corner = |i: usize, j: usize| corners[i + j * (nx + 1)];
+        let mut roads = Vec::new();
+        for j in 0..=ny {
+            for i in 0..=nx {
+                if i < nx {
+                    roads.push((corner(i, j), corner(i + 1, j)));
+                }
+                if j < ny {
+                    roads.push((corner(i, j), corner(i, j + 1)));
+                }
+            }
+        }
+        let mut lots = Vec::new();
+        let inset = self.road_width / 2.0 + self.sidewalk_width;
+        for j in 0..ny {
+            for i in 0..nx {
+                let block = [
+                    corner(i, j),
+                    corner(i + 1, j),
+                    corner(i + 1, j + 1),
+                    corner(i, j + 1),
+                ];
+                if let Some(block) = shrink(block, inset) {
+                    self.subdivide(block, &mut lots);
+                }
+            }
+        }
+        (roads, lots)
+    }
+
+    /// Divide a `block` into lots along it's longer sides, in two rows if
+    /// it's deep enough.
+    fn subdivide(&self, block: [Point; 4], lots: &mut Vec<Lot>) {
+        let along = length(sub(block[1], block[0])).max(length(sub(block[2], block[3])));
+        let across = length(sub(block[3], block[0])).max(length(sub(block[2], block[1])));
+        // Rotate the corners so the first side is the longer one.
+        let block = if along >= across {
+            block
+        } else {
+            [block[1], block[2], block[3], block[0]]
+        };
+        let (along, across) = (along.max(across), along.min(across));
+        let columns = ((along / self.lot_width).round() as usize).max(1);
+        let rows = if across >= 2.0 * self.lot_width { 2 } else { 1 };
+        let at = |u: f32, v: f32| lerp(lerp(block[0], block[1], u), lerp(block[3], block[2], u), v);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (u0, u1) = (
+                    column as f32 / columns as f32,
+                    (column + 1) as f32 / columns as f32,
+                );
+                let (v0, v1) = (row as f32 / rows as f32, (row + 1) as f32 / rows as f32);
+                lots.push(Lot {
+                    corners: [at(u0, v0), at(u1, v0), at(u1, v1), at(u0, v1)],
+                });
+            }
+        }
+    }
+
+    /// Get the lots of the layout over an area `size_x` by `size_y`.
+    pub fn lots(&self, size_x: f32, size_y: f32) -> Vec<Lot> {
+        self.layout(size_x, size_y).1
+    }
+
+    /// Paint roads, sidewalks and lots over the ground of `buf`, returning
+    /// the lots.
+    ///
+    /// The top voxel of every column is painted, or the bottom voxel of
+    /// empty columns.
+    pub fn rasterize(&self, buf: &mut ArrayVoxelBuffer<Rgba>) -> Vec<Lot> {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let (roads, lots) = self.layout(size_x as f32, size_y as f32);
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                let distance = roads
+                    .iter()
+                    .map(|&(a, b)| segment_distance(p, a, b))
+                    .fold(f32::INFINITY, f32::min);
+                let color = if distance <= self.road_width / 2.0 {
+                    self.road_color
+                } else if distance <= self.road_width / 2.0 + self.sidewalk_width {
+                    self.sidewalk_color
+                } else if lots.iter().any(|lot| lot.contains(p)) {
+                    self.lot_color
+                } else {
+                    continue;
+                };
+                let top = (0..size_z)
+                    .rev()
+                    .find(|&z| buf.voxel(x, y, z).0[3] != 0)
+                    .unwrap_or(0);
+                *buf.voxel_mut(x, y, top) = color;
+            }
+        }
+        lots
+    }
+}
+
+impl Default for RoadNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the distance from `p` to the line segment from `a` to `b`.
+fn segment_distance(p: Point, a: Point, b: Point) -> f32 {
+    let (ab, ap) = (sub(b, a), sub(p, a));
+    let t = ((ap[0] * ab[0] + ap[1] * ab[1]) / (ab[0] * ab[0] + ab[1] * ab[1]).max(f32::EPSILON))
+        .clamp(0.0, 1.0);
+    length(sub(ap, [ab[0] * t, ab[1] * t]))
+}
+
+/// Move every side of a convex counter clockwise quadrilateral `distance`
+/// inwards, or get `None` if nothing is left of it.
+fn shrink(quad: [Point; 4], distance: f32) -> Option<[Point; 4]> {
+    // Every side as a point on it after moving it inwards, and it's direction.
+    let sides: Vec<(Point, Point)> = (0..4)
+        .map(|k| {
+            let (a, b) = (quad[k], quad[(k + 1) % 4]);
+            let d = sub(b, a);
+            let l = length(d).max(f32::EPSILON);
+            ([a[0] - d[1] / l * distance, a[1] + d[0] / l * distance], d)
+        })
+        .collect();
+    let mut corners = [[0.0; 2]; 4];
+    for k in 0..4 {
+        let (p1, d1) = sides[(k + 3) % 4];
+        let (p2, d2) = sides[k];
+        let denominator = cross(d1, d2);
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+        let t = cross(sub(p2, p1), d2) / denominator;
+        corners[k] = [p1[0] + d1[0] * t, p1[1] + d1[1] * t];
+    }
+    // Sides that flipped direction mean the quadrilateral shrank away.
+    let flipped = (0..4).any(|k| {
+        let d = sub(corners[(k + 1) % 4], corners[k]);
+        d[0] * sides[k].1[0] + d[1] * sides[k].1[1] <= 0.0
+    });
+    (!flipped).then_some(corners)
+}
@@ -0,0 +1,187 @@
+use crate::noise::{Generator, NoiseParams};
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+
+/// The distinct transparency levels of a cloud, keeping under the
+/// MagicaVoxel palette limit.
+const ALPHA_LEVELS: u32 = 16;
+
+/// A grid of cloud densities from 0.0 for clear air to 1.0 for the thickest
+/// cloud.
+#[derive(Clone, Debug)]
+pub struct DensityField {
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    values: Vec<f32>,
+}
+
+impl DensityField {
+    pub fn dimensions(&self) -> (u32, u32, u32) {
+        (self.size_x, self.size_y, self.size_z)
+    }
+
+    /// Get the density at `(x, y, z)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y, z)` is outside the grid.
+    pub fn density(&self, x: u32, y: u32, z: u32) -> f32 {
+        assert!(x < self.size_x && y < self.size_y && z < self.size_z);
+        self.values[x as usize
+            + y as usize * self.size_x as usize
+            + z as usize * self.size_x as usize * self.size_y as usize]
+    }
+
+    /// Get all densities, x first, then y, then z.
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+}
+
+/// A volumetric cloud generator, thresholding fractal noise into cloud
+/// densities.
+///
+/// The coverage sets how much of the sky is cloudy, and the softness how
+/// gradually the density rises from the edge of a cloud to it's core. The
+/// density also fades out towards the bottom and top of the buffer, giving
+/// clouds flatter bottoms than tops.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::clouds::Clouds;
+/// Clouds::new(64, 64, 16)
+///     .coverage(0.4)
+///     .softness(0.2)
+///     .seed(5)
+///     .render()
+///     .save("test/volumes/clouds.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Clouds {
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    coverage: f32,
+    softness: f32,
+    frequency: f64,
+    octaves: usize,
+    color: Rgba,
+    seed: u64,
+}
+
+impl Clouds {
+    /// Create a generator filling a buffer `size_x` by `size_y` by `size_z`
+    /// with clouds.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> Clouds {
+        Clouds {
+            size_x: size_x.max(1),
+            size_y: size_y.max(1),
+            size_z: size_z.max(1),
+            coverage: 0.5,
+            softness: 0.15,
+            frequency: 1.0 / 24.0,
+            octaves: 5,
+            color: Rgba([245, 245, 250, 255]),
+            seed: 0,
+        }
+    }
+
+    /// Set the `coverage` of the sky, from 0.0 for clear skies to 1.0 for
+    /// an overcast one.
+    pub fn coverage(&mut self, coverage: f32) -> &mut Self {
+        self.coverage = coverage.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the `softness` of the cloud edges, as the range of noise values
+    /// over which density rises from 0.0 to 1.0.
+    pub fn softness(&mut self, softness: f32) -> &mut Self {
+        self.softness = softness.max(f32::EPSILON);
+        self
+    }
+
+    /// Set the frequency of the noise, where lower frequencies give larger
+    /// clouds.
+    pub fn frequency(&mut self, frequency: f64) -> &mut Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Set the number of noise octaves, where more octaves give wispier
+    /// edges.
+    pub fn octaves(&mut self, octaves: usize) -> &mut Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn color(&mut self, color: Rgba) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the `seed` of the noise.
+    ///
+    /// The same seed always gives the same clouds.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Generate the cloud densities.
+    pub fn density(&self) -> DensityField {
+        let mut params = NoiseParams::new();
+        params
+            .generator(Generator::Fbm)
+            .frequency(self.frequency)
+            .octaves(self.octaves)
+            .seed(Seed::new(self.seed).noise());
+        let noise = params.sampler();
+        // Fractal noise mostly stays within -0.4 to 0.4, centered on 0.0, so
+        // half coverage thresholds at the middle of that range.
+        let threshold = 0.4 - 0.8 * self.coverage;
+        let mut values = Vec::with_capacity((self.size_x * self.size_y * self.size_z) as usize);
+        for z in 0..self.size_z {
+            let height = (z as f32 + 0.5) / self.size_z as f32;
+            // Rise quickly from the bottom and fade slowly towards the top.
+            let profile = (height / 0.2).min(1.0) * ((1.0 - height) / 0.6).min(1.0);
+            for y in 0..self.size_y {
+                for x in 0..self.size_x {
+                    let n = noise(x as f64, y as f64, z as f64) as f32;
+                    let density = (n - threshold) / self.softness;
+                    values.push((density * profile).clamp(0.0, 1.0));
+                }
+            }
+        }
+        DensityField {
+            size_x: self.size_x,
+            size_y: self.size_y,
+            size_z: self.size_z,
+            values,
+        }
+    }
+
+    /// Generate the clouds into a new buffer, as voxels of the cloud color
+    /// with the density as their transparency.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let field = self.density();
+        let mut buf = ArrayVoxelBuffer::new(self.size_x, self.size_y, self.size_z);
+        for z in 0..self.size_z {
+            for y in 0..self.size_y {
+                for x in 0..self.size_x {
+                    let level = (field.density(x, y, z) * ALPHA_LEVELS as f32).ceil();
+                    if level == 0.0 {
+                        continue;
+                    }
+                    let alpha = level / ALPHA_LEVELS as f32 * self.color.0[3] as f32;
+                    let mut color = self.color;
+                    color.0[3] = alpha.round() as u8;
+                    *buf.voxel_mut(x, y, z) = color;
+                }
+            }
+        }
+        buf
+    }
+}
@@ -0,0 +1,453 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::compat::HashMap;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+use core::str::FromStr;
+use enterpolation::{linear::Linear, Curve, Generator};
+use palette::{IntoColor, Lab, LinSrgba, Srgb, Srgba};
+use serde::{Deserialize, Deserializer};
+
+/// A palette of up to 255 colors, the most a MagicaVoxel .vox file holds,
+/// with optional names.
+///
+/// Colors keep the index they were inserted at, so buffers saved with
+/// `ArrayVoxelBuffer::save_with_palette` share palette indices, and
+/// `quantize` snaps the colors of a buffer to the palette. Colors are
+/// matched by their distance in CIE L*a*b* space, which follows perceived
+/// color differences more closely than RGB.
+///
+/// # Examples
+///
+/// Snap a rainbow to a few named colors and save it with their indices.
+/// ```
+/// # use voxgen::color::Palette;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let mut palette = Palette::new();
+/// palette.insert_named("red", Rgba([200, 40, 40, 255]));
+/// palette.insert_named("leaf", Rgba([60, 140, 50, 255]));
+/// palette.insert_named("sky", Rgba([90, 150, 220, 255]));
+/// assert_eq!(palette.named("leaf"), Some(Rgba([60, 140, 50, 255])));
+/// assert_eq!(palette.remaining(), 252);
+///
+/// let mut buf = ArrayVoxelBuffer::new(16, 1, 1);
+/// for x in 0..16 {
+///     *buf.voxel_mut(x, 0, 0) = Rgba([255 - x as u8 * 16, x as u8 * 8, x as u8 * 16, 255]);
+/// }
+/// palette.quantize(&mut buf);
+/// assert_eq!(*buf.voxel(0, 0, 0), Rgba([200, 40, 40, 255]));
+/// assert_eq!(*buf.voxel(15, 0, 0), Rgba([90, 150, 220, 255]));
+/// buf.save_with_palette("test/volumes/palette.vox", &palette)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<Rgba>,
+    labs: Vec<Lab>,
+    names: HashMap<String, usize>,
+}
+
+impl Palette {
+    /// The most colors a palette holds.
+    pub const MAX_COLORS: usize = 255;
+
+    pub fn new() -> Palette {
+        Palette {
+            colors: Vec::new(),
+            labs: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Create the default MagicaVoxel palette, see `MAGICAVOXEL_PALETTE`.
+    pub fn magicavoxel() -> Palette {
+        Palette {
+            colors: MAGICAVOXEL_PALETTE.to_vec(),
+            labs: MAGICAVOXEL_PALETTE.iter().map(|&c| lab(c)).collect(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Add `color`, returning it's index, or `None` if the palette is full.
+    ///
+    /// Colors already in the palette keep their index.
+    pub fn insert(&mut self, color: Rgba) -> Option<usize> {
+        if let Some(i) = self.index_of(color) {
+            return Some(i);
+        }
+        if self.is_full() {
+            return None;
+        }
+        self.colors.push(color);
+        self.labs.push(lab(color));
+        Some(self.colors.len() - 1)
+    }
+
+    /// Add `color` like `insert`, and name it `name`, replacing the color
+    /// named `name` before if any.
+    pub fn insert_named(&mut self, name: &str, color: Rgba) -> Option<usize> {
+        let i = self.insert(color)?;
+        self.names.insert(name.to_string(), i);
+        Some(i)
+    }
+
+    /// Get the color named `name`.
+    pub fn named(&self, name: &str) -> Option<Rgba> {
+        self.names.get(name).map(|&i| self.colors[i])
+    }
+
+    /// Get the color at `index`.
+    pub fn color(&self, index: usize) -> Option<Rgba> {
+        self.colors.get(index).copied()
+    }
+
+    /// Get the index of `color`, if it's exactly in the palette.
+    pub fn index_of(&self, color: Rgba) -> Option<usize> {
+        self.colors.iter().position(|&c| c == color)
+    }
+
+    /// Get the index of the color closest to `color` in L*a*b* space, or
+    /// `None` if the palette is empty.
+    ///
+    /// Transparency is ignored.
+    pub fn nearest(&self, color: Rgba) -> Option<usize> {
+        let target = lab(color);
+        self.labs
+            .iter()
+            .map(|l| {
+                let (dl, da, db) = (l.l - target.l, l.a - target.a, l.b - target.b);
+                dl * dl + da * da + db * db
+            })
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Get the colors in index order.
+    pub fn colors(&self) -> &[Rgba] {
+        &self.colors
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// Get the number of colors that can still be added.
+    pub fn remaining(&self) -> usize {
+        Self::MAX_COLORS - self.colors.len()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Replace the color of every visible voxel of `buf` with the nearest
+    /// color of the palette, keeping it's transparency.
+    ///
+    /// Does nothing if the palette is empty.
+    pub fn quantize(&self, buf: &mut ArrayVoxelBuffer<Rgba>) {
+        if self.is_empty() {
+            return;
+        }
+        let mut nearest: HashMap<Rgba, Rgba> = HashMap::new();
+        for (_, voxel) in buf.enumerate_mut().filter(|(_, v)| v.0[3] != 0) {
+            let Rgba([r, g, b, _]) = *nearest
+                .entry(*voxel)
+                .or_insert_with(|| self.colors[self.nearest(*voxel).unwrap()]);
+            *voxel = Rgba([r, g, b, voxel.0[3]]);
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The default MagicaVoxel palette, as packed `0xAABBGGRR` colors indexed
+/// by color index, copied from the .vox file format spec.
+///
+/// Index 0 is the empty voxel.
+#[rustfmt::skip]
+const DEFAULT_PALETTE: [u32; 256] = [
+    0x00000000, 0xffffffff, 0xffccffff, 0xff99ffff, 0xff66ffff, 0xff33ffff, 0xff00ffff, 0xffffccff,
+    0xffccccff, 0xff99ccff, 0xff66ccff, 0xff33ccff, 0xff00ccff, 0xffff99ff, 0xffcc99ff, 0xff9999ff,
+    0xff6699ff, 0xff3399ff, 0xff0099ff, 0xffff66ff, 0xffcc66ff, 0xff9966ff, 0xff6666ff, 0xff3366ff,
+    0xff0066ff, 0xffff33ff, 0xffcc33ff, 0xff9933ff, 0xff6633ff, 0xff3333ff, 0xff0033ff, 0xffff00ff,
+    0xffcc00ff, 0xff9900ff, 0xff6600ff, 0xff3300ff, 0xff0000ff, 0xffffffcc, 0xffccffcc, 0xff99ffcc,
+    0xff66ffcc, 0xff33ffcc, 0xff00ffcc, 0xffffcccc, 0xffcccccc, 0xff99cccc, 0xff66cccc, 0xff33cccc,
+    0xff00cccc, 0xffff99cc, 0xffcc99cc, 0xff9999cc, 0xff6699cc, 0xff3399cc, 0xff0099cc, 0xffff66cc,
+    0xffcc66cc, 0xff9966cc, 0xff6666cc, 0xff3366cc, 0xff0066cc, 0xffff33cc, 0xffcc33cc, 0xff9933cc,
+    0xff6633cc, 0xff3333cc, 0xff0033cc, 0xffff00cc, 0xffcc00cc, 0xff9900cc, 0xff6600cc, 0xff3300cc,
+    0xff0000cc, 0xffffff99, 0xffccff99, 0xff99ff99, 0xff66ff99, 0xff33ff99, 0xff00ff99, 0xffffcc99,
+    0xffcccc99, 0xff99cc99, 0xff66cc99, 0xff33cc99, 0xff00cc99, 0xffff9999, 0xffcc9999, 0xff999999,
+    0xff669999, 0xff339999, 0xff009999, 0xffff6699, 0xffcc6699, 0xff996699, 0xff666699, 0xff336699,
+    0xff006699, 0xffff3399, 0xffcc3399, 0xff993399, 0xff663399, 0xff333399, 0xff003399, 0xffff0099,
+    0xffcc0099, 0xff990099, 0xff660099, 0xff330099, 0xff000099, 0xffffff66, 0xffccff66, 0xff99ff66,
+    0xff66ff66, 0xff33ff66, 0xff00ff66, 0xffffcc66, 0xffcccc66, 0xff99cc66, 0xff66cc66, 0xff33cc66,
+    0xff00cc66, 0xffff9966, 0xffcc9966, 0xff999966, 0xff669966, 0xff339966, 0xff009966, 0xffff6666,
+    0xffcc6666, 0xff996666, 0xff666666, 0xff336666, 0xff006666, 0xffff3366, 0xffcc3366, 0xff993366,
+    0xff663366, 0xff333366, 0xff003366, 0xffff0066, 0xffcc0066, 0xff990066, 0xff660066, 0xff330066,
+    0xff000066, 0xffffff33, 0xffccff33, 0xff99ff33, 0xff66ff33, 0xff33ff33, 0xff00ff33, 0xffffcc33,
+    0xffcccc33, 0xff99cc33, 0xff66cc33, 0xff33cc33, 0xff00cc33, 0xffff9933, 0xffcc9933, 0xff999933,
+    0xff669933, 0xff339933, 0xff009933, 0xffff6633, 0xffcc6633, 0xff996633, 0xff666633, 0xff336633,
+    0xff006633, 0xffff3333, 0xffcc3333, 0xff993333, 0xff663333, 0xff333333, 0xff003333, 0xffff0033,
+    0xffcc0033, 0xff990033, 0xff660033, 0xff330033, 0xff000033, 0xffffff00, 0xffccff00, 0xff99ff00,
+    0xff66ff00, 0xff33ff00, 0xff00ff00, 0xffffcc00, 0xffcccc00, 0xff99cc00, 0xff66cc00, 0xff33cc00,
+    0xff00cc00, 0xffff9900, 0xffcc9900, 0xff999900, 0xff669900, 0xff339900, 0xff009900, 0xffff6600,
+    0xffcc6600, 0xff996600, 0xff666600, 0xff336600, 0xff006600, 0xffff3300, 0xffcc3300, 0xff993300,
+    0xff663300, 0xff333300, 0xff003300, 0xffff0000, 0xffcc0000, 0xff990000, 0xff660000, 0xff330000,
+    0xffee0000, 0xffdd0000, 0xffbb0000, 0xffaa0000, 0xff880000, 0xff770000, 0xff550000, 0xff440000,
+    0xff220000, 0xff110000, 0xff00ee00, 0xff00dd00, 0xff00bb00, 0xff00aa00, 0xff008800, 0xff007700,
+    0xff005500, 0xff004400, 0xff002200, 0xff001100, 0xff0000ee, 0xff0000dd, 0xff0000bb, 0xff0000aa,
+    0xff000088, 0xff000077, 0xff000055, 0xff000044, 0xff000022, 0xff000011, 0xffeeeeee, 0xffdddddd,
+    0xffbbbbbb, 0xffaaaaaa, 0xff888888, 0xff777777, 0xff555555, 0xff444444, 0xff222222, 0xff111111,
+];
+
+/// The colors of the default MagicaVoxel palette, which the editor uses for
+/// new models and files without a palette.
+///
+/// Color index `i` of a .vox file is `MAGICAVOXEL_PALETTE[i - 1]`, the
+/// same as `Palette::magicavoxel().color(i - 1)`.
+pub const MAGICAVOXEL_PALETTE: [Rgba; Palette::MAX_COLORS] = {
+    let mut colors = [Rgba([0; 4]); Palette::MAX_COLORS];
+    let mut i = 0;
+    while i < Palette::MAX_COLORS {
+        colors[i] = Rgba(DEFAULT_PALETTE[i + 1].to_le_bytes());
+        i += 1;
+    }
+    colors
+};
+
+/// An error returned when a color can't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseColorError {
+    /// The color isn't a valid hex color or color name.
+    Invalid(String),
+    /// A gradient was given no colors.
+    Empty,
+}
+
+impl core::fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseColorError::Invalid(color) => write!(f, "invalid color `{}`", color),
+            ParseColorError::Empty => write!(f, "gradient has no colors"),
+        }
+    }
+}
+
+impl core::error::Error for ParseColorError {}
+
+impl Rgba {
+    /// Parse a `#rrggbb` or `#rrggbbaa` hex color, with or without the `#`.
+    ///
+    /// Colors without an alpha channel are opaque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::Rgba;
+    /// assert_eq!(Rgba::from_hex("#8b5a2b"), Ok(Rgba([0x8b, 0x5a, 0x2b, 255])));
+    /// assert_eq!(Rgba::from_hex("#8b5a2b80")?.to_hex(), "#8b5a2b80");
+    /// assert_eq!(Rgba::named("ForestGreen"), Some(Rgba([34, 139, 34, 255])));
+    /// assert_eq!("tomato".parse(), Ok(Rgba([255, 99, 71, 255])));
+    /// assert!("#ggg".parse::<Rgba>().is_err());
+    /// # Ok::<(), voxgen::color::ParseColorError>(())
+    /// ```
+    pub fn from_hex(hex: &str) -> Result<Rgba, ParseColorError> {
+        let invalid = || ParseColorError::Invalid(hex.to_string());
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+            return Err(invalid());
+        }
+        let mut rgba = [255; 4];
+        for (i, channel) in rgba.iter_mut().enumerate().take(digits.len() / 2) {
+            *channel = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Rgba(rgba))
+    }
+
+    /// Get the opaque CSS color `name`, ignoring case, or `None` if there's
+    /// no such color.
+    ///
+    /// `"transparent"` is the empty voxel color.
+    pub fn named(name: &str) -> Option<Rgba> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Some(Rgba([0, 0, 0, 0]));
+        }
+        palette::named::from_str(&name).map(|c| Rgba([c.red, c.green, c.blue, 255]))
+    }
+
+    /// Format as a `#rrggbb` hex color, or `#rrggbbaa` if it isn't opaque.
+    pub fn to_hex(&self) -> String {
+        let Rgba([r, g, b, a]) = *self;
+        if a == 255 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+}
+
+/// Parses CSS color names and hex colors.
+impl FromStr for Rgba {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Rgba, ParseColorError> {
+        match Rgba::named(s) {
+            Some(color) => Ok(color),
+            None => Rgba::from_hex(s),
+        }
+    }
+}
+
+/// Deserializes colors written as `[r, g, b, a]` arrays, hex colors or CSS
+/// color names, so config files can use whichever reads best.
+impl<'de> Deserialize<'de> for Rgba {
+    fn deserialize<D>(deserializer: D) -> Result<Rgba, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Color {
+            Bytes([u8; 4]),
+            Text(String),
+        }
+        match Color::deserialize(deserializer)? {
+            Color::Bytes(bytes) => Ok(Rgba(bytes)),
+            Color::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// The control points of `Gradient::rainbow`.
+const RAINBOW: [Rgba; 7] = [
+    Rgba([255, 0, 0, 255]),
+    Rgba([255, 255, 0, 255]),
+    Rgba([0, 255, 0, 255]),
+    Rgba([0, 255, 255, 255]),
+    Rgba([0, 0, 255, 255]),
+    Rgba([255, 0, 255, 255]),
+    Rgba([255, 0, 0, 255]),
+];
+
+/// A color gradient through evenly spaced control points, blended in linear
+/// RGB.
+///
+/// Sample it at any position from 0.0 to 1.0 with `sample`, or take evenly
+/// spaced colors with `take`, like the colors of a rainbow mode render.
+///
+/// # Examples
+///
+/// Color a pillar from dark to light wood.
+/// ```
+/// # use voxgen::color::Gradient;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let wood = Gradient::from_hex(["#4a2e19", "#8b5a2b", "#d2a76a"])?;
+/// assert_eq!(wood.sample(0.0), Rgba([0x4a, 0x2e, 0x19, 255]));
+/// let mut buf = ArrayVoxelBuffer::new(1, 1, 16);
+/// for (z, color) in wood.take(16).into_iter().enumerate() {
+///     *buf.voxel_mut(0, 0, z as u32) = color;
+/// }
+/// buf.save("test/volumes/gradient.vox")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    colors: Vec<Rgba>,
+    linear: Vec<LinSrgba>,
+}
+
+impl Gradient {
+    /// Create a gradient through the control points `colors`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `colors` is empty.
+    pub fn new(colors: Vec<Rgba>) -> Gradient {
+        assert!(!colors.is_empty(), "Gradient needs at least one color");
+        let linear = colors
+            .iter()
+            .map(|Rgba(c)| Srgba::from(*c).into_linear())
+            .collect();
+        Gradient { colors, linear }
+    }
+
+    /// Create a gradient through red, yellow, green, cyan, blue, magenta and
+    /// back to red.
+    pub fn rainbow() -> Gradient {
+        Gradient::new(RAINBOW.to_vec())
+    }
+
+    /// Create a gradient through the `#rrggbb` or `#rrggbbaa` hex colors
+    /// `colors`.
+    pub fn from_hex<I, S>(colors: I) -> Result<Gradient, ParseColorError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let colors = colors
+            .into_iter()
+            .map(|color| Rgba::from_hex(color.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.is_empty() {
+            return Err(ParseColorError::Empty);
+        }
+        Ok(Gradient::new(colors))
+    }
+
+    /// Get the control points.
+    pub fn colors(&self) -> &[Rgba] {
+        &self.colors
+    }
+
+    /// Get the color at position `t`, from the first control point at 0.0
+    /// to the last at 1.0.
+    pub fn sample(&self, t: f32) -> Rgba {
+        match self.curve() {
+            Some(curve) => Rgba(Srgba::from_linear(curve.gen(t.clamp(0.0, 1.0))).into()),
+            None => self.colors[0],
+        }
+    }
+
+    /// Get `n` evenly spaced colors, from the first control point to the
+    /// last.
+    pub fn take(&self, n: usize) -> Vec<Rgba> {
+        match self.curve() {
+            Some(curve) => curve
+                .take(n)
+                .map(|linear| Rgba(Srgba::from_linear(linear).into()))
+                .collect(),
+            None => vec![self.colors[0]; n],
+        }
+    }
+
+    /// Build the curve through the control points, or `None` for a single
+    /// control point.
+    fn curve(&self) -> Option<impl Curve<f32, Output = LinSrgba> + '_> {
+        Linear::builder()
+            .elements(&self.linear[..])
+            .equidistant::<f32>()
+            .normalized()
+            .build()
+            .ok()
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self::rainbow()
+    }
+}
+
+/// Convert `color` to L*a*b*, ignoring transparency.
+fn lab(Rgba([r, g, b, _]): Rgba) -> Lab {
+    Srgb::new(r, g, b).into_format::<f32>().into_color()
+}
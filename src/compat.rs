@@ -0,0 +1,37 @@
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap, HashSet};
+
+#[cfg(not(feature = "std"))]
+pub use hashbrown::{HashMap, HashSet};
+
+#[cfg(feature = "std")]
+pub use std::time::Instant;
+
+/// A stand-in for `std::time::Instant` without a clock, that measures every
+/// duration as zero.
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug)]
+pub struct Instant;
+
+#[cfg(not(feature = "std"))]
+impl Instant {
+    pub fn now() -> Instant {
+        Instant
+    }
+
+    pub fn elapsed(&self) -> core::time::Duration {
+        core::time::Duration::ZERO
+    }
+}
+
+/// The names of the standard library prelude missing from the core prelude,
+/// for `use crate::compat::prelude::*` without `std`.
+#[cfg(not(feature = "std"))]
+pub mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::format;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+    pub use num_traits::Float;
+}
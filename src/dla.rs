@@ -0,0 +1,255 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+
+/// The most steps a single particle walks before growth gives up on it.
+const MAX_WALK_STEPS: usize = 1 << 20;
+
+/// The distinct colors of an age gradient, keeping under the MagicaVoxel
+/// palette limit.
+const AGE_LEVELS: u32 = 32;
+
+const DIRECTIONS: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// A diffusion-limited aggregation generator, growing branching clusters
+/// like corals, lightning and crystals.
+///
+/// Particles walk randomly from around the cluster until they touch it,
+/// sticking with a probability given by the stickiness. Low stickiness lets
+/// particles slip further in, giving denser clusters, while a drift pulls
+/// the walks in a direction, growing the cluster against it.
+///
+/// # Examples
+///
+/// Grow a coral up from the sea floor, colored from old to young.
+/// ```
+/// # use voxgen::dla::Dla;
+/// # use voxgen::voxel_buffer::Rgba;
+/// Dla::new(32, 32, 32)
+///     .seed_points(vec![(16, 16, 0)])
+///     .particles(1500)
+///     .drift([0.0, 0.0, -0.3])
+///     .age_colors(Rgba([200, 80, 90, 255]), Rgba([250, 200, 180, 255]))
+///     .seed(1)
+///     .render()
+///     .save("test/volumes/dla.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Dla {
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    seed_points: Vec<(u32, u32, u32)>,
+    particles: usize,
+    stickiness: f64,
+    drift: [f32; 3],
+    color: Rgba,
+    age_colors: Option<(Rgba, Rgba)>,
+    seed: u64,
+}
+
+impl Dla {
+    /// Create a generator growing a cluster in a buffer `size_x` by `size_y`
+    /// by `size_z`, from a single seed point in the center.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> Dla {
+        let (size_x, size_y, size_z) = (size_x.max(1), size_y.max(1), size_z.max(1));
+        Dla {
+            size_x,
+            size_y,
+            size_z,
+            seed_points: vec![(size_x / 2, size_y / 2, size_z / 2)],
+            particles: 1000,
+            stickiness: 1.0,
+            drift: [0.0; 3],
+            color: Rgba([180, 220, 255, 255]),
+            age_colors: None,
+            seed: 0,
+        }
+    }
+
+    /// Set the points the cluster grows from.
+    ///
+    /// Points outside the buffer are ignored.
+    pub fn seed_points(&mut self, points: Vec<(u32, u32, u32)>) -> &mut Self {
+        self.seed_points = points;
+        self
+    }
+
+    /// Set the number of particles added to the cluster.
+    pub fn particles(&mut self, n: usize) -> &mut Self {
+        self.particles = n;
+        self
+    }
+
+    /// Set the probability of a particle sticking on every step it touches
+    /// the cluster.
+    pub fn stickiness(&mut self, stickiness: f64) -> &mut Self {
+        self.stickiness = stickiness.clamp(0.01, 1.0);
+        self
+    }
+
+    /// Set the `drift` biasing the walks, with a length from 0.0 for no bias
+    /// to 1.0 for a strong one.
+    pub fn drift(&mut self, drift: [f32; 3]) -> &mut Self {
+        self.drift = drift;
+        self
+    }
+
+    /// Set the `color` of the cluster, clearing any age colors.
+    pub fn color(&mut self, color: Rgba) -> &mut Self {
+        self.color = color;
+        self.age_colors = None;
+        self
+    }
+
+    /// Color the cluster by age, from `oldest` at the seed points to
+    /// `youngest` at the last particle stuck.
+    pub fn age_colors(&mut self, oldest: Rgba, youngest: Rgba) -> &mut Self {
+        self.age_colors = Some((oldest, youngest));
+        self
+    }
+
+    /// Set the `seed` of the random number generator driving the walks.
+    ///
+    /// The same seed always gives the same cluster.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    fn index(&self, p: [i64; 3]) -> Option<usize> {
+        let inside = p[0] >= 0
+            && p[1] >= 0
+            && p[2] >= 0
+            && p[0] < self.size_x as i64
+            && p[1] < self.size_y as i64
+            && p[2] < self.size_z as i64;
+        inside.then(|| {
+            (p[0] + p[1] * self.size_x as i64 + p[2] * (self.size_x * self.size_y) as i64) as usize
+        })
+    }
+
+    /// Grow the cluster, returning it's voxels in the order they joined it,
+    /// starting with the seed points.
+    ///
+    /// Growth stops early if the buffer fills up or a particle can't reach
+    /// the cluster.
+    pub fn grow(&self) -> Vec<(u32, u32, u32)> {
+        let mut rng = Seed::new(self.seed).rng();
+        let mut occupied = vec![false; (self.size_x * self.size_y * self.size_z) as usize];
+        let mut cluster = Vec::new();
+        let (mut min, mut max) = ([i64::MAX; 3], [i64::MIN; 3]);
+        for &(x, y, z) in &self.seed_points {
+            let p = [x as i64, y as i64, z as i64];
+            if let Some(i) = self.index(p) {
+                if !occupied[i] {
+                    occupied[i] = true;
+                    cluster.push((x, y, z));
+                    min = std::array::from_fn(|k| min[k].min(p[k]));
+                    max = std::array::from_fn(|k| max[k].max(p[k]));
+                }
+            }
+        }
+        if cluster.is_empty() {
+            return cluster;
+        }
+        let size = [self.size_x as i64, self.size_y as i64, self.size_z as i64];
+        let weights: Vec<f32> = DIRECTIONS
+            .iter()
+            .map(|d| {
+                let along: f32 = (0..3).map(|k| d[k] as f32 * self.drift[k]).sum();
+                (1.0 + along).max(0.05)
+            })
+            .collect();
+        let total: f32 = weights.iter().sum();
+        'particles: for _ in 0..self.particles {
+            // Particles start on a box around the cluster, and start over
+            // if they wander too far from it.
+            let margin = 3;
+            let spawn_min: [i64; 3] = std::array::from_fn(|k| (min[k] - margin).max(0));
+            let spawn_max: [i64; 3] = std::array::from_fn(|k| (max[k] + margin).min(size[k] - 1));
+            let kill_min: [i64; 3] = std::array::from_fn(|k| min[k] - 3 * margin);
+            let kill_max: [i64; 3] = std::array::from_fn(|k| max[k] + 3 * margin);
+            let mut p = [0; 3];
+            let mut spawn = true;
+            for steps in 0.. {
+                if steps == MAX_WALK_STEPS {
+                    break 'particles;
+                }
+                if spawn {
+                    let face = rng.gen_range(0..3);
+                    p = std::array::from_fn(|k| rng.gen_range(spawn_min[k]..=spawn_max[k]));
+                    p[face] = if rng.gen() {
+                        spawn_min[face]
+                    } else {
+                        spawn_max[face]
+                    };
+                    spawn = occupied[self.index(p).unwrap()];
+                    continue;
+                }
+                let touching = DIRECTIONS.iter().any(|d| {
+                    self.index([p[0] + d[0], p[1] + d[1], p[2] + d[2]])
+                        .is_some_and(|i| occupied[i])
+                });
+                if touching && rng.gen_bool(self.stickiness) {
+                    break;
+                }
+                let mut pick = rng.gen::<f32>() * total;
+                let mut direction = DIRECTIONS[5];
+                for (d, w) in DIRECTIONS.iter().zip(&weights) {
+                    if pick < *w {
+                        direction = *d;
+                        break;
+                    }
+                    pick -= w;
+                }
+                let next = [
+                    p[0] + direction[0],
+                    p[1] + direction[1],
+                    p[2] + direction[2],
+                ];
+                match self.index(next) {
+                    Some(i) if occupied[i] => {}
+                    Some(_) if (0..3).all(|k| next[k] >= kill_min[k] && next[k] <= kill_max[k]) => {
+                        p = next;
+                    }
+                    _ => spawn = true,
+                }
+            }
+            occupied[self.index(p).unwrap()] = true;
+            cluster.push((p[0] as u32, p[1] as u32, p[2] as u32));
+            min = std::array::from_fn(|k| min[k].min(p[k]));
+            max = std::array::from_fn(|k| max[k].max(p[k]));
+        }
+        cluster
+    }
+
+    /// Grow the cluster into a new buffer.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let cluster = self.grow();
+        let mut buf = ArrayVoxelBuffer::new(self.size_x, self.size_y, self.size_z);
+        let last = cluster.len().saturating_sub(1).max(1) as f32;
+        for (age, &(x, y, z)) in cluster.iter().enumerate() {
+            *buf.voxel_mut(x, y, z) = match self.age_colors {
+                None => self.color,
+                Some((oldest, youngest)) => {
+                    let level = (age as f32 / last * (AGE_LEVELS - 1) as f32).round();
+                    let t = level / (AGE_LEVELS - 1) as f32;
+                    Rgba(std::array::from_fn(|c| {
+                        (oldest.0[c] as f32 + (youngest.0[c] as f32 - oldest.0[c] as f32) * t)
+                            .round() as u8
+                    }))
+                }
+            };
+        }
+        buf
+    }
+}
@@ -0,0 +1,417 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+/// A rectangle of tiles, from `(x, y)` up to but not including `(x + width,
+/// y + depth)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub depth: u32,
+}
+
+impl Rect {
+    /// Get the tile at the center of the rectangle.
+    pub fn center(&self) -> (u32, u32) {
+        (self.x + self.width / 2, self.y + self.depth / 2)
+    }
+
+    /// Check whether the tile at `(x, y)` is inside the rectangle.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.depth
+    }
+}
+
+/// What a dungeon tile is made of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tile {
+    Rock,
+    Room,
+    Corridor,
+    /// A corridor tile where it enters a room.
+    Door,
+}
+
+/// A door where a corridor enters a room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Door {
+    pub x: u32,
+    pub y: u32,
+    /// The index of the room the door opens into.
+    pub room: usize,
+}
+
+/// A corridor connecting two rooms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Corridor {
+    /// The indices of the connected rooms.
+    pub rooms: (usize, usize),
+    /// The tiles along the corridor from the center of the first room to the
+    /// center of the second, including the tiles inside the rooms.
+    pub path: Vec<(u32, u32)>,
+}
+
+/// The structure of a generated dungeon, for placing things in it and
+/// driving game logic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    size_x: u32,
+    size_y: u32,
+    tiles: Vec<Tile>,
+    rooms: Vec<Rect>,
+    corridors: Vec<Corridor>,
+    doors: Vec<Door>,
+}
+
+impl Layout {
+    /// Get the layout dimensions in tiles as a tuple `(size_x, size_y)`.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.size_x, self.size_y)
+    }
+
+    /// Get the tile at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside the layout.
+    pub fn tile(&self, x: u32, y: u32) -> Tile {
+        assert!(x < self.size_x && y < self.size_y);
+        self.tiles[(x + y * self.size_x) as usize]
+    }
+
+    pub fn rooms(&self) -> &[Rect] {
+        &self.rooms
+    }
+
+    pub fn corridors(&self) -> &[Corridor] {
+        &self.corridors
+    }
+
+    pub fn doors(&self) -> &[Door] {
+        &self.doors
+    }
+
+    fn set_tile(&mut self, x: u32, y: u32, tile: Tile) {
+        self.tiles[(x + y * self.size_x) as usize] = tile;
+    }
+
+    /// Get the index of the room containing `(x, y)`, if any.
+    fn room_at(&self, x: u32, y: u32) -> Option<usize> {
+        self.rooms.iter().position(|room| room.contains(x, y))
+    }
+}
+
+/// A rooms and corridors dungeon generator using binary space partitioning.
+///
+/// The area is split in two recursively until the parts fit the largest room
+/// or are too small to hold two rooms, a room of random size is placed in every part, and the
+/// closest rooms of every pair of sibling parts are joined by an L shaped
+/// corridor, so every room can be reached.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::dungeon::Dungeon;
+/// let (buf, layout) = Dungeon::new().size(64, 48).seed(3).render();
+/// assert!(layout.rooms().len() > 1);
+/// buf.save("test/volumes/dungeon.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Dungeon {
+    size_x: u32,
+    size_y: u32,
+    min_room_size: u32,
+    max_room_size: u32,
+    wall_height: u32,
+    seed: u64,
+    floor_color: Rgba,
+    corridor_color: Rgba,
+    wall_color: Rgba,
+    door_color: Rgba,
+}
+
+impl Dungeon {
+    pub fn new() -> Dungeon {
+        Dungeon {
+            size_x: 48,
+            size_y: 48,
+            min_room_size: 4,
+            max_room_size: 12,
+            wall_height: 4,
+            seed: 0,
+            floor_color: Rgba([120, 110, 100, 255]),
+            corridor_color: Rgba([90, 85, 80, 255]),
+            wall_color: Rgba([60, 60, 70, 255]),
+            door_color: Rgba([140, 90, 40, 255]),
+        }
+    }
+
+    /// Set the dungeon size in tiles, which are one voxel each.
+    pub fn size(&mut self, size_x: u32, size_y: u32) -> &mut Self {
+        self.size_x = size_x;
+        self.size_y = size_y;
+        self
+    }
+
+    /// Set the smallest and largest width and depth of rooms, not counting
+    /// their walls.
+    pub fn room_size(&mut self, min: u32, max: u32) -> &mut Self {
+        self.min_room_size = min.max(1);
+        self.max_room_size = max.max(self.min_room_size);
+        self
+    }
+
+    pub fn wall_height(&mut self, height: u32) -> &mut Self {
+        self.wall_height = height.max(1);
+        self
+    }
+
+    /// Set the `seed` of the random number generator splitting the area and
+    /// placing rooms.
+    ///
+    /// The same seed always gives the same dungeon.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the floor colors of rooms and corridors.
+    pub fn floor_colors(&mut self, room: Rgba, corridor: Rgba) -> &mut Self {
+        self.floor_color = room;
+        self.corridor_color = corridor;
+        self
+    }
+
+    pub fn wall_color(&mut self, color: Rgba) -> &mut Self {
+        self.wall_color = color;
+        self
+    }
+
+    /// Set the `color` of the floors and lintels of doors.
+    pub fn door_color(&mut self, color: Rgba) -> &mut Self {
+        self.door_color = color;
+        self
+    }
+
+    /// Generate the layout of a dungeon.
+    pub fn generate(&self) -> Layout {
+        let mut rng = Seed::new(self.seed).rng();
+        let mut layout = Layout {
+            size_x: self.size_x,
+            size_y: self.size_y,
+            tiles: vec![Tile::Rock; (self.size_x * self.size_y) as usize],
+            rooms: Vec::new(),
+            corridors: Vec::new(),
+            doors: Vec::new(),
+        };
+        let area = Rect {
+            x: 0,
+            y: 0,
+            width: self.size_x,
+            depth: self.size_y,
+        };
+        self.partition(area, &mut layout, &mut rng);
+        for corridor in &layout.corridors {
+            for &(x, y) in &corridor.path {
+                let tile = &mut layout.tiles[(x + y * layout.size_x) as usize];
+                if *tile == Tile::Rock {
+                    *tile = Tile::Corridor;
+                }
+            }
+        }
+        // A door is the first corridor tile outside a room along a path.
+        let mut doors = Vec::new();
+        for corridor in &layout.corridors {
+            for pair in corridor.path.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let (room_a, room_b) = (layout.room_at(a.0, a.1), layout.room_at(b.0, b.1));
+                let door = match (room_a, room_b) {
+                    (Some(room), None) => Some((b, room)),
+                    (None, Some(room)) => Some((a, room)),
+                    _ => None,
+                };
+                if let Some(((x, y), room)) = door {
+                    if !doors.contains(&Door { x, y, room }) {
+                        doors.push(Door { x, y, room });
+                    }
+                }
+            }
+        }
+        for door in &doors {
+            layout.set_tile(door.x, door.y, Tile::Door);
+        }
+        layout.doors = doors;
+        layout
+    }
+
+    /// Split `area` until it fits a single room, place the rooms and connect
+    /// them, returning the indices of the rooms placed inside it.
+    fn partition(&self, area: Rect, layout: &mut Layout, rng: &mut ChaCha8Rng) -> Vec<usize> {
+        // Every part keeps a margin of one tile for walls on each side, and
+        // parts already fitting the largest room aren't split any further.
+        let (min_part, max_part) = (self.min_room_size + 2, self.max_room_size + 2);
+        let can_split_x = area.width >= 2 * min_part && area.width > max_part;
+        let can_split_y = area.depth >= 2 * min_part && area.depth > max_part;
+        let split_x = match (can_split_x, can_split_y) {
+            (false, false) => {
+                return self.place_room(area, layout, rng).into_iter().collect();
+            }
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => {
+                if area.width * 4 > area.depth * 5 {
+                    true
+                } else if area.depth * 4 > area.width * 5 {
+                    false
+                } else {
+                    rng.gen()
+                }
+            }
+        };
+        let (first, second) = if split_x {
+            let at = rng.gen_range(min_part..=area.width - min_part);
+            (
+                Rect { width: at, ..area },
+                Rect {
+                    x: area.x + at,
+                    width: area.width - at,
+                    ..area
+                },
+            )
+        } else {
+            let at = rng.gen_range(min_part..=area.depth - min_part);
+            (
+                Rect { depth: at, ..area },
+                Rect {
+                    y: area.y + at,
+                    depth: area.depth - at,
+                    ..area
+                },
+            )
+        };
+        let mut first = self.partition(first, layout, rng);
+        let second = self.partition(second, layout, rng);
+        let closest = first
+            .iter()
+            .flat_map(|&a| second.iter().map(move |&b| (a, b)))
+            .min_by_key(|&(a, b)| {
+                let (a, b) = (layout.rooms[a].center(), layout.rooms[b].center());
+                a.0.abs_diff(b.0).pow(2) + a.1.abs_diff(b.1).pow(2)
+            });
+        if let Some((a, b)) = closest {
+            let path = corridor(
+                layout.rooms[a].center(),
+                layout.rooms[b].center(),
+                rng.gen(),
+            );
+            layout.corridors.push(Corridor {
+                rooms: (a, b),
+                path,
+            });
+        }
+        first.extend(second);
+        first
+    }
+
+    /// Place a room of random size and position inside `area`, if it fits.
+    fn place_room(&self, area: Rect, layout: &mut Layout, rng: &mut ChaCha8Rng) -> Option<usize> {
+        let max_width = area.width.checked_sub(2)?.min(self.max_room_size);
+        let max_depth = area.depth.checked_sub(2)?.min(self.max_room_size);
+        if max_width < self.min_room_size || max_depth < self.min_room_size {
+            return None;
+        }
+        let width = rng.gen_range(self.min_room_size..=max_width);
+        let depth = rng.gen_range(self.min_room_size..=max_depth);
+        let room = Rect {
+            x: area.x + 1 + rng.gen_range(0..=max_width - width),
+            y: area.y + 1 + rng.gen_range(0..=max_depth - depth),
+            width,
+            depth,
+        };
+        for y in room.y..room.y + room.depth {
+            for x in room.x..room.x + room.width {
+                layout.set_tile(x, y, Tile::Room);
+            }
+        }
+        layout.rooms.push(room);
+        Some(layout.rooms.len() - 1)
+    }
+
+    /// Generate a dungeon and build it into a new buffer, returning both the
+    /// buffer and the layout.
+    ///
+    /// Every tile is one voxel. Rooms, corridors and doors get a floor in
+    /// the bottom layer, and rock next to them becomes walls rising
+    /// `wall_height` voxels above the floor. Doors get a lintel at the top
+    /// of the walls.
+    pub fn render(&self) -> (ArrayVoxelBuffer<Rgba>, Layout) {
+        let layout = self.generate();
+        let (size_x, size_y) = layout.dimensions();
+        let mut buf = ArrayVoxelBuffer::new(size_x, size_y, self.wall_height + 1);
+        for y in 0..size_y {
+            for x in 0..size_x {
+                match layout.tile(x, y) {
+                    Tile::Room => *buf.voxel_mut(x, y, 0) = self.floor_color,
+                    Tile::Corridor => *buf.voxel_mut(x, y, 0) = self.corridor_color,
+                    Tile::Door => {
+                        *buf.voxel_mut(x, y, 0) = self.door_color;
+                        *buf.voxel_mut(x, y, self.wall_height) = self.door_color;
+                    }
+                    Tile::Rock => {
+                        let open = (-1..=1)
+                            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                            .any(|(dx, dy)| {
+                                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                                nx >= 0
+                                    && ny >= 0
+                                    && nx < size_x as i64
+                                    && ny < size_y as i64
+                                    && layout.tile(nx as u32, ny as u32) != Tile::Rock
+                            });
+                        if open {
+                            for z in 0..=self.wall_height {
+                                *buf.voxel_mut(x, y, z) = self.wall_color;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        (buf, layout)
+    }
+}
+
+impl Default for Dungeon {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the tiles of an L shaped path from `a` to `b`, moving along x first
+/// if `x_first`, otherwise along y first.
+fn corridor(a: (u32, u32), b: (u32, u32), x_first: bool) -> Vec<(u32, u32)> {
+    let corner = if x_first { (b.0, a.1) } else { (a.0, b.1) };
+    let mut path = vec![a];
+    for target in [corner, b] {
+        let mut p = *path.last().unwrap();
+        while p != target {
+            p.0 = step(p.0, target.0);
+            p.1 = step(p.1, target.1);
+            path.push(p);
+        }
+    }
+    path
+}
+
+/// Step `from` one towards `to`.
+fn step(from: u32, to: u32) -> u32 {
+    match from.cmp(&to) {
+        std::cmp::Ordering::Less => from + 1,
+        std::cmp::Ordering::Greater => from - 1,
+        std::cmp::Ordering::Equal => from,
+    }
+}
@@ -0,0 +1,281 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+use std::collections::HashMap;
+
+/// The distinct colors of an age gradient, keeping under the MagicaVoxel
+/// palette limit.
+const AGE_LEVELS: u32 = 32;
+
+/// The directions growth spreads in, in the order of the direction weights.
+const DIRECTIONS: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// An Eden model surface growth generator, growing compact clusters for
+/// ores, frost, moss and crystals.
+///
+/// The cluster grows one voxel at a time into a random empty voxel next to
+/// it. Every voxel next to the cluster is picked with a weight summed over
+/// the cluster voxels it touches, by the direction from them to it. Faceting
+/// favors voxels touching more of the cluster, filling in hollows and
+/// growing flat crystal faces.
+///
+/// # Examples
+///
+/// Grow frost crystals sideways, sparing the top and bottom.
+/// ```
+/// # use voxgen::eden::Eden;
+/// # use voxgen::voxel_buffer::Rgba;
+/// Eden::new(32, 32, 16)
+///     .seed_points(vec![(8, 8, 8), (24, 20, 8)])
+///     .cells(2000)
+///     .weights([1.0, 1.0, 1.0, 1.0, 0.1, 0.1])
+///     .faceting(2.0)
+///     .age_colors(Rgba([120, 160, 220, 255]), Rgba([235, 245, 255, 255]))
+///     .seed(2)
+///     .render()
+///     .save("test/volumes/eden.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Eden {
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    seed_points: Vec<(u32, u32, u32)>,
+    cells: usize,
+    weights: [f32; 6],
+    faceting: f32,
+    color: Rgba,
+    age_colors: Option<(Rgba, Rgba)>,
+    seed: u64,
+}
+
+impl Eden {
+    /// Create a generator growing a cluster in a buffer `size_x` by `size_y`
+    /// by `size_z`, from a single seed point in the center.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> Eden {
+        let (size_x, size_y, size_z) = (size_x.max(1), size_y.max(1), size_z.max(1));
+        Eden {
+            size_x,
+            size_y,
+            size_z,
+            seed_points: vec![(size_x / 2, size_y / 2, size_z / 2)],
+            cells: 1000,
+            weights: [1.0; 6],
+            faceting: 0.0,
+            color: Rgba([90, 140, 60, 255]),
+            age_colors: None,
+            seed: 0,
+        }
+    }
+
+    /// Set the points the cluster grows from.
+    ///
+    /// Points outside the buffer are ignored.
+    pub fn seed_points(&mut self, points: Vec<(u32, u32, u32)>) -> &mut Self {
+        self.seed_points = points;
+        self
+    }
+
+    /// Set the number of voxels added to the cluster.
+    pub fn cells(&mut self, n: usize) -> &mut Self {
+        self.cells = n;
+        self
+    }
+
+    /// Set the weights of growing towards +x, -x, +y, -y, +z and -z.
+    ///
+    /// Negative weights count as 0.0.
+    pub fn weights(&mut self, weights: [f32; 6]) -> &mut Self {
+        self.weights = weights.map(|w| w.max(0.0));
+        self
+    }
+
+    /// Set the `faceting`, the power of the number of cluster voxels a
+    /// voxel touches it's weight is multiplied by, from 0.0 for rough
+    /// clusters to about 4.0 for smooth crystal faces.
+    pub fn faceting(&mut self, faceting: f32) -> &mut Self {
+        self.faceting = faceting.max(0.0);
+        self
+    }
+
+    /// Set the `color` of the cluster, clearing any age colors.
+    pub fn color(&mut self, color: Rgba) -> &mut Self {
+        self.color = color;
+        self.age_colors = None;
+        self
+    }
+
+    /// Color the cluster by age, from `oldest` at the seed points to
+    /// `youngest` at the last voxel added.
+    pub fn age_colors(&mut self, oldest: Rgba, youngest: Rgba) -> &mut Self {
+        self.age_colors = Some((oldest, youngest));
+        self
+    }
+
+    /// Set the `seed` of the random number generator picking voxels.
+    ///
+    /// The same seed always gives the same cluster.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    fn index(&self, p: [i64; 3]) -> Option<usize> {
+        let inside = p[0] >= 0
+            && p[1] >= 0
+            && p[2] >= 0
+            && p[0] < self.size_x as i64
+            && p[1] < self.size_y as i64
+            && p[2] < self.size_z as i64;
+        inside.then(|| {
+            (p[0] + p[1] * self.size_x as i64 + p[2] * (self.size_x * self.size_y) as i64) as usize
+        })
+    }
+
+    /// Grow the cluster, returning it's voxels in the order they joined it,
+    /// starting with the seed points.
+    ///
+    /// Growth stops early if the cluster has no room left to grow.
+    pub fn grow(&self) -> Vec<(u32, u32, u32)> {
+        let blocked = vec![false; (self.size_x * self.size_y * self.size_z) as usize];
+        self.grow_around(blocked)
+    }
+
+    /// Grow the cluster into the empty voxels of `buf`, around it's other
+    /// voxels, returning the cluster like `grow` does.
+    ///
+    /// Seed points on voxels that aren't empty are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` isn't the size of the generator.
+    pub fn grow_into(&self, buf: &mut ArrayVoxelBuffer<Rgba>) -> Vec<(u32, u32, u32)> {
+        assert_eq!(buf.dimensions(), (self.size_x, self.size_y, self.size_z));
+        let mut blocked = Vec::with_capacity((self.size_x * self.size_y * self.size_z) as usize);
+        for z in 0..self.size_z {
+            for y in 0..self.size_y {
+                for x in 0..self.size_x {
+                    blocked.push(buf.voxel(x, y, z).0[3] != 0);
+                }
+            }
+        }
+        let cluster = self.grow_around(blocked);
+        self.draw(&cluster, buf);
+        cluster
+    }
+
+    /// Grow the cluster into a new buffer.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let mut buf = ArrayVoxelBuffer::new(self.size_x, self.size_y, self.size_z);
+        self.draw(&self.grow(), &mut buf);
+        buf
+    }
+
+    /// Grow the cluster, keeping out of `blocked` voxels.
+    fn grow_around(&self, mut occupied: Vec<bool>) -> Vec<(u32, u32, u32)> {
+        let mut rng = Seed::new(self.seed).rng();
+        let mut cluster = Vec::new();
+        // The empty voxels next to the cluster, with their positions in the
+        // candidate list.
+        let mut candidates: Vec<[i64; 3]> = Vec::new();
+        let mut weights: Vec<f32> = Vec::new();
+        let mut slots: HashMap<usize, usize> = HashMap::new();
+        // The voxels added since the candidates were last updated.
+        let mut pending = Vec::new();
+        for &(x, y, z) in &self.seed_points {
+            let p = [x as i64, y as i64, z as i64];
+            if let Some(i) = self.index(p).filter(|&i| !occupied[i]) {
+                occupied[i] = true;
+                cluster.push((x, y, z));
+                pending.push(p);
+            }
+        }
+        for _ in 0..self.cells {
+            for p in pending.drain(..) {
+                if let Some(slot) = slots.remove(&self.index(p).unwrap()) {
+                    candidates.swap_remove(slot);
+                    weights.swap_remove(slot);
+                    if slot < candidates.len() {
+                        slots.insert(self.index(candidates[slot]).unwrap(), slot);
+                    }
+                }
+                for d in DIRECTIONS {
+                    let q = [p[0] + d[0], p[1] + d[1], p[2] + d[2]];
+                    match self.index(q) {
+                        Some(i) if !occupied[i] => {
+                            let weight = self.weight(q, &occupied);
+                            match slots.get(&i) {
+                                Some(&slot) => weights[slot] = weight,
+                                None => {
+                                    slots.insert(i, candidates.len());
+                                    candidates.push(q);
+                                    weights.push(weight);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            let total: f32 = weights.iter().sum();
+            if total <= 0.0 {
+                break;
+            }
+            let mut pick = rng.gen::<f32>() * total;
+            let mut chosen = candidates.len() - 1;
+            for (slot, w) in weights.iter().enumerate() {
+                if pick < *w {
+                    chosen = slot;
+                    break;
+                }
+                pick -= w;
+            }
+            let p = candidates[chosen];
+            occupied[self.index(p).unwrap()] = true;
+            cluster.push((p[0] as u32, p[1] as u32, p[2] as u32));
+            pending.push(p);
+        }
+        cluster
+    }
+
+    /// Get the weight of growing into the empty voxel `p`.
+    fn weight(&self, p: [i64; 3], occupied: &[bool]) -> f32 {
+        let mut weight = 0.0;
+        let mut touching = 0;
+        for (d, w) in DIRECTIONS.iter().zip(self.weights) {
+            // The cluster voxel p would grow from in direction d.
+            let from = [p[0] - d[0], p[1] - d[1], p[2] - d[2]];
+            if self.index(from).is_some_and(|i| occupied[i]) {
+                weight += w;
+                touching += 1;
+            }
+        }
+        weight * (touching as f32).powf(self.faceting)
+    }
+
+    /// Draw the `cluster` into `buf`, colored by age.
+    fn draw(&self, cluster: &[(u32, u32, u32)], buf: &mut ArrayVoxelBuffer<Rgba>) {
+        let last = cluster.len().saturating_sub(1).max(1) as f32;
+        for (age, &(x, y, z)) in cluster.iter().enumerate() {
+            *buf.voxel_mut(x, y, z) = match self.age_colors {
+                None => self.color,
+                Some((oldest, youngest)) => {
+                    let level = (age as f32 / last * (AGE_LEVELS - 1) as f32).round();
+                    let t = level / (AGE_LEVELS - 1) as f32;
+                    Rgba(std::array::from_fn(|c| {
+                        (oldest.0[c] as f32 + (youngest.0[c] as f32 - oldest.0[c] as f32) * t)
+                            .round() as u8
+                    }))
+                }
+            };
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::l_system::{LimitError, ParseError};
+#[cfg(feature = "std")]
+use crate::tiles::TileError;
+use crate::voxel_buffer::{CapacityError, VoxError};
+#[cfg(feature = "std")]
+use crate::wfc::Contradiction;
+use thiserror::Error;
+
+/// An error from any fallible part of the crate, for embedding generation
+/// in applications that handle every error the same way.
+///
+/// Every module error converts into it with `?`.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::{presets, RenderOptions};
+/// fn render() -> voxgen::Result<()> {
+///     let buf = RenderOptions::new()
+///         .derivation_length(2)
+///         .auto_fit(true)
+///         .render(&presets::koch_island())?;
+///     buf.try_voxel(0, 0, 0)?;
+///     buf.save("test/volumes/error_koch_island.vox")?;
+///     Ok(())
+/// }
+/// render()?;
+/// # Ok::<(), voxgen::Error>(())
+/// ```
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file failed.
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// An L System axiom or production couldn't be parsed.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    /// An L System render went over a limit.
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+    /// A buffer was too large to create.
+    #[error(transparent)]
+    Capacity(#[from] CapacityError),
+    /// A buffer breaks the limits of MagicaVoxel .vox files.
+    #[error(transparent)]
+    Vox(#[from] VoxError),
+    /// A tile couldn't be added or a map couldn't be assembled.
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Tile(#[from] TileError),
+    /// Wave function collapse couldn't satisfy it's adjacency rules.
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Contradiction(#[from] Contradiction),
+    /// A voxel position is outside the buffer `dimensions`.
+    #[error("voxel {position:?} is outside the buffer dimensions {dimensions:?}")]
+    OutOfBounds {
+        position: (u32, u32, u32),
+        dimensions: (u32, u32, u32),
+    },
+}
+
+/// A result with the crate `Error`.
+pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,194 @@
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+
+/// The distinct colors of a depth gradient, keeping under the MagicaVoxel
+/// palette limit.
+const DEPTH_LEVELS: u32 = 32;
+
+/// A classic three dimensional fractal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fractal {
+    /// The Menger sponge, a cube with tunnels bored through the middle of
+    /// every face at every level. Voxels are colored by the level of the
+    /// largest tunnel they border, so it looks best at sizes that are
+    /// powers of 3.
+    MengerSponge,
+    /// The Sierpinski tetrahedron, a tetrahedron of four half size
+    /// tetrahedra at every level. Voxels are colored by how many levels
+    /// they're in a corner tetrahedron other than the one at the origin, so
+    /// it looks best at sizes that are powers of 2.
+    SierpinskiTetrahedron,
+    /// The Mandelbulb of `power`, rendered by distance estimation so thin
+    /// features don't fall between voxels. Voxels are colored by the
+    /// iteration their point escaped at.
+    Mandelbulb { power: f32 },
+}
+
+/// A generator rasterizing classic fractals directly into cubic buffers.
+///
+/// Voxels are colored with a gradient from the first color at the
+/// shallowest iteration depth to the second at the deepest.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::fractals::{Fractal, FractalParams};
+/// # use voxgen::voxel_buffer::ArrayVoxelBuffer;
+/// let mut buf = ArrayVoxelBuffer::new(128, 48, 48);
+/// let sponge = FractalParams::new(Fractal::MengerSponge).size(27).iterations(3).render();
+/// let tetrahedron = FractalParams::new(Fractal::SierpinskiTetrahedron)
+///     .size(32)
+///     .iterations(4)
+///     .render();
+/// let bulb = FractalParams::new(Fractal::Mandelbulb { power: 8.0 })
+///     .size(48)
+///     .iterations(8)
+///     .render();
+/// buf.stamp(&sponge, 0, 10, 0);
+/// buf.stamp(&tetrahedron, 32, 8, 0);
+/// buf.stamp(&bulb, 72, 0, 0);
+/// buf.save("test/volumes/fractals.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct FractalParams {
+    fractal: Fractal,
+    size: u32,
+    iterations: u32,
+    colors: (Rgba, Rgba),
+}
+
+impl FractalParams {
+    pub fn new(fractal: Fractal) -> FractalParams {
+        FractalParams {
+            fractal,
+            size: 64,
+            iterations: 4,
+            colors: (Rgba([60, 70, 160, 255]), Rgba([240, 200, 120, 255])),
+        }
+    }
+
+    /// Set the `size` of the cubic buffer the fractal fills.
+    pub fn size(&mut self, size: u32) -> &mut Self {
+        self.size = size.max(1);
+        self
+    }
+
+    /// Set the number of levels of detail of the Menger sponge and
+    /// Sierpinski tetrahedron, or the number of iterations of the
+    /// Mandelbulb.
+    pub fn iterations(&mut self, iterations: u32) -> &mut Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Set the colors of the `shallowest` and `deepest` voxels.
+    pub fn colors(&mut self, shallowest: Rgba, deepest: Rgba) -> &mut Self {
+        self.colors = (shallowest, deepest);
+        self
+    }
+
+    /// Rasterize the fractal into a new buffer.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let mut buf = ArrayVoxelBuffer::new(self.size, self.size, self.size);
+        let (a, b) = self.colors;
+        for z in 0..self.size {
+            for y in 0..self.size {
+                for x in 0..self.size {
+                    // The middle of the voxel, scaled to the unit cube.
+                    let p = [x, y, z].map(|c| (c as f32 + 0.5) / self.size as f32);
+                    let depth = match self.fractal {
+                        Fractal::MengerSponge => self.menger(p),
+                        Fractal::SierpinskiTetrahedron => self.sierpinski(p),
+                        Fractal::Mandelbulb { power } => self.mandelbulb(p, power),
+                    };
+                    if let Some(t) = depth {
+                        let level = (t.clamp(0.0, 1.0) * (DEPTH_LEVELS - 1) as f32).round();
+                        let t = level / (DEPTH_LEVELS - 1) as f32;
+                        *buf.voxel_mut(x, y, z) = Rgba(std::array::from_fn(|c| {
+                            (a.0[c] as f32 + (b.0[c] as f32 - a.0[c] as f32) * t).round() as u8
+                        }));
+                    }
+                }
+            }
+        }
+        buf
+    }
+
+    /// Get the depth of `p` in the Menger sponge from 0.0 to 1.0, or
+    /// `None` if it's in a tunnel.
+    fn menger(&self, mut p: [f32; 3]) -> Option<f32> {
+        let mut border = None;
+        for level in 0..self.iterations {
+            p = p.map(|c| c * 3.0);
+            let middles = p.iter().filter(|&&c| c.floor() == 1.0).count();
+            if middles >= 2 {
+                return None;
+            }
+            if middles == 1 && border.is_none() {
+                border = Some(level);
+            }
+            p = p.map(|c| c.fract());
+        }
+        Some(border.unwrap_or(self.iterations) as f32 / self.iterations as f32)
+    }
+
+    /// Get the depth of `p` in the Sierpinski tetrahedron from 0.0 to 1.0,
+    /// or `None` if it's outside it.
+    fn sierpinski(&self, p: [f32; 3]) -> Option<f32> {
+        // The tetrahedron has corners at alternate corners of the unit cube,
+        // (0, 0, 0), (1, 1, 0), (1, 0, 1) and (0, 1, 1). In coordinates
+        // along it's edges from the origin it's the corner of a unit cube cut
+        // off by x + y + z = 1, and it's cells at the last level are the
+        // cells of a grid whose coordinates share no set bits.
+        let b = [
+            (p[0] + p[1] - p[2]) / 2.0,
+            (p[0] - p[1] + p[2]) / 2.0,
+            (-p[0] + p[1] + p[2]) / 2.0,
+        ];
+        if b.iter().any(|&c| c < 0.0) || b.iter().sum::<f32>() > 1.0 {
+            return None;
+        }
+        let cells = (1u32 << self.iterations.min(16)) as f32;
+        let scaled = b.map(|c| c * cells);
+        let cell = scaled.map(|c| (c.floor() as u32).min(cells as u32 - 1));
+        if cell[0] & cell[1] != 0 || cell[0] & cell[2] != 0 || cell[1] & cell[2] != 0 {
+            return None;
+        }
+        // Every grid cube holds the upright tetrahedron of the cell, and the
+        // upside down one left out between it's neighbors.
+        let inside: f32 = scaled.iter().zip(cell).map(|(c, i)| c - i as f32).sum();
+        if inside > 1.0 {
+            return None;
+        }
+        let corners = (cell[0] | cell[1] | cell[2]).count_ones();
+        Some(corners as f32 / self.iterations.min(16) as f32)
+    }
+
+    /// Get the depth of `p` in the Mandelbulb of `power` from 0.0 to 1.0,
+    /// or `None` if it's further than half a voxel from it.
+    fn mandelbulb(&self, p: [f32; 3], power: f32) -> Option<f32> {
+        // The bulb fits within a radius of about 1.2 for the usual powers.
+        let scale = 2.4;
+        let c = p.map(|c| (c - 0.5) * scale);
+        let mut z = c;
+        let mut dr = 1.0;
+        for i in 0..self.iterations {
+            let r = (z[0] * z[0] + z[1] * z[1] + z[2] * z[2]).sqrt();
+            if r > 2.0 {
+                let estimate = 0.5 * r.ln() * r / dr;
+                let half_voxel = 0.5 * scale / self.size as f32;
+                return (estimate < half_voxel).then(|| i as f32 / self.iterations as f32);
+            }
+            dr = r.powf(power - 1.0) * power * dr + 1.0;
+            let theta = (z[2] / r.max(f32::EPSILON)).acos() * power;
+            let phi = z[1].atan2(z[0]) * power;
+            let zr = r.powf(power);
+            z = [
+                zr * theta.sin() * phi.cos() + c[0],
+                zr * theta.sin() * phi.sin() + c[1],
+                zr * theta.cos() + c[2],
+            ];
+        }
+        Some(1.0)
+    }
+}
@@ -0,0 +1,218 @@
+use crate::automata::{Neighborhood, Rule};
+use wgpu::util::DeviceExt;
+
+/// The compute shader stepping a life-like cellular automaton, matching
+/// `automata::Automaton::step` cell for cell.
+const AUTOMATON_SHADER: &str = r#"
+struct Params {
+    size: vec3<u32>,
+    // 1 if cells outside the grid count as alive.
+    boundary: u32,
+    // Bit n is set if n alive neighbors give birth or survival.
+    birth: u32,
+    survival: u32,
+    // 1 for the 26 cell Moore neighborhood, 0 for the 6 cell von Neumann.
+    moore: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> cells: array<u32>;
+@group(0) @binding(2) var<storage, read_write> back: array<u32>;
+
+fn alive(p: vec3<i32>) -> u32 {
+    let size = vec3<i32>(params.size);
+    if any(p < vec3<i32>(0)) || any(p >= size) {
+        return params.boundary;
+    }
+    return cells[u32(p.x + p.y * size.x + p.z * size.x * size.y)];
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn step(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id >= params.size) {
+        return;
+    }
+    let p = vec3<i32>(id);
+    var neighbors = 0u;
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let distance = abs(x) + abs(y) + abs(z);
+                if (params.moore == 1u && distance > 0) || distance == 1 {
+                    neighbors += alive(p + vec3<i32>(x, y, z));
+                }
+            }
+        }
+    }
+    let i = id.x + id.y * params.size.x + id.z * params.size.x * params.size.y;
+    var rule = params.birth;
+    if cells[i] == 1u {
+        rule = params.survival;
+    }
+    back[i] = (rule >> neighbors) & 1u;
+}
+"#;
+
+/// The settings of an automaton step, laid out like `Params` in
+/// `AUTOMATON_SHADER`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct AutomatonParams {
+    size: [u32; 3],
+    boundary: u32,
+    birth: u32,
+    survival: u32,
+    moore: u32,
+    _padding: u32,
+}
+
+/// A GPU running generation passes with compute shaders.
+///
+/// Passes give the same results as their CPU versions, so outputs can be
+/// checked against them. Only passes with exact integer arithmetic run on
+/// the GPU, so noise fills stay on the CPU, since shaders lack the 64 bit
+/// floats the noise functions use. Cellular automaton steps are the only
+/// pass so far.
+///
+/// Creating a `Gpu` compiles every shader, so create one and reuse it.
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    automaton: wgpu::ComputePipeline,
+}
+
+impl Gpu {
+    /// Connect to the default GPU, or return `None` if there isn't one.
+    pub fn new() -> Option<Gpu> {
+        let instance = wgpu::Instance::default();
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .ok()?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("automaton"),
+            source: wgpu::ShaderSource::Wgsl(AUTOMATON_SHADER.into()),
+        });
+        let automaton = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("automaton"),
+            layout: None,
+            module: &module,
+            entry_point: Some("step"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Some(Gpu {
+            device,
+            queue,
+            automaton,
+        })
+    }
+
+    /// Step the automaton of `cells` in a grid of `size` by `steps` steps
+    /// of `rule`, where cells outside the grid are alive if `boundary`.
+    ///
+    /// Both grids stay on the GPU between steps, and are only copied back
+    /// once at the end.
+    pub(crate) fn automaton_steps(
+        &self,
+        size: [u32; 3],
+        cells: &mut [bool],
+        rule: &Rule,
+        boundary: bool,
+        steps: usize,
+    ) {
+        if steps == 0 || cells.is_empty() {
+            return;
+        }
+        let (birth, survival) = rule.masks();
+        let params = AutomatonParams {
+            size,
+            boundary: boundary as u32,
+            birth,
+            survival,
+            moore: (rule.neighborhood() == Neighborhood::Moore) as u32,
+            _padding: 0,
+        };
+        let params = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("automaton params"),
+                contents: bytemuck::bytes_of(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let words: Vec<u32> = cells.iter().map(|&alive| alive as u32).collect();
+        let usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST;
+        let grids = [0, 1].map(|_| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("automaton cells"),
+                    contents: bytemuck::cast_slice(&words),
+                    usage,
+                })
+        });
+        // One bind group per direction, swapping the grids every step.
+        let layout = self.automaton.get_bind_group_layout(0);
+        let bind_groups = [(0, 1), (1, 0)].map(|(from, to)| {
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("automaton step"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: grids[from].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: grids[to].as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.automaton);
+            for step in 0..steps {
+                pass.set_bind_group(0, &bind_groups[step % 2], &[]);
+                pass.dispatch_workgroups(
+                    size[0].div_ceil(4),
+                    size[1].div_ceil(4),
+                    size[2].div_ceil(4),
+                );
+            }
+        }
+        let result = &grids[steps % 2];
+        let bytes = (words.len() * 4) as u64;
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("automaton readback"),
+            size: bytes,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_buffer_to_buffer(result, 0, &staging, 0, bytes);
+        self.queue.submit([encoder.finish()]);
+        staging.map_async(wgpu::MapMode::Read, .., |mapped| {
+            mapped.expect("mapping the automaton readback failed")
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .expect("waiting for the automaton steps failed");
+        let view = staging
+            .get_mapped_range(..)
+            .expect("reading the automaton readback failed");
+        let words: &[u32] = bytemuck::cast_slice(&view);
+        for (cell, &word) in cells.iter_mut().zip(words) {
+            *cell = word == 1;
+        }
+    }
+}
@@ -1,17 +1,86 @@
-use crate::voxel_buffer::{Voxel, Rgba};
-use crate::turtle_graphics::TurtleGraphics;
-use enterpolation::{linear::ConstEquidistantLinear, Curve};
+use crate::color::Gradient;
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::compat::Instant;
+use crate::compat::{HashMap, HashSet};
+use crate::progress::{Progress, ProgressCallback, Stage};
+use crate::seed::Seed;
+use crate::turtle_graphics::{Bounds, TurtleGraphics};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, Voxel};
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::cell::Cell;
+use core::hash::Hash;
+use core::time::Duration;
+use expression::{parse_expression, parse_identifier, Expression};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::combinator::value;
-use nom::multi::many0;
-use nom::sequence::separated_pair;
+use nom::character::complete::{char, none_of, space0};
+use nom::combinator::{not, opt};
+use nom::error::Error;
+use nom::multi::{many0, separated_list1};
+use nom::number::complete::float;
+use nom::sequence::{delimited, preceded, terminated, tuple};
 use nom::IResult;
-use palette::{LinSrgba, Srgba};
-use std::collections::HashMap;
-use std::hash::Hash;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use scene::{Scene, Variation};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Arithmetic and logical expressions for parametric productions.
+mod expression;
+
+/// Reading the `.l` L System files of L-studio and cpfg.
+#[cfg(feature = "std")]
+mod cpfg;
+
+/// Ready made L Systems from The Algorithmic Beauty of Plants and elsewhere.
+///
+/// # Examples
+///
+/// Render the first bracketed plant of ABOP figure 1.24 in green.
+/// ```
+/// # use voxgen::l_system::{presets, RenderOptions};
+/// # use voxgen::voxel_buffer::Rgba;
+/// RenderOptions::new()
+///     .symbol_color('F', Rgba([34, 139, 34, 255]))
+///     .derivation_length(3)
+///     .step_size(1.0)
+///     .angle_increment(25.7_f32.to_radians())
+///     .offset_y(-30.0)
+///     .render(&presets::plant_a())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub mod presets;
+
+/// Render an L System across a grid of settings in parallel.
+#[cfg(feature = "std")]
+pub mod sweep;
+
+/// Leaves, flowers and fruit to bind to symbols with `RenderOptions::callback`.
+pub mod organs;
+
+/// Render several L Systems into one buffer, each from it's own position.
+pub mod scene;
+
+/// Grow L Systems over existing models from seed voxels on their surface.
+pub mod overgrowth;
+
+/// Score rendered L Systems with fitness functions for evolutionary search.
+#[cfg(feature = "std")]
+pub mod fitness;
+
+/// Re-render L System definition files whenever they change.
+#[cfg(feature = "std")]
+pub mod watch;
+
+/// A turtle action that symbols are interpreted as when rendering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Command {
     Draw,
     Step,
@@ -21,72 +90,1605 @@ pub enum Command {
     DrawRight,
     SubfigureA,
     SubfigureB,
+    PitchDown,
+    PitchUp,
+    RollLeft,
+    RollRight,
+    TurnAround,
+    Push,
+    Pop,
+    DecrementWidth,
+    ScaleStep,
+}
+
+impl Command {
+    /// Get the default command for `symbol`.
+    ///
+    /// `F` draws, `f` steps, `+` and `-` turn left and right, `L` and `R`
+    /// draw around a left and right turn, `A` and `B` are silent subfigures,
+    /// `&` and `^` pitch down and up, `\` and `/` roll left and right, `|`
+    /// turns around, `[` and `]` push and pop the turtle state, `!` makes
+    /// lines thinner and `"` makes steps shorter. Other symbols have no
+    /// default command.
+    pub fn from_symbol(symbol: char) -> Option<Command> {
+        match symbol {
+            'F' => Some(Command::Draw),
+            'f' => Some(Command::Step),
+            '+' => Some(Command::Left),
+            '-' => Some(Command::Right),
+            'L' => Some(Command::DrawLeft),
+            'R' => Some(Command::DrawRight),
+            'A' => Some(Command::SubfigureA),
+            'B' => Some(Command::SubfigureB),
+            '&' => Some(Command::PitchDown),
+            '^' => Some(Command::PitchUp),
+            '\\' => Some(Command::RollLeft),
+            '/' => Some(Command::RollRight),
+            '|' => Some(Command::TurnAround),
+            '[' => Some(Command::Push),
+            ']' => Some(Command::Pop),
+            '!' => Some(Command::DecrementWidth),
+            '"' => Some(Command::ScaleStep),
+            _ => None,
+        }
+    }
+
+    /// Get the default symbol of the command, the inverse of
+    /// `Command::from_symbol`.
+    pub fn symbol(self) -> char {
+        match self {
+            Command::Draw => 'F',
+            Command::Step => 'f',
+            Command::Left => '+',
+            Command::Right => '-',
+            Command::DrawLeft => 'L',
+            Command::DrawRight => 'R',
+            Command::SubfigureA => 'A',
+            Command::SubfigureB => 'B',
+            Command::PitchDown => '&',
+            Command::PitchUp => '^',
+            Command::RollLeft => '\\',
+            Command::RollRight => '/',
+            Command::TurnAround => '|',
+            Command::Push => '[',
+            Command::Pop => ']',
+            Command::DecrementWidth => '!',
+            Command::ScaleStep => '"',
+        }
+    }
+}
+
+/// An assignment of turtle actions to symbols, see
+/// `RenderOptions::symbol_map`.
+///
+/// Symbols without a command are skipped when rendering.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct SymbolMap {
+    #[serde(serialize_with = "sorted")]
+    commands: HashMap<char, Command>,
+}
+
+impl Default for SymbolMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolMap {
+    /// Create a map with the default commands of `Command::from_symbol`.
+    pub fn new() -> SymbolMap {
+        let symbols = "Ff+-LRAB&^\\/|[]!\"";
+        SymbolMap {
+            commands: symbols
+                .chars()
+                .filter_map(|c| Command::from_symbol(c).map(|command| (c, command)))
+                .collect(),
+        }
+    }
+
+    /// Create a map without any commands.
+    pub fn empty() -> SymbolMap {
+        SymbolMap {
+            commands: HashMap::new(),
+        }
+    }
+
+    /// Create a map with only the standard turtle interpretation of The
+    /// Algorithmic Beauty of Plants.
+    ///
+    /// Like `SymbolMap::new`, but `L`, `R`, `A` and `B` have no command.
+    pub fn standard() -> SymbolMap {
+        let mut map = SymbolMap::new();
+        for symbol in ['L', 'R', 'A', 'B'] {
+            map.remove(symbol);
+        }
+        map
+    }
+
+    /// Interpret `symbol` as `command`.
+    pub fn set(&mut self, symbol: char, command: Command) -> &mut Self {
+        self.commands.insert(symbol, command);
+        self
+    }
+
+    /// Remove the command of `symbol`, so that it's skipped.
+    pub fn remove(&mut self, symbol: char) -> &mut Self {
+        self.commands.remove(&symbol);
+        self
+    }
+
+    /// Get the command of `symbol`, if any.
+    pub fn get(&self, symbol: char) -> Option<Command> {
+        self.commands.get(&symbol).copied()
+    }
+}
+
+/// A symbol with its actual parameter values.
+///
+/// Modules without parameters use the defaults from `RenderOptions` when
+/// rendered. Parametric `F(l)` and `f(l)` modules move `l` voxels,
+/// `+(a)` and `-(a)` modules turn `a` degrees, `!(w)` modules set the line
+/// width to `w` voxels and `"(s)` modules scale the step size by `s`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    symbol: char,
+    params: Vec<f32>,
+    growth: f32,
+}
+
+impl Module {
+    /// Get the symbol of the module.
+    pub fn symbol(&self) -> char {
+        self.symbol
+    }
+
+    /// Get the actual parameter values of the module.
+    pub fn params(&self) -> &[f32] {
+        &self.params
+    }
+
+    /// Get the fraction of it's lifetime the module has reached, from 0 to 1.
+    ///
+    /// Only modules developed with `LSystem::develop` grow, all other modules
+    /// are fully grown with a growth of 1. Steps are scaled by the growth of
+    /// their module when rendered.
+    pub fn growth(&self) -> f32 {
+        self.growth
+    }
 }
 
-fn parse_sentence(sentence: &str) -> IResult<&str, Vec<Command>> {
-    many0(alt((
-        value(Command::Draw, tag("F")),
-        value(Command::Step, tag("f")),
-        value(Command::Left, tag("+")),
-        value(Command::Right, tag("-")),
-        value(Command::DrawLeft, tag("L")),
-        value(Command::DrawRight, tag("R")),
-        value(Command::SubfigureA, tag("A")),
-        value(Command::SubfigureB, tag("B")),
-    )))(sentence)
+impl core::fmt::Display for Module {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.symbol)?;
+        if !self.params.is_empty() {
+            let params: Vec<String> = self.params.iter().map(f32::to_string).collect();
+            write!(f, "({})", params.join(","))?;
+        }
+        Ok(())
+    }
 }
 
-fn parse_productions(rules: Vec<&str>) -> IResult<&str, HashMap<Command, Vec<Command>>> {
-    let mut output = HashMap::new();
+/// A sentence of modules, such as a derivation of an L System.
+///
+/// Sentences are written in the axiom syntax of `LSystem::try_new`, so that
+/// they can be logged, compared with textbook derivations and parsed back.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::{LSystem, Sentence};
+/// let l_system = LSystem::try_new("branch", "A(1)", vec!["A(l)→F(l)[+A(l/2)]"])?;
+/// let sentence = l_system.sentence(2);
+/// assert_eq!(sentence.to_string(), "F(1)[+F(0.5)[+A(0.25)]]");
+/// assert_eq!(sentence, "F(1) [+F(0.5) [+A(0.25)]]".parse::<Sentence>()?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sentence(pub Vec<Module>);
+
+impl Sentence {
+    /// Get the modules of the sentence.
+    pub fn modules(&self) -> &[Module] {
+        &self.0
+    }
+}
+
+impl From<Vec<Module>> for Sentence {
+    fn from(modules: Vec<Module>) -> Sentence {
+        Sentence(modules)
+    }
+}
+
+impl core::fmt::Display for Sentence {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for module in &self.0 {
+            write!(f, "{}", module)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::str::FromStr for Sentence {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Sentence, ParseError> {
+        let no_bindings = HashMap::new();
+        Ok(Sentence(
+            parse_axiom(s)?
+                .iter()
+                .map(|t| t.expand(&no_bindings))
+                .collect(),
+        ))
+    }
+}
+
+/// A sequence of turtle commands, written with the default symbol of each.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::{Command, Commands};
+/// let commands: Commands = "F+[f]".parse()?;
+/// assert_eq!(
+///     commands.0,
+///     vec![Command::Draw, Command::Left, Command::Push, Command::Step, Command::Pop]
+/// );
+/// assert_eq!(commands.to_string(), "F+[f]");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Commands(pub Vec<Command>);
+
+impl From<Vec<Command>> for Commands {
+    fn from(commands: Vec<Command>) -> Commands {
+        Commands(commands)
+    }
+}
+
+impl core::fmt::Display for Commands {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for command in &self.0 {
+            write!(f, "{}", command.symbol())?;
+        }
+        Ok(())
+    }
+}
+
+impl core::str::FromStr for Commands {
+    type Err = ParseError;
+
+    /// Parse the default symbols of commands, ignoring whitespace.
+    fn from_str(s: &str) -> Result<Commands, ParseError> {
+        let mut commands = Vec::new();
+        for (i, c) in s.char_indices() {
+            if c.is_whitespace() {
+                continue;
+            }
+            match Command::from_symbol(c) {
+                Some(command) => commands.push(command),
+                None => {
+                    let message = format!("unknown command `{}`", c);
+                    return Err(ParseError::new(s, &s[i..], &message));
+                }
+            }
+        }
+        Ok(Commands(commands))
+    }
+}
+
+/// An error describing why an axiom or production could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    rule: String,
+    position: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(rule: &str, rest: &str, message: &str) -> ParseError {
+        let offset = rule.len() - rest.len();
+        ParseError {
+            rule: rule.to_string(),
+            position: rule[..offset].chars().count(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Get the axiom or production that could not be parsed.
+    pub fn rule(&self) -> &str {
+        &self.rule
+    }
+
+    /// Get the character position in the rule where parsing failed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Get a description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} in `{}` at position {}",
+            self.message, self.rule, self.position
+        )
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// An error returned when rendering exceeds one of the `RenderOptions`
+/// limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitError {
+    /// The derived sentence has more modules than the given maximum.
+    Symbols(usize),
+    /// More voxels were drawn than the given maximum.
+    Voxels(usize),
+    /// The buffer of the given dimensions is too large to create, either
+    /// over the `max_voxels` limit when fitted to the drawing, or over the
+    /// memory budget, see `set_memory_budget`.
+    Canvas((u64, u64, u64)),
+}
+
+impl core::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LimitError::Symbols(max) => write!(f, "derivation exceeds {} symbols", max),
+            LimitError::Voxels(max) => write!(f, "rendering exceeds {} drawn voxels", max),
+            LimitError::Canvas((x, y, z)) => {
+                write!(f, "canvas of {}x{}x{} voxels is too large", x, y, z)
+            }
+        }
+    }
+}
+
+impl core::error::Error for LimitError {}
+
+/// Parse any symbol except whitespace and the characters used by the
+/// production syntax.
+fn parse_symbol(input: &str) -> IResult<&str, char> {
+    preceded(not(tag("->")), none_of(" \t\r\n(),<>:=→"))(input)
+}
+
+/// Parse an optional parenthesized, comma separated parameter list.
+fn parse_params<'a, O>(
+    param: fn(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>> {
+    move |input| {
+        opt(delimited(
+            char('('),
+            separated_list1(char(','), delimited(space0, param, space0)),
+            char(')'),
+        ))(input)
+        .map(|(rest, params)| (rest, params.unwrap_or_default()))
+    }
+}
+
+/// A module in a production successor with parameter expressions.
+#[derive(Clone, Debug)]
+struct Template {
+    symbol: char,
+    params: Vec<Expression>,
+}
+
+fn parse_template(input: &str) -> IResult<&str, Template> {
+    let (rest, (symbol, params)) = tuple((parse_symbol, parse_params(parse_expression)))(input)?;
+    Ok((rest, Template { symbol, params }))
+}
+
+/// Parse a sentence of modules, optionally separated by whitespace.
+fn parse_sentence(sentence: &str) -> IResult<&str, Vec<Template>> {
+    terminated(many0(preceded(space0, parse_template)), space0)(sentence)
+}
+
+/// A module in a production predecessor or context with formal parameters.
+#[derive(Clone, Debug)]
+struct Pattern {
+    symbol: char,
+    params: Vec<String>,
+}
+
+impl Pattern {
+    fn matches(&self, module: &Module) -> bool {
+        self.symbol == module.symbol && self.params.len() == module.params.len()
+    }
+}
+
+fn parse_pattern(input: &str) -> IResult<&str, Pattern> {
+    let (rest, (symbol, params)) = tuple((parse_symbol, parse_params(parse_identifier)))(input)?;
+    Ok((rest, Pattern { symbol, params }))
+}
+
+fn parse_patterns(input: &str) -> IResult<&str, Vec<Pattern>> {
+    terminated(many0(preceded(space0, parse_pattern)), space0)(input)
+}
+
+/// Parse `token` surrounded by optional whitespace.
+fn parse_token<'a>(token: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    delimited(space0, tag(token), space0)
+}
+
+/// Describe the unparsed remainder of a rule.
+fn unexpected(rest: &str) -> String {
+    match rest.chars().next() {
+        Some(c) => format!("unexpected `{}`", c),
+        None => "unexpected end of rule".to_string(),
+    }
+}
+
+/// Parse a complete axiom.
+fn parse_axiom(axiom: &str) -> Result<Vec<Template>, ParseError> {
+    match parse_sentence(axiom) {
+        Ok(("", sentence)) => Ok(sentence),
+        Ok((rest, _)) | Err(nom::Err::Error(Error { input: rest, .. })) => {
+            Err(ParseError::new(axiom, rest, &unexpected(rest)))
+        }
+        Err(_) => Err(ParseError::new(axiom, axiom, "invalid axiom")),
+    }
+}
+
+/// A production `left < predecessor > right : condition → successor`.
+///
+/// The left and right contexts are empty for context-free productions, and
+/// productions without a condition always apply.
+#[derive(Debug)]
+struct Production {
+    left: Vec<Pattern>,
+    predecessor: Pattern,
+    right: Vec<Pattern>,
+    condition: Option<Expression>,
+    probability: Option<f32>,
+    successor: Vec<Template>,
+}
+
+/// Parse a complete production, accepting `→`, `->` or `=` as the arrow.
+fn parse_production(rule: &str) -> Result<Production, ParseError> {
+    let error = |rest: &str, message: &str| ParseError::new(rule, rest, message);
+    let (rest, _) = space0::<_, Error<&str>>(rule).unwrap_or((rule, ""));
+    let (rest, left) = match terminated(parse_patterns, tag("<"))(rest) {
+        Ok((rest, left)) => (rest, left),
+        Err(_) => (rest, Vec::new()),
+    };
+    let (rest, predecessor) = preceded(space0, parse_pattern)(rest)
+        .map_err(|_| error(rest, "expected a predecessor module"))?;
+    let (rest, right) = match preceded(parse_token(">"), parse_patterns)(rest) {
+        Ok((rest, right)) => (rest, right),
+        Err(_) => (rest, Vec::new()),
+    };
+    let (rest, condition) = match parse_token(":")(rest) {
+        Ok((after, _)) => {
+            let (rest, condition) =
+                parse_expression(after).map_err(|_| error(after, "expected a condition"))?;
+            (rest, Some(condition))
+        }
+        Err(_) => (rest, None),
+    };
+    let (rest, _) = alt((parse_token("→"), parse_token("->"), parse_token("=")))(rest)
+        .map_err(|_| error(rest, "expected `→`, `->` or `=`"))?;
+    let (rest, probability) = match char::<_, Error<&str>>('(')(rest) {
+        Ok((after, _)) => {
+            let (rest, probability) = terminated(preceded(space0, float), parse_token(")"))(after)
+                .map_err(|_| error(after, "expected a probability"))?;
+            (rest, Some(probability))
+        }
+        Err(_) => (rest, None),
+    };
+    let (rest, successor) =
+        parse_sentence(rest).map_err(|_| error(rest, "expected a successor"))?;
+    if !rest.is_empty() {
+        return Err(error(rest, &unexpected(rest)));
+    }
+    let production = Production {
+        left,
+        predecessor,
+        right,
+        condition,
+        probability,
+        successor,
+    };
+    if let Some(variable) = unbound_parameter(&production) {
+        let message = format!("unbound parameter `{}`", variable);
+        return Err(error(rule, &message));
+    }
+    Ok(production)
+}
+
+/// Find a parameter used in the condition or successor of `production` that
+/// isn't bound by the predecessor or its context.
+fn unbound_parameter(production: &Production) -> Option<&str> {
+    let bound: HashSet<&str> = production
+        .left
+        .iter()
+        .chain([&production.predecessor])
+        .chain(&production.right)
+        .flat_map(|p| p.params.iter().map(String::as_str))
+        .collect();
+    production
+        .condition
+        .iter()
+        .chain(production.successor.iter().flat_map(|t| &t.params))
+        .flat_map(Expression::variables)
+        .find(|variable| !bound.contains(variable))
+}
+
+fn parse_productions(rules: Vec<&str>) -> Result<Vec<Production>, ParseError> {
+    let mut output = Vec::new();
     for rule in rules {
-        let pair = separated_pair(parse_sentence, tag("→"), parse_sentence)(rule)?;
-        output.insert(pair.1 .0[0], pair.1 .1);
+        output.push(parse_production(rule)?);
     }
-    Ok(("", output))
+    // Context-sensitive productions take precedence over context-free ones.
+    output.sort_by_key(|p| core::cmp::Reverse(p.left.len() + p.right.len()));
+    Ok(output)
 }
 
+/// Find the index of the bracket matching the one at `i`, searching forward
+/// from a `[` or backward from a `]`.
+fn matching_bracket(sentence: &[Module], i: usize) -> Option<usize> {
+    let forward = sentence[i].symbol == '[';
+    let mut depth = 0;
+    let mut j = i;
+    loop {
+        match sentence[j].symbol {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            return Some(j);
+        }
+        if forward {
+            j += 1;
+            if j >= sentence.len() {
+                return None;
+            }
+        } else {
+            j = j.checked_sub(1)?;
+        }
+    }
+}
+
+/// The schema of an L System definition file, also used to serialize
+/// `LSystem`.
+#[derive(Deserialize, Serialize)]
+struct Definition {
+    name: String,
+    axiom: String,
+    productions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    decompositions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    homomorphisms: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    ignore: String,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted"
+    )]
+    lifetimes: HashMap<char, f32>,
+    #[serde(default, skip_serializing)]
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    render: RenderDefinition,
+}
+
+/// The optional `render` section of an L System definition file.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RenderDefinition {
+    derivation_length: Option<u32>,
+    step_size: Option<f32>,
+    angle_increment_degrees: Option<f32>,
+    size_x: Option<u32>,
+    size_y: Option<u32>,
+    offset_x: Option<f32>,
+    offset_y: Option<f32>,
+    size_z: Option<u32>,
+    offset_z: Option<f32>,
+    initial_heading_degrees: Option<f32>,
+    initial_pitch_degrees: Option<f32>,
+    rainbow: Option<bool>,
+    max_symbols: Option<usize>,
+    max_voxels: Option<usize>,
+    seed: Option<u64>,
+    voxels_per_unit: Option<f32>,
+}
+
+/// Read an L System definition file, as JSON if `path` has a `.json`
+/// extension, as an L-studio file if it has a `.l` extension and as TOML
+/// otherwise.
+#[cfg(feature = "std")]
+fn read_definition(path: &Path) -> io::Result<Definition> {
+    let text = std::fs::read_to_string(path)?;
+    if path.extension().is_some_and(|e| e == "l") {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        cpfg::read(&name, &text)
+    } else if path.extension().is_some_and(|e| e == "json") {
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    } else {
+        toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// The rules of an L System as they were written, kept to serialize it.
+#[derive(Debug, Default)]
+struct Rules {
+    axiom: String,
+    productions: Vec<String>,
+    decompositions: Vec<String>,
+    homomorphisms: Vec<String>,
+}
+
+/// Convert rules to the owned strings kept in `Rules`.
+fn to_strings(rules: &[&str]) -> Vec<String> {
+    rules.iter().map(|rule| rule.to_string()).collect()
+}
+
+/// An L System, serialized with serde like an L System definition file.
+///
+/// See `LSystem::from_file` for the format. The `render` section of a
+/// definition is ignored when deserializing, see `RenderOptions` to store
+/// render options.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::LSystem;
+/// let mut l_system = LSystem::try_new("growth", "A", vec!["A → F[+A]A"])?;
+/// l_system.lifetime('A', 2.0);
+/// let json = serde_json::to_string(&l_system)?;
+/// let replayed: LSystem = serde_json::from_str(&json)?;
+/// assert_eq!(replayed.sentence(3), l_system.sentence(3));
+/// assert_eq!(serde_json::to_string(&replayed)?, json);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
 #[derive(Debug)]
 pub struct LSystem {
     name: String,
-    axiom: Vec<Command>,
-    productions: HashMap<Command, Vec<Command>>,
+    axiom: Vec<Module>,
+    productions: Vec<Production>,
+    decompositions: Vec<Production>,
+    homomorphisms: Vec<Production>,
+    ignore: HashSet<char>,
+    lifetimes: HashMap<char, f32>,
+    rules: Rules,
+}
+
+impl Serialize for LSystem {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut ignore: Vec<char> = self.ignore.iter().copied().collect();
+        ignore.sort_unstable();
+        Definition {
+            name: self.name.clone(),
+            axiom: self.rules.axiom.clone(),
+            productions: self.rules.productions.clone(),
+            decompositions: self.rules.decompositions.clone(),
+            homomorphisms: self.rules.homomorphisms.clone(),
+            ignore: ignore.into_iter().collect(),
+            lifetimes: self.lifetimes.clone(),
+            render: RenderDefinition::default(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LSystem {
+    fn deserialize<D>(deserializer: D) -> Result<LSystem, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let definition = Definition::deserialize(deserializer)?;
+        LSystem::from_definition(&definition).map_err(serde::de::Error::custom)
+    }
 }
 
+/// The maximum number of times decomposition and homomorphism rules are
+/// applied to the modules they produce, guarding against rules that never
+/// stop matching.
+const MAX_REWRITE_DEPTH: u32 = 64;
+
 impl LSystem {
-    pub fn new(name: &str, axiom: &str, productions: Vec<&str>) -> LSystem {
-        LSystem {
+    /// Create a new L System from an `axiom` and a list of `productions`.
+    ///
+    /// Productions are written `predecessor → successor`, where `->` or `=`
+    /// may be used instead of `→`, and modules may be separated by
+    /// whitespace. Context-sensitive
+    /// productions add a left context, a right context or both, as in
+    /// `left < predecessor > right → successor`, and take precedence over
+    /// context-free productions for the same predecessor.
+    ///
+    /// Modules may carry parameters, as in `F(1, 0.5)`. Parametric
+    /// productions name the parameters of their predecessor and context
+    /// modules, may add a condition after a `:`, and compute successor
+    /// parameters with arithmetic expressions, for example
+    /// `F(l, w) : l > 1 → F(l / 2, w * 0.7)+F(l / 2, w * 0.7)`. A pattern only
+    /// matches modules with the same number of parameters.
+    ///
+    /// Stochastic productions give a probability in parentheses after the
+    /// arrow, as in `F →(0.33) F[+F]F[-F]F`. When several stochastic
+    /// productions apply to a module, one of them is picked at random with
+    /// the given relative probabilities. See `LSystem::seeded_derivation`.
+    ///
+    /// Returns a `ParseError` describing the offending rule and position if
+    /// the axiom or a production can't be parsed.
+    pub fn try_new(name: &str, axiom: &str, productions: Vec<&str>) -> Result<LSystem, ParseError> {
+        let no_bindings = HashMap::new();
+        let rules = Rules {
+            axiom: axiom.to_string(),
+            productions: to_strings(&productions),
+            ..Rules::default()
+        };
+        Ok(LSystem {
             name: name.to_string(),
-            axiom: parse_sentence(axiom).unwrap().1,
-            productions: parse_productions(productions).unwrap().1,
+            axiom: parse_axiom(axiom)?
+                .iter()
+                .map(|t| t.expand(&no_bindings))
+                .collect(),
+            productions: parse_productions(productions)?,
+            decompositions: Vec::new(),
+            homomorphisms: Vec::new(),
+            ignore: HashSet::new(),
+            lifetimes: HashMap::new(),
+            rules,
+        })
+    }
+
+    /// Create a new L System, panicking if a rule can't be parsed.
+    ///
+    /// See `LSystem::try_new` for the rule syntax.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a description of the offending rule and position if the
+    /// axiom or a production can't be parsed.
+    #[deprecated(note = "use `LSystem::try_new` to handle parse errors")]
+    pub fn new(name: &str, axiom: &str, productions: Vec<&str>) -> LSystem {
+        match Self::try_new(name, axiom, productions) {
+            Ok(l_system) => l_system,
+            Err(e) => panic!("{}", e),
         }
     }
 
-    fn derive(&self, sentence: &Vec<Command>, n: u32) -> Vec<Command> {
-        if n == 0 {
-            sentence.clone()
+    /// Load an L System from the definition file at `path`.
+    ///
+    /// Definition files are TOML, or JSON if `path` has a `.json` extension,
+    /// with a `name`, an `axiom`, a list of `productions` and optionally
+    /// lists of `decompositions` and `homomorphisms`, the symbols to
+    /// `ignore` when matching contexts and a table of symbol `lifetimes` for
+    /// `LSystem::develop`. The optional `render` section is read
+    /// by `RenderOptions::from_file`. Rules that can't be parsed are reported
+    /// as `InvalidData` errors.
+    ///
+    /// Files with a `.l` extension are read in the L-studio and cpfg dialect
+    /// instead, named after the file. Their `derivation length` and `seed`
+    /// are read by `RenderOptions::from_file`.
+    ///
+    /// ```toml
+    /// name = "koch"
+    /// axiom = "F-F-F-F"
+    /// productions = ["F → F-F+F+FF-F-F+F"]
+    ///
+    /// [render]
+    /// derivation_length = 2
+    /// angle_increment_degrees = 90.0
+    /// rainbow = true
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{LSystem, RenderOptions};
+    /// let l_system = LSystem::from_file("test/systems/koch.toml")?;
+    /// let options = RenderOptions::from_file("test/systems/koch.toml")?;
+    /// let plant = LSystem::from_file("test/systems/plant.l")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn from_file<P>(path: P) -> io::Result<LSystem>
+    where
+        P: AsRef<Path>,
+    {
+        let definition = read_definition(path.as_ref())?;
+        LSystem::from_definition(&definition)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Create an L System from the rules of a `definition`.
+    fn from_definition(definition: &Definition) -> Result<LSystem, ParseError> {
+        let productions = definition.productions.iter().map(String::as_str).collect();
+        let decompositions = definition
+            .decompositions
+            .iter()
+            .map(String::as_str)
+            .collect();
+        let homomorphisms = definition
+            .homomorphisms
+            .iter()
+            .map(String::as_str)
+            .collect();
+        let mut l_system = LSystem::try_new(&definition.name, &definition.axiom, productions)?;
+        l_system
+            .decompositions(decompositions)?
+            .homomorphisms(homomorphisms)?
+            .ignore(&definition.ignore);
+        for (&symbol, &lifetime) in &definition.lifetimes {
+            l_system.lifetime(symbol, lifetime);
+        }
+        Ok(l_system)
+    }
+
+    /// Set the `symbols` to be skipped when matching contexts.
+    ///
+    /// Geometric symbols such as `+` and `-` usually don't take part in
+    /// signal propagation and are ignored, as described in The Algorithmic
+    /// Beauty of Plants section 1.7.
+    pub fn ignore(&mut self, symbols: &str) -> &mut Self {
+        self.ignore = symbols.chars().collect();
+        self
+    }
+
+    /// Set the decomposition rules, as described in The Algorithmic Beauty
+    /// of Plants section 1.10.3.
+    ///
+    /// Decomposition rules use the syntax of productions, without contexts or
+    /// probabilities. After every derivation step, and on the axiom, the
+    /// first applicable decomposition rule replaces a module, and the modules
+    /// it produces are decomposed again until no rule matches. This splits a
+    /// compound module into it's parts within the same step.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ParseError` if a rule can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::LSystem;
+    /// let mut l_system = LSystem::try_new("leaves", "A", vec!["A→AB"])?;
+    /// l_system.decompositions(vec!["B→[+L][-L]"])?;
+    /// assert_eq!(l_system.sentence(2).to_string(), "A[+L][-L][+L][-L]");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn decompositions(&mut self, rules: Vec<&str>) -> Result<&mut Self, ParseError> {
+        let sources = to_strings(&rules);
+        self.decompositions = parse_productions(rules)?;
+        self.rules.decompositions = sources;
+        Ok(self)
+    }
+
+    /// Set the interpretation homomorphism, as described in The Algorithmic
+    /// Beauty of Plants section 1.10.3.
+    ///
+    /// Homomorphism rules use the syntax of productions, without contexts or
+    /// probabilities. They map the abstract modules of a derived sentence to
+    /// the modules that are drawn, applying recursively like decompositions,
+    /// but only at render time so the derivation never sees their output.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ParseError` if a rule can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{LSystem, Module};
+    /// let mut l_system = LSystem::try_new("internodes", "I", vec!["I→II"])?;
+    /// l_system.homomorphisms(vec!["I→FF"])?;
+    /// assert_eq!(l_system.sentence(1).to_string(), "II");
+    /// let drawn: Vec<Module> = l_system.modules(1)
+    ///     .into_iter()
+    ///     .flat_map(|m| l_system.interpret(m))
+    ///     .collect();
+    /// assert_eq!(drawn.len(), 4);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn homomorphisms(&mut self, rules: Vec<&str>) -> Result<&mut Self, ParseError> {
+        let sources = to_strings(&rules);
+        self.homomorphisms = parse_productions(rules)?;
+        self.rules.homomorphisms = sources;
+        Ok(self)
+    }
+
+    /// Map a derived `module` to the modules drawn for it by the
+    /// homomorphism rules.
+    ///
+    /// Modules no rule matches are drawn as they are.
+    pub fn interpret(&self, module: Module) -> Vec<Module> {
+        let mut output = Vec::new();
+        self.rewrite(&self.homomorphisms, module, 0, &mut output);
+        output
+    }
+
+    /// Decompose every module of a `sentence`.
+    fn decompose(&self, sentence: Vec<Module>) -> Vec<Module> {
+        if self.decompositions.is_empty() {
+            return sentence;
+        }
+        let mut output = Vec::new();
+        for module in sentence {
+            self.rewrite(&self.decompositions, module, 0, &mut output);
+        }
+        output
+    }
+
+    /// Replace `module` by the successor of the first applicable rule, and
+    /// recursively rewrite that successor, appending the result to `output`.
+    fn rewrite(&self, rules: &[Production], module: Module, depth: u32, output: &mut Vec<Module>) {
+        let successor = match self
+            .applicable(rules, core::slice::from_ref(&module), 0)
+            .next()
+        {
+            Some((p, bindings)) if depth < MAX_REWRITE_DEPTH => Some(self.expand(p, &bindings)),
+            _ => None,
+        };
+        match successor {
+            Some(successor) => {
+                for module in successor {
+                    self.rewrite(rules, module, depth + 1, output);
+                }
+            }
+            None => output.push(module),
+        }
+    }
+
+    /// Expand the successor of a production with it's parameter `bindings`,
+    /// and decompose it.
+    fn expand(&self, p: &Production, bindings: &HashMap<&str, f32>) -> Vec<Module> {
+        self.decompose(p.successor.iter().map(|t| t.expand(bindings)).collect())
+    }
+
+    /// Set the `lifetime` of modules with `symbol` for timed development.
+    ///
+    /// A non-positive `lifetime` removes it. See `LSystem::develop`.
+    pub fn lifetime(&mut self, symbol: char, lifetime: f32) -> &mut Self {
+        if lifetime > 0.0 {
+            self.lifetimes.insert(symbol, lifetime);
         } else {
-            let mut derivation = Vec::new();
-            for c in sentence {
-                if self.productions.contains_key(c) {
-                    derivation.extend(self.productions[c].clone());
-                } else {
-                    derivation.push(*c);
+            self.lifetimes.remove(&symbol);
+        }
+        self
+    }
+
+    /// Develop the axiom for `t` units of continuous time, as in the timed
+    /// DOL-systems of The Algorithmic Beauty of Plants chapter 6.
+    ///
+    /// All modules start at age 0. A module with a `lifetime` is replaced by
+    /// it's successor, starting at age 0 again, once it's age reaches it's
+    /// lifetime, or stays fully grown if no production matches it. Modules
+    /// without a lifetime never change. The `growth` of
+    /// each module of the result is it's age over it's lifetime, so that
+    /// rendering successive times shows smooth growth instead of discrete
+    /// derivation steps. Stochastic productions are picked with a random
+    /// number generator seeded with `seed`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::LSystem;
+    /// let mut l_system = LSystem::try_new("growth", "A", vec!["A→FA"])?;
+    /// l_system.lifetime('A', 1.0).lifetime('F', 2.0);
+    /// let modules = l_system.develop(2.5, 0);
+    /// let growth: Vec<f32> = modules.iter().map(|m| m.growth()).collect();
+    /// assert_eq!(growth, vec![0.75, 0.25, 0.5]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn develop(&self, t: f32, seed: u64) -> Vec<Module> {
+        let mut rng = Seed::new(seed).rng();
+        let mut sentence = self.decompose(self.axiom.clone());
+        let mut births = vec![0.0; sentence.len()];
+        // Modules without a production stop at full growth when they reach
+        // their lifetime, with a birth of minus infinity.
+        let death = |module: &Module, birth: f32| {
+            self.lifetimes
+                .get(&module.symbol)
+                .map(|lifetime| birth + lifetime)
+                .filter(|death| death.is_finite())
+        };
+        loop {
+            // Replace every module that reaches it's lifetime next at once,
+            // so that contexts are matched against a consistent sentence.
+            let next = sentence
+                .iter()
+                .zip(&births)
+                .filter_map(|(m, &b)| death(m, b))
+                .fold(f32::INFINITY, f32::min);
+            if next > t {
+                break;
+            }
+            let mut developed = Vec::new();
+            let mut developed_births = Vec::new();
+            for (i, module) in sentence.iter().enumerate() {
+                if death(module, births[i]) != Some(next) {
+                    developed.push(module.clone());
+                    developed_births.push(births[i]);
+                    continue;
+                }
+                match self.production(&sentence, i, &mut rng) {
+                    Some((p, bindings)) => developed.extend(self.expand(p, &bindings)),
+                    None => {
+                        developed.push(module.clone());
+                        developed_births.push(f32::NEG_INFINITY);
+                    }
+                }
+                developed_births.resize(developed.len(), next);
+            }
+            sentence = developed;
+            births = developed_births;
+        }
+        for (module, birth) in sentence.iter_mut().zip(births) {
+            if let Some(lifetime) = self.lifetimes.get(&module.symbol) {
+                module.growth = ((t - birth) / lifetime).clamp(0.0, 1.0);
+            }
+        }
+        sentence
+    }
+
+    /// Match the modules before `i` against the `context`.
+    ///
+    /// Branches closed before `i` are skipped, and entering a branch moves the
+    /// match to its parent. Returns the indices of the matched modules.
+    fn left_context(
+        &self,
+        sentence: &[Module],
+        i: usize,
+        context: &[Pattern],
+    ) -> Option<Vec<usize>> {
+        let mut matched = Vec::new();
+        let mut j = i;
+        for expected in context.iter().rev() {
+            loop {
+                j = j.checked_sub(1)?;
+                match sentence[j].symbol {
+                    ']' => j = matching_bracket(sentence, j)?,
+                    '[' => (),
+                    _ if expected.matches(&sentence[j]) => break,
+                    c if self.ignore.contains(&c) => (),
+                    _ => return None,
+                }
+            }
+            matched.push(j);
+        }
+        matched.reverse();
+        Some(matched)
+    }
+
+    /// Match the modules after `i` against the `context`.
+    ///
+    /// Branches that the context does not descend into are skipped, and a
+    /// context `]` skips to the end of the current branch. Returns the
+    /// indices of the matched modules.
+    fn right_context(
+        &self,
+        sentence: &[Module],
+        i: usize,
+        context: &[Pattern],
+    ) -> Option<Vec<usize>> {
+        let mut matched = Vec::new();
+        let mut j = i + 1;
+        for expected in context {
+            if expected.symbol == ']' {
+                let mut depth = 0;
+                loop {
+                    match sentence.get(j)?.symbol {
+                        '[' => depth += 1,
+                        ']' if depth == 0 => break,
+                        ']' => depth -= 1,
+                        _ => (),
+                    }
+                    j += 1;
+                }
+            } else {
+                loop {
+                    let module = sentence.get(j)?;
+                    match module.symbol {
+                        _ if expected.matches(module) => break,
+                        '[' => j = matching_bracket(sentence, j)?,
+                        c if self.ignore.contains(&c) => (),
+                        _ => return None,
+                    }
+                    j += 1;
                 }
             }
-            self.derive(&derivation, n - 1)
+            matched.push(j);
+            j += 1;
         }
+        Some(matched)
+    }
+
+    /// Find the production to apply to the module at `i`, along with the
+    /// values bound to it's formal parameters.
+    ///
+    /// The first applicable production is used unless it is stochastic, in
+    /// which case one of the applicable stochastic productions is picked
+    /// with `rng`.
+    fn production<'a>(
+        &'a self,
+        sentence: &'a [Module],
+        i: usize,
+        rng: &mut ChaCha8Rng,
+    ) -> Option<(&'a Production, HashMap<&'a str, f32>)> {
+        let mut applicable = self.applicable(&self.productions, sentence, i);
+        let first = applicable.next()?;
+        if first.0.probability.is_none() {
+            return Some(first);
+        }
+        let candidates: Vec<_> = [first]
+            .into_iter()
+            .chain(applicable)
+            .filter(|(p, _)| p.probability.is_some())
+            .collect();
+        let total: f32 = candidates.iter().filter_map(|(p, _)| p.probability).sum();
+        let mut choice = rng.gen::<f32>() * total;
+        let last = candidates.len() - 1;
+        for (j, candidate) in candidates.into_iter().enumerate() {
+            choice -= candidate.0.probability.unwrap_or(0.0);
+            if choice < 0.0 || j == last {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    /// Get every one of the `rules` that applies to the module at `i`, in
+    /// order, along with the parameter bindings of each.
+    fn applicable<'a>(
+        &'a self,
+        rules: &'a [Production],
+        sentence: &'a [Module],
+        i: usize,
+    ) -> impl Iterator<Item = (&'a Production, HashMap<&'a str, f32>)> + 'a {
+        rules.iter().filter_map(move |p| {
+            if !p.predecessor.matches(&sentence[i]) {
+                return None;
+            }
+            let left = self.left_context(sentence, i, &p.left)?;
+            let right = self.right_context(sentence, i, &p.right)?;
+            let patterns = p.left.iter().chain([&p.predecessor]).chain(&p.right);
+            let indices = left.into_iter().chain([i]).chain(right);
+            let mut bindings = HashMap::new();
+            for (pattern, j) in patterns.zip(indices) {
+                for (name, value) in pattern.params.iter().zip(&sentence[j].params) {
+                    bindings.insert(name.as_str(), *value);
+                }
+            }
+            match &p.condition {
+                Some(condition) if condition.evaluate(&bindings) == 0.0 => None,
+                _ => Some((p, bindings)),
+            }
+        })
+    }
+
+    /// Derive the whole sentence of `n` steps from `sentence`.
+    ///
+    /// Two buffers are swapped between steps, and each step reserves room
+    /// for the sentence growing by the same factor as the step before, so
+    /// long derivations reallocate rarely instead of once per step.
+    ///
+    /// Every finished step is reported to `progress`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(steps = n)))]
+    fn derive(
+        &self,
+        sentence: &[Module],
+        n: u32,
+        rng: &mut ChaCha8Rng,
+        progress: Option<&ProgressCallback>,
+    ) -> Vec<Module> {
+        let mut current = sentence.to_vec();
+        let mut next = Vec::new();
+        // The growth of the last step, starting from the longest successor.
+        let mut growth = self
+            .productions
+            .iter()
+            .map(|p| p.successor.len())
+            .max()
+            .unwrap_or(1)
+            .max(1) as f64;
+        for step in 0..n {
+            next.clear();
+            let estimate = (current.len() as f64 * growth).min(isize::MAX as f64) as usize;
+            next.reserve(estimate);
+            for (i, module) in current.iter().enumerate() {
+                match self.production(&current, i, rng) {
+                    Some((p, bindings)) => next.extend(self.expand(p, &bindings)),
+                    None => next.push(module.clone()),
+                }
+            }
+            growth = next.len() as f64 / current.len().max(1) as f64;
+            core::mem::swap(&mut current, &mut next);
+            if let Some(progress) = progress {
+                progress(Progress {
+                    stage: Stage::Derivation,
+                    done: step as usize + 1,
+                    total: Some(n as usize),
+                });
+            }
+        }
+        current
     }
 
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Get the default commands of the sentence derived in `n` steps.
+    ///
+    /// Symbols without a default command are skipped.
     pub fn commands(&self, n: u32) -> Vec<Command> {
-        self.derive(&self.axiom, n)
+        self.modules(n)
+            .iter()
+            .filter_map(|m| Command::from_symbol(m.symbol))
+            .collect()
+    }
+
+    /// Get the sentence derived in `n` steps.
+    ///
+    /// Stochastic productions are picked with a seed of 0.
+    pub fn sentence(&self, n: u32) -> Sentence {
+        Sentence(self.modules(n))
+    }
+
+    /// Get the modules of the sentence derived in `n` steps.
+    ///
+    /// Stochastic productions are picked with a seed of 0.
+    pub fn modules(&self, n: u32) -> Vec<Module> {
+        self.derivation(n).collect()
+    }
+
+    /// Lazily derive the sentence of `n` steps, yielding it's modules in
+    /// order.
+    ///
+    /// Context-free L Systems are expanded depth first, so only the modules
+    /// on the path from the axiom to the current module are kept in memory.
+    /// Context-sensitive productions need the whole previous sentence to
+    /// match contexts, so L Systems using them are derived up front.
+    ///
+    /// Deterministic L Systems whose productions don't bind parameters are
+    /// memoized: the expansions of small subtrees are computed once per
+    /// module and depth, and repeated subtrees are copied from the cache.
+    ///
+    /// Stochastic productions are picked with a seed of 0.
+    pub fn derivation(&self, n: u32) -> Derivation<'_> {
+        self.seeded_derivation(n, 0)
+    }
+
+    /// Lazily derive the sentence of `n` steps, picking stochastic
+    /// productions with a random number generator seeded with `seed`.
+    ///
+    /// The same seed always gives the same sentence, on any machine.
+    pub fn seeded_derivation(&self, n: u32, seed: u64) -> Derivation<'_> {
+        self.derivation_with_progress(n, seed, None)
+    }
+
+    /// Lazily derive the sentence of `n` steps like `seeded_derivation`,
+    /// reporting the steps of derivations done up front to `progress`.
+    fn derivation_with_progress(
+        &self,
+        n: u32,
+        seed: u64,
+        progress: Option<&ProgressCallback>,
+    ) -> Derivation<'_> {
+        let mut rng = Seed::new(seed).rng();
+        let context_free = self
+            .productions
+            .iter()
+            .all(|p| p.left.is_empty() && p.right.is_empty());
+        let memoizable = self
+            .productions
+            .iter()
+            .all(|p| p.probability.is_none() && p.predecessor.params.is_empty());
+        let axiom = self.decompose(self.axiom.clone());
+        let stack = if context_free {
+            vec![(n, axiom.into(), 0)]
+        } else {
+            vec![(0, self.derive(&axiom, n, &mut rng, progress).into(), 0)]
+        };
+        Derivation {
+            l_system: self,
+            stack,
+            rng,
+            memo: (context_free && memoizable).then(Memo::default),
+        }
+    }
+
+    /// Get the successor of a single `module` in a context-free derivation.
+    fn successor(&self, module: &Module, rng: &mut ChaCha8Rng) -> Vec<Module> {
+        match self.production(core::slice::from_ref(module), 0, rng) {
+            Some((p, bindings)) => self.expand(p, &bindings),
+            None => vec![module.clone()],
+        }
+    }
+
+    /// Estimate the number of modules `sentence` derives in `n` steps.
+    ///
+    /// Every module is expected to grow by the first production of it's
+    /// symbol, or by the stochastic productions of it's symbol weighted by
+    /// their probabilities, ignoring contexts and conditions.
+    fn estimated_len(&self, sentence: &[Module], n: u32) -> usize {
+        let mut rules: HashMap<char, Vec<&Production>> = HashMap::new();
+        for p in &self.productions {
+            rules.entry(p.predecessor.symbol).or_default().push(p);
+        }
+        // The expected lengths of the modules of each symbol after a number
+        // of steps, where symbols without productions stay 1 module long.
+        let mut lens: HashMap<char, f64> = HashMap::new();
+        for _ in 0..n {
+            let len = |p: &Production| {
+                p.successor
+                    .iter()
+                    .map(|t| lens.get(&t.symbol).copied().unwrap_or(1.0))
+                    .sum::<f64>()
+            };
+            lens = rules
+                .iter()
+                .map(|(&symbol, productions)| {
+                    let expected = if productions[0].probability.is_none() {
+                        len(productions[0])
+                    } else {
+                        let (weighted, total) = productions
+                            .iter()
+                            .filter_map(|p| p.probability.map(|probability| (p, probability)))
+                            .fold((0.0, 0.0), |(weighted, total), (p, probability)| {
+                                let probability = probability as f64;
+                                (weighted + probability * len(p), total + probability)
+                            });
+                        weighted / total
+                    };
+                    (symbol, expected)
+                })
+                .collect();
+        }
+        let len: f64 = sentence
+            .iter()
+            .map(|m| lens.get(&m.symbol).copied().unwrap_or(1.0))
+            .sum();
+        len.min(usize::MAX as f64) as usize
+    }
+
+    /// Get the number of modules `module` derives in `n` steps, memoized.
+    fn expansion_len(
+        &self,
+        memo: &mut Memo,
+        module: &Module,
+        n: u32,
+        rng: &mut ChaCha8Rng,
+    ) -> usize {
+        if n == 0 {
+            return 1;
+        }
+        let key = Memo::key(module, n);
+        if let Some(&len) = memo.lens.get(&key) {
+            return len;
+        }
+        let len = self.successor(module, rng).iter().fold(0usize, |len, m| {
+            len.saturating_add(self.expansion_len(memo, m, n - 1, rng))
+        });
+        memo.lens.insert(key, len);
+        len
+    }
+
+    /// Get the modules `module` derives in `n` steps, memoized.
+    fn expansion(
+        &self,
+        memo: &mut Memo,
+        module: &Module,
+        n: u32,
+        rng: &mut ChaCha8Rng,
+    ) -> Rc<[Module]> {
+        if n == 0 {
+            return Rc::from([module.clone()]);
+        }
+        let key = Memo::key(module, n);
+        if let Some(expansion) = memo.expansions.get(&key) {
+            return expansion.clone();
+        }
+        let mut expansion = Vec::new();
+        for m in self.successor(module, rng) {
+            expansion.extend_from_slice(&self.expansion(memo, &m, n - 1, rng));
+        }
+        let expansion: Rc<[Module]> = expansion.into();
+        memo.expansions.insert(key, expansion.clone());
+        expansion
+    }
+}
+
+/// The most modules a memoized subtree expansion may hold.
+const MAX_MEMOIZED_EXPANSION: usize = 1 << 12;
+
+/// Cached subtree expansions of a deterministic derivation, keyed by the
+/// symbol, parameter bits and remaining derivation steps of a module.
+#[derive(Default)]
+struct Memo {
+    lens: HashMap<(char, Vec<u32>, u32), usize>,
+    expansions: HashMap<(char, Vec<u32>, u32), Rc<[Module]>>,
+}
+
+impl Memo {
+    fn key(module: &Module, n: u32) -> (char, Vec<u32>, u32) {
+        let params = module.params.iter().map(|p| p.to_bits()).collect();
+        (module.symbol, params, n)
+    }
+}
+
+/// A lazy derivation of an L System sentence.
+///
+/// Created by `LSystem::derivation` and `LSystem::seeded_derivation`.
+pub struct Derivation<'a> {
+    l_system: &'a LSystem,
+    /// Modules still to be derived the given number of times, and the index
+    /// of the next one.
+    stack: Vec<(u32, Rc<[Module]>, usize)>,
+    rng: ChaCha8Rng,
+    memo: Option<Memo>,
+}
+
+impl Derivation<'_> {
+    /// Estimate the number of modules left to derive, exactly for sentences
+    /// derived up front.
+    fn estimated_len(&self) -> usize {
+        self.stack
+            .iter()
+            .map(|(n, modules, i)| self.l_system.estimated_len(&modules[*i..], *n))
+            .fold(0, usize::saturating_add)
+    }
+}
+
+impl Iterator for Derivation<'_> {
+    type Item = Module;
+
+    fn next(&mut self) -> Option<Module> {
+        loop {
+            let (n, modules, i) = self.stack.last_mut()?;
+            let n = *n;
+            let module = match modules.get(*i) {
+                Some(module) => module.clone(),
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            *i += 1;
+            if n == 0 {
+                return Some(module);
+            }
+            let l_system = self.l_system;
+            if let Some(memo) = &mut self.memo {
+                let len = l_system.expansion_len(memo, &module, n, &mut self.rng);
+                if len <= MAX_MEMOIZED_EXPANSION {
+                    let expansion = l_system.expansion(memo, &module, n, &mut self.rng);
+                    self.stack.push((0, expansion, 0));
+                    continue;
+                }
+            }
+            let successor = l_system.successor(&module, &mut self.rng);
+            self.stack.push((n - 1, successor.into(), 0));
+        }
+    }
+}
+
+impl Template {
+    fn expand(&self, bindings: &HashMap<&str, f32>) -> Module {
+        Module {
+            symbol: self.symbol,
+            params: self.params.iter().map(|e| e.evaluate(bindings)).collect(),
+            growth: 1.0,
+        }
     }
 }
 
+/// Statistics about a render, see `RenderOptions::render_with_stats`.
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    symbols: HashMap<char, usize>,
+    drawn_voxels: usize,
+    bounds: Option<Bounds>,
+    clipped_segments: usize,
+    derivation_time: Duration,
+    drawing_time: Duration,
+}
+
+impl RenderStats {
+    /// Get the number of modules with `symbol` in the derived sentence.
+    pub fn symbol_count(&self, symbol: char) -> usize {
+        self.symbols.get(&symbol).copied().unwrap_or(0)
+    }
+
+    /// Get the number of modules of each symbol in the derived sentence.
+    pub fn symbol_counts(&self) -> &HashMap<char, usize> {
+        &self.symbols
+    }
+
+    /// Get the total number of modules in the derived sentence.
+    pub fn symbol_total(&self) -> usize {
+        self.symbols.values().sum()
+    }
+
+    /// Get the number of voxels drawn, counting voxels drawn over more than
+    /// once each time.
+    pub fn drawn_voxels(&self) -> usize {
+        self.drawn_voxels
+    }
+
+    /// Get the smallest and largest locations drawn, or `None` if nothing was
+    /// drawn.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    /// Get the number of segments cut short by a collision or by the edges
+    /// of the buffer.
+    pub fn clipped_segments(&self) -> usize {
+        self.clipped_segments
+    }
+
+    /// Get the time spent deriving the sentence.
+    ///
+    /// Without the `std` feature there's no clock, and times are always 0.
+    pub fn derivation_time(&self) -> Duration {
+        self.derivation_time
+    }
+
+    /// Get the time spent drawing the sentence, 0 without the `std`
+    /// feature.
+    pub fn drawing_time(&self) -> Duration {
+        self.drawing_time
+    }
+}
+
+/// A user defined interpretation of a module, see `RenderOptions::callback`.
+type Callback = Arc<dyn Fn(&mut TurtleGraphics, &[f32]) + Send + Sync>;
+
+/// Serialize a map keyed by symbols with it's keys in order, so that
+/// serialized settings don't change between runs, and as strings, since
+/// formats like TOML only have string keys.
+fn sorted<V, S>(map: &HashMap<char, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    V: Serialize,
+    S: Serializer,
+{
+    map.iter()
+        .map(|(symbol, value)| (symbol.to_string(), value))
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
 /// Render an L System string in 3D with it's turtle intepretation.
+///
+/// Render options serialize with serde, so a whole generation setup can be
+/// stored alongside it's `LSystem` and replayed. Settings missing when
+/// deserializing keep their defaults. Callbacks and progress callbacks
+/// can't be serialized and are skipped, so bind them again after
+/// deserializing.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::{presets, LSystem, RenderOptions};
+/// # use voxgen::voxel_buffer::Rgba;
+/// let l_system = presets::stochastic_plant();
+/// let mut options = RenderOptions::new();
+/// options
+///     .derivation_length(4)
+///     .auto_fit(true)
+///     .seed(7)
+///     .symbol_color('F', Rgba([90, 60, 30, 255]));
+/// let stored = (toml::to_string(&l_system)?, toml::to_string(&options)?);
+/// let replayed: LSystem = toml::from_str(&stored.0)?;
+/// let replayed_options: RenderOptions = toml::from_str(&stored.1)?;
+/// let replay = replayed_options.render(&replayed)?;
+/// assert_eq!(replay, options.render(&l_system)?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct RenderOptions {
     derivation_length: u32,
     step_size: f32,
@@ -97,7 +1699,37 @@ pub struct RenderOptions {
     offset_x: f32,
     offset_y: f32,
     offset_z: f32,
+    initial_heading: f32,
+    initial_pitch: f32,
+    width: f32,
+    width_decrement: f32,
+    step_scale: f32,
+    branch_step_scale: f32,
+    branch_width_scale: f32,
+    seed: u64,
+    time: Option<f32>,
+    auto_fit: bool,
     rainbow: bool,
+    gradient: Vec<Rgba>,
+    gradient_steps: usize,
+    symbols: SymbolMap,
+    #[serde(skip)]
+    callbacks: HashMap<char, Callback>,
+    #[serde(serialize_with = "sorted")]
+    subsystems: HashMap<char, Arc<(LSystem, RenderOptions)>>,
+    #[serde(serialize_with = "sorted")]
+    colors: HashMap<char, Vec<Rgba>>,
+    max_symbols: Option<usize>,
+    max_voxels: Option<usize>,
+    voxels_per_unit: f32,
+    #[serde(skip)]
+    progress: Option<ProgressCallback>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RenderOptions {
@@ -105,17 +1737,104 @@ impl RenderOptions {
         RenderOptions {
             derivation_length: 2,
             step_size: 2.0,
-            angle_increment: std::f32::consts::FRAC_PI_2,
+            angle_increment: core::f32::consts::FRAC_PI_2,
             size_x: 64,
             size_y: 64,
             size_z: 64,
             offset_x: 0.0,
             offset_y: 0.0,
             offset_z: 0.0,
+            initial_heading: 0.0,
+            initial_pitch: 0.0,
+            width: 1.0,
+            width_decrement: 1.0,
+            step_scale: 0.9,
+            branch_step_scale: 1.0,
+            branch_width_scale: 1.0,
+            seed: 0,
+            time: None,
+            auto_fit: false,
             rainbow: false,
+            gradient: Gradient::rainbow().colors().to_vec(),
+            gradient_steps: 250,
+            symbols: SymbolMap::new(),
+            callbacks: HashMap::new(),
+            subsystems: HashMap::new(),
+            colors: HashMap::new(),
+            max_symbols: None,
+            max_voxels: None,
+            voxels_per_unit: 1.0,
+            progress: None,
         }
     }
 
+    /// Load render options from the `render` section of the L System
+    /// definition file at `path`.
+    ///
+    /// The section may set `derivation_length`, `step_size`,
+    /// `angle_increment_degrees`, `size_x`, `size_y`, `size_z`, `offset_x`,
+    /// `offset_y`, `offset_z`, `initial_heading_degrees`,
+    /// `initial_pitch_degrees`, `rainbow`, `max_symbols`, `max_voxels`,
+    /// `seed` and `voxels_per_unit`. Missing settings keep their defaults.
+    /// See `LSystem::from_file` for the file format.
+    #[cfg(feature = "std")]
+    pub fn from_file<P>(path: P) -> io::Result<RenderOptions>
+    where
+        P: AsRef<Path>,
+    {
+        let render = read_definition(path.as_ref())?.render;
+        let mut options = RenderOptions::new();
+        if let Some(n) = render.derivation_length {
+            options.derivation_length(n);
+        }
+        if let Some(d) = render.step_size {
+            options.step_size(d);
+        }
+        if let Some(delta) = render.angle_increment_degrees {
+            options.angle_increment(delta.to_radians());
+        }
+        if let Some(size_x) = render.size_x {
+            options.size_x(size_x);
+        }
+        if let Some(size_y) = render.size_y {
+            options.size_y(size_y);
+        }
+        if let Some(offset) = render.offset_x {
+            options.offset_x(offset);
+        }
+        if let Some(offset) = render.offset_y {
+            options.offset_y(offset);
+        }
+        if let Some(size_z) = render.size_z {
+            options.size_z(size_z);
+        }
+        if let Some(offset) = render.offset_z {
+            options.offset_z(offset);
+        }
+        if let Some(angle) = render.initial_heading_degrees {
+            options.initial_heading(angle.to_radians());
+        }
+        if let Some(angle) = render.initial_pitch_degrees {
+            options.initial_pitch(angle.to_radians());
+        }
+        if let Some(rainbow) = render.rainbow {
+            options.rainbow(rainbow);
+        }
+        if let Some(max) = render.max_symbols {
+            options.max_symbols(max);
+        }
+        if let Some(max) = render.max_voxels {
+            options.max_voxels(max);
+        }
+        if let Some(seed) = render.seed {
+            options.seed(seed);
+        }
+        if let Some(scale) = render.voxels_per_unit {
+            options.voxels_per_unit(scale);
+        }
+        Ok(options)
+    }
+
     pub fn step_size(&mut self, d: f32) -> &mut Self {
         self.step_size = d;
         self
@@ -131,6 +1850,15 @@ impl RenderOptions {
         self
     }
 
+    /// Seed the random choices made while rendering, such as picking
+    /// stochastic productions. Defaults to 0.
+    ///
+    /// Rendering with the same seed always gives the same result.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
     pub fn size_x(&mut self, size_x: u32) -> &mut Self {
         self.size_x = size_x;
         self
@@ -151,88 +1879,932 @@ impl RenderOptions {
         self
     }
 
+    pub fn size_z(&mut self, size_z: u32) -> &mut Self {
+        self.size_z = size_z;
+        self
+    }
+
+    /// Move the starting position of the turtle `offset` voxels up from the
+    /// bottom of the buffer.
+    pub fn offset_z(&mut self, offset: f32) -> &mut Self {
+        self.offset_z = offset;
+        self
+    }
+
+    /// Turn the turtle `angle` radians left from facing north before
+    /// rendering.
+    pub fn initial_heading(&mut self, angle: f32) -> &mut Self {
+        self.initial_heading = angle;
+        self
+    }
+
+    /// Pitch the turtle `angle` radians up before rendering, after turning
+    /// it to the initial heading.
+    ///
+    /// A pitch of 90° makes 3D systems grow upward, along `z`.
+    pub fn initial_pitch(&mut self, angle: f32) -> &mut Self {
+        self.initial_pitch = angle;
+        self
+    }
+
+    /// Render the timed development of the L System at time `t` instead of
+    /// the sentence derived in `derivation_length` steps.
+    ///
+    /// See `LSystem::develop`.
+    pub fn time(&mut self, t: f32) -> &mut Self {
+        self.time = Some(t);
+        self
+    }
+
+    /// Size the rendered buffer to fit the drawing exactly, ignoring the size
+    /// and offset options.
+    ///
+    /// The L System is interpreted twice, once to measure the drawing and
+    /// once to draw it.
+    pub fn auto_fit(&mut self, auto_fit: bool) -> &mut Self {
+        self.auto_fit = auto_fit;
+        self
+    }
+
+    /// Set the initial line width in voxels. Defaults to 1.
+    pub fn width(&mut self, width: f32) -> &mut Self {
+        self.width = width;
+        self
+    }
+
+    /// Set the number of voxels in one unit of the step size and line widths.
+    /// Defaults to 1.
+    ///
+    /// See `TurtleGraphics::voxels_per_unit`. The size and offset options are
+    /// still in voxels, so combine with `auto_fit` to render the same rules
+    /// as a quick low resolution preview and a detailed final model.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{LSystem, RenderOptions};
+    /// # use voxgen::voxel_buffer::VoxelBuffer;
+    /// let plant = LSystem::try_new("plant", "F", vec!["F → F[+F]F[-F]F"])?;
+    /// let mut options = RenderOptions::new();
+    /// options.step_size(2.0).auto_fit(true).voxels_per_unit(0.5);
+    /// let preview = options.render(&plant)?;
+    /// assert_eq!(preview.dimensions(), (7, 10, 1));
+    /// let final_render = options.voxels_per_unit(4.0).render(&plant)?;
+    /// assert_eq!(final_render.dimensions(), (51, 75, 3));
+    /// final_render.save("test/volumes/voxels_per_unit_final.vox")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn voxels_per_unit(&mut self, voxels_per_unit: f32) -> &mut Self {
+        self.voxels_per_unit = voxels_per_unit;
+        self
+    }
+
+    /// Set how many voxels thinner `!` modules without a parameter make lines.
+    /// Defaults to 1.
+    pub fn width_decrement(&mut self, decrement: f32) -> &mut Self {
+        self.width_decrement = decrement;
+        self
+    }
+
+    /// Set the factor `"` modules without a parameter scale the step size by.
+    /// Defaults to 0.9.
+    pub fn step_scale(&mut self, factor: f32) -> &mut Self {
+        self.step_scale = factor;
+        self
+    }
+
+    /// Scale the step size by `factor` in every nested branch, so that a
+    /// branch `n` brackets deep takes steps `factor` to the power of `n`
+    /// times as long. Defaults to 1.
+    pub fn branch_step_scale(&mut self, factor: f32) -> &mut Self {
+        self.branch_step_scale = factor;
+        self
+    }
+
+    /// Scale the line width by `factor` in every nested branch, like
+    /// `branch_step_scale`. Defaults to 1.
+    pub fn branch_width_scale(&mut self, factor: f32) -> &mut Self {
+        self.branch_width_scale = factor;
+        self
+    }
+
+    /// Sample `len` evenly spaced colors from the rainbow mode gradient,
+    /// interpolating between it's control points in linear RGB.
     pub fn get_rainbow(&self, len: usize) -> Vec<[u8; 4]> {
-        let curve = ConstEquidistantLinear::<f32, _, 7>::equidistant_unchecked([
-            LinSrgba::new(1.0, 0.0, 0.0, 1.0),
-            LinSrgba::new(1.0, 1.0, 0.0, 1.0),
-            LinSrgba::new(0.0, 1.0, 0.0, 1.0),
-            LinSrgba::new(0.0, 1.0, 1.0, 1.0),
-            LinSrgba::new(0.0, 0.0, 1.0, 1.0),
-            LinSrgba::new(1.0, 0.0, 1.0, 1.0),
-            LinSrgba::new(1.0, 0.0, 0.0, 1.0),
-        ]);
-        let mut gradient: Vec<[u8; 4]> = Vec::new();
-        for srgba in curve.take(len) {
-            let rgba = Srgba::from_linear(srgba).into();
-            gradient.push(rgba);
-        }
-        gradient
+        Gradient::new(self.gradient.clone())
+            .take(len)
+            .into_iter()
+            .map(|Rgba(c)| c)
+            .collect()
     }
 
+    /// Color successive drawing symbols in successive colors of a gradient.
+    ///
+    /// The gradient is a rainbow unless set with `gradient`.
     pub fn rainbow(&mut self, rainbow: bool) -> &mut Self {
         self.rainbow = rainbow;
         self
     }
 
-    fn draw(&self, turtle: &mut TurtleGraphics, c: Command) {
-        match c {
-            Command::Step => turtle.step(self.step_size),
-            Command::Draw => turtle.draw(self.step_size),
-            Command::Left => turtle.left(self.angle_increment),
-            Command::Right => turtle.right(self.angle_increment),
+    /// Use a gradient through the `colors` control points in rainbow mode.
+    ///
+    /// An empty list restores the default rainbow.
+    pub fn gradient(&mut self, colors: Vec<Rgba>) -> &mut Self {
+        self.gradient = if colors.is_empty() {
+            Gradient::rainbow().colors().to_vec()
+        } else {
+            colors
+        };
+        self
+    }
+
+    /// Spread the rainbow mode gradient over `n` drawing symbols.
+    ///
+    /// Later symbols keep the last color of the gradient. Defaults to 250.
+    pub fn gradient_steps(&mut self, n: usize) -> &mut Self {
+        self.gradient_steps = n.max(1);
+        self
+    }
+
+    /// Draw `symbol` in `color`.
+    ///
+    /// Overrides the rainbow gradient for `symbol`.
+    pub fn symbol_color(&mut self, symbol: char, color: Rgba) -> &mut Self {
+        self.colors.insert(symbol, vec![color]);
+        self
+    }
+
+    /// Draw successive occurrences of `symbol` in successive colors of
+    /// `gradient`, keeping the last color once it runs out.
+    ///
+    /// Overrides the rainbow gradient for `symbol`. An empty gradient removes
+    /// any color set for `symbol`.
+    pub fn symbol_gradient(&mut self, symbol: char, gradient: Vec<Rgba>) -> &mut Self {
+        if gradient.is_empty() {
+            self.colors.remove(&symbol);
+        } else {
+            self.colors.insert(symbol, gradient);
+        }
+        self
+    }
+
+    /// Interpret `symbol` as `command` when rendering.
+    ///
+    /// Overrides the command of `symbol` in the symbol map.
+    pub fn symbol(&mut self, symbol: char, command: Command) -> &mut Self {
+        self.symbols.set(symbol, command);
+        self
+    }
+
+    /// Interpret symbols with the commands of `symbols` when rendering.
+    ///
+    /// Defaults to `SymbolMap::new`, the commands of `Command::from_symbol`.
+    /// Symbols without a command are skipped when rendering.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{Command, LSystem, RenderOptions, SymbolMap};
+    /// // Draw the edges of a dragon curve with standard notation.
+    /// let l_system = LSystem::try_new("dragon", "L", vec!["L→L+R", "R→L-R"])?;
+    /// let mut symbols = SymbolMap::standard();
+    /// symbols.set('L', Command::Draw).set('R', Command::Draw);
+    /// RenderOptions::new()
+    ///     .symbol_map(symbols)
+    ///     .derivation_length(6)
+    ///     .auto_fit(true)
+    ///     .render(&l_system)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn symbol_map(&mut self, symbols: SymbolMap) -> &mut Self {
+        self.symbols = symbols;
+        self
+    }
+
+    /// Fail rendering when the derived sentence has more than `max` modules.
+    ///
+    /// Context-free L Systems are derived lazily, so rendering stops as soon
+    /// as the limit is reached instead of deriving the whole sentence first.
+    pub fn max_symbols(&mut self, max: usize) -> &mut Self {
+        self.max_symbols = Some(max);
+        self
+    }
+
+    /// Fail rendering when more than `max` voxels have been drawn.
+    ///
+    /// Voxels drawn over more than once count each time. With `auto_fit`,
+    /// rendering also fails before drawing when the fitted buffer holds more
+    /// than `max` voxels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{LSystem, LimitError, RenderOptions};
+    /// let sparse = LSystem::try_new("sparse", "F(1)f(20000)+F(1)f(20000)&F(1)f(20000)F(1)", vec![])?;
+    /// let result = RenderOptions::new()
+    ///     .auto_fit(true)
+    ///     .max_voxels(100)
+    ///     .render(&sparse);
+    /// assert!(matches!(result, Err(LimitError::Canvas(_))));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn max_voxels(&mut self, max: usize) -> &mut Self {
+        self.max_voxels = Some(max);
+        self
+    }
+
+    /// Report the progress of rendering to `callback`.
+    ///
+    /// Derivations done up front, of context-sensitive L Systems, report
+    /// every step as `Stage::Derivation`. Drawing reports the modules of the
+    /// derived sentence drawn so far as `Stage::Rasterization` every 1024
+    /// modules and once at the end. Context-free sentences are derived
+    /// lazily while drawing, so their total is estimated from the expected
+    /// growth of every symbol, and may be off for conditional productions;
+    /// the last report always has `done` equal to `total`. Auto fitted
+    /// renders draw twice, so they report both stages twice. Saving with
+    /// `save` also reports the z slices encoded as `Stage::Export`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{presets, RenderOptions};
+    /// # use voxgen::progress::Stage;
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use std::sync::Arc;
+    /// let drawn = Arc::new(AtomicUsize::new(0));
+    /// let counter = drawn.clone();
+    /// RenderOptions::new()
+    ///     .derivation_length(3)
+    ///     .step_size(1.0)
+    ///     .auto_fit(true)
+    ///     .progress(move |progress| {
+    ///         if progress.stage == Stage::Rasterization {
+    ///             assert!(progress.fraction().is_some());
+    ///             counter.store(progress.done, Ordering::Relaxed);
+    ///         }
+    ///     })
+    ///     .render(&presets::koch_island())?;
+    /// assert!(drawn.load(Ordering::Relaxed) > 1024);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn progress<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Interpret `symbol` by calling `callback` with the turtle and the
+    /// parameters of each module when rendering.
+    ///
+    /// Callbacks take precedence over commands, letting modules draw anything
+    /// the turtle can, for example stamping a leaf at the turtle's position.
+    /// With `auto_fit`, callbacks are also called with the measuring turtle
+    /// from `TurtleGraphics::measure`, which has an empty buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{LSystem, RenderOptions};
+    /// // Draw a dot of `d` steps ahead for every `D(d)` module.
+    /// let l_system = LSystem::try_new("dots", "D(1)+D(2)+D(3)", vec![])?;
+    /// RenderOptions::new()
+    ///     .callback('D', |turtle, params| {
+    ///         let d = params.first().copied().unwrap_or(1.0);
+    ///         turtle.step(d);
+    ///         turtle.draw(0.0);
+    ///     })
+    ///     .render(&l_system)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn callback<F>(&mut self, symbol: char, callback: F) -> &mut Self
+    where
+        F: Fn(&mut TurtleGraphics, &[f32]) + Send + Sync + 'static,
+    {
+        self.callbacks.insert(symbol, Arc::new(callback));
+        self
+    }
+
+    /// Interpret `symbol` by rendering `l_system` with `options` from the
+    /// current turtle state, like a subfigure.
+    ///
+    /// The subfigure is drawn in the turtle's current position, orientation
+    /// and color, and the turtle state is restored afterwards. A parameter
+    /// scales the subfigure, so `S(0.5)` draws it at half size. Subfigures
+    /// may contain subfigures of their own.
+    ///
+    /// Only the derivation, step, angle, color and limit options of
+    /// `options` are used. The `l_system` should have balanced brackets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{LSystem, RenderOptions};
+    /// // Draw a small Koch island at the end of every branch.
+    /// let tree = LSystem::try_new("tree", "F[+FS][-FS]FS", vec![])?;
+    /// let island = LSystem::try_new("island", "F-F-F-F", vec!["F→F-F+F+FF-F-F+F"])?;
+    /// RenderOptions::new()
+    ///     .step_size(4.0)
+    ///     .angle_increment(0.5)
+    ///     .subsystem('S', island, RenderOptions::new().derivation_length(1).step_size(1.0))
+    ///     .render(&tree)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn subsystem(
+        &mut self,
+        symbol: char,
+        l_system: LSystem,
+        options: &RenderOptions,
+    ) -> &mut Self {
+        self.subsystems
+            .insert(symbol, Arc::new((l_system, options.clone())));
+        self
+    }
+
+    fn command(&self, symbol: char) -> Option<Command> {
+        self.symbols.get(symbol)
+    }
+
+    fn draw(&self, turtle: &mut TurtleGraphics, module: &Module) {
+        if let Some(callback) = self.callbacks.get(&module.symbol) {
+            callback(turtle, &module.params);
+            return;
+        }
+        let length = module.params.first().copied().unwrap_or(self.step_size) * module.growth;
+        let step_size = self.step_size * module.growth;
+        let angle = module
+            .params
+            .first()
+            .map_or(self.angle_increment, |a| a.to_radians());
+        let command = match self.command(module.symbol) {
+            Some(command) => command,
+            None => return,
+        };
+        match command {
+            Command::Step => turtle.step(length),
+            Command::Draw if module.growth < 1.0 => {
+                // Growing lines thicken along with their length.
+                let width = turtle.stroke_width();
+                turtle.width(width * module.growth);
+                turtle.draw(length);
+                turtle.width(width);
+            }
+            Command::Draw => turtle.draw(length),
+            Command::Left => turtle.left(angle),
+            Command::Right => turtle.right(angle),
+            Command::PitchDown => turtle.pitch_down(angle),
+            Command::PitchUp => turtle.pitch_up(angle),
+            Command::RollLeft => turtle.roll_left(angle),
+            Command::RollRight => turtle.roll_right(angle),
+            Command::TurnAround => turtle.turn_around(),
             Command::DrawLeft => {
-                turtle.draw(self.step_size);
+                turtle.draw(step_size);
                 turtle.left(self.angle_increment);
-                turtle.draw(self.step_size);
+                turtle.draw(step_size);
             }
             Command::DrawRight => {
-                turtle.draw(self.step_size);
+                turtle.draw(step_size);
                 turtle.right(self.angle_increment);
-                turtle.draw(self.step_size);
+                turtle.draw(step_size);
+            }
+            Command::Push => {
+                turtle.push();
+                turtle.scale_step(self.branch_step_scale);
+                turtle.scale_width(self.branch_width_scale);
+            }
+            Command::Pop => turtle.pop(),
+            Command::DecrementWidth => match module.params.first() {
+                Some(&width) => turtle.width(width),
+                None => turtle.decrement_width(self.width_decrement),
+            },
+            Command::ScaleStep => {
+                turtle.scale_step(module.params.first().copied().unwrap_or(self.step_scale))
             }
             _ => (),
         }
     }
 
-    pub fn render(&self, l_system: LSystem) {
-        let mut turtle = TurtleGraphics::new(self.size_x, self.size_y, self.size_z);
+    /// Render the turtle interpretation of `l_system` into a new buffer.
+    ///
+    /// The turtle starts in the center of the bottom of a `size_x` by `size_y`
+    /// by `size_z` buffer, moved by `offset_x`, `offset_y` and `offset_z`,
+    /// facing north turned by `initial_heading` and `initial_pitch`, drawing
+    /// lines `width` voxels wide.
+    ///
+    /// With `auto_fit`, the size and offset options are ignored and the
+    /// buffer is sized to fit the drawing exactly instead. Otherwise voxels
+    /// drawn outside of the buffer are clipped, see
+    /// `RenderStats::clipped_segments`.
+    ///
+    /// Returns an error if the `max_symbols` or `max_voxels` limit is
+    /// exceeded, or if the buffer is too large to create.
+    pub fn render(&self, l_system: &LSystem) -> Result<ArrayVoxelBuffer<Rgba>, LimitError> {
+        self.render_with_stats(l_system).map(|(buf, _)| buf)
+    }
+
+    /// Render `l_system` like `render`, also reporting statistics about the
+    /// derivation and drawing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{presets, RenderOptions};
+    /// let (_, stats) = RenderOptions::new()
+    ///     .derivation_length(1)
+    ///     .auto_fit(true)
+    ///     .render_with_stats(&presets::koch_island())?;
+    /// assert_eq!(stats.symbol_count('F'), 32);
+    /// assert_eq!(stats.clipped_segments(), 0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// Drawings growing past a fixed size buffer are clipped to it.
+    /// ```
+    /// # use voxgen::l_system::{LSystem, RenderOptions};
+    /// let l_system = LSystem::try_new("line", "F", vec!["F → FF"])?;
+    /// let (buf, stats) = RenderOptions::new()
+    ///     .derivation_length(6)
+    ///     .size_x(8)
+    ///     .size_y(8)
+    ///     .size_z(8)
+    ///     .render_with_stats(&l_system)?;
+    /// assert_eq!(buf.enumerate_occupied().count(), 4);
+    /// assert!(stats.clipped_segments() > 0);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, name = "render"))]
+    pub fn render_with_stats(
+        &self,
+        l_system: &LSystem,
+    ) -> Result<(ArrayVoxelBuffer<Rgba>, RenderStats), LimitError> {
+        let mut stats = RenderStats::default();
+        let turtle = if self.auto_fit {
+            self.render_fitted(l_system, &mut stats)?
+        } else {
+            self.render_centered(l_system, &mut stats)?
+        };
+        stats.drawn_voxels = turtle.drawn_voxels();
+        stats.bounds = turtle.bounds();
+        stats.clipped_segments = turtle.clipped_segments();
+        Ok((turtle.into_buf(), stats))
+    }
+
+    /// Render with the turtle starting in the center of the buffer, moved by
+    /// the offset options.
+    fn render_centered(
+        &self,
+        l_system: &LSystem,
+        stats: &mut RenderStats,
+    ) -> Result<TurtleGraphics, LimitError> {
+        let mut turtle =
+            TurtleGraphics::try_new(self.size_x, self.size_y, self.size_z).map_err(|_| {
+                LimitError::Canvas((self.size_x as u64, self.size_y as u64, self.size_z as u64))
+            })?;
+        turtle.width(self.width);
         // Initialize the turtle in the center of the canvas.
         turtle.step(self.size_x as f32 / 2.0);
-        turtle.left(std::f32::consts::FRAC_PI_2);
+        turtle.left(core::f32::consts::FRAC_PI_2);
         turtle.step(self.size_y as f32 / 2.0);
         // Offset per configuration.
         turtle.step(self.offset_y);
-        turtle.right(std::f32::consts::FRAC_PI_2);
+        turtle.right(core::f32::consts::FRAC_PI_2);
         turtle.step(self.offset_x);
-        turtle.left(std::f32::consts::FRAC_PI_2);
+        turtle.left(core::f32::consts::FRAC_PI_2);
+        let (x, y, z) = turtle.position();
+        turtle.move_to(x, y, z + self.offset_z as i32);
+        turtle.voxels_per_unit(self.voxels_per_unit);
+        self.orient(&mut turtle);
+        self.render_into(l_system, &mut turtle, stats)?;
+        Ok(turtle)
+    }
+
+    /// Turn the turtle from facing north to the initial heading and pitch.
+    fn orient(&self, turtle: &mut TurtleGraphics) {
+        turtle.left(self.initial_heading);
+        turtle.pitch_up(self.initial_pitch);
+    }
+
+    /// Render into a buffer that exactly fits the drawing, found by measuring
+    /// a dry run of the interpretation first.
+    fn render_fitted(
+        &self,
+        l_system: &LSystem,
+        stats: &mut RenderStats,
+    ) -> Result<TurtleGraphics, LimitError> {
+        let bounds = self.measure(l_system, stats)?;
+        self.render_within(l_system, bounds, stats)
+    }
+
+    /// Measure the bounds of the drawing with a dry run of the
+    /// interpretation, adding it's timings to `stats`.
+    fn measure(&self, l_system: &LSystem, stats: &mut RenderStats) -> Result<Bounds, LimitError> {
+        let mut measure = TurtleGraphics::measure();
+        measure.width(self.width);
+        measure.voxels_per_unit(self.voxels_per_unit);
+        measure.left(core::f32::consts::FRAC_PI_2);
+        self.orient(&mut measure);
+        let mut measure_stats = RenderStats::default();
+        self.render_into(l_system, &mut measure, &mut measure_stats)?;
+        stats.derivation_time += measure_stats.derivation_time;
+        stats.drawing_time += measure_stats.drawing_time;
+        Ok(measure.bounds().unwrap_or(((0, 0, 0), (0, 0, 0))))
+    }
+
+    /// Render into a buffer spanning `bounds`, as measured by `measure`.
+    fn render_within(
+        &self,
+        l_system: &LSystem,
+        (min, max): Bounds,
+        stats: &mut RenderStats,
+    ) -> Result<TurtleGraphics, LimitError> {
+        let side = |min: i32, max: i32| (max as i64 - min as i64 + 1) as u64;
+        let dimensions = (side(min.0, max.0), side(min.1, max.1), side(min.2, max.2));
+        let too_large = LimitError::Canvas(dimensions);
+        let volume = dimensions.0 as u128 * dimensions.1 as u128 * dimensions.2 as u128;
+        if self.max_voxels.is_some_and(|max| volume > max as u128) {
+            return Err(too_large);
+        }
+        let size = |side: u64| u32::try_from(side).map_err(|_| too_large);
+        let mut turtle = TurtleGraphics::try_new(
+            size(dimensions.0)?,
+            size(dimensions.1)?,
+            size(dimensions.2)?,
+        )
+        .map_err(|_| too_large)?;
+        turtle.width(self.width);
+        turtle.voxels_per_unit(self.voxels_per_unit);
+        turtle.move_to(-min.0, -min.1, -min.2);
+        turtle.left(core::f32::consts::FRAC_PI_2);
+        self.orient(&mut turtle);
+        self.render_into(l_system, &mut turtle, stats)?;
+        Ok(turtle)
+    }
+
+    /// Render an instance of `l_system` at each of `positions` into one
+    /// buffer of the size options, varied by `variation`.
+    ///
+    /// Every instance gets it's own seed, see `Scene::place_instances`. The
+    /// offset and `auto_fit` options are ignored.
+    ///
+    /// # Examples
+    ///
+    /// Scatter a small forest of varied plants.
+    /// ```
+    /// # use voxgen::l_system::{presets, scene::Variation, RenderOptions};
+    /// # use voxgen::scatter::PoissonDisk;
+    /// let trees = PoissonDisk::new().min_distance(20.0).seed(3).sample_area(64.0, 64.0);
+    /// let forest = RenderOptions::new()
+    ///     .derivation_length(3)
+    ///     .step_size(4.0)
+    ///     .angle_increment(25.7_f32.to_radians())
+    ///     .initial_pitch(std::f32::consts::FRAC_PI_2)
+    ///     .size_x(96)
+    ///     .size_y(96)
+    ///     .size_z(96)
+    ///     .render_instances(
+    ///         &presets::stochastic_plant(),
+    ///         trees.iter().map(|&[x, y]| (x as i32 + 16, y as i32 + 16, 0)),
+    ///         Variation::new().scale(0.6, 1.2).rotation(std::f32::consts::PI),
+    ///     )?;
+    /// forest.save("test/volumes/forest.vox")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn render_instances<I>(
+        &self,
+        l_system: &LSystem,
+        positions: I,
+        variation: &Variation,
+    ) -> Result<ArrayVoxelBuffer<Rgba>, LimitError>
+    where
+        I: IntoIterator<Item = (i32, i32, i32)>,
+    {
+        Scene::new(self.size_x, self.size_y, self.size_z)
+            .place_instances(l_system, self, positions, variation)
+            .render()
+    }
 
-        let commands = l_system.commands(self.derivation_length);
+    /// Render the turtle interpretation of `l_system` with an existing
+    /// `turtle`, starting from it's current state.
+    ///
+    /// The size and offset options are ignored. Voxels already drawn by
+    /// `turtle` count towards the `max_voxels` limit.
+    pub fn render_to(
+        &self,
+        l_system: &LSystem,
+        turtle: &mut TurtleGraphics,
+    ) -> Result<(), LimitError> {
+        self.render_into(l_system, turtle, &mut RenderStats::default())
+    }
+
+    /// Render with `turtle`, counting derived symbols and timing the
+    /// derivation and drawing in `stats`.
+    fn render_into(
+        &self,
+        l_system: &LSystem,
+        turtle: &mut TurtleGraphics,
+        stats: &mut RenderStats,
+    ) -> Result<(), LimitError> {
         let mut i = 0;
-        let r = self.get_rainbow(250);
-        for c in &commands {
-            match c {
-                Command::Step => (),
-                Command::Left => (),
-                Command::Right => (),
+        let r = self.get_rainbow(self.gradient_steps);
+        let mut occurrences: HashMap<char, usize> = HashMap::new();
+        let start = Instant::now();
+        let (modules, total): (Box<dyn Iterator<Item = Module>>, usize) = match self.time {
+            Some(t) => {
+                let modules = l_system.develop(t, self.seed);
+                let total = modules.len();
+                (Box::new(modules.into_iter()), total)
+            }
+            None => {
+                let derivation = l_system.derivation_with_progress(
+                    self.derivation_length,
+                    self.seed,
+                    self.progress.as_ref(),
+                );
+                let total = derivation.estimated_len();
+                (Box::new(derivation), total)
+            }
+        };
+        // Progress counts derived modules, before homomorphisms.
+        let derived = Rc::new(Cell::new(0));
+        let counter = derived.clone();
+        let mut modules: Box<dyn Iterator<Item = Module>> =
+            Box::new(modules.inspect(move |_| counter.set(counter.get() + 1)));
+        if !l_system.homomorphisms.is_empty() {
+            modules = Box::new(modules.flat_map(|module| l_system.interpret(module)));
+        }
+        stats.derivation_time += start.elapsed();
+        for n in 0.. {
+            let start = Instant::now();
+            let next = modules.next();
+            stats.derivation_time += start.elapsed();
+            let module = match next {
+                Some(module) => module,
+                None => {
+                    self.report_drawing(derived.get(), derived.get());
+                    break;
+                }
+            };
+            if n % 1024 == 0 && n > 0 {
+                self.report_drawing(derived.get(), total.max(derived.get()));
+            }
+            *stats.symbols.entry(module.symbol).or_insert(0) += 1;
+            let start = Instant::now();
+            if let Some(max) = self.max_symbols.filter(|&max| n >= max) {
+                return Err(LimitError::Symbols(max));
+            }
+            match self.command(module.symbol) {
+                None => (),
+                Some(Command::Step) => (),
+                Some(Command::Left) => (),
+                Some(Command::Right) => (),
+                Some(Command::PitchDown) => (),
+                Some(Command::PitchUp) => (),
+                Some(Command::RollLeft) => (),
+                Some(Command::RollRight) => (),
+                Some(Command::TurnAround) => (),
+                Some(Command::Push) => (),
+                Some(Command::Pop) => (),
+                Some(Command::DecrementWidth) => (),
+                Some(Command::ScaleStep) => (),
                 _ => {
-                    if i < 250 - 1 {
+                    if i < self.gradient_steps - 1 {
                         i += 1
-                    } else {
-                    };
+                    }
                 }
             }
-            if self.rainbow {
+            if let Some(gradient) = self.colors.get(&module.symbol) {
+                let j = occurrences.entry(module.symbol).or_insert(0);
+                turtle.color(gradient[(*j).min(gradient.len() - 1)]);
+                *j += 1;
+            } else if self.rainbow {
                 turtle.color(*<Rgba>::from_slice(&r[i]));
             }
-            self.draw(&mut turtle, *c);
+            if let Some(subsystem) = self.subsystems.get(&module.symbol) {
+                let (l_system, options) = subsystem.as_ref();
+                turtle.push();
+                if let Some(&scale) = module.params.first() {
+                    turtle.scale_step(scale);
+                }
+                let result = options.render_to(l_system, turtle);
+                turtle.pop();
+                result?;
+            } else {
+                self.draw(turtle, &module);
+            }
+            stats.drawing_time += start.elapsed();
+            if let Some(max) = self.max_voxels.filter(|&max| turtle.drawn_voxels() > max) {
+                return Err(LimitError::Voxels(max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Report `modules` of an expected `total` drawn so far to the progress
+    /// callback.
+    fn report_drawing(&self, modules: usize, total: usize) {
+        if let Some(progress) = &self.progress {
+            progress(Progress {
+                stage: Stage::Rasterization,
+                done: modules,
+                total: Some(total),
+            });
+        }
+    }
+
+    /// Render a quick top-down preview of `l_system` and save it as a PNG
+    /// image to `path`.
+    ///
+    /// The preview shows the topmost drawn voxel of every column, with north
+    /// up, in an image that exactly fits the drawing. No voxel buffer is
+    /// allocated, so previews are fast even for large systems. The size and
+    /// offset options are ignored.
+    #[cfg(feature = "std")]
+    pub fn render_png<P>(&self, l_system: &LSystem, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut sketch = TurtleGraphics::sketch();
+        sketch.width(self.width);
+        sketch.voxels_per_unit(self.voxels_per_unit);
+        sketch.left(core::f32::consts::FRAC_PI_2);
+        self.orient(&mut sketch);
+        self.render_to(l_system, &mut sketch)
+            .map_err(io::Error::other)?;
+        let ((min_x, min_y, _), (max_x, max_y, _)) =
+            sketch.bounds().unwrap_or(((0, 0, 0), (0, 0, 0)));
+        let (width, height) = ((max_x - min_x + 1) as u32, (max_y - min_y + 1) as u32);
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        for ((x, y), Rgba(color)) in sketch.projection().unwrap_or_default() {
+            let row = (max_y - y) as usize;
+            let i = (row * width as usize + (x - min_x) as usize) * 4;
+            pixels[i..i + 4].copy_from_slice(&color);
+        }
+        let file = io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(io::Error::other)
+    }
+
+    /// Render `l_system` and save it as a MagicaVoxel .vox file to `path`.
+    ///
+    /// Exceeding a render limit is reported as an `io::Error` wrapping the
+    /// `LimitError`. The progress callback also gets the z slices encoded as
+    /// `Stage::Export`.
+    #[cfg(feature = "std")]
+    pub fn save<P>(&self, l_system: &LSystem, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let buf = self.render(l_system).map_err(io::Error::other)?;
+        match &self.progress {
+            Some(progress) => buf.save_with_progress(path, |p| progress(p)),
+            None => buf.save(path),
         }
-        turtle
-            .buf()
-            .save(format!(
-                "test/volumes/{}_{}.vox",
-                l_system.name(),
-                self.derivation_length
-            ))
-            .unwrap();
     }
+
+    /// Render every derivation step of `l_system` from the axiom up to
+    /// `derivation_length` as the frames of an animation.
+    ///
+    /// Returns `derivation_length + 1` buffers, the first one being the
+    /// rendered axiom.
+    pub fn render_animation(
+        &self,
+        l_system: &LSystem,
+    ) -> Result<Vec<ArrayVoxelBuffer<Rgba>>, LimitError> {
+        let mut options = self.clone();
+        (0..=self.derivation_length)
+            .map(|n| options.derivation_length(n).render(l_system))
+            .collect()
+    }
+
+    /// Render every derivation step of `l_system` and save the frames as
+    /// numbered MagicaVoxel .vox files.
+    ///
+    /// The frame for derivation step `n` is saved next to `path` with `_n`
+    /// appended to it's file stem, e.g. `dragon_0.vox`, `dragon_1.vox` and
+    /// so on for a `path` of `dragon.vox`.
+    #[cfg(feature = "std")]
+    pub fn save_animation<P>(&self, l_system: &LSystem, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let frames = self.render_animation(l_system).map_err(io::Error::other)?;
+        save_frames(&frames, path.as_ref())
+    }
+
+    /// Render the timed development of `l_system` over `duration` units of
+    /// time as `frames` evenly spaced frames of an animation, from time 0 to
+    /// `duration`.
+    ///
+    /// Unlike `render_animation`, modules grow continuously between
+    /// derivation steps: every line is drawn with it's length and width
+    /// scaled by the `growth` of it's module, as in the developmental models
+    /// of The Algorithmic Beauty of Plants chapter 6. Give the symbols a
+    /// lifetime with `LSystem::lifetime`, see `LSystem::develop`.
+    ///
+    /// With `auto_fit`, every frame is the same size, fitting the drawings
+    /// of all frames, so the plant stays in place as it grows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::l_system::{LSystem, RenderOptions};
+    /// # use voxgen::voxel_buffer::VoxelBuffer;
+    /// let mut l_system = LSystem::try_new("sprout", "A", vec!["A → F[+A][-A]FA"])?;
+    /// l_system.lifetime('A', 1.0).lifetime('F', 2.0);
+    /// let frames = RenderOptions::new()
+    ///     .step_size(4.0)
+    ///     .width(3.0)
+    ///     .angle_increment(30_f32.to_radians())
+    ///     .auto_fit(true)
+    ///     .render_growth(&l_system, 3.0, 13)?;
+    /// assert_eq!(frames.len(), 13);
+    /// assert!(frames.iter().all(|f| f.dimensions() == frames[0].dimensions()));
+    /// let voxels = |n: usize| frames[n].enumerate_occupied().count();
+    /// assert!((1..13).all(|n| voxels(n) >= voxels(n - 1)));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn render_growth(
+        &self,
+        l_system: &LSystem,
+        duration: f32,
+        frames: usize,
+    ) -> Result<Vec<ArrayVoxelBuffer<Rgba>>, LimitError> {
+        let times = (0..frames).map(|i| match frames {
+            1 => duration,
+            _ => duration * i as f32 / (frames - 1) as f32,
+        });
+        let options: Vec<RenderOptions> = times
+            .map(|t| {
+                let mut options = self.clone();
+                options.time(t);
+                options
+            })
+            .collect();
+        if !self.auto_fit {
+            return options.iter().map(|o| o.render(l_system)).collect();
+        }
+        let mut stats = RenderStats::default();
+        let mut bounds: Option<Bounds> = None;
+        for o in &options {
+            let (min, max) = o.measure(l_system, &mut stats)?;
+            bounds = Some(match bounds {
+                None => (min, max),
+                Some((a, b)) => (
+                    (a.0.min(min.0), a.1.min(min.1), a.2.min(min.2)),
+                    (b.0.max(max.0), b.1.max(max.1), b.2.max(max.2)),
+                ),
+            });
+        }
+        options
+            .iter()
+            .map(|o| {
+                let bounds = bounds.unwrap();
+                Ok(o.render_within(l_system, bounds, &mut stats)?.into_buf())
+            })
+            .collect()
+    }
+
+    /// Render the timed development of `l_system` like `render_growth` and
+    /// save the frames as numbered MagicaVoxel .vox files, like
+    /// `save_animation`.
+    #[cfg(feature = "std")]
+    pub fn save_growth<P>(
+        &self,
+        l_system: &LSystem,
+        duration: f32,
+        frames: usize,
+        path: P,
+    ) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let frames = self
+            .render_growth(l_system, duration, frames)
+            .map_err(io::Error::other)?;
+        save_frames(&frames, path.as_ref())
+    }
+}
+
+/// Save `frames` as MagicaVoxel .vox files next to `path`, appending the
+/// frame number to it's file stem.
+#[cfg(feature = "std")]
+fn save_frames(frames: &[ArrayVoxelBuffer<Rgba>], path: &Path) -> io::Result<()> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    for (n, frame) in frames.iter().enumerate() {
+        let name = if extension.is_empty() {
+            format!("{}_{}", stem, n)
+        } else {
+            format!("{}_{}.{}", stem, n, extension)
+        };
+        frame.save(path.with_file_name(name))?;
+    }
+    Ok(())
 }
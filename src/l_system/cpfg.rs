@@ -0,0 +1,145 @@
+use super::{Definition, RenderDefinition};
+use std::collections::HashMap;
+use std::io;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Remove `/* */` and `//` comments.
+fn strip_comments(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("/*") {
+        output.push_str(&rest[..start]);
+        rest = match rest[start..].find("*/") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+    output.push_str(rest);
+    output
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Replace every whole word defined with `#define` by it's value.
+fn substitute(line: &str, defines: &HashMap<String, String>) -> String {
+    let mut output = String::new();
+    let mut word = String::new();
+    for c in line.chars().chain(['\n']) {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        match defines.get(&word) {
+            Some(value) => output.push_str(value),
+            None => output.push_str(&word),
+        }
+        word.clear();
+        output.push(c);
+    }
+    output.pop();
+    output
+}
+
+/// Rewrite a cpfg production in the syntax of `LSystem::try_new`.
+///
+/// The `-->` arrow becomes `→`, `*` wildcard contexts and conditions are
+/// dropped, and a trailing `: probability` moves after the arrow.
+fn convert_production(rule: &str) -> Option<String> {
+    let (head, successor) = rule.split_once("-->")?;
+    let (head, condition) = match head.split_once(':') {
+        Some((head, condition)) if condition.trim() != "*" => (head, Some(condition.trim())),
+        Some((head, _)) => (head, None),
+        None => (head, None),
+    };
+    let mut head = head.trim();
+    if let Some(rest) = head.strip_prefix('*') {
+        head = rest.trim_start().strip_prefix('<').unwrap_or(rest).trim();
+    }
+    if let Some(rest) = head.strip_suffix('*') {
+        head = rest.trim_end().strip_suffix('>').unwrap_or(rest).trim();
+    }
+    let (successor, probability) = match successor.rsplit_once(':') {
+        Some((successor, p)) if p.trim().parse::<f32>().is_ok() => (successor, Some(p.trim())),
+        _ => (successor, None),
+    };
+    let mut production = head.to_string();
+    if let Some(condition) = condition {
+        production += &format!(" : {}", condition);
+    }
+    production += " →";
+    if let Some(probability) = probability {
+        production += &format!("({})", probability);
+    }
+    production += &format!(" {}", successor.trim());
+    Some(production)
+}
+
+/// Read an L System in the `.l` file dialect of L-studio and cpfg.
+///
+/// Supports `#define` constants, comments, the `derivation length`, `axiom`,
+/// `ignore` and `seed` statements, context-sensitive, parametric and
+/// stochastic productions, `*` wildcards, and the rules of the
+/// `decomposition` and `homomorphism` sections. `consider` statements are
+/// reported as `InvalidData` errors, and other statements are skipped.
+pub fn read(name: &str, text: &str) -> io::Result<Definition> {
+    let mut defines = HashMap::new();
+    let mut axiom = None;
+    let mut productions = Vec::new();
+    let mut decompositions = Vec::new();
+    let mut homomorphisms = Vec::new();
+    let mut section = &mut productions;
+    let mut ignore = String::new();
+    let mut render = RenderDefinition::default();
+    for line in strip_comments(text).lines() {
+        let line = line.trim();
+        if let Some(define) = line.strip_prefix("#define") {
+            let mut parts = define.split_whitespace();
+            if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                defines.insert(key.to_string(), value.to_string());
+            }
+            continue;
+        }
+        let line = substitute(line, &defines);
+        let (keyword, value) = match line.split_once(':') {
+            Some((keyword, value)) if !line.contains("-->") => {
+                (keyword.trim().to_lowercase(), value.trim())
+            }
+            _ => (String::new(), line.as_str()),
+        };
+        match keyword.as_str() {
+            "derivation length" => {
+                let n = value
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid derivation length `{}`", value)))?;
+                render.derivation_length = Some(n);
+            }
+            "axiom" => axiom = Some(value.to_string()),
+            "ignore" => ignore.extend(value.chars().filter(|c| !c.is_whitespace())),
+            "seed" => render.seed = value.parse().ok(),
+            "consider" => return Err(invalid("`consider` is not supported".to_string())),
+            _ if value.starts_with("decomposition") => section = &mut decompositions,
+            _ if value.starts_with("homomorphism") => section = &mut homomorphisms,
+            _ if value == "endlsystem" => break,
+            _ => {
+                if let Some(production) = convert_production(value) {
+                    section.push(production);
+                }
+            }
+        }
+    }
+    Ok(Definition {
+        name: name.to_string(),
+        axiom: axiom.ok_or_else(|| invalid("missing axiom".to_string()))?,
+        productions,
+        decompositions,
+        homomorphisms,
+        ignore,
+        lifetimes: HashMap::new(),
+        render,
+    })
+}
@@ -0,0 +1,206 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::compat::HashMap;
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{alpha1, alphanumeric1, char, digit0, digit1, space0};
+use nom::combinator::{map, map_res, recognize};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, tuple};
+use nom::IResult;
+
+/// A binary operator in a parametric expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+/// An arithmetic or logical expression over module parameters.
+///
+/// Logical operators treat `0.0` as false and any other value as true, and
+/// produce `1.0` for true and `0.0` for false.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Number(f32),
+    Variable(String),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Evaluate the expression with variables bound to `bindings`.
+    ///
+    /// Unbound variables evaluate to NaN.
+    pub fn evaluate(&self, bindings: &HashMap<&str, f32>) -> f32 {
+        let truth = |b: bool| if b { 1.0 } else { 0.0 };
+        match self {
+            Expression::Number(n) => *n,
+            Expression::Variable(name) => bindings.get(name.as_str()).copied().unwrap_or(f32::NAN),
+            Expression::Negate(e) => -e.evaluate(bindings),
+            Expression::Not(e) => truth(e.evaluate(bindings) == 0.0),
+            Expression::Binary(op, l, r) => {
+                let (l, r) = (l.evaluate(bindings), r.evaluate(bindings));
+                match op {
+                    Operator::Add => l + r,
+                    Operator::Subtract => l - r,
+                    Operator::Multiply => l * r,
+                    Operator::Divide => l / r,
+                    Operator::Power => l.powf(r),
+                    Operator::Less => truth(l < r),
+                    Operator::LessEqual => truth(l <= r),
+                    Operator::Greater => truth(l > r),
+                    Operator::GreaterEqual => truth(l >= r),
+                    Operator::Equal => truth(l == r),
+                    Operator::NotEqual => truth(l != r),
+                    Operator::And => truth(l != 0.0 && r != 0.0),
+                    Operator::Or => truth(l != 0.0 || r != 0.0),
+                }
+            }
+        }
+    }
+
+    /// Get the names of all variables used in the expression.
+    pub fn variables(&self) -> Vec<&str> {
+        match self {
+            Expression::Number(_) => Vec::new(),
+            Expression::Variable(name) => vec![name.as_str()],
+            Expression::Negate(e) | Expression::Not(e) => e.variables(),
+            Expression::Binary(_, l, r) => {
+                let mut variables = l.variables();
+                variables.extend(r.variables());
+                variables
+            }
+        }
+    }
+}
+
+/// Parse a parameter name, a letter followed by letters, digits or `_`.
+pub fn parse_identifier(input: &str) -> IResult<&str, String> {
+    map(
+        recognize(pair(alpha1, many0(alt((alphanumeric1, tag("_")))))),
+        String::from,
+    )(input)
+}
+
+fn parse_number(input: &str) -> IResult<&str, Expression> {
+    map_res(
+        alt((
+            recognize(tuple((digit1, char('.'), digit0))),
+            recognize(pair(char('.'), digit1)),
+            digit1,
+        )),
+        |s: &str| s.parse().map(Expression::Number),
+    )(input)
+}
+
+fn parse_primary(input: &str) -> IResult<&str, Expression> {
+    delimited(
+        space0,
+        alt((
+            parse_number,
+            map(parse_identifier, Expression::Variable),
+            delimited(char('('), parse_expression, preceded(space0, char(')'))),
+        )),
+        space0,
+    )(input)
+}
+
+fn parse_unary(input: &str) -> IResult<&str, Expression> {
+    alt((
+        map(preceded(preceded(space0, char('-')), parse_unary), |e| {
+            Expression::Negate(Box::new(e))
+        }),
+        map(preceded(preceded(space0, char('!')), parse_unary), |e| {
+            Expression::Not(Box::new(e))
+        }),
+        parse_primary,
+    ))(input)
+}
+
+fn parse_power(input: &str) -> IResult<&str, Expression> {
+    let (input, base) = parse_unary(input)?;
+    // Exponentiation is right associative.
+    match preceded(char('^'), parse_power)(input) {
+        Ok((rest, exponent)) => Ok((
+            rest,
+            Expression::Binary(Operator::Power, Box::new(base), Box::new(exponent)),
+        )),
+        Err(_) => Ok((input, base)),
+    }
+}
+
+/// Parse a left associative chain of `operand`s joined by `operators`.
+///
+/// An operator that isn't followed by a valid operand ends the chain without
+/// being consumed, so that the expression can be followed by other syntax.
+fn parse_chain<'a>(
+    input: &'a str,
+    operand: fn(&'a str) -> IResult<&'a str, Expression>,
+    operators: &[(&str, Operator)],
+) -> IResult<&'a str, Expression> {
+    let (mut input, mut left) = operand(input)?;
+    'chain: loop {
+        for (token, operator) in operators {
+            if let Ok((rest, right)) = preceded(tag(*token), operand)(input) {
+                left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+                input = rest;
+                continue 'chain;
+            }
+        }
+        return Ok((input, left));
+    }
+}
+
+fn parse_multiplicative(input: &str) -> IResult<&str, Expression> {
+    parse_chain(
+        input,
+        parse_power,
+        &[("*", Operator::Multiply), ("/", Operator::Divide)],
+    )
+}
+
+fn parse_additive(input: &str) -> IResult<&str, Expression> {
+    parse_chain(
+        input,
+        parse_multiplicative,
+        &[("+", Operator::Add), ("-", Operator::Subtract)],
+    )
+}
+
+fn parse_comparison(input: &str) -> IResult<&str, Expression> {
+    parse_chain(
+        input,
+        parse_additive,
+        &[
+            ("<=", Operator::LessEqual),
+            (">=", Operator::GreaterEqual),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ],
+    )
+}
+
+fn parse_and(input: &str) -> IResult<&str, Expression> {
+    parse_chain(input, parse_comparison, &[("&&", Operator::And)])
+}
+
+/// Parse an expression with the usual arithmetic, comparison and logical
+/// operators, parentheses, numbers and parameter names.
+pub fn parse_expression(input: &str) -> IResult<&str, Expression> {
+    parse_chain(input, parse_and, &[("||", Operator::Or)])
+}
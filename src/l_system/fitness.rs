@@ -0,0 +1,175 @@
+use super::{LSystem, LimitError, RenderOptions};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rayon::prelude::*;
+
+/// A function scoring a rendered buffer, where higher scores are fitter.
+pub type Fitness = Box<dyn Fn(&ArrayVoxelBuffer<Rgba>) -> f32 + Send + Sync>;
+
+/// Renders L Systems headlessly and scores them with weighted fitness
+/// functions, to explore rule parameters with evolutionary search.
+///
+/// Setting the `max_symbols` and `max_voxels` limits of the render options
+/// keeps runaway candidates from exhausting memory, and `auto_fit` keeps
+/// candidates from drawing outside the buffer.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::{fitness::{self, Evaluator}, LSystem, RenderOptions};
+/// let mut options = RenderOptions::new();
+/// options.derivation_length(3).auto_fit(true).max_voxels(100_000);
+/// let mut evaluator = Evaluator::new(&options);
+/// evaluator
+///     .criterion(1.0, fitness::symmetry)
+///     .criterion(0.001, fitness::volume);
+/// let candidates: Vec<LSystem> = [20.0, 25.0, 30.0]
+///     .iter()
+///     .map(|a| format!("F→F[+({a})F][-({a})F]F"))
+///     .map(|rule| LSystem::try_new("plant", "F", vec![&rule]))
+///     .collect::<Result<_, _>>()?;
+/// let scores = evaluator.evaluate_all(&candidates);
+/// assert!(scores.iter().all(|score| score.is_ok()));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Evaluator {
+    options: RenderOptions,
+    criteria: Vec<(f32, Fitness)>,
+}
+
+impl Evaluator {
+    /// Create an evaluator rendering with `options` and no criteria.
+    pub fn new(options: &RenderOptions) -> Evaluator {
+        Evaluator {
+            options: options.clone(),
+            criteria: Vec::new(),
+        }
+    }
+
+    /// Add a `fitness` function to the score, multiplied by `weight`.
+    pub fn criterion<F>(&mut self, weight: f32, fitness: F) -> &mut Self
+    where
+        F: Fn(&ArrayVoxelBuffer<Rgba>) -> f32 + Send + Sync + 'static,
+    {
+        self.criteria.push((weight, Box::new(fitness)));
+        self
+    }
+
+    /// Score a rendered buffer with the weighted sum of every criterion.
+    pub fn score(&self, buf: &ArrayVoxelBuffer<Rgba>) -> f32 {
+        self.criteria
+            .iter()
+            .map(|(weight, fitness)| weight * fitness(buf))
+            .sum()
+    }
+
+    /// Render `l_system` and score the result.
+    ///
+    /// Returns an error if the `max_symbols` or `max_voxels` limit is
+    /// exceeded.
+    pub fn evaluate(&self, l_system: &LSystem) -> Result<f32, LimitError> {
+        self.options.render(l_system).map(|buf| self.score(&buf))
+    }
+
+    /// Render and score every one of the `candidates` in parallel, returning
+    /// the scores in the same order.
+    pub fn evaluate_all(&self, candidates: &[LSystem]) -> Vec<Result<f32, LimitError>> {
+        candidates
+            .par_iter()
+            .map(|l_system| self.evaluate(l_system))
+            .collect()
+    }
+}
+
+/// Check whether the voxel at `(x, y, z)` of `buf` is drawn.
+fn filled(buf: &ArrayVoxelBuffer<Rgba>, x: u32, y: u32, z: u32) -> bool {
+    buf.voxel(x, y, z).0[3] != 0
+}
+
+/// Count the drawn voxels of `buf`.
+pub fn volume(buf: &ArrayVoxelBuffer<Rgba>) -> f32 {
+    let (size_x, size_y, size_z) = buf.dimensions();
+    let mut volume = 0;
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                if filled(buf, x, y, z) {
+                    volume += 1;
+                }
+            }
+        }
+    }
+    volume as f32
+}
+
+/// Get the fraction of drawn voxels of `buf` mirrored by a drawn voxel
+/// across the plane through the center of the x axis.
+///
+/// Scores 1.0 for a perfectly symmetric drawing and 0.0 for an empty one.
+pub fn symmetry(buf: &ArrayVoxelBuffer<Rgba>) -> f32 {
+    let (size_x, size_y, size_z) = buf.dimensions();
+    let mut drawn = 0;
+    let mut mirrored = 0;
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                if filled(buf, x, y, z) {
+                    drawn += 1;
+                    if filled(buf, size_x - 1 - x, y, z) {
+                        mirrored += 1;
+                    }
+                }
+            }
+        }
+    }
+    if drawn == 0 {
+        0.0
+    } else {
+        mirrored as f32 / drawn as f32
+    }
+}
+
+/// Create a fitness function matching the drawing against the drawn voxels
+/// of a `target` shape.
+///
+/// The drawing is stretched over the target's bounding box, so only the
+/// shape and not the size has to match. Scores the intersection over union
+/// of the drawn voxels, from 0.0 for disjoint shapes to 1.0 for identical
+/// ones.
+pub fn shape_match(
+    target: ArrayVoxelBuffer<Rgba>,
+) -> impl Fn(&ArrayVoxelBuffer<Rgba>) -> f32 + Send + Sync {
+    move |buf| {
+        let (size_x, size_y, size_z) = target.dimensions();
+        let (buf_x, buf_y, buf_z) = buf.dimensions();
+        let mut intersection = 0;
+        let mut union = 0;
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    // Sample the drawing at the nearest voxel.
+                    let drawn = buf_x > 0
+                        && buf_y > 0
+                        && buf_z > 0
+                        && filled(
+                            buf,
+                            x * buf_x / size_x,
+                            y * buf_y / size_y,
+                            z * buf_z / size_z,
+                        );
+                    let wanted = filled(&target, x, y, z);
+                    if drawn && wanted {
+                        intersection += 1;
+                    }
+                    if drawn || wanted {
+                        union += 1;
+                    }
+                }
+            }
+        }
+        if union == 0 {
+            0.0
+        } else {
+            intersection as f32 / union as f32
+        }
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::turtle_graphics::TurtleGraphics;
+use crate::voxel_buffer::Rgba;
+use core::f32::consts::{FRAC_PI_2, TAU};
+
+/// Get the size factor given by the first parameter of a module.
+fn size(params: &[f32]) -> f32 {
+    params.first().copied().unwrap_or(1.0)
+}
+
+/// Draw a flat leaf blade in the plane of the turtle's heading and left
+/// vectors, leaving the turtle state unchanged.
+fn blade(turtle: &mut TurtleGraphics, length: f32, width: f32) {
+    let half_width = (width / 2.0).round() as i32;
+    for offset in -half_width..=half_width {
+        // The blade is an ellipse, so each strip along it's length gets
+        // shorter towards the edges.
+        let edge = offset as f32 / (half_width as f32 + 0.5);
+        let half_chord = length / 2.0 * (1.0 - edge * edge).sqrt();
+        turtle.push();
+        turtle.left(FRAC_PI_2);
+        turtle.step(offset as f32);
+        turtle.right(FRAC_PI_2);
+        turtle.step(length / 2.0 - half_chord);
+        turtle.draw(2.0 * half_chord);
+        turtle.pop();
+    }
+}
+
+/// A leaf of `length` by `width` voxels growing along the turtle's heading.
+///
+/// The leaf lies in the plane of the turtle's heading and left vectors. A
+/// module parameter scales the leaf, so `L(0.5)` draws a leaf of half size.
+/// Bind it to a symbol with `RenderOptions::callback`.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::{organs, LSystem, RenderOptions};
+/// # use voxgen::voxel_buffer::Rgba;
+/// let l_system = LSystem::try_new("sprout", "FF[+L][-L(0.5)]F", vec![])?;
+/// RenderOptions::new()
+///     .callback('L', organs::leaf(8.0, 4.0, Rgba([34, 139, 34, 255])))
+///     .render(&l_system)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn leaf(
+    length: f32,
+    width: f32,
+    color: Rgba,
+) -> impl Fn(&mut TurtleGraphics, &[f32]) + Send + Sync {
+    move |turtle, params| {
+        let size = size(params);
+        turtle.push();
+        turtle.color(color);
+        blade(turtle, length * size, width * size);
+        turtle.pop();
+    }
+}
+
+/// A flower of `petals` petals of `length` voxels around a center of
+/// `center_color`, facing along the turtle's heading.
+///
+/// A module parameter scales the flower. Bind it to a symbol with
+/// `RenderOptions::callback`.
+pub fn flower(
+    petals: u32,
+    length: f32,
+    petal_color: Rgba,
+    center_color: Rgba,
+) -> impl Fn(&mut TurtleGraphics, &[f32]) + Send + Sync {
+    move |turtle, params| {
+        let size = size(params);
+        turtle.push();
+        turtle.color(petal_color);
+        for _ in 0..petals {
+            turtle.roll_right(TAU / petals.max(1) as f32);
+            turtle.push();
+            // Open the petals slightly forward, like a cup.
+            turtle.pitch_down(FRAC_PI_2 * 0.8);
+            blade(turtle, length * size, length * size / 2.0);
+            turtle.pop();
+        }
+        turtle.color(center_color);
+        turtle.draw(0.0);
+        turtle.pop();
+    }
+}
+
+/// A round fruit of `diameter` voxels hanging from the turtle's position
+/// along it's heading.
+///
+/// The fruit is scaled by the turtle's current step scale and a module
+/// parameter. Bind it to a symbol with `RenderOptions::callback`.
+pub fn fruit(diameter: f32, color: Rgba) -> impl Fn(&mut TurtleGraphics, &[f32]) + Send + Sync {
+    move |turtle, params| {
+        let diameter = diameter * size(params);
+        turtle.push();
+        turtle.color(color);
+        turtle.step(diameter / 2.0);
+        turtle.width(diameter * turtle.scale());
+        turtle.draw(0.0);
+        turtle.pop();
+    }
+}
@@ -0,0 +1,195 @@
+use super::{LSystem, LimitError, RenderOptions, RenderStats};
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::seed::Seed;
+use crate::turtle_graphics::{CollisionMode, TurtleGraphics};
+use crate::voxel_buffer::{Anchor, ArrayVoxelBuffer, Rgba, VoxelBuffer};
+
+/// The directions to the 6 face neighbors of a voxel.
+const FACES: [(i32, i32, i32); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// A voxel on the surface of a model to grow an L System from, facing away
+/// from the surface along it's normal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrowthSeed {
+    position: (u32, u32, u32),
+    normal: [f32; 3],
+}
+
+impl GrowthSeed {
+    /// Create a seed at voxel `position` growing towards `normal`.
+    pub fn new(position: (u32, u32, u32), normal: [f32; 3]) -> GrowthSeed {
+        GrowthSeed { position, normal }
+    }
+
+    /// Find a seed at every visible voxel of `buf` for which `predicate`
+    /// returns `true`, and that has at least one empty face neighbor.
+    ///
+    /// The normal of a seed points from it towards it's empty neighbors,
+    /// where voxels outside of `buf` are empty. Voxels whose empty neighbors
+    /// cancel out, like the voxels of a wall 1 voxel thick, face their
+    /// first empty neighbor in the order +x, -x, +y, -y, +z, -z.
+    pub fn find<F>(buf: &ArrayVoxelBuffer<Rgba>, mut predicate: F) -> Vec<GrowthSeed>
+    where
+        F: FnMut((u32, u32, u32), &Rgba) -> bool,
+    {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let empty = |x: i32, y: i32, z: i32| {
+            x < 0
+                || y < 0
+                || z < 0
+                || x as u32 >= size_x
+                || y as u32 >= size_y
+                || z as u32 >= size_z
+                || buf.voxel(x as u32, y as u32, z as u32).0[3] == 0
+        };
+        buf.enumerate_occupied()
+            .filter(|&(position, voxel)| predicate(position, voxel))
+            .filter_map(|((x, y, z), _)| {
+                let open: Vec<_> = FACES
+                    .into_iter()
+                    .filter(|&(dx, dy, dz)| empty(x as i32 + dx, y as i32 + dy, z as i32 + dz))
+                    .collect();
+                let first = *open.first()?;
+                let sum = open
+                    .iter()
+                    .fold((0, 0, 0), |a, d| (a.0 + d.0, a.1 + d.1, a.2 + d.2));
+                let (dx, dy, dz) = if sum == (0, 0, 0) { first } else { sum };
+                let length = ((dx * dx + dy * dy + dz * dz) as f32).sqrt();
+                Some(GrowthSeed {
+                    position: (x, y, z),
+                    normal: [dx as f32 / length, dy as f32 / length, dz as f32 / length],
+                })
+            })
+            .collect()
+    }
+
+    /// Find a seed at every voxel of `buf` colored `color`, like `find`.
+    pub fn with_color(buf: &ArrayVoxelBuffer<Rgba>, color: Rgba) -> Vec<GrowthSeed> {
+        GrowthSeed::find(buf, |_, &voxel| voxel == color)
+    }
+
+    /// Get the position of the seed voxel.
+    pub fn position(&self) -> (u32, u32, u32) {
+        self.position
+    }
+
+    /// Get the unit direction the seed grows towards.
+    pub fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    /// Get the neighbor of the seed voxel the L System starts from, along
+    /// the largest component of the normal.
+    fn start(&self) -> (i32, i32, i32) {
+        let (x, y, z) = self.position;
+        Anchor::new((x as i32, y as i32, z as i32), self.normal).next()
+    }
+}
+
+/// An L System grown over an existing model from growth seeds, such as vines
+/// and moss over an imported building.
+///
+/// The turtle starts in the empty voxel next to each seed, facing along it's
+/// normal and then turned by the initial heading and pitch of the options.
+/// Every seed renders with it's own seed derived from the seed of the
+/// options, so stochastic L Systems grow differently from each seed. The
+/// size, offset and `auto_fit` options are ignored.
+///
+/// By default growth stops short of the voxels of the model, with
+/// `CollisionMode::Truncate`.
+///
+/// # Examples
+///
+/// Grow vines from the green voxels on the roof of a house.
+/// ```
+/// # use voxgen::l_system::{overgrowth::{GrowthSeed, Overgrowth}, LSystem, RenderOptions};
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let stone = Rgba([120, 120, 120, 255]);
+/// let moss = Rgba([60, 110, 40, 255]);
+/// let mut house = ArrayVoxelBuffer::new(32, 32, 32);
+/// for (x, y) in (8..24).flat_map(|x| (8..24).map(move |y| (x, y))) {
+///     for z in 0..12 {
+///         *house.voxel_mut(x, y, z) = stone;
+///     }
+///     if x % 5 == 0 && y % 5 == 0 {
+///         *house.voxel_mut(x, y, 11) = moss;
+///     }
+/// }
+/// let seeds = GrowthSeed::with_color(&house, moss);
+/// assert_eq!(seeds.len(), 9);
+/// assert!(seeds.iter().all(|seed| seed.normal() == [0.0, 0.0, 1.0]));
+///
+/// let vine = LSystem::try_new("vine", "F", vec!["F → F[+F]F[-F]"])?;
+/// let mut options = RenderOptions::new();
+/// options
+///     .derivation_length(2)
+///     .step_size(1.0)
+///     .angle_increment(30_f32.to_radians())
+///     .symbol_color('F', moss);
+/// let overgrown = Overgrowth::new(&vine, &options).grow(house, &seeds)?;
+/// assert_eq!(*overgrown.voxel(10, 10, 12), moss);
+/// overgrown.save("test/volumes/overgrowth.vox")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct Overgrowth<'a> {
+    l_system: &'a LSystem,
+    options: RenderOptions,
+    collision_mode: CollisionMode,
+}
+
+impl<'a> Overgrowth<'a> {
+    /// Grow `l_system` rendered with `options`.
+    pub fn new(l_system: &'a LSystem, options: &RenderOptions) -> Overgrowth<'a> {
+        Overgrowth {
+            l_system,
+            options: options.clone(),
+            collision_mode: CollisionMode::Truncate,
+        }
+    }
+
+    /// Set how growth handles the voxels of the model and earlier growth.
+    pub fn collision_mode(&mut self, mode: CollisionMode) -> &mut Self {
+        self.collision_mode = mode;
+        self
+    }
+
+    /// Grow from every seed in order over `buf`.
+    ///
+    /// Returns an error if a seed exceeds the `max_symbols` or `max_voxels`
+    /// limit, where the voxel limit counts the growth of earlier seeds too,
+    /// but not the voxels of `buf`.
+    pub fn grow(
+        &self,
+        buf: ArrayVoxelBuffer<Rgba>,
+        seeds: &[GrowthSeed],
+    ) -> Result<ArrayVoxelBuffer<Rgba>, LimitError> {
+        let mut turtle = TurtleGraphics::from_buf(buf);
+        turtle.collision_mode(self.collision_mode);
+        turtle.voxels_per_unit(self.options.voxels_per_unit);
+        let master = Seed::new(self.options.seed);
+        let mut options = self.options.clone();
+        for (i, seed) in seeds.iter().enumerate() {
+            options.seed(master.index(i as u64).value());
+            let (x, y, z) = seed.start();
+            turtle.push();
+            turtle.move_to(x, y, z);
+            turtle.face(seed.normal);
+            turtle.width(options.width);
+            options.orient(&mut turtle);
+            let result =
+                options.render_into(self.l_system, &mut turtle, &mut RenderStats::default());
+            turtle.pop();
+            result?;
+        }
+        Ok(turtle.into_buf())
+    }
+}
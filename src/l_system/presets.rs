@@ -0,0 +1,118 @@
+use super::LSystem;
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+
+fn preset(name: &str, axiom: &str, productions: Vec<&str>) -> LSystem {
+    LSystem::try_new(name, axiom, productions).expect("preset rules are valid")
+}
+
+/// The quadratic Koch island, ABOP figure 1.7a.
+///
+/// Render with an angle increment of 90°.
+pub fn koch_island() -> LSystem {
+    preset("koch-island", "F-F-F-F", vec!["F→F-F+F+FF-F-F+F"])
+}
+
+/// The Koch snowflake.
+///
+/// Render with an angle increment of 60°.
+pub fn koch_snowflake() -> LSystem {
+    preset("koch-snowflake", "F--F--F", vec!["F→F+F--F+F"])
+}
+
+/// The quadratic snowflake curve, ABOP figure 1.7b.
+///
+/// Render with an angle increment of 90°.
+pub fn quadratic_snowflake() -> LSystem {
+    preset("quadratic-snowflake", "-F", vec!["F→F+F-F-F+F"])
+}
+
+/// The dragon curve, using the `L` and `R` edge symbols.
+///
+/// Render with an angle increment of 90°.
+pub fn dragon() -> LSystem {
+    preset("dragon", "L", vec!["L→L+R+", "R→-L-R"])
+}
+
+/// The Sierpinski gasket, using the `L` and `R` edge symbols.
+///
+/// Render with an angle increment of 60°.
+pub fn sierpinski_gasket() -> LSystem {
+    preset("sierpinski-gasket", "R", vec!["L→R+L+R", "R→L-R-L"])
+}
+
+/// The 2D Hilbert curve.
+///
+/// Render with an angle increment of 90°.
+pub fn hilbert() -> LSystem {
+    preset("hilbert", "A", vec!["A→+BF-AFA-FB+", "B→-AF+BFB+FA-"])
+}
+
+/// The 3D Hilbert curve, ABOP figure 1.11.
+///
+/// Render with an angle increment of 90°.
+pub fn hilbert_3d() -> LSystem {
+    preset(
+        "hilbert-3d",
+        "A",
+        vec![
+            "A→B-F+CFC+F-D&F^D-F+&&CFC+F+B//",
+            "B→A&F^CFB^F^D^^-F-D^|F^B|FC^F^A//",
+            "C→|D^|F^B-F+C^F^A&&FA&F^C+F+B^F^D//",
+            "D→|CFB-F+B|FA&F^A&&FB-F+B|FC//",
+        ],
+    )
+}
+
+/// The bracketed plant of ABOP figure 1.24a.
+///
+/// Render with a derivation length of 5 and an angle increment of 25.7°.
+pub fn plant_a() -> LSystem {
+    preset("plant-a", "F", vec!["F→F[+F]F[-F]F"])
+}
+
+/// The bracketed plant of ABOP figure 1.24b.
+///
+/// Render with a derivation length of 5 and an angle increment of 20°.
+pub fn plant_b() -> LSystem {
+    preset("plant-b", "F", vec!["F→F[+F]F[-F][F]"])
+}
+
+/// The bracketed plant of ABOP figure 1.24c.
+///
+/// Render with a derivation length of 4 and an angle increment of 22.5°.
+pub fn plant_c() -> LSystem {
+    preset("plant-c", "F", vec!["F→FF-[-F+F+F]+[+F-F-F]"])
+}
+
+/// The bracketed plant of ABOP figure 1.24d.
+///
+/// Render with a derivation length of 7 and an angle increment of 20°.
+pub fn plant_d() -> LSystem {
+    preset("plant-d", "X", vec!["X→F[+X]F[-X]+X", "F→FF"])
+}
+
+/// The bracketed plant of ABOP figure 1.24e.
+///
+/// Render with a derivation length of 7 and an angle increment of 25.7°.
+pub fn plant_e() -> LSystem {
+    preset("plant-e", "X", vec!["X→F[+X][-X]FX", "F→FF"])
+}
+
+/// The bracketed plant of ABOP figure 1.24f.
+///
+/// Render with a derivation length of 5 and an angle increment of 22.5°.
+pub fn plant_f() -> LSystem {
+    preset("plant-f", "X", vec!["X→F-[[X]+X]+F[+FX]-X", "F→FF"])
+}
+
+/// The stochastic plant of ABOP figure 1.27, different for every seed.
+///
+/// Render with a derivation length of 5 and an angle increment of 25.7°.
+pub fn stochastic_plant() -> LSystem {
+    preset(
+        "stochastic-plant",
+        "F",
+        vec!["F→(0.33) F[+F]F[-F]F", "F→(0.33) F[+F]F", "F→(0.34) F[-F]F"],
+    )
+}
@@ -0,0 +1,268 @@
+use super::{LSystem, LimitError, RenderOptions, RenderStats};
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::seed::Seed;
+use crate::turtle_graphics::TurtleGraphics;
+use crate::voxel_buffer::{Anchor, ArrayVoxelBuffer, Rgba};
+use rand::Rng;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// Several L Systems placed into one buffer, such as a small forest of
+/// varied trees.
+///
+/// Each placement is rendered with it's own `RenderOptions`, so every L
+/// System keeps it's own seed, step size, colors, initial heading and initial
+/// pitch. The size, offset and `auto_fit` options of the placements are
+/// ignored in favor of the scene's size and the placement's position.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::l_system::{presets, scene::Scene, RenderOptions};
+/// let plant = presets::stochastic_plant();
+/// let mut options = RenderOptions::new();
+/// options
+///     .derivation_length(2)
+///     .step_size(2.0)
+///     .angle_increment(25.7_f32.to_radians());
+/// let mut scene = Scene::new(64, 64, 1);
+/// for (i, x) in [16, 32, 48].into_iter().enumerate() {
+///     scene.place(&plant, options.clone().seed(i as u64), x, 4, 0);
+/// }
+/// let buf = scene.render()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct Scene<'a> {
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    placements: Vec<Placement<'a>>,
+}
+
+/// An L System rendered from a position in a scene.
+#[derive(Clone)]
+struct Placement<'a> {
+    l_system: &'a LSystem,
+    options: RenderOptions,
+    position: (i32, i32, i32),
+    scale: f32,
+    rotation: f32,
+    facing: Option<[f32; 3]>,
+}
+
+/// How instances placed with `Scene::place_instances` differ from each
+/// other, besides the seed of their L System.
+///
+/// Defaults to no variation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variation {
+    min_scale: f32,
+    max_scale: f32,
+    max_rotation: f32,
+    seed: u64,
+}
+
+impl Default for Variation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Variation {
+    pub fn new() -> Variation {
+        Variation {
+            min_scale: 1.0,
+            max_scale: 1.0,
+            max_rotation: 0.0,
+            seed: 0,
+        }
+    }
+
+    /// Scale every instance by a random factor from `min` to `max`,
+    /// multiplying the `voxels_per_unit` of it's options.
+    pub fn scale(&mut self, min: f32, max: f32) -> &mut Self {
+        self.min_scale = min;
+        self.max_scale = max.max(min);
+        self
+    }
+
+    /// Turn every instance by a random angle of up to `max_angle` radians
+    /// left or right around the vertical, before it's initial heading and
+    /// pitch.
+    pub fn rotation(&mut self, max_angle: f32) -> &mut Self {
+        self.max_rotation = max_angle.abs();
+        self
+    }
+
+    /// Seed the random scales and rotations. Defaults to 0.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+}
+
+impl<'a> Scene<'a> {
+    /// Create an empty scene of `size_x` by `size_y` by `size_z` voxels.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> Scene<'a> {
+        Scene {
+            size_x,
+            size_y,
+            size_z,
+            placements: Vec::new(),
+        }
+    }
+
+    /// Place `l_system`, rendered with `options`, with the turtle starting at
+    /// voxel `(x, y, z)` facing north turned by the initial heading and pitch
+    /// of `options`.
+    ///
+    /// The drawing must stay inside the scene, as with `RenderOptions::render`.
+    pub fn place(
+        &mut self,
+        l_system: &'a LSystem,
+        options: &RenderOptions,
+        x: i32,
+        y: i32,
+        z: i32,
+    ) -> &mut Self {
+        self.placements.push(Placement {
+            l_system,
+            options: options.clone(),
+            position: (x, y, z),
+            scale: 1.0,
+            rotation: 0.0,
+            facing: None,
+        });
+        self
+    }
+
+    /// Place `l_system` on `anchor`, growing out of it.
+    ///
+    /// The L System starts next to the anchor, at `Anchor::next`, facing
+    /// along the anchor's direction and then turned by the initial heading
+    /// and pitch of `options`, the same way `Overgrowth` grows from a seed.
+    ///
+    /// # Examples
+    ///
+    /// Hang a lantern from every hook of a wall.
+    /// ```
+    /// # use voxgen::l_system::{scene::Scene, LSystem, RenderOptions};
+    /// # use voxgen::voxel_buffer::{Anchor, ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut wall = ArrayVoxelBuffer::filled(24, 1, 16, Rgba([140, 130, 120, 255]));
+    /// for x in [4, 12, 20] {
+    ///     wall.set_anchor(&format!("hook_{}", x), Anchor::new((x, 1, 14), [0.0, 1.0, 0.0]));
+    /// }
+    /// let lantern = LSystem::try_new("lantern", "F&&FF", vec![])?;
+    /// let mut options = RenderOptions::new();
+    /// options
+    ///     .step_size(2.0)
+    ///     .angle_increment(45_f32.to_radians())
+    ///     .symbol_color('F', Rgba([230, 180, 60, 255]));
+    /// let mut scene = Scene::new(24, 8, 16);
+    /// for (_, hook) in wall.anchors() {
+    ///     scene.place_on_anchor(&lantern, &options, &hook);
+    /// }
+    /// let mut buf = scene.render()?;
+    /// assert_eq!(*buf.voxel(12, 2, 14), Rgba([230, 180, 60, 255]));
+    /// buf.stamp(&wall, 0, 0, 0);
+    /// buf.save("test/volumes/lanterns.vox")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn place_on_anchor(
+        &mut self,
+        l_system: &'a LSystem,
+        options: &RenderOptions,
+        anchor: &Anchor,
+    ) -> &mut Self {
+        self.placements.push(Placement {
+            l_system,
+            options: options.clone(),
+            position: anchor.next(),
+            scale: 1.0,
+            rotation: 0.0,
+            facing: Some(anchor.direction()),
+        });
+        self
+    }
+
+    /// Place an instance of `l_system` at each of `positions`, like `place`,
+    /// varied by `variation`.
+    ///
+    /// The `i`th instance renders with the seed `Seed::new(seed).index(i)`,
+    /// where `seed` is the seed of `options`, so stochastic L Systems give
+    /// every instance it's own shape.
+    pub fn place_instances<I>(
+        &mut self,
+        l_system: &'a LSystem,
+        options: &RenderOptions,
+        positions: I,
+        variation: &Variation,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = (i32, i32, i32)>,
+    {
+        let seed = Seed::new(options.seed);
+        let mut rng = Seed::new(variation.seed).rng();
+        for (i, position) in positions.into_iter().enumerate() {
+            let mut options = options.clone();
+            options.seed(seed.index(i as u64).value());
+            let scale = rng.gen_range(variation.min_scale..=variation.max_scale);
+            let rotation = rng.gen_range(-variation.max_rotation..=variation.max_rotation);
+            self.placements.push(Placement {
+                l_system,
+                options,
+                position,
+                scale,
+                rotation,
+                facing: None,
+            });
+        }
+        self
+    }
+
+    /// Render every placement in order into a new buffer.
+    ///
+    /// Later placements draw over earlier ones. Returns an error if a
+    /// placement exceeds it's `max_symbols` or `max_voxels` limit, where the
+    /// voxel limit counts the voxels of earlier placements too.
+    pub fn render(&self) -> Result<ArrayVoxelBuffer<Rgba>, LimitError> {
+        let mut turtle = TurtleGraphics::new(self.size_x, self.size_y, self.size_z);
+        for placement in &self.placements {
+            let (x, y, z) = placement.position;
+            turtle.push();
+            turtle.move_to(x, y, z);
+            match placement.facing {
+                Some(direction) => turtle.face(direction),
+                None => turtle.left(core::f32::consts::FRAC_PI_2 + placement.rotation),
+            }
+            turtle.width(placement.options.width);
+            turtle.voxels_per_unit(placement.options.voxels_per_unit * placement.scale);
+            placement.options.orient(&mut turtle);
+            let result = placement.options.render_into(
+                placement.l_system,
+                &mut turtle,
+                &mut RenderStats::default(),
+            );
+            turtle.pop();
+            result?;
+        }
+        Ok(turtle.into_buf())
+    }
+
+    /// Render the scene and save it as a single MagicaVoxel .vox file to
+    /// `path`.
+    ///
+    /// Exceeding a render limit is reported as an `io::Error` wrapping the
+    /// `LimitError`.
+    #[cfg(feature = "std")]
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.render().map_err(io::Error::other)?.save(path)
+    }
+}
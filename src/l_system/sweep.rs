@@ -0,0 +1,116 @@
+use super::{LSystem, RenderOptions};
+use rayon::prelude::*;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A grid of render options to render an L System across in parallel.
+///
+/// Every combination of the given derivation lengths, angle increments and
+/// seeds is rendered. Settings without values keep the value of the base
+/// `RenderOptions`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use voxgen::l_system::{presets, sweep::Sweep, RenderOptions};
+/// let paths = Sweep::new()
+///     .derivation_lengths(vec![3, 4, 5])
+///     .angle_increments_degrees(vec![20.0, 25.7, 30.0])
+///     .seeds(vec![0, 1])
+///     .save(
+///         &presets::stochastic_plant(),
+///         RenderOptions::new().auto_fit(true),
+///         "sweeps",
+///     )?;
+/// assert_eq!(paths.len(), 18);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Sweep {
+    derivation_lengths: Vec<u32>,
+    angle_increments: Vec<f32>,
+    seeds: Vec<u64>,
+}
+
+impl Sweep {
+    pub fn new() -> Sweep {
+        Sweep::default()
+    }
+
+    pub fn derivation_lengths(&mut self, lengths: Vec<u32>) -> &mut Self {
+        self.derivation_lengths = lengths;
+        self
+    }
+
+    pub fn angle_increments_degrees(&mut self, angles: Vec<f32>) -> &mut Self {
+        self.angle_increments = angles;
+        self
+    }
+
+    pub fn seeds(&mut self, seeds: Vec<u64>) -> &mut Self {
+        self.seeds = seeds;
+        self
+    }
+
+    /// Get the file name and render options of every combination.
+    fn grid(&self, name: &str, options: &RenderOptions) -> Vec<(String, RenderOptions)> {
+        fn values<T: Copy>(values: &[T]) -> Vec<Option<T>> {
+            if values.is_empty() {
+                vec![None]
+            } else {
+                values.iter().copied().map(Some).collect()
+            }
+        }
+        let mut grid = Vec::new();
+        for n in values(&self.derivation_lengths) {
+            for angle in values(&self.angle_increments) {
+                for seed in values(&self.seeds) {
+                    let mut options = options.clone();
+                    let mut file_name = name.to_string();
+                    if let Some(n) = n {
+                        options.derivation_length(n);
+                        file_name += &format!("_n{}", n);
+                    }
+                    if let Some(angle) = angle {
+                        options.angle_increment(angle.to_radians());
+                        file_name += &format!("_a{}", angle);
+                    }
+                    if let Some(seed) = seed {
+                        options.seed(seed);
+                        file_name += &format!("_s{}", seed);
+                    }
+                    grid.push((file_name + ".vox", options));
+                }
+            }
+        }
+        grid
+    }
+
+    /// Render `l_system` with every combination of settings applied to
+    /// `options` in parallel, and save each as a MagicaVoxel .vox file in
+    /// the directory `dir`.
+    ///
+    /// Files are named after the L System and the settings that vary, as in
+    /// `plant_n4_a25.7_s1.vox`. The directory is created if needed. Returns
+    /// the paths of the saved files, or the first error encountered.
+    pub fn save<P>(
+        &self,
+        l_system: &LSystem,
+        options: &RenderOptions,
+        dir: P,
+    ) -> io::Result<Vec<PathBuf>>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        self.grid(l_system.name(), options)
+            .into_par_iter()
+            .map(|(file_name, options)| {
+                let path = dir.join(file_name);
+                options.save(l_system, &path)?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,131 @@
+use super::{LSystem, RenderOptions};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A runner re-rendering an L System definition file whenever it changes,
+/// for a tight feedback loop while writing rules.
+///
+/// The file is checked for changes by polling it's modification time, so
+/// it works on every platform and with editors that replace files instead
+/// of writing them. Every change loads the L System with
+/// `LSystem::from_file` and it's render options with
+/// `RenderOptions::from_file`, renders it, and saves it to the output file
+/// if there is one. Rules that can't be parsed and renders over a limit are
+/// reported like any other error, and the runner keeps watching for the
+/// fix.
+///
+/// With the `viewer` feature, `viewer::watch` shows every render in a live
+/// window instead.
+///
+/// # Examples
+///
+/// Re-render a definition file to a `.vox` file on every save, printing
+/// errors, until the render is over 100 voxels wide.
+/// ```no_run
+/// # use voxgen::l_system::watch::Watch;
+/// # use voxgen::voxel_buffer::VoxelBuffer;
+/// # use std::ops::ControlFlow;
+/// Watch::new("test/systems/koch.toml")
+///     .output("test/volumes/koch_watch.vox")
+///     .run(|result| match result {
+///         Ok(buf) if buf.dimensions().0 > 100 => ControlFlow::Break(()),
+///         Ok(_) => ControlFlow::Continue(()),
+///         Err(e) => {
+///             eprintln!("{}", e);
+///             ControlFlow::Continue(())
+///         }
+///     });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Watch {
+    path: PathBuf,
+    output: Option<PathBuf>,
+    interval: Duration,
+    modified: Option<Option<SystemTime>>,
+}
+
+impl Watch {
+    /// Create a runner watching the definition file at `path`, checking
+    /// for changes every 250 milliseconds.
+    pub fn new<P>(path: P) -> Watch
+    where
+        P: AsRef<Path>,
+    {
+        Watch {
+            path: path.as_ref().to_path_buf(),
+            output: None,
+            interval: Duration::from_millis(250),
+            modified: None,
+        }
+    }
+
+    /// Save every render as a MagicaVoxel `.vox` file to `path`.
+    pub fn output<P>(&mut self, path: P) -> &mut Self
+    where
+        P: AsRef<Path>,
+    {
+        self.output = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set how often the file is checked for changes.
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Get the path of the watched definition file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get how often the file is checked for changes.
+    #[cfg(feature = "viewer")]
+    pub(crate) fn poll_interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Re-render the file if it changed since the last poll, returning the
+    /// result, or `None` if it didn't change.
+    ///
+    /// The first poll always renders. A missing file is reported once, and
+    /// renders again once it's back.
+    pub fn poll(&mut self) -> Option<crate::Result<ArrayVoxelBuffer<Rgba>>> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        Some(self.render())
+    }
+
+    /// Poll the file for changes until `f` breaks, calling `f` with the
+    /// result of every render.
+    pub fn run<F>(&mut self, mut f: F)
+    where
+        F: FnMut(crate::Result<ArrayVoxelBuffer<Rgba>>) -> ControlFlow<()>,
+    {
+        loop {
+            if let Some(result) = self.poll() {
+                if f(result).is_break() {
+                    return;
+                }
+            }
+            std::thread::sleep(self.interval);
+        }
+    }
+
+    /// Load, render and save the file.
+    fn render(&self) -> crate::Result<ArrayVoxelBuffer<Rgba>> {
+        let l_system = LSystem::from_file(&self.path)?;
+        let buf = RenderOptions::from_file(&self.path)?.render(&l_system)?;
+        if let Some(output) = &self.output {
+            buf.save(output)?;
+        }
+        Ok(buf)
+    }
+}
@@ -1,10 +1,28 @@
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub use error::{Error, Result};
+
+/// The collections, `alloc` types and float math of the standard library
+/// for the core modules, from other crates without `std`.
+mod compat;
+
+/// The crate wide error type.
+mod error;
+
+/// The types most generators start with, for `use voxgen::prelude::*`.
+pub mod prelude;
 
 /// A voxel grid data structure.
 ///
 /// Implemented based on the [image](https://crates.io/crates/image) crate.
 pub mod voxel_buffer;
 
+/// Manage the colors of buffers with palettes and gradients.
+pub mod color;
+
 /// Draw on voxel buffers using turtle graphics.
 ///
 /// Use basic turtle graphics commands and save outputs as magicavoxel .vox
@@ -22,39 +40,223 @@ pub mod turtle_graphics;
 /// Render a Sierpinski gasket.
 /// ```
 /// # use voxgen::l_system::{LSystem, RenderOptions};
-/// let l_system = LSystem::new(
+/// let l_system = LSystem::try_new(
 ///     "sierpinski-gasket",
 ///     "R",
 ///     vec![
 ///         "L→R+L+R",
 ///         "R→L-R-L",
 ///     ]
-/// );
+/// )?;
 /// RenderOptions::new()
 ///     .derivation_length(3)
 ///     .step_size(4.0)
 ///     .angle_increment(std::f32::consts::FRAC_PI_3)
 ///     .offset_y(-20.0)
-///     .render(l_system);
+///     .save(&l_system, "test/volumes/sierpinski-gasket_3.vox")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 ///
 /// Render a Hilbert curve.
 /// ```
 /// # use voxgen::l_system::{LSystem, RenderOptions};
-/// let l_system = LSystem::new(
+/// let l_system = LSystem::try_new(
 ///     "hilbert",
 ///     "A",
 ///     vec![
 ///         "A→+BF-AFA-FB+",
 ///         "B→-AF+BFB+FA-",
 ///     ],
-/// );
+/// )?;
 /// RenderOptions::new()
 ///     .size_x(127)
 ///     .size_y(127)
 ///     .offset_x(63.0)
 ///     .offset_y(-63.0)
 ///     .derivation_length(6)
-///     .render(l_system);
+///     .save(&l_system, "test/volumes/hilbert_6.vox")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub mod l_system;
+
+/// Fill voxel buffers with seeded coherent noise.
+///
+/// Implemented with the [noise](https://crates.io/crates/noise) crate.
+#[cfg(feature = "std")]
+pub mod noise;
+
+/// Synthesize voxel buffers resembling an example with Wave Function
+/// Collapse.
+///
+/// Implemented based on the simple tiled model of
+/// [WaveFunctionCollapse](https://github.com/mxgmn/WaveFunctionCollapse).
+#[cfg(feature = "std")]
+pub mod wfc;
+
+/// Evolve voxel buffers with three dimensional cellular automata.
+#[cfg(feature = "std")]
+pub mod automata;
+
+/// Generate terrain heightmaps and fill voxel buffers with them.
+#[cfg(feature = "std")]
+pub mod terrain;
+
+/// Author shapes as signed distance fields and rasterize them into voxel
+/// buffers.
+///
+/// Implemented based on the distance functions of [Inigo
+/// Quilez](https://iquilezles.org/articles/distfunctions/).
+#[cfg(feature = "std")]
+pub mod sdf;
+
+/// Load triangle meshes and voxelize them into voxel buffers, and mesh the
+/// surfaces of voxel buffers.
+#[cfg(feature = "std")]
+pub mod mesh;
+
+/// Grow trees with the space colonization algorithm and draw them using
+/// turtle graphics.
+///
+/// Implemented based on [Modeling Trees with a Space Colonization
+/// Algorithm](http://algorithmicbotany.org/papers/colonization.egwnp2007.html).
+#[cfg(feature = "std")]
+pub mod space_colonization;
+
+/// Generate mazes and carve them into voxel buffers.
+///
+/// Implemented based on the algorithms described in [Mazes for
+/// Programmers](https://pragprog.com/titles/jbmaze/mazes-for-programmers/).
+#[cfg(feature = "std")]
+pub mod maze;
+
+/// Generate rooms and corridors dungeons and build them into voxel buffers.
+#[cfg(feature = "std")]
+pub mod dungeon;
+
+/// Scatter points with a minimum spacing for placing objects.
+///
+/// Implemented based on [Fast Poisson Disk Sampling in Arbitrary
+/// Dimensions](https://www.cs.ubc.ca/~rbridson/docs/bridson-siggraph07-poissondisk.pdf).
+#[cfg(feature = "std")]
+pub mod scatter;
+
+/// Partition voxel buffers into the cells of Voronoi diagrams.
+#[cfg(feature = "std")]
+pub mod voronoi;
+
+/// Lay out city roads and building lots over voxel buffers.
+#[cfg(feature = "std")]
+pub mod city;
+
+/// Generate buildings from parameters.
+#[cfg(feature = "std")]
+pub mod building;
+
+/// Grow branching clusters with diffusion-limited aggregation.
+#[cfg(feature = "std")]
+pub mod dla;
+
+/// Simulate reaction-diffusion and draw the patterns into voxel buffers.
+#[cfg(feature = "std")]
+pub mod reaction_diffusion;
+
+/// Carve caves out of terrain in voxel buffers.
+#[cfg(feature = "std")]
+pub mod caves;
+
+/// Generate asteroids and rocks from noise displaced ellipsoids.
+#[cfg(feature = "std")]
+pub mod asteroid;
+
+/// Generate volumetric clouds from thresholded fractal noise.
+#[cfg(feature = "std")]
+pub mod clouds;
+
+/// Trace and carve rivers down terrain.
+#[cfg(feature = "std")]
+pub mod rivers;
+
+/// Compose generation stages into cached, re-runnable pipelines.
+#[cfg(feature = "std")]
+pub mod pipeline;
+
+/// Derive reproducible seeds for every generator of a scene from one master
+/// seed.
+pub mod seed;
+
+/// Generate mirrored spaceships and vehicles from stacked primitives.
+#[cfg(feature = "std")]
+pub mod spaceship;
+
+/// Grow compact clusters and crystals with the Eden model.
+#[cfg(feature = "std")]
+pub mod eden;
+
+/// Blend creature bodies and blobs from skeletons of metaballs.
+#[cfg(feature = "std")]
+pub mod metaballs;
+
+/// Assemble maps by stamping tiles from a tile library onto a grid.
+#[cfg(feature = "std")]
+pub mod tiles;
+
+/// Texture the surfaces of buffers with noise, bricks, stones and wood grain.
+#[cfg(feature = "std")]
+pub mod texture;
+
+/// Scatter clustered stars through colored nebula fog.
+#[cfg(feature = "std")]
+pub mod starfield;
+
+/// Rasterize classic fractals, the Menger sponge, the Sierpinski tetrahedron
+/// and the Mandelbulb.
+#[cfg(feature = "std")]
+pub mod fractals;
+
+/// Settle sand and water with falling sand physics.
+#[cfg(feature = "std")]
+pub mod sand;
+
+/// Bake sunlight and shadows into voxel colors.
+#[cfg(feature = "std")]
+pub mod lighting;
+
+/// Generate large buffers in chunks across all cores, in memory or streamed
+/// to disk.
+#[cfg(feature = "std")]
+pub mod chunks;
+
+/// Run heavy generation passes on the GPU with compute shaders.
+#[cfg(feature = "gpu")]
+pub mod gpu;
+
+/// Report the progress of long generations to callbacks.
+pub mod progress;
+
+/// Render buffers to images with a software raycaster.
+#[cfg(feature = "std")]
+pub mod preview;
+
+/// View buffers in an interactive window with an orbit camera.
+#[cfg(feature = "viewer")]
+pub mod viewer;
+
+/// Script buffers, turtles and L Systems from Python.
+///
+/// Build the extension module with
+/// `cargo rustc --release --features python --crate-type cdylib`, and
+/// copy `target/release/libvoxgen.so` to `voxgen.so` (or `voxgen.pyd` from
+/// `voxgen.dll` on Windows) next to the notebook or script importing it.
+///
+/// ```python
+/// import voxgen
+///
+/// plant = voxgen.LSystem("plant", "F", ["F → F[+F]F[-F]F"])
+/// options = voxgen.RenderOptions(derivation_length=3, step_size=1.0, auto_fit=True)
+/// buf = options.render(plant)
+/// buf[0, 0, 0] = (255, 0, 0, 255)
+/// buf.save("plant.vox")
+/// buf.save_preview("plant.png")
+/// ```
+#[cfg(feature = "python")]
+pub mod python;
@@ -0,0 +1,180 @@
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_6};
+
+/// The face normals of a voxel.
+const FACES: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// A directional light bake, darkening voxels in shadow and brightening
+/// faces turned to the sun, so exports read well in viewers without
+/// lighting of their own.
+///
+/// Every surface voxel casts a ray towards the sun through the voxel grid,
+/// and is in shadow if the ray hits another voxel before leaving the
+/// buffer. Lit voxels are brightened by how squarely their most sunward
+/// exposed face faces the sun. Voxels whose exposed faces all face away
+/// from the sun shadow themselves. Brightness is rounded to a few levels,
+/// keeping under the MagicaVoxel palette limit.
+///
+/// Bake after any pass that recolors voxels by their color, such as
+/// `terrain::Colorizer`, since baked colors won't match.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::lighting::Sunlight;
+/// # use voxgen::terrain::{Colorizer, DiamondSquare};
+/// let mut buf = DiamondSquare::new().roughness(0.6).seed(2).render(64, 64, 24);
+/// Colorizer::new().colorize(&mut buf);
+/// Sunlight::new().direction(0.8, 0.5).shadow(0.45).bake(&mut buf);
+/// buf.save("test/volumes/sunlight.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sunlight {
+    direction: [f32; 3],
+    shadow: f32,
+    highlight: f32,
+    levels: u32,
+}
+
+impl Sunlight {
+    /// Create a bake with the sun 45 degrees around from +x towards +y and
+    /// 30 degrees up.
+    pub fn new() -> Sunlight {
+        let mut sunlight = Sunlight {
+            direction: [0.0; 3],
+            shadow: 0.4,
+            highlight: 0.15,
+            levels: 4,
+        };
+        sunlight.direction(FRAC_PI_4, FRAC_PI_6);
+        sunlight
+    }
+
+    /// Set the direction of the sun, `azimuth` radians around from +x
+    /// towards +y and `elevation` radians up from the horizon.
+    pub fn direction(&mut self, azimuth: f32, elevation: f32) -> &mut Self {
+        let elevation = elevation.clamp(0.0, FRAC_PI_2);
+        self.direction = [
+            elevation.cos() * azimuth.cos(),
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+        ];
+        self
+    }
+
+    /// Set how much voxels in shadow are darkened, from 0.0 to 1.0.
+    pub fn shadow(&mut self, darkness: f32) -> &mut Self {
+        self.shadow = darkness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set how much faces facing the sun squarely are brightened, as a
+    /// fraction of their color.
+    pub fn highlight(&mut self, amount: f32) -> &mut Self {
+        self.highlight = amount.max(0.0);
+        self
+    }
+
+    /// Set the number of brightness levels of lit voxels.
+    pub fn levels(&mut self, levels: u32) -> &mut Self {
+        self.levels = levels.max(1);
+        self
+    }
+
+    /// Bake the light into the colors of the surface voxels of `buf`.
+    pub fn bake(&self, buf: &mut ArrayVoxelBuffer<Rgba>) {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let inside = |p: [i64; 3]| {
+            p[0] >= 0
+                && p[1] >= 0
+                && p[2] >= 0
+                && p[0] < size_x as i64
+                && p[1] < size_y as i64
+                && p[2] < size_z as i64
+        };
+        let solid = |buf: &ArrayVoxelBuffer<Rgba>, p: [i64; 3]| {
+            inside(p) && buf.voxel(p[0] as u32, p[1] as u32, p[2] as u32).0[3] != 0
+        };
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let p = [x as i64, y as i64, z as i64];
+                    if !solid(buf, p) {
+                        continue;
+                    }
+                    let mut exposed = false;
+                    let mut facing: f32 = 0.0;
+                    for n in FACES {
+                        if !solid(buf, [p[0] + n[0], p[1] + n[1], p[2] + n[2]]) {
+                            exposed = true;
+                            let dot = (0..3).map(|i| n[i] as f32 * self.direction[i]).sum();
+                            facing = facing.max(dot);
+                        }
+                    }
+                    if !exposed {
+                        continue;
+                    }
+                    let factor = if facing <= 0.0 || self.occluded(buf, p, &solid) {
+                        1.0 - self.shadow
+                    } else {
+                        let steps = self.levels as f32;
+                        1.0 + self.highlight * (facing * steps).ceil() / steps
+                    };
+                    let voxel = buf.voxel_mut(x, y, z);
+                    for c in &mut voxel.0[..3] {
+                        *c = (*c as f32 * factor).round().min(255.0) as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check if the ray from the voxel at `p` towards the sun hits another
+    /// voxel before leaving the buffer.
+    fn occluded<F>(&self, buf: &ArrayVoxelBuffer<Rgba>, p: [i64; 3], solid: &F) -> bool
+    where
+        F: Fn(&ArrayVoxelBuffer<Rgba>, [i64; 3]) -> bool,
+    {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let d = self.direction;
+        // Step through the voxels the ray crosses, from the middle of the
+        // voxel, crossing the nearest voxel boundary every time.
+        let mut cell = p;
+        let step = d.map(|c| if c > 0.0 { 1 } else { -1 });
+        let delta = d.map(|c| 1.0 / c.abs().max(f32::EPSILON));
+        let mut next = delta.map(|c| c / 2.0);
+        let limit = (size_x + size_y + size_z) as usize;
+        for _ in 0..limit {
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+            let outside = cell[0] < 0
+                || cell[1] < 0
+                || cell[2] < 0
+                || cell[0] >= size_x as i64
+                || cell[1] >= size_y as i64
+                || cell[2] >= size_z as i64;
+            if outside {
+                return false;
+            }
+            if solid(buf, cell) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Default for Sunlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
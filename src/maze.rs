@@ -0,0 +1,397 @@
+use crate::seed::{self, Seed};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+/// The algorithm carving the passages of every level of a maze.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// A depth first search, giving long winding corridors with few dead
+    /// ends.
+    RecursiveBacktracker,
+    /// Randomized Prim's algorithm, giving many short dead ends.
+    Prim,
+    /// Wilson's algorithm of loop erased random walks, picking every
+    /// possible maze with the same probability.
+    Wilson,
+}
+
+/// A passage direction out of a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    East,
+    West,
+    North,
+    South,
+    Up,
+    Down,
+}
+
+impl Direction {
+    const ALL: [Direction; 6] = [
+        Direction::East,
+        Direction::West,
+        Direction::North,
+        Direction::South,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    fn offset(self) -> (i64, i64, i64) {
+        match self {
+            Direction::East => (1, 0, 0),
+            Direction::West => (-1, 0, 0),
+            Direction::North => (0, 1, 0),
+            Direction::South => (0, -1, 0),
+            Direction::Up => (0, 0, 1),
+            Direction::Down => (0, 0, -1),
+        }
+    }
+
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+}
+
+/// A perfect maze of cells on one or more levels, with exactly one path
+/// between any two cells.
+///
+/// Every level is a maze of it's own, and consecutive levels are connected
+/// by a single staircase.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::maze::{Algorithm, Maze, MazeStyle};
+/// let maze = Maze::generate(8, 8, 2, Algorithm::Wilson, 5);
+/// maze.to_buffer(&MazeStyle::new()).save("test/volumes/maze.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Maze {
+    cells_x: u32,
+    cells_y: u32,
+    levels: u32,
+    /// The open directions of every cell, as bits of `Direction`.
+    passages: Vec<u8>,
+}
+
+impl Maze {
+    /// Generate a maze of `cells_x` by `cells_y` cells on each of `levels`
+    /// levels with `algorithm`, picking passages with a random number
+    /// generator seeded with `seed`.
+    ///
+    /// The same seed always gives the same maze.
+    pub fn generate(
+        cells_x: u32,
+        cells_y: u32,
+        levels: u32,
+        algorithm: Algorithm,
+        seed: u64,
+    ) -> Maze {
+        let (cells_x, cells_y, levels) = (cells_x.max(1), cells_y.max(1), levels.max(1));
+        let mut maze = Maze {
+            cells_x,
+            cells_y,
+            levels,
+            passages: vec![0; (cells_x * cells_y * levels) as usize],
+        };
+        let mut rng = Seed::new(seed).rng();
+        for z in 0..levels {
+            match algorithm {
+                Algorithm::RecursiveBacktracker => maze.backtrack(z, &mut rng),
+                Algorithm::Prim => maze.prim(z, &mut rng),
+                Algorithm::Wilson => maze.wilson(z, &mut rng),
+            }
+            if z > 0 {
+                let x = rng.gen_range(0..cells_x);
+                let y = rng.gen_range(0..cells_y);
+                maze.open((x, y, z - 1), Direction::Up);
+            }
+        }
+        maze
+    }
+
+    /// Get the maze dimensions in cells as a tuple `(cells_x, cells_y,
+    /// levels)`.
+    pub fn dimensions(&self) -> (u32, u32, u32) {
+        (self.cells_x, self.cells_y, self.levels)
+    }
+
+    /// Check whether the cell at `(x, y, z)` has a passage in `direction`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y, z)` is outside the maze.
+    pub fn is_open(&self, x: u32, y: u32, z: u32, direction: Direction) -> bool {
+        self.passages[self.index((x, y, z))] & direction.bit() != 0
+    }
+
+    fn index(&self, (x, y, z): (u32, u32, u32)) -> usize {
+        assert!(x < self.cells_x && y < self.cells_y && z < self.levels);
+        (x + y * self.cells_x + z * self.cells_x * self.cells_y) as usize
+    }
+
+    /// Get the neighbor of `cell` in `direction`, if it's inside the maze.
+    fn neighbor(&self, cell: (u32, u32, u32), direction: Direction) -> Option<(u32, u32, u32)> {
+        let (dx, dy, dz) = direction.offset();
+        let x = u32::try_from(cell.0 as i64 + dx)
+            .ok()
+            .filter(|&x| x < self.cells_x)?;
+        let y = u32::try_from(cell.1 as i64 + dy)
+            .ok()
+            .filter(|&y| y < self.cells_y)?;
+        let z = u32::try_from(cell.2 as i64 + dz)
+            .ok()
+            .filter(|&z| z < self.levels)?;
+        Some((x, y, z))
+    }
+
+    /// Get the neighbors of `cell` on the same level, with their directions.
+    fn level_neighbors(&self, cell: (u32, u32, u32)) -> Vec<(Direction, (u32, u32, u32))> {
+        Direction::ALL[..4]
+            .iter()
+            .filter_map(|&d| self.neighbor(cell, d).map(|n| (d, n)))
+            .collect()
+    }
+
+    /// Open a passage from `cell` in `direction`, and back.
+    fn open(&mut self, cell: (u32, u32, u32), direction: Direction) {
+        if let Some(next) = self.neighbor(cell, direction) {
+            let (i, j) = (self.index(cell), self.index(next));
+            self.passages[i] |= direction.bit();
+            self.passages[j] |= direction.opposite().bit();
+        }
+    }
+
+    fn backtrack(&mut self, z: u32, rng: &mut ChaCha8Rng) {
+        let mut visited = vec![false; self.passages.len()];
+        let start = (
+            rng.gen_range(0..self.cells_x),
+            rng.gen_range(0..self.cells_y),
+            z,
+        );
+        visited[self.index(start)] = true;
+        let mut stack = vec![start];
+        while let Some(&cell) = stack.last() {
+            let unvisited: Vec<_> = self
+                .level_neighbors(cell)
+                .into_iter()
+                .filter(|(_, n)| !visited[self.index(*n)])
+                .collect();
+            match unvisited.choose(rng) {
+                Some(&(direction, next)) => {
+                    self.open(cell, direction);
+                    visited[self.index(next)] = true;
+                    stack.push(next);
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    fn prim(&mut self, z: u32, rng: &mut ChaCha8Rng) {
+        let mut visited = vec![false; self.passages.len()];
+        let start = (
+            rng.gen_range(0..self.cells_x),
+            rng.gen_range(0..self.cells_y),
+            z,
+        );
+        visited[self.index(start)] = true;
+        let mut frontier = self.level_neighbors(start);
+        while !frontier.is_empty() {
+            let (_, cell) = frontier.swap_remove(seed::index(rng, frontier.len()));
+            if visited[self.index(cell)] {
+                continue;
+            }
+            let neighbors = self.level_neighbors(cell);
+            let (visited_neighbors, unvisited): (Vec<_>, Vec<_>) = neighbors
+                .into_iter()
+                .partition(|(_, n)| visited[self.index(*n)]);
+            if let Some(&(direction, _)) = visited_neighbors.choose(rng) {
+                self.open(cell, direction);
+            }
+            visited[self.index(cell)] = true;
+            frontier.extend(unvisited);
+        }
+    }
+
+    fn wilson(&mut self, z: u32, rng: &mut ChaCha8Rng) {
+        let mut in_maze = vec![false; self.passages.len()];
+        let cells: Vec<_> = (0..self.cells_y)
+            .flat_map(|y| (0..self.cells_x).map(move |x| (x, y, z)))
+            .collect();
+        if let Some(first) = cells.choose(rng) {
+            in_maze[self.index(*first)] = true;
+        }
+        for &start in &cells {
+            if in_maze[self.index(start)] {
+                continue;
+            }
+            // Walk randomly until hitting the maze, remembering the last
+            // direction left from every cell, which erases loops.
+            let mut exits = std::collections::HashMap::new();
+            let mut cell = start;
+            while !in_maze[self.index(cell)] {
+                let neighbors = self.level_neighbors(cell);
+                let &(direction, next) = neighbors.choose(rng).unwrap_or(&(Direction::East, cell));
+                exits.insert(cell, direction);
+                cell = next;
+            }
+            let mut cell = start;
+            while !in_maze[self.index(cell)] {
+                let direction = exits[&cell];
+                in_maze[self.index(cell)] = true;
+                self.open(cell, direction);
+                cell = self.neighbor(cell, direction).unwrap_or(cell);
+            }
+        }
+    }
+
+    /// Carve the maze into a new buffer of solid walls.
+    ///
+    /// Every cell is a square corridor `corridor_width` voxels wide and
+    /// `corridor_height` voxels tall, separated from it's neighbors by
+    /// walls `wall_thickness` voxels thick and from the levels above and
+    /// below by a floor one voxel thick. Staircases spiral up the walls of
+    /// the cell below a passage up.
+    pub fn to_buffer(&self, style: &MazeStyle) -> ArrayVoxelBuffer<Rgba> {
+        let pitch = style.corridor_width + style.wall_thickness;
+        let level_height = style.corridor_height + 1;
+        let size_x = self.cells_x * pitch + style.wall_thickness;
+        let size_y = self.cells_y * pitch + style.wall_thickness;
+        let size_z = self.levels * level_height + 1;
+        let mut buf = ArrayVoxelBuffer::new(size_x, size_y, size_z);
+        for z in 0..size_z {
+            let color = if z % level_height == 0 {
+                style.floor_color
+            } else {
+                style.wall_color
+            };
+            buf.fill_region((0, 0, z), (size_x, size_y, z + 1), color);
+        }
+        let width = style.corridor_width;
+        let wall = style.wall_thickness;
+        for level in 0..self.levels {
+            let floor = level * level_height;
+            let (z0, z1) = (floor + 1, floor + level_height);
+            for cy in 0..self.cells_y {
+                for cx in 0..self.cells_x {
+                    let (x0, y0) = (wall + cx * pitch, wall + cy * pitch);
+                    clear(&mut buf, (x0, y0, z0), (x0 + width, y0 + width, z1));
+                    if self.is_open(cx, cy, level, Direction::East) {
+                        clear(&mut buf, (x0 + width, y0, z0), (x0 + pitch, y0 + width, z1));
+                    }
+                    if self.is_open(cx, cy, level, Direction::North) {
+                        clear(&mut buf, (x0, y0 + width, z0), (x0 + width, y0 + pitch, z1));
+                    }
+                    if self.is_open(cx, cy, level, Direction::Up) {
+                        // Open the floor above and build stairs up to it.
+                        let top = floor + level_height;
+                        clear(&mut buf, (x0, y0, top), (x0 + width, y0 + width, top + 1));
+                        let ring = ring(width);
+                        for step in 0..level_height {
+                            let (sx, sy) = ring[step as usize % ring.len()];
+                            *buf.voxel_mut(x0 + sx, y0 + sy, floor + 1 + step) = style.stair_color;
+                        }
+                    }
+                }
+            }
+        }
+        buf
+    }
+}
+
+/// Empty the voxels of `buf` from `min` up to but not including `max`.
+fn clear(buf: &mut ArrayVoxelBuffer<Rgba>, min: (u32, u32, u32), max: (u32, u32, u32)) {
+    buf.fill_region(min, max, Rgba([0, 0, 0, 0]));
+}
+
+/// Get the positions around the edge of a square `width` voxels wide, in
+/// order around it.
+fn ring(width: u32) -> Vec<(u32, u32)> {
+    if width <= 1 {
+        return vec![(0, 0)];
+    }
+    let last = width - 1;
+    let mut ring = Vec::new();
+    ring.extend((0..last).map(|x| (x, 0)));
+    ring.extend((0..last).map(|y| (last, y)));
+    ring.extend((1..=last).rev().map(|x| (x, last)));
+    ring.extend((1..=last).rev().map(|y| (0, y)));
+    ring
+}
+
+/// The dimensions and colors of a maze carved into a voxel buffer.
+#[derive(Clone, Debug)]
+pub struct MazeStyle {
+    corridor_width: u32,
+    corridor_height: u32,
+    wall_thickness: u32,
+    wall_color: Rgba,
+    floor_color: Rgba,
+    stair_color: Rgba,
+}
+
+impl MazeStyle {
+    pub fn new() -> MazeStyle {
+        MazeStyle {
+            corridor_width: 3,
+            corridor_height: 3,
+            wall_thickness: 1,
+            wall_color: Rgba([140, 140, 150, 255]),
+            floor_color: Rgba([90, 80, 70, 255]),
+            stair_color: Rgba([170, 120, 60, 255]),
+        }
+    }
+
+    pub fn corridor_width(&mut self, width: u32) -> &mut Self {
+        self.corridor_width = width.max(1);
+        self
+    }
+
+    pub fn corridor_height(&mut self, height: u32) -> &mut Self {
+        self.corridor_height = height.max(1);
+        self
+    }
+
+    pub fn wall_thickness(&mut self, thickness: u32) -> &mut Self {
+        self.wall_thickness = thickness.max(1);
+        self
+    }
+
+    pub fn wall_color(&mut self, color: Rgba) -> &mut Self {
+        self.wall_color = color;
+        self
+    }
+
+    pub fn floor_color(&mut self, color: Rgba) -> &mut Self {
+        self.floor_color = color;
+        self
+    }
+
+    pub fn stair_color(&mut self, color: Rgba) -> &mut Self {
+        self.stair_color = color;
+        self
+    }
+}
+
+impl Default for MazeStyle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,706 @@
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer, VoxelSource};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// An RGBA texture image.
+#[derive(Clone, Debug)]
+struct Texture {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgba>,
+}
+
+impl Texture {
+    /// Sample the nearest texel at texture coordinates `uv`, wrapping around
+    /// the edges.
+    fn sample(&self, uv: [f32; 2]) -> Rgba {
+        let x = (uv[0].rem_euclid(1.0) * self.width as f32) as u32;
+        let y = (uv[1].rem_euclid(1.0) * self.height as f32) as u32;
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        self.pixels[(x + y * self.width) as usize]
+    }
+}
+
+/// A triangle of a mesh with it's optional color sources.
+#[derive(Clone, Debug)]
+struct Triangle {
+    positions: [[f32; 3]; 3],
+    colors: Option<[[f32; 4]; 3]>,
+    uvs: Option<[[f32; 2]; 3]>,
+    /// The base color of the material, multiplied with the vertex colors or
+    /// texture.
+    color: Option<[f32; 4]>,
+    texture: Option<usize>,
+}
+
+impl Triangle {
+    /// Get the color at barycentric coordinates `b`, or `None` if the
+    /// triangle has no color sources.
+    fn color(&self, b: [f32; 3], textures: &[Texture]) -> Option<Rgba> {
+        let interpolate = |values: [f32; 3]| values[0] * b[0] + values[1] * b[1] + values[2] * b[2];
+        let mut color = self.color;
+        if let Some(colors) = self.colors {
+            let vertex: [f32; 4] =
+                std::array::from_fn(|c| interpolate([colors[0][c], colors[1][c], colors[2][c]]));
+            color = Some(multiply(color.unwrap_or([1.0; 4]), vertex));
+        }
+        if let (Some(uvs), Some(texture)) = (self.uvs, self.texture) {
+            let uv = [
+                interpolate([uvs[0][0], uvs[1][0], uvs[2][0]]),
+                interpolate([uvs[0][1], uvs[1][1], uvs[2][1]]),
+            ];
+            let texel = textures[texture].sample(uv).0.map(|c| c as f32 / 255.0);
+            color = Some(multiply(color.unwrap_or([1.0; 4]), texel));
+        }
+        color.map(|c| Rgba(c.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)))
+    }
+}
+
+fn multiply(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
+}
+
+/// Convert a point from the Y up axes of OBJ and glTF to the Z up axes of
+/// MagicaVoxel.
+fn z_up(p: [f32; 3]) -> [f32; 3] {
+    [p[0], -p[2], p[1]]
+}
+
+/// A triangle mesh with optional vertex colors, material colors and
+/// textures, loaded for voxelization.
+///
+/// Positions are converted from the Y up axes of OBJ and glTF files to the Z
+/// up axes of voxel buffers.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    triangles: Vec<Triangle>,
+    textures: Vec<Texture>,
+}
+
+impl Mesh {
+    /// Load a mesh from the Wavefront `.obj` or glTF `.gltf` or `.glb` file
+    /// at `path`, picked by it's extension.
+    pub fn from_file<P>(path: P) -> io::Result<Mesh>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gltf") | Some("glb") => Mesh::from_gltf(path),
+            _ => Mesh::from_obj(path),
+        }
+    }
+
+    /// Load a mesh from the Wavefront `.obj` file at `path`.
+    ///
+    /// Vertex colors given after the vertex positions are read, as are the
+    /// diffuse colors and textures of materials in `mtllib` files. Polygons
+    /// are split into triangle fans.
+    pub fn from_obj<P>(path: P) -> io::Result<Mesh>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut mesh = Mesh::default();
+        let mut positions = Vec::new();
+        let mut colors = Vec::new();
+        let mut uvs = Vec::new();
+        let mut materials = HashMap::new();
+        let mut material = None;
+        for (n, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let numbers = |words: std::str::SplitWhitespace| -> io::Result<Vec<f32>> {
+                words
+                    .map(|w| {
+                        w.parse()
+                            .map_err(|_| invalid(format!("line {}: invalid number `{}`", n + 1, w)))
+                    })
+                    .collect()
+            };
+            match words.next() {
+                Some("v") => {
+                    let v = numbers(words)?;
+                    if v.len() < 3 {
+                        return Err(invalid(format!(
+                            "line {}: vertex needs 3 coordinates",
+                            n + 1
+                        )));
+                    }
+                    positions.push(z_up([v[0], v[1], v[2]]));
+                    colors.push((v.len() >= 6).then(|| [v[3], v[4], v[5], 1.0]));
+                }
+                Some("vt") => {
+                    let vt = numbers(words)?;
+                    // Flip v so that image rows count down from the top.
+                    uvs.push([
+                        vt.first().copied().unwrap_or(0.0),
+                        1.0 - vt.get(1).copied().unwrap_or(0.0),
+                    ]);
+                }
+                Some("f") => {
+                    let mut corners = Vec::new();
+                    for word in words {
+                        let mut indices = word.split('/');
+                        let v = obj_index(indices.next(), positions.len())
+                            .and_then(|v| v.ok_or_else(|| "missing vertex".to_string()))
+                            .map_err(|e| invalid(format!("line {}: {}", n + 1, e)))?;
+                        let vt = obj_index(indices.next(), uvs.len())
+                            .map_err(|e| invalid(format!("line {}: {}", n + 1, e)))?;
+                        corners.push((v, vt));
+                    }
+                    for i in 1..corners.len().saturating_sub(1) {
+                        let corners = [corners[0], corners[i], corners[i + 1]];
+                        let (color, texture) = material
+                            .as_ref()
+                            .and_then(|m| materials.get(m))
+                            .copied()
+                            .unwrap_or((None, None));
+                        let vertex_colors: Option<Vec<[f32; 4]>> =
+                            corners.iter().map(|(v, _)| colors[*v]).collect();
+                        let texture_uvs: Option<Vec<[f32; 2]>> =
+                            corners.iter().map(|(_, vt)| vt.map(|vt| uvs[vt])).collect();
+                        mesh.triangles.push(Triangle {
+                            positions: corners.map(|(v, _)| positions[v]),
+                            colors: vertex_colors.map(|c| [c[0], c[1], c[2]]),
+                            uvs: texture_uvs.map(|uv| [uv[0], uv[1], uv[2]]),
+                            color,
+                            texture,
+                        });
+                    }
+                }
+                Some("mtllib") => {
+                    let name = words.collect::<Vec<_>>().join(" ");
+                    let library = path.with_file_name(name);
+                    mesh.read_mtl(&library, &mut materials)?;
+                }
+                Some("usemtl") => material = words.next().map(str::to_string),
+                _ => (),
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Read the diffuse colors and textures of the materials in the `.mtl`
+    /// file at `path`.
+    fn read_mtl(
+        &mut self,
+        path: &Path,
+        materials: &mut HashMap<String, (Option<[f32; 4]>, Option<usize>)>,
+    ) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        let mut current = None;
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("newmtl") => {
+                    current = words.next().map(str::to_string);
+                    if let Some(name) = &current {
+                        materials.insert(name.clone(), (None, None));
+                    }
+                }
+                Some("Kd") => {
+                    let kd: Vec<f32> = words.filter_map(|w| w.parse().ok()).collect();
+                    let material = current.as_ref().and_then(|name| materials.get_mut(name));
+                    if let (Some(material), [r, g, b, ..]) = (material, kd.as_slice()) {
+                        material.0 = Some([*r, *g, *b, 1.0]);
+                    }
+                }
+                Some("map_Kd") if current.is_some() => {
+                    let name = words.last().unwrap_or_default();
+                    let image = image::open(path.with_file_name(name))
+                        .map_err(|e| invalid(e.to_string()))?
+                        .to_rgba8();
+                    self.textures.push(Texture {
+                        width: image.width(),
+                        height: image.height(),
+                        pixels: image.pixels().map(|p| Rgba(p.0)).collect(),
+                    });
+                    let material = current.as_ref().and_then(|name| materials.get_mut(name));
+                    if let Some(material) = material {
+                        material.1 = Some(self.textures.len() - 1);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a mesh from the glTF `.gltf` or `.glb` file at `path`.
+    ///
+    /// Every triangle primitive of the default scene is read with it's node
+    /// transforms applied, along with it's vertex colors and the base color
+    /// and base color texture of it's material.
+    pub fn from_gltf<P>(path: P) -> io::Result<Mesh>
+    where
+        P: AsRef<Path>,
+    {
+        let (document, buffers, images) = gltf::import(path).map_err(|e| invalid(e.to_string()))?;
+        let mut mesh = Mesh::default();
+        for image in &images {
+            let channels = match image.format {
+                gltf::image::Format::R8G8B8 => 3,
+                gltf::image::Format::R8G8B8A8 => 4,
+                format => return Err(invalid(format!("unsupported image format {:?}", format))),
+            };
+            let pixels = image
+                .pixels
+                .chunks(channels)
+                .map(|p| Rgba([p[0], p[1], p[2], p.get(3).copied().unwrap_or(255)]))
+                .collect();
+            mesh.textures.push(Texture {
+                width: image.width,
+                height: image.height,
+                pixels,
+            });
+        }
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| invalid("glTF file has no scene".to_string()))?;
+        let identity = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let mut nodes: Vec<_> = scene.nodes().map(|node| (node, identity)).collect();
+        while let Some((node, parent)) = nodes.pop() {
+            let transform = mat_mul(parent, node.transform().matrix());
+            nodes.extend(node.children().map(|child| (child, transform)));
+            let primitives = node.mesh().into_iter().flat_map(|m| m.primitives());
+            for primitive in primitives {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                    Some(positions) => positions
+                        .map(|p| z_up(transform_point(transform, p)))
+                        .collect(),
+                    None => continue,
+                };
+                let colors: Option<Vec<[f32; 4]>> =
+                    reader.read_colors(0).map(|c| c.into_rgba_f32().collect());
+                let pbr = primitive.material().pbr_metallic_roughness();
+                let texture = pbr.base_color_texture();
+                let uvs: Option<Vec<[f32; 2]>> = texture
+                    .as_ref()
+                    .and_then(|info| reader.read_tex_coords(info.tex_coord()))
+                    .map(|uvs| uvs.into_f32().collect());
+                let indices: Vec<usize> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+                    None => (0..positions.len()).collect(),
+                };
+                for corners in indices.chunks_exact(3) {
+                    let corners = [corners[0], corners[1], corners[2]];
+                    mesh.triangles.push(Triangle {
+                        positions: corners.map(|i| positions[i]),
+                        colors: colors.as_ref().map(|c| corners.map(|i| c[i])),
+                        uvs: uvs.as_ref().map(|uv| corners.map(|i| uv[i])),
+                        color: Some(pbr.base_color_factor()),
+                        texture: texture.as_ref().map(|info| info.texture().source().index()),
+                    });
+                }
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Get the number of triangles.
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Get the smallest and largest corners of the box around every vertex.
+    fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let mut points = self.triangles.iter().flat_map(|t| t.positions);
+        let first = points.next()?;
+        Some(points.fold((first, first), |(min, max), p| {
+            (
+                std::array::from_fn(|i| min[i].min(p[i])),
+                std::array::from_fn(|i| max[i].max(p[i])),
+            )
+        }))
+    }
+}
+
+/// Resolve a one based or negative OBJ `index` into a list of `len` items.
+fn obj_index(index: Option<&str>, len: usize) -> Result<Option<usize>, String> {
+    let index = match index.filter(|i| !i.is_empty()) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+    let i: i64 = index
+        .parse()
+        .map_err(|_| format!("invalid index `{}`", index))?;
+    let i = if i < 0 { len as i64 + i } else { i - 1 };
+    if i < 0 || i as usize >= len {
+        return Err(format!("index `{}` out of range", index));
+    }
+    Ok(Some(i as usize))
+}
+
+/// Multiply two column major 4x4 matrices.
+fn mat_mul(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    std::array::from_fn(|col| {
+        std::array::from_fn(|row| (0..4).map(|k| a[k][row] * b[col][k]).sum())
+    })
+}
+
+/// Transform a point by a column major 4x4 matrix.
+fn transform_point(m: [[f32; 4]; 4], p: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|row| m[0][row] * p[0] + m[1][row] * p[1] + m[2][row] * p[2] + m[3][row])
+}
+
+/// How the inside of a voxelized mesh is filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fill {
+    /// Only the voxels the surface passes through.
+    Shell,
+    /// The surface and every voxel enclosed by it, found by flood filling
+    /// the outside.
+    Solid,
+}
+
+/// Settings to voxelize meshes into voxel buffers.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::mesh::{Fill, Mesh, Voxelizer};
+/// let mesh = Mesh::from_file("test/meshes/tetrahedron.obj")?;
+/// Voxelizer::new()
+///     .resolution(32)
+///     .fill(Fill::Solid)
+///     .voxelize(&mesh)
+///     .save("test/volumes/tetrahedron.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Voxelizer {
+    resolution: u32,
+    fill: Fill,
+    color: Rgba,
+}
+
+impl Voxelizer {
+    pub fn new() -> Voxelizer {
+        Voxelizer {
+            resolution: 64,
+            fill: Fill::Shell,
+            color: Rgba([200, 200, 200, 255]),
+        }
+    }
+
+    /// Set the number of voxels along the longest side of the mesh.
+    pub fn resolution(&mut self, resolution: u32) -> &mut Self {
+        self.resolution = resolution.max(1);
+        self
+    }
+
+    pub fn fill(&mut self, fill: Fill) -> &mut Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Set the `color` of triangles without vertex colors, material colors
+    /// or textures.
+    pub fn color(&mut self, color: Rgba) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    /// Voxelize `mesh` into a new buffer fitting it, scaled so that it's
+    /// longest side spans the resolution.
+    ///
+    /// Surface voxels take the color of the mesh where it passes through
+    /// them. With `Fill::Solid`, enclosed voxels take the color of the
+    /// nearest surface voxel below them along the x axis.
+    pub fn voxelize(&self, mesh: &Mesh) -> ArrayVoxelBuffer<Rgba> {
+        let (min, max) = match mesh.bounds() {
+            Some(bounds) => bounds,
+            None => return ArrayVoxelBuffer::new(1, 1, 1),
+        };
+        let extent = (0..3).map(|i| max[i] - min[i]).fold(0.0, f32::max);
+        let scale = if extent > 0.0 {
+            (self.resolution - 1) as f32 / extent
+        } else {
+            1.0
+        };
+        let size: [u32; 3] = std::array::from_fn(|i| ((max[i] - min[i]) * scale) as u32 + 1);
+        let mut buf = ArrayVoxelBuffer::new(size[0], size[1], size[2]);
+        for triangle in &mesh.triangles {
+            let corners = triangle
+                .positions
+                .map(|p| std::array::from_fn::<f32, 3, _>(|i| (p[i] - min[i]) * scale));
+            // Sample the triangle at least twice per voxel along it's edges.
+            let longest = (0..3)
+                .map(|i| {
+                    let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                    (0..3).map(|j| (a[j] - b[j]).powi(2)).sum::<f32>().sqrt()
+                })
+                .fold(0.0, f32::max);
+            let n = (longest * 2.0).ceil().max(1.0) as u32;
+            for i in 0..=n {
+                for j in 0..=n - i {
+                    let b = [
+                        i as f32 / n as f32,
+                        j as f32 / n as f32,
+                        (n - i - j) as f32 / n as f32,
+                    ];
+                    let p: [f32; 3] = std::array::from_fn(|k| {
+                        corners[0][k] * b[0] + corners[1][k] * b[1] + corners[2][k] * b[2]
+                    });
+                    let voxel: [u32; 3] =
+                        std::array::from_fn(|k| (p[k].round() as u32).min(size[k] - 1));
+                    let color = triangle.color(b, &mesh.textures).unwrap_or(self.color);
+                    *buf.voxel_mut(voxel[0], voxel[1], voxel[2]) = color;
+                }
+            }
+        }
+        if self.fill == Fill::Solid {
+            fill_enclosed(&mut buf);
+        }
+        buf
+    }
+}
+
+impl Default for Voxelizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fill the empty voxels of `buf` that can't be reached from it's border
+/// through other empty voxels.
+fn fill_enclosed(buf: &mut ArrayVoxelBuffer<Rgba>) {
+    let (size_x, size_y, size_z) = buf.dimensions();
+    let index = |x: u32, y: u32, z: u32| (x + y * size_x + z * size_x * size_y) as usize;
+    let mut outside = vec![false; (size_x * size_y * size_z) as usize];
+    let mut queue = VecDeque::new();
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let border = x == 0
+                    || y == 0
+                    || z == 0
+                    || x == size_x - 1
+                    || y == size_y - 1
+                    || z == size_z - 1;
+                if border && buf.voxel(x, y, z).0[3] == 0 {
+                    outside[index(x, y, z)] = true;
+                    queue.push_back((x, y, z));
+                }
+            }
+        }
+    }
+    while let Some((x, y, z)) = queue.pop_front() {
+        for (dx, dy, dz) in [
+            (1, 0, 0),
+            (-1, 0, 0),
+            (0, 1, 0),
+            (0, -1, 0),
+            (0, 0, 1),
+            (0, 0, -1),
+        ] {
+            let (nx, ny, nz) = (
+                x.wrapping_add_signed(dx),
+                y.wrapping_add_signed(dy),
+                z.wrapping_add_signed(dz),
+            );
+            if nx >= size_x || ny >= size_y || nz >= size_z {
+                continue;
+            }
+            let i = index(nx, ny, nz);
+            if !outside[i] && buf.voxel(nx, ny, nz).0[3] == 0 {
+                outside[i] = true;
+                queue.push_back((nx, ny, nz));
+            }
+        }
+    }
+    for z in 0..size_z {
+        for y in 0..size_y {
+            let mut color = None;
+            for x in 0..size_x {
+                let voxel = *buf.voxel(x, y, z);
+                if voxel.0[3] != 0 {
+                    color = Some(voxel);
+                } else if !outside[index(x, y, z)] {
+                    if let Some(color) = color {
+                        *buf.voxel_mut(x, y, z) = color;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The face normals of a voxel.
+const FACES: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// The faces between the visible and empty voxels of a buffer, as an
+/// indexed triangle mesh for game engines and viewers.
+///
+/// Every exposed voxel face is a quad of 4 vertices with the face's
+/// normal and the voxel's color, split into 2 triangles wound counter
+/// clockwise seen from outside. Positions are in voxels, in the Z up axes
+/// of the buffer, and faces between voxels of the buffer are never
+/// included.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::mesh::SurfaceMesh;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let mut buf = ArrayVoxelBuffer::new(4, 4, 4);
+/// *buf.voxel_mut(1, 1, 1) = Rgba([200, 40, 40, 255]);
+/// *buf.voxel_mut(2, 1, 1) = Rgba([200, 40, 40, 255]);
+/// let mesh = SurfaceMesh::from_buffer(&buf);
+/// assert_eq!(mesh.positions().len(), 10 * 4);
+/// assert_eq!(mesh.indices().len(), 10 * 6);
+/// mesh.save_obj("test/volumes/surface_mesh.obj")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SurfaceMesh {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<Rgba>,
+    indices: Vec<u32>,
+}
+
+impl SurfaceMesh {
+    /// Build the surface of the visible voxels of `source`, such as a
+    /// buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::mesh::SurfaceMesh;
+    /// # use voxgen::voxel_buffer::{from_fn, Rgba};
+    /// let slab = from_fn((4, 4, 1), |_, _, _| Rgba([90, 90, 90, 255]));
+    /// let mesh = SurfaceMesh::from_buffer(&slab);
+    /// assert_eq!(mesh.indices().len(), (2 * 16 + 4 * 4) * 6);
+    /// ```
+    pub fn from_buffer<S>(source: &S) -> SurfaceMesh
+    where
+        S: VoxelSource<Voxel = Rgba>,
+    {
+        let (size_x, size_y, size_z) = source.source_dimensions();
+        let solid = |p: [i64; 3]| {
+            (0..3).all(|i| p[i] >= 0)
+                && p[0] < size_x as i64
+                && p[1] < size_y as i64
+                && p[2] < size_z as i64
+                && source.sample(p[0] as u32, p[1] as u32, p[2] as u32).0[3] != 0
+        };
+        let mut mesh = SurfaceMesh::default();
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let p = [x as i64, y as i64, z as i64];
+                    if !solid(p) {
+                        continue;
+                    }
+                    let color = source.sample(x, y, z);
+                    for n in FACES {
+                        if solid([p[0] + n[0], p[1] + n[1], p[2] + n[2]]) {
+                            continue;
+                        }
+                        // The face lies on the far side of the voxel along
+                        // it's normal if the normal is positive, spanning the
+                        // other two axes in the order that winds it counter
+                        // clockwise around a positive normal.
+                        let axis = (0..3).find(|&i| n[i] != 0).unwrap();
+                        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                        let base = mesh.positions.len() as u32;
+                        for (du, dv) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                            let mut position = p.map(|c| c as f32);
+                            position[axis] += (n[axis] > 0) as u32 as f32;
+                            position[u] += du;
+                            position[v] += dv;
+                            mesh.positions.push(position);
+                            mesh.normals.push(n.map(|c| c as f32));
+                            mesh.colors.push(color);
+                        }
+                        let corners = if n[axis] > 0 {
+                            [0, 1, 2, 0, 2, 3]
+                        } else {
+                            [0, 2, 1, 0, 3, 2]
+                        };
+                        mesh.indices.extend(corners.map(|i| base + i));
+                    }
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Get the position of every vertex.
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    /// Get the normal of every vertex, pointing out of the voxel.
+    pub fn normals(&self) -> &[[f32; 3]] {
+        &self.normals
+    }
+
+    /// Get the color of every vertex.
+    pub fn colors(&self) -> &[Rgba] {
+        &self.colors
+    }
+
+    /// Get the vertex indices of every triangle, three at a time.
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Save the mesh as a Wavefront `.obj` file to `path`.
+    ///
+    /// Positions are converted to the Y up axes of OBJ files, and colors
+    /// are written after the positions, as read by `Mesh::from_obj` and
+    /// most modeling tools.
+    pub fn save_obj<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        for (p, color) in self.positions.iter().zip(&self.colors) {
+            let [x, y, z] = y_up(*p);
+            let [r, g, b, _] = color.0.map(|c| c as f32 / 255.0);
+            writeln!(out, "v {} {} {} {} {} {}", x, y, z, r, g, b)?;
+        }
+        for n in &self.normals {
+            let [x, y, z] = y_up(*n);
+            writeln!(out, "vn {} {} {}", x, y, z)?;
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+            writeln!(out, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c)?;
+        }
+        out.flush()
+    }
+}
+
+/// Convert a point from the Z up axes of MagicaVoxel to the Y up axes of
+/// OBJ and glTF, undoing `z_up`.
+fn y_up(p: [f32; 3]) -> [f32; 3] {
+    [p[0], p[2], -p[1]]
+}
@@ -0,0 +1,170 @@
+use crate::sdf::{Point, Sdf};
+
+/// A part of a metaball skeleton, a ball or a bone between two points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Element {
+    Ball { center: Point, radius: f32 },
+    Bone { a: Point, b: Point, radius: f32 },
+}
+
+impl Element {
+    fn radius(&self) -> f32 {
+        match *self {
+            Element::Ball { radius, .. } | Element::Bone { radius, .. } => radius,
+        }
+    }
+
+    /// Get the distance from `p` to the skeleton of the element.
+    fn distance(&self, p: Point) -> f32 {
+        let closest = match *self {
+            Element::Ball { center, .. } => center,
+            Element::Bone { a, b, .. } => {
+                let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+                let ap = [p[0] - a[0], p[1] - a[1], p[2] - a[2]];
+                let length2 = ab[0] * ab[0] + ab[1] * ab[1] + ab[2] * ab[2];
+                let t = ((ap[0] * ab[0] + ap[1] * ab[1] + ap[2] * ab[2])
+                    / length2.max(f32::EPSILON))
+                .clamp(0.0, 1.0);
+                [a[0] + ab[0] * t, a[1] + ab[1] * t, a[2] + ab[2] * t]
+            }
+        };
+        let d = [p[0] - closest[0], p[1] - closest[1], p[2] - closest[2]];
+        (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+    }
+}
+
+/// An implicit surface blended from a skeleton of balls and bones, for
+/// creature bodies and blobs defined by a few control points.
+///
+/// Every element adds a field falling smoothly from 1.0 on it's skeleton
+/// to 0.0 at `blend` times it's radius, and the surface is where the
+/// field reaches the value a lone element has at it's radius. Lone
+/// elements keep their radius, while nearby elements swell into each
+/// other.
+///
+/// # Examples
+///
+/// A grub with a head, a tapering body and stubby legs.
+/// ```
+/// # use voxgen::metaballs::Metaballs;
+/// # use voxgen::sdf::rasterize;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+/// let mut grub = Metaballs::new();
+/// grub.ball([24.0, 8.0, 8.0], 5.0)
+///     .bone([24.0, 12.0, 7.0], [24.0, 28.0, 6.0], 4.0)
+///     .bone([24.0, 28.0, 6.0], [24.0, 40.0, 5.0], 2.5);
+/// for y in [16.0, 24.0, 32.0] {
+///     grub.bone([20.0, y, 5.0], [17.0, y, 1.0], 1.0)
+///         .bone([28.0, y, 5.0], [31.0, y, 1.0], 1.0);
+/// }
+/// let mut buf = ArrayVoxelBuffer::new(48, 48, 16);
+/// rasterize(&grub.to_sdf(), &mut buf, |p| match grub.dominant(p) {
+///     Some(0) => Rgba([90, 60, 40, 255]),
+///     _ => Rgba([220, 200, 150, 255]),
+/// });
+/// buf.save("test/volumes/metaballs.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Metaballs {
+    elements: Vec<Element>,
+    blend: f32,
+}
+
+impl Metaballs {
+    pub fn new() -> Metaballs {
+        Metaballs {
+            elements: Vec::new(),
+            blend: 2.0,
+        }
+    }
+
+    /// Add a ball of `radius` around `center`.
+    pub fn ball(&mut self, center: Point, radius: f32) -> &mut Self {
+        self.elements.push(Element::Ball {
+            center,
+            radius: radius.max(f32::EPSILON),
+        });
+        self
+    }
+
+    /// Add a bone of `radius` around the line segment from `a` to `b`.
+    pub fn bone(&mut self, a: Point, b: Point, radius: f32) -> &mut Self {
+        self.elements.push(Element::Bone {
+            a,
+            b,
+            radius: radius.max(f32::EPSILON),
+        });
+        self
+    }
+
+    /// Set how far the field of every element reaches, as a multiple of
+    /// it's radius, where larger values blend elements from further apart.
+    pub fn blend(&mut self, blend: f32) -> &mut Self {
+        self.blend = blend.max(1.01);
+        self
+    }
+
+    /// Get the field of the element of `index` at `p`.
+    fn element_field(&self, index: usize, p: Point) -> f32 {
+        let element = &self.elements[index];
+        let t = element.distance(p) / (element.radius() * self.blend);
+        if t >= 1.0 {
+            0.0
+        } else {
+            (1.0 - t * t).powi(3)
+        }
+    }
+
+    /// Get the field at `p`, summed over all elements.
+    pub fn field(&self, p: Point) -> f32 {
+        (0..self.elements.len())
+            .map(|i| self.element_field(i, p))
+            .sum()
+    }
+
+    /// Get the field value of the surface.
+    pub fn threshold(&self) -> f32 {
+        (1.0 - 1.0 / (self.blend * self.blend)).powi(3)
+    }
+
+    /// Get the index of the element adding the most to the field at `p`, in
+    /// the order they were added, or `None` if none reach it.
+    ///
+    /// Coloring by the dominant element gives every part of a body it's own
+    /// color.
+    pub fn dominant(&self, p: Point) -> Option<usize> {
+        (0..self.elements.len())
+            .map(|i| (i, self.element_field(i, p)))
+            .filter(|(_, field)| *field > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Get the surface as a distance field, for rasterizing and combining
+    /// with other shapes.
+    ///
+    /// The sign of the distance is exact, but the distance itself is only
+    /// estimated from the field, so smooth blends with it are uneven.
+    pub fn to_sdf(&self) -> Sdf {
+        let metaballs = self.clone();
+        let threshold = self.threshold();
+        // Scale the field difference by the slope of a lone element's field
+        // at it's surface, to roughly match distances near it.
+        let slope =
+            6.0 / (self.blend * self.blend) * (1.0 - 1.0 / (self.blend * self.blend)).powi(2);
+        let radius = self
+            .elements
+            .iter()
+            .map(Element::radius)
+            .fold(0.0, f32::max)
+            .max(f32::EPSILON);
+        Sdf::new(move |p| (threshold - metaballs.field(p)) * radius / slope)
+    }
+}
+
+impl Default for Metaballs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,194 @@
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use ::noise::core::worley::ReturnType;
+use ::noise::{Fbm, MultiFractal, NoiseFn, OpenSimplex, Perlin, RidgedMulti, Worley};
+
+/// The kind of coherent noise to sample.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Generator {
+    /// A single octave of Perlin noise.
+    Perlin,
+    /// A single octave of OpenSimplex noise, with fewer directional
+    /// artifacts than Perlin noise.
+    Simplex,
+    /// Fractal Brownian motion, summing octaves of Perlin noise.
+    Fbm,
+    /// Ridged multifractal noise, for mountain ridges and canyons.
+    Ridged,
+    /// Worley noise, the distance to the nearest of randomly scattered
+    /// feature points, for cells, scales and cracks.
+    Worley,
+}
+
+/// The settings of a noise generator.
+///
+/// Voxel coordinates are multiplied by the `frequency` before sampling, so
+/// lower frequencies give larger features. The `octaves`, `lacunarity` and
+/// `persistence` only apply to the fractal generators.
+#[derive(Clone, Debug)]
+pub struct NoiseParams {
+    generator: Generator,
+    seed: u32,
+    frequency: f64,
+    octaves: usize,
+    lacunarity: f64,
+    persistence: f64,
+    threshold: f64,
+    color: Rgba,
+}
+
+impl NoiseParams {
+    pub fn new() -> NoiseParams {
+        NoiseParams {
+            generator: Generator::Fbm,
+            seed: 0,
+            frequency: 1.0 / 16.0,
+            octaves: 6,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            threshold: 0.0,
+            color: Rgba([128, 128, 128, 255]),
+        }
+    }
+
+    pub fn generator(&mut self, generator: Generator) -> &mut Self {
+        self.generator = generator;
+        self
+    }
+
+    pub fn seed(&mut self, seed: u32) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn frequency(&mut self, frequency: f64) -> &mut Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn octaves(&mut self, octaves: usize) -> &mut Self {
+        self.octaves = octaves.clamp(1, Fbm::<Perlin>::MAX_OCTAVES);
+        self
+    }
+
+    pub fn lacunarity(&mut self, lacunarity: f64) -> &mut Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    pub fn persistence(&mut self, persistence: f64) -> &mut Self {
+        self.persistence = persistence;
+        self
+    }
+
+    /// Set the noise value above which `fill_noise` fills a voxel.
+    ///
+    /// Noise values lie roughly between -1.0 and 1.0, so a threshold of 0.0
+    /// fills about half of the buffer.
+    pub fn threshold(&mut self, threshold: f64) -> &mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the `color` of filled voxels.
+    pub fn color(&mut self, color: Rgba) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    /// Create a function sampling the noise at voxel coordinates.
+    ///
+    /// The same seed always gives the same noise.
+    pub fn sampler(&self) -> impl Fn(f64, f64, f64) -> f64 {
+        let source: Box<dyn NoiseFn<f64, 3>> = match self.generator {
+            Generator::Perlin => Box::new(Perlin::new(self.seed)),
+            Generator::Simplex => Box::new(OpenSimplex::new(self.seed)),
+            Generator::Fbm => Box::new(
+                Fbm::<Perlin>::new(self.seed)
+                    .set_octaves(self.octaves)
+                    .set_lacunarity(self.lacunarity)
+                    .set_persistence(self.persistence),
+            ),
+            Generator::Ridged => Box::new(
+                RidgedMulti::<Perlin>::new(self.seed)
+                    .set_octaves(self.octaves)
+                    .set_lacunarity(self.lacunarity)
+                    .set_persistence(self.persistence),
+            ),
+            Generator::Worley => {
+                Box::new(Worley::new(self.seed).set_return_type(ReturnType::Distance))
+            }
+        };
+        let frequency = self.frequency;
+        move |x, y, z| source.get([x * frequency, y * frequency, z * frequency])
+    }
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fill every voxel of `buf` where the noise is above the threshold of
+/// `params` with it's color, leaving the other voxels unchanged.
+///
+/// # Examples
+///
+/// Carve a cave system out of a block of stone.
+/// ```
+/// # use voxgen::noise::{fill_noise, Generator, NoiseParams};
+/// # use voxgen::voxel_buffer::ArrayVoxelBuffer;
+/// let mut buf = ArrayVoxelBuffer::new(32, 32, 32);
+/// fill_noise(&mut buf, NoiseParams::new().generator(Generator::Simplex).seed(7));
+/// buf.save("test/volumes/caves.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn fill_noise(buf: &mut ArrayVoxelBuffer<Rgba>, params: &NoiseParams) {
+    let sample = params.sampler();
+    let (size_x, size_y, size_z) = buf.dimensions();
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                if sample(x as f64, y as f64, z as f64) > params.threshold {
+                    // SAFETY: (x, y, z) is inside the buffer dimensions.
+                    unsafe { *buf.voxel_unchecked_mut(x, y, z) = params.color };
+                }
+            }
+        }
+    }
+}
+
+/// Fill the columns of `buf` up to heights given by two dimensional noise,
+/// with the color of `params`.
+///
+/// Noise values from -1.0 to 1.0 map to heights from the bottom to the top
+/// of the buffer. The threshold of `params` is ignored.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::noise::{heightmap_from_noise, Generator, NoiseParams};
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+/// let mut buf = ArrayVoxelBuffer::new(64, 64, 24);
+/// let mut params = NoiseParams::new();
+/// params
+///     .generator(Generator::Ridged)
+///     .frequency(1.0 / 32.0)
+///     .color(Rgba([90, 110, 60, 255]));
+/// heightmap_from_noise(&mut buf, &params);
+/// buf.save("test/volumes/terrain.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn heightmap_from_noise(buf: &mut ArrayVoxelBuffer<Rgba>, params: &NoiseParams) {
+    let sample = params.sampler();
+    let (size_x, size_y, size_z) = buf.dimensions();
+    for y in 0..size_y {
+        for x in 0..size_x {
+            let value = (sample(x as f64, y as f64, 0.0) + 1.0) / 2.0;
+            let height = (value.clamp(0.0, 1.0) * size_z as f64).round() as u32;
+            for z in 0..height.min(size_z) {
+                *buf.voxel_mut(x, y, z) = params.color;
+            }
+        }
+    }
+}
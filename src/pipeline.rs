@@ -0,0 +1,390 @@
+use crate::automata::{Automaton, Rule};
+use crate::noise::{fill_noise, heightmap_from_noise, NoiseParams};
+use crate::progress::{self, Progress, ProgressCallback};
+use crate::seed::Seed;
+use crate::terrain::{Colorizer, Heightmap};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A step of a generation recipe, making a buffer from the buffers of the
+/// stages it takes as inputs.
+///
+/// Closures taking the input buffers and a seed are stages too.
+pub trait Stage {
+    /// Run the stage on the buffers of it's `inputs`, with a `seed` for any
+    /// randomness.
+    fn run(
+        &self,
+        inputs: &[&ArrayVoxelBuffer<Rgba>],
+        seed: u64,
+    ) -> io::Result<ArrayVoxelBuffer<Rgba>>;
+}
+
+impl<F> Stage for F
+where
+    F: Fn(&[&ArrayVoxelBuffer<Rgba>], u64) -> io::Result<ArrayVoxelBuffer<Rgba>>,
+{
+    fn run(
+        &self,
+        inputs: &[&ArrayVoxelBuffer<Rgba>],
+        seed: u64,
+    ) -> io::Result<ArrayVoxelBuffer<Rgba>> {
+        self(inputs, seed)
+    }
+}
+
+/// Get the single input of a stage, or an error for any other number of
+/// inputs.
+fn single<'a>(
+    stage: &str,
+    inputs: &[&'a ArrayVoxelBuffer<Rgba>],
+) -> io::Result<&'a ArrayVoxelBuffer<Rgba>> {
+    match inputs {
+        [input] => Ok(input),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} takes 1 input, got {}", stage, inputs.len()),
+        )),
+    }
+}
+
+/// A stage filling a new buffer with three dimensional noise above the
+/// threshold of `params`, as `fill_noise` does.
+#[derive(Clone, Debug)]
+pub struct Noise {
+    pub size: (u32, u32, u32),
+    pub params: NoiseParams,
+}
+
+impl Stage for Noise {
+    fn run(&self, _: &[&ArrayVoxelBuffer<Rgba>], seed: u64) -> io::Result<ArrayVoxelBuffer<Rgba>> {
+        let mut buf = ArrayVoxelBuffer::new(self.size.0, self.size.1, self.size.2);
+        fill_noise(&mut buf, self.params.clone().seed(Seed::new(seed).noise()));
+        Ok(buf)
+    }
+}
+
+/// A stage filling the columns of a new buffer up to heights given by two
+/// dimensional noise, as `heightmap_from_noise` does.
+#[derive(Clone, Debug)]
+pub struct Terrain {
+    pub size: (u32, u32, u32),
+    pub params: NoiseParams,
+}
+
+impl Stage for Terrain {
+    fn run(&self, _: &[&ArrayVoxelBuffer<Rgba>], seed: u64) -> io::Result<ArrayVoxelBuffer<Rgba>> {
+        let mut buf = ArrayVoxelBuffer::new(self.size.0, self.size.1, self.size.2);
+        heightmap_from_noise(&mut buf, self.params.clone().seed(Seed::new(seed).noise()));
+        Ok(buf)
+    }
+}
+
+/// A stage eroding the terrain of it's input with thermal and then
+/// hydraulic erosion, rebuilding it with `Heightmap::to_buffer`.
+#[derive(Clone, Debug)]
+pub struct Erode {
+    pub thermal_iterations: usize,
+    pub talus: f32,
+    pub droplets: usize,
+    pub sea_level: f32,
+}
+
+impl Stage for Erode {
+    fn run(
+        &self,
+        inputs: &[&ArrayVoxelBuffer<Rgba>],
+        seed: u64,
+    ) -> io::Result<ArrayVoxelBuffer<Rgba>> {
+        let input = single("Erode", inputs)?;
+        let mut heightmap = Heightmap::from_buffer(input);
+        heightmap
+            .thermal_erosion(self.thermal_iterations, self.talus)
+            .hydraulic_erosion(self.droplets, seed);
+        Ok(heightmap.to_buffer(input.dimensions().2, self.sea_level))
+    }
+}
+
+/// A stage smoothing the solid voxels of it's input with a cellular
+/// automaton, drawing the survivors with `color`.
+#[derive(Clone, Debug)]
+pub struct Smooth {
+    pub rule: Rule,
+    pub steps: usize,
+    pub color: Rgba,
+}
+
+impl Stage for Smooth {
+    fn run(
+        &self,
+        inputs: &[&ArrayVoxelBuffer<Rgba>],
+        _: u64,
+    ) -> io::Result<ArrayVoxelBuffer<Rgba>> {
+        let input = single("Smooth", inputs)?;
+        let mut automaton = Automaton::from_buffer(input, self.rule.clone());
+        automaton.boundary(true).steps(self.steps);
+        Ok(automaton.to_buffer(self.color))
+    }
+}
+
+/// A stage recoloring a copy of the terrain of it's input with a
+/// `Colorizer`.
+#[derive(Clone, Debug)]
+pub struct Colorize(pub Colorizer);
+
+impl Stage for Colorize {
+    fn run(
+        &self,
+        inputs: &[&ArrayVoxelBuffer<Rgba>],
+        _: u64,
+    ) -> io::Result<ArrayVoxelBuffer<Rgba>> {
+        let mut buf = single("Colorize", inputs)?.clone();
+        self.0.colorize(&mut buf);
+        Ok(buf)
+    }
+}
+
+/// A stage saving it's input as a MagicaVoxel .vox file, passing it on
+/// unchanged.
+#[derive(Clone, Debug)]
+pub struct Save(pub PathBuf);
+
+impl Stage for Save {
+    fn run(
+        &self,
+        inputs: &[&ArrayVoxelBuffer<Rgba>],
+        _: u64,
+    ) -> io::Result<ArrayVoxelBuffer<Rgba>> {
+        let input = single("Save", inputs)?;
+        input.save(&self.0)?;
+        Ok(input.clone())
+    }
+}
+
+/// A handle to a stage added to a `Pipeline`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+struct Node {
+    name: String,
+    stage: Box<dyn Stage>,
+    inputs: Vec<NodeId>,
+}
+
+/// A graph of generation stages, each taking the buffers of earlier stages
+/// as inputs, making complex recipes declarative and re-runnable.
+///
+/// Buffers are cached, so asking for the output of a stage only runs the
+/// stages it depends on that haven't run yet. Replacing a stage or changing
+/// the seed clears the cached buffers that depend on it.
+///
+/// Every stage gets it's own seed, mixed from the pipeline seed and the
+/// stage name, so adding stages doesn't change the seeds of the others.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::noise::{Generator, NoiseParams};
+/// # use voxgen::pipeline::{Colorize, Erode, Pipeline, Save, Terrain};
+/// # use voxgen::terrain::Colorizer;
+/// let mut pipeline = Pipeline::new();
+/// let mut params = NoiseParams::new();
+/// params.generator(Generator::Fbm).frequency(1.0 / 32.0);
+/// let terrain = pipeline.add("terrain", Terrain { size: (64, 64, 24), params }, &[]);
+/// let eroded = pipeline.add(
+///     "erode",
+///     Erode { thermal_iterations: 10, talus: 0.03, droplets: 5000, sea_level: 0.3 },
+///     &[terrain],
+/// );
+/// let colored = pipeline.add("colorize", Colorize(Colorizer::new()), &[eroded]);
+/// let saved = pipeline.add("save", Save("test/volumes/pipeline.vox".into()), &[colored]);
+/// pipeline.seed(3).run(saved)?;
+/// // Only the stages after the terrain run again.
+/// let mut colorizer = Colorizer::new();
+/// colorizer.levels(4);
+/// pipeline.replace(colored, Colorize(colorizer));
+/// assert!(pipeline.cached(eroded).is_some() && pipeline.cached(saved).is_none());
+/// pipeline.run(saved)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Pipeline {
+    nodes: Vec<Node>,
+    cache: Vec<Option<ArrayVoxelBuffer<Rgba>>>,
+    seed: u64,
+    progress: Option<ProgressCallback>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline {
+            nodes: Vec::new(),
+            cache: Vec::new(),
+            seed: 0,
+            progress: None,
+        }
+    }
+
+    /// Set the `seed` the seeds of the stages are mixed from, clearing all
+    /// cached buffers if it changes.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        if seed != self.seed {
+            self.seed = seed;
+            self.cache.iter_mut().for_each(|buf| *buf = None);
+        }
+        self
+    }
+
+    /// Report every stage run to `callback` as `Stage::Generation`, out of
+    /// the stages a run needs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::noise::NoiseParams;
+    /// # use voxgen::pipeline::{Colorize, Noise, Pipeline};
+    /// # use voxgen::terrain::Colorizer;
+    /// # use std::sync::{Arc, Mutex};
+    /// let fractions = Arc::new(Mutex::new(Vec::new()));
+    /// let log = fractions.clone();
+    /// let mut pipeline = Pipeline::new();
+    /// pipeline.progress(move |progress| log.lock().unwrap().push(progress.fraction()));
+    /// let params = NoiseParams::new();
+    /// let noise = pipeline.add("noise", Noise { size: (8, 8, 8), params }, &[]);
+    /// let colored = pipeline.add("colorize", Colorize(Colorizer::new()), &[noise]);
+    /// pipeline.run(colored)?;
+    /// // The noise is cached, so only the colorize stage runs again.
+    /// pipeline.invalidate(colored).run(colored)?;
+    /// assert_eq!(*fractions.lock().unwrap(), [Some(0.5), Some(1.0), Some(1.0)]);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn progress<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Add a `stage` taking the buffers of `inputs` as it's inputs,
+    /// returning a handle to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an input isn't a stage of this pipeline.
+    pub fn add<S>(&mut self, name: &str, stage: S, inputs: &[NodeId]) -> NodeId
+    where
+        S: Stage + 'static,
+    {
+        assert!(
+            inputs.iter().all(|input| input.0 < self.nodes.len()),
+            "Pipeline input {:?} out of bounds {}",
+            inputs,
+            self.nodes.len()
+        );
+        self.nodes.push(Node {
+            name: name.to_string(),
+            stage: Box::new(stage),
+            inputs: inputs.to_vec(),
+        });
+        self.cache.push(None);
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Replace the stage of `id`, clearing the cached buffers of it and all
+    /// stages depending on it.
+    pub fn replace<S>(&mut self, id: NodeId, stage: S) -> &mut Self
+    where
+        S: Stage + 'static,
+    {
+        self.nodes[id.0].stage = Box::new(stage);
+        self.invalidate(id)
+    }
+
+    /// Clear the cached buffers of `id` and all stages depending on it, so
+    /// they run again next time.
+    pub fn invalidate(&mut self, id: NodeId) -> &mut Self {
+        let mut stale = vec![false; self.nodes.len()];
+        stale[id.0] = true;
+        // Inputs always come before the stages taking them.
+        for i in id.0..self.nodes.len() {
+            stale[i] |= self.nodes[i].inputs.iter().any(|input| stale[input.0]);
+            if stale[i] {
+                self.cache[i] = None;
+            }
+        }
+        self
+    }
+
+    /// Get the name of the stage of `id`.
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.nodes[id.0].name
+    }
+
+    /// Get the seed the stage of `id` runs with, derived from the pipeline
+    /// seed with the name of the stage like `Seed::derive`.
+    pub fn stage_seed(&self, id: NodeId) -> u64 {
+        Seed::new(self.seed).derive(&self.nodes[id.0].name).value()
+    }
+
+    /// Get the cached buffer of `id`, if it has run since it was last
+    /// cleared.
+    pub fn cached(&self, id: NodeId) -> Option<&ArrayVoxelBuffer<Rgba>> {
+        self.cache[id.0].as_ref()
+    }
+
+    /// Run the stage of `id` and the stages it depends on, skipping those
+    /// with cached buffers, and get it's buffer.
+    pub fn run(&mut self, id: NodeId) -> io::Result<&ArrayVoxelBuffer<Rgba>> {
+        let mut needed = vec![false; id.0 + 1];
+        needed[id.0] = true;
+        for i in (0..=id.0).rev() {
+            if needed[i] && self.cache[i].is_none() {
+                for input in &self.nodes[i].inputs {
+                    needed[input.0] = true;
+                }
+            }
+        }
+        let total = (0..=id.0)
+            .filter(|&i| needed[i] && self.cache[i].is_none())
+            .count();
+        let mut done = 0;
+        for (i, needed) in needed.into_iter().enumerate() {
+            if !needed || self.cache[i].is_some() {
+                continue;
+            }
+            let node = &self.nodes[i];
+            let inputs: Vec<&ArrayVoxelBuffer<Rgba>> = node
+                .inputs
+                .iter()
+                .map(|input| self.cache[input.0].as_ref().unwrap())
+                .collect();
+            let buf = node.stage.run(&inputs, self.stage_seed(NodeId(i)))?;
+            self.cache[i] = Some(buf);
+            done += 1;
+            if let Some(progress) = &self.progress {
+                progress(Progress {
+                    stage: progress::Stage::Generation,
+                    done,
+                    total: Some(total),
+                });
+            }
+        }
+        Ok(self.cache[id.0].as_ref().unwrap())
+    }
+
+    /// Run every stage, skipping those with cached buffers.
+    pub fn run_all(&mut self) -> io::Result<()> {
+        for i in 0..self.nodes.len() {
+            self.run(NodeId(i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
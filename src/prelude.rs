@@ -0,0 +1,7 @@
+pub use crate::l_system::{LSystem, RenderOptions};
+#[cfg(feature = "std")]
+pub use crate::preview::Preview;
+pub use crate::seed::Seed;
+pub use crate::turtle_graphics::TurtleGraphics;
+pub use crate::voxel_buffer::{ArrayVoxelBuffer, BufferBuilder, Rgba, VoxelBuffer, VoxelSource};
+pub use crate::Error;
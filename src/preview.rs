@@ -0,0 +1,300 @@
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use image::RgbaImage;
+use rayon::prelude::*;
+use std::f32::consts::{FRAC_PI_4, FRAC_PI_6};
+use std::io;
+use std::path::Path;
+
+/// How a preview camera projects the buffer onto the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Parallel rays, keeping voxels the same size at any distance, like
+    /// isometric art.
+    Orthographic,
+    /// Rays spreading from the camera over a vertical field of view of
+    /// `fov` radians.
+    Perspective { fov: f32 },
+}
+
+/// A software raycaster rendering buffers to images from an orbiting
+/// camera, for thumbnails, turntables and comparing generator outputs
+/// without a viewer.
+///
+/// The camera orbits the middle of the buffer looking at it, and is
+/// framed so the whole buffer fits the image at a zoom of 1.0. Every pixel
+/// casts a ray through the voxel grid and takes the color of the first
+/// visible voxel it hits, shaded by how squarely the face it hit faces the
+/// light. Rays hitting nothing take the background color.
+///
+/// # Examples
+///
+/// Render a turntable of a tree, one frame every 45 degrees.
+/// ```
+/// # use voxgen::preview::{Preview, Projection};
+/// # use voxgen::l_system::{presets, RenderOptions};
+/// let buf = RenderOptions::new()
+///     .derivation_length(3)
+///     .step_size(1.0)
+///     .auto_fit(true)
+///     .render(&presets::plant_a())?;
+/// let mut preview = Preview::new();
+/// preview.size(96, 96).projection(Projection::Perspective { fov: 0.7 });
+/// for frame in 0..8 {
+///     let image = preview.orbit(frame as f32 * std::f32::consts::FRAC_PI_4, 0.4).render(&buf);
+///     assert_eq!(image.dimensions(), (96, 96));
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Preview {
+    width: u32,
+    height: u32,
+    azimuth: f32,
+    elevation: f32,
+    zoom: f32,
+    projection: Projection,
+    light: [f32; 3],
+    ambient: f32,
+    background: Rgba,
+}
+
+impl Preview {
+    /// Create a 256 by 256 orthographic preview, with the camera 45 degrees
+    /// around from +x towards +y and 30 degrees up.
+    pub fn new() -> Preview {
+        let mut preview = Preview {
+            width: 256,
+            height: 256,
+            azimuth: FRAC_PI_4,
+            elevation: FRAC_PI_6,
+            zoom: 1.0,
+            projection: Projection::Orthographic,
+            light: [0.0; 3],
+            ambient: 0.35,
+            background: Rgba([0, 0, 0, 0]),
+        };
+        preview.light(FRAC_PI_4 + 0.6, 1.0);
+        preview
+    }
+
+    /// Set the image `width` and `height` in pixels.
+    pub fn size(&mut self, width: u32, height: u32) -> &mut Self {
+        self.width = width.max(1);
+        self.height = height.max(1);
+        self
+    }
+
+    /// Place the camera `azimuth` radians around the buffer from +x towards
+    /// +y and `elevation` radians up from the horizon.
+    pub fn orbit(&mut self, azimuth: f32, elevation: f32) -> &mut Self {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.001;
+        self.azimuth = azimuth;
+        self.elevation = elevation.clamp(-limit, limit);
+        self
+    }
+
+    /// Set the `zoom`, where 1.0 fits the whole buffer in the image and
+    /// larger zooms magnify it.
+    pub fn zoom(&mut self, zoom: f32) -> &mut Self {
+        self.zoom = zoom.max(f32::EPSILON);
+        self
+    }
+
+    /// Set the camera `projection`.
+    pub fn projection(&mut self, projection: Projection) -> &mut Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Shine the light from `azimuth` radians around from +x towards +y and
+    /// `elevation` radians up from the horizon.
+    pub fn light(&mut self, azimuth: f32, elevation: f32) -> &mut Self {
+        self.light = direction(azimuth, elevation);
+        self
+    }
+
+    /// Set the brightness of faces turned away from the light, from 0.0 for
+    /// black to 1.0 for unshaded.
+    pub fn ambient(&mut self, ambient: f32) -> &mut Self {
+        self.ambient = ambient.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the color of pixels whose rays hit nothing.
+    pub fn background(&mut self, color: Rgba) -> &mut Self {
+        self.background = color;
+        self
+    }
+
+    /// Render `buf` into a new image.
+    pub fn render(&self, buf: &ArrayVoxelBuffer<Rgba>) -> RgbaImage {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let size = [size_x as f32, size_y as f32, size_z as f32];
+        let center = size.map(|s| s / 2.0);
+        let radius = (size.iter().map(|s| s * s).sum::<f32>()).sqrt() / 2.0;
+        let back = direction(self.azimuth, self.elevation);
+        let forward = back.map(|c| -c);
+        let right = normalize(cross(forward, [0.0, 0.0, 1.0]));
+        let up = cross(right, forward);
+        let aspect = self.width as f32 / self.height as f32;
+        // Half the height of the view at the center of the buffer, fitting
+        // the bounding sphere of the buffer in the shorter side.
+        let half_height = radius / self.zoom / aspect.min(1.0);
+        let mut pixels = vec![0; self.width as usize * self.height as usize * 4];
+        pixels
+            .par_chunks_mut(self.width as usize * 4)
+            .enumerate()
+            .for_each(|(row, line)| {
+                let v = 1.0 - 2.0 * (row as f32 + 0.5) / self.height as f32;
+                for (column, pixel) in line.chunks_exact_mut(4).enumerate() {
+                    let u = 2.0 * (column as f32 + 0.5) / self.width as f32 - 1.0;
+                    let (origin, ray) = match self.projection {
+                        Projection::Orthographic => {
+                            let origin = add(
+                                add(center, scale(back, radius * 2.0)),
+                                add(
+                                    scale(right, u * half_height * aspect),
+                                    scale(up, v * half_height),
+                                ),
+                            );
+                            (origin, forward)
+                        }
+                        Projection::Perspective { fov } => {
+                            let tan = (fov.clamp(0.01, 3.0) / 2.0).tan();
+                            let distance = half_height / tan;
+                            let origin = add(center, scale(back, distance));
+                            let ray = add(
+                                forward,
+                                add(scale(right, u * tan * aspect), scale(up, v * tan)),
+                            );
+                            (origin, normalize(ray))
+                        }
+                    };
+                    let color = match cast(buf, origin, ray) {
+                        None => self.background,
+                        Some((voxel, normal)) => {
+                            let dot: f32 = (0..3).map(|i| normal[i] * self.light[i]).sum();
+                            let factor = self.ambient + (1.0 - self.ambient) * dot.max(0.0);
+                            let mut color = voxel;
+                            for c in &mut color.0[..3] {
+                                *c = (*c as f32 * factor).round() as u8;
+                            }
+                            color.0[3] = 255;
+                            color
+                        }
+                    };
+                    pixel.copy_from_slice(&color.0);
+                }
+            });
+        RgbaImage::from_raw(self.width, self.height, pixels).unwrap()
+    }
+
+    /// Render `buf` and save it as a PNG image to `path`.
+    pub fn save<P>(&self, buf: &ArrayVoxelBuffer<Rgba>, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.render(buf)
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(io::Error::other)
+    }
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cast a ray from `origin` along `ray` through `buf`, returning the first
+/// visible voxel it hits and the normal of the face it entered through.
+fn cast(buf: &ArrayVoxelBuffer<Rgba>, origin: [f32; 3], ray: [f32; 3]) -> Option<(Rgba, [f32; 3])> {
+    let (size_x, size_y, size_z) = buf.dimensions();
+    if size_x == 0 || size_y == 0 || size_z == 0 {
+        return None;
+    }
+    let size = [size_x as f32, size_y as f32, size_z as f32];
+    // Clip the ray to the box of the buffer, noting the axis it enters by.
+    let (mut enter, mut exit, mut axis) = (0.0f32, f32::INFINITY, 0);
+    for i in 0..3 {
+        if ray[i].abs() < f32::EPSILON {
+            if origin[i] < 0.0 || origin[i] > size[i] {
+                return None;
+            }
+            continue;
+        }
+        let (a, b) = ((0.0 - origin[i]) / ray[i], (size[i] - origin[i]) / ray[i]);
+        let (near, far) = (a.min(b), a.max(b));
+        if near > enter {
+            enter = near;
+            axis = i;
+        }
+        exit = exit.min(far);
+    }
+    if enter > exit {
+        return None;
+    }
+    // Step through the voxels the ray crosses, crossing the nearest voxel
+    // boundary every time, like `lighting::Sunlight`.
+    let start = add(origin, scale(ray, enter));
+    let mut cell = [0i64; 3];
+    for i in 0..3 {
+        cell[i] = (start[i].floor() as i64).clamp(0, size[i] as i64 - 1);
+    }
+    let step = ray.map(|c| if c > 0.0 { 1 } else { -1 });
+    let delta = ray.map(|c| 1.0 / c.abs().max(f32::EPSILON));
+    let mut next = [0.0; 3];
+    for i in 0..3 {
+        let boundary = if ray[i] > 0.0 {
+            cell[i] as f32 + 1.0
+        } else {
+            cell[i] as f32
+        };
+        next[i] = enter + (boundary - start[i]) * step[i] as f32 * delta[i];
+    }
+    loop {
+        let voxel = *buf.voxel(cell[0] as u32, cell[1] as u32, cell[2] as u32);
+        if voxel.0[3] != 0 {
+            let mut normal = [0.0; 3];
+            normal[axis] = -step[axis] as f32;
+            return Some((voxel, normal));
+        }
+        axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+        cell[axis] += step[axis];
+        next[axis] += delta[axis];
+        if cell[axis] < 0 || cell[axis] >= size[axis] as i64 {
+            return None;
+        }
+    }
+}
+
+/// Get the unit vector `azimuth` radians around from +x towards +y and
+/// `elevation` radians up.
+pub(crate) fn direction(azimuth: f32, elevation: f32) -> [f32; 3] {
+    [
+        elevation.cos() * azimuth.cos(),
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+    ]
+}
+
+pub(crate) fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub(crate) fn scale(a: [f32; 3], factor: f32) -> [f32; 3] {
+    a.map(|c| c * factor)
+}
+
+pub(crate) fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt();
+    scale(a, 1.0 / length.max(f32::EPSILON))
+}
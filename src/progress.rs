@@ -0,0 +1,40 @@
+use alloc::sync::Arc;
+
+/// A stage of a long generation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Deriving the sentence of an L System, counted in derivation steps.
+    Derivation,
+    /// Drawing into a buffer, counted in modules interpreted or z slices
+    /// filled.
+    Rasterization,
+    /// Running generators, counted in pipeline stages run or chunks
+    /// generated.
+    Generation,
+    /// Saving a buffer, counted in z slices encoded.
+    Export,
+}
+
+/// A progress report of a stage, passed to progress callbacks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The stage in progress.
+    pub stage: Stage,
+    /// The units of work done so far.
+    pub done: usize,
+    /// The units of work in the stage, or `None` if it isn't known ahead,
+    /// like the modules of a lazily derived sentence.
+    pub total: Option<usize>,
+}
+
+impl Progress {
+    /// Get the fraction of the stage done, from 0.0 to 1.0, or `None` if
+    /// the total isn't known.
+    pub fn fraction(&self) -> Option<f32> {
+        self.total
+            .map(|total| (self.done as f32 / total.max(1) as f32).min(1.0))
+    }
+}
+
+/// A progress callback, called from whichever thread made progress.
+pub type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
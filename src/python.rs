@@ -0,0 +1,348 @@
+use crate::l_system::{LSystem, RenderOptions};
+use crate::preview::Preview;
+use crate::turtle_graphics::{InterpretOptions, TurtleGraphics};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use crate::Error;
+use pyo3::exceptions::{PyIOError, PyIndexError, PyMemoryError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> PyErr {
+        let message = error.to_string();
+        match error {
+            Error::Io(e) => e.into(),
+            Error::OutOfBounds { .. } => PyIndexError::new_err(message),
+            Error::Capacity(_) => PyMemoryError::new_err(message),
+            Error::Parse(_) => PyValueError::new_err(message),
+            _ => PyRuntimeError::new_err(message),
+        }
+    }
+}
+
+/// A color as an `(r, g, b, a)` tuple.
+type Color = (u8, u8, u8, u8);
+
+fn rgba((r, g, b, a): Color) -> Rgba {
+    Rgba([r, g, b, a])
+}
+
+/// A buffer of RGBA voxels, indexed `buf[x, y, z]` with colors as
+/// `(r, g, b, a)` tuples.
+#[pyclass(name = "Buffer", module = "voxgen")]
+struct PyBuffer {
+    buf: ArrayVoxelBuffer<Rgba>,
+}
+
+#[pymethods]
+impl PyBuffer {
+    #[new]
+    fn new(size_x: u32, size_y: u32, size_z: u32) -> PyResult<PyBuffer> {
+        let buf = ArrayVoxelBuffer::try_new(size_x, size_y, size_z).map_err(Error::from)?;
+        Ok(PyBuffer { buf })
+    }
+
+    /// Load a MagicaVoxel `.vox` file.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<PyBuffer> {
+        Ok(PyBuffer {
+            buf: ArrayVoxelBuffer::load(path)?,
+        })
+    }
+
+    /// The `(size_x, size_y, size_z)` of the buffer.
+    #[getter]
+    fn dimensions(&self) -> (u32, u32, u32) {
+        self.buf.dimensions()
+    }
+
+    fn __getitem__(&self, position: (u32, u32, u32)) -> PyResult<Color> {
+        let (x, y, z) = position;
+        let Rgba([r, g, b, a]) = *self.buf.try_voxel(x, y, z)?;
+        Ok((r, g, b, a))
+    }
+
+    fn __setitem__(&mut self, position: (u32, u32, u32), color: Color) -> PyResult<()> {
+        let (x, y, z) = position;
+        *self.buf.try_voxel_mut(x, y, z)? = rgba(color);
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        let (size_x, size_y, size_z) = self.buf.dimensions();
+        format!("Buffer({}, {}, {})", size_x, size_y, size_z)
+    }
+
+    /// Get the RGBA bytes of every voxel, x fastest then y then z, for
+    /// `numpy.frombuffer(data, numpy.uint8).reshape(size_z, size_y, size_x, 4)`.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let data: Vec<u8> = self
+            .buf
+            .enumerate()
+            .flat_map(|(_, voxel)| voxel.0)
+            .collect();
+        PyBytes::new(py, &data)
+    }
+
+    /// Save as a MagicaVoxel `.vox` file.
+    fn save(&self, path: &str) -> PyResult<()> {
+        Ok(self.buf.save(path)?)
+    }
+
+    /// Save the slice at height `z` as a PNG image, north up.
+    fn save_slice(&self, path: &str, z: u32) -> PyResult<()> {
+        self.buf
+            .to_image(z)
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    /// Render a shaded preview as a PNG image, with the camera `azimuth`
+    /// radians around from +x towards +y and `elevation` radians up.
+    #[pyo3(signature = (path, width=256, height=256, azimuth=0.785, elevation=0.524, zoom=1.0))]
+    fn save_preview(
+        &self,
+        path: &str,
+        width: u32,
+        height: u32,
+        azimuth: f32,
+        elevation: f32,
+        zoom: f32,
+    ) -> PyResult<()> {
+        Ok(Preview::new()
+            .size(width, height)
+            .orbit(azimuth, elevation)
+            .zoom(zoom)
+            .save(&self.buf, path)?)
+    }
+}
+
+/// A LOGO-style drawing turtle, drawing into a buffer.
+#[pyclass(name = "TurtleGraphics", module = "voxgen")]
+struct PyTurtleGraphics {
+    turtle: TurtleGraphics,
+}
+
+#[pymethods]
+impl PyTurtleGraphics {
+    #[new]
+    fn new(size_x: u32, size_y: u32, size_z: u32) -> PyResult<PyTurtleGraphics> {
+        let turtle = TurtleGraphics::try_new(size_x, size_y, size_z).map_err(Error::from)?;
+        Ok(PyTurtleGraphics { turtle })
+    }
+
+    /// The `(x, y, z)` position of the turtle.
+    #[getter]
+    fn position(&self) -> (i32, i32, i32) {
+        self.turtle.position()
+    }
+
+    fn move_to(&mut self, x: i32, y: i32, z: i32) {
+        self.turtle.move_to(x, y, z);
+    }
+
+    fn draw_to(&mut self, x: i32, y: i32, z: i32) {
+        self.turtle.draw_to(x, y, z);
+    }
+
+    fn step(&mut self, step_size: f32) {
+        self.turtle.step(step_size);
+    }
+
+    fn draw(&mut self, step_size: f32) {
+        self.turtle.draw(step_size);
+    }
+
+    fn left(&mut self, angle: f32) {
+        self.turtle.left(angle);
+    }
+
+    fn right(&mut self, angle: f32) {
+        self.turtle.right(angle);
+    }
+
+    fn pitch_down(&mut self, angle: f32) {
+        self.turtle.pitch_down(angle);
+    }
+
+    fn pitch_up(&mut self, angle: f32) {
+        self.turtle.pitch_up(angle);
+    }
+
+    fn roll_left(&mut self, angle: f32) {
+        self.turtle.roll_left(angle);
+    }
+
+    fn roll_right(&mut self, angle: f32) {
+        self.turtle.roll_right(angle);
+    }
+
+    fn turn_around(&mut self) {
+        self.turtle.turn_around();
+    }
+
+    fn push(&mut self) {
+        self.turtle.push();
+    }
+
+    fn pop(&mut self) {
+        self.turtle.pop();
+    }
+
+    fn color(&mut self, color: Color) {
+        self.turtle.color(rgba(color));
+    }
+
+    fn width(&mut self, width: f32) {
+        self.turtle.width(width);
+    }
+
+    /// Execute turtle commands like `"F+F-F[+F]F"`.
+    #[pyo3(signature = (commands, step_size=2.0, angle_increment=std::f32::consts::FRAC_PI_2))]
+    fn interpret(&mut self, commands: &str, step_size: f32, angle_increment: f32) {
+        let mut options = InterpretOptions::new();
+        options
+            .step_size(step_size)
+            .angle_increment(angle_increment);
+        self.turtle.interpret(commands, &options);
+    }
+
+    /// Get a copy of the drawing.
+    fn buffer(&mut self) -> PyBuffer {
+        PyBuffer {
+            buf: self.turtle.buf().clone(),
+        }
+    }
+}
+
+/// An L System, written with the production syntax of the Rust crate.
+#[pyclass(name = "LSystem", module = "voxgen")]
+struct PyLSystem {
+    l_system: LSystem,
+}
+
+#[pymethods]
+impl PyLSystem {
+    #[new]
+    fn new(name: &str, axiom: &str, productions: Vec<String>) -> PyResult<PyLSystem> {
+        let productions = productions.iter().map(String::as_str).collect();
+        let l_system = LSystem::try_new(name, axiom, productions).map_err(Error::from)?;
+        Ok(PyLSystem { l_system })
+    }
+
+    /// Load an L System definition file.
+    #[staticmethod]
+    fn from_file(path: &str) -> PyResult<PyLSystem> {
+        Ok(PyLSystem {
+            l_system: LSystem::from_file(path)?,
+        })
+    }
+
+    /// Get the sentence after `n` derivation steps.
+    fn sentence(&self, n: u32) -> String {
+        self.l_system.sentence(n).to_string()
+    }
+}
+
+/// Options rendering an L System with it's turtle interpretation, set by
+/// keyword.
+#[pyclass(name = "RenderOptions", module = "voxgen")]
+struct PyRenderOptions {
+    options: RenderOptions,
+}
+
+#[pymethods]
+impl PyRenderOptions {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        derivation_length=None,
+        step_size=None,
+        angle_increment=None,
+        size=None,
+        offset=None,
+        auto_fit=None,
+        rainbow=None,
+        seed=None,
+        voxels_per_unit=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        derivation_length: Option<u32>,
+        step_size: Option<f32>,
+        angle_increment: Option<f32>,
+        size: Option<(u32, u32, u32)>,
+        offset: Option<(f32, f32, f32)>,
+        auto_fit: Option<bool>,
+        rainbow: Option<bool>,
+        seed: Option<u64>,
+        voxels_per_unit: Option<f32>,
+    ) -> PyRenderOptions {
+        let mut options = RenderOptions::new();
+        if let Some(n) = derivation_length {
+            options.derivation_length(n);
+        }
+        if let Some(d) = step_size {
+            options.step_size(d);
+        }
+        if let Some(delta) = angle_increment {
+            options.angle_increment(delta);
+        }
+        if let Some((x, y, z)) = size {
+            options.size_x(x).size_y(y).size_z(z);
+        }
+        if let Some((x, y, z)) = offset {
+            options.offset_x(x).offset_y(y).offset_z(z);
+        }
+        if let Some(auto_fit) = auto_fit {
+            options.auto_fit(auto_fit);
+        }
+        if let Some(rainbow) = rainbow {
+            options.rainbow(rainbow);
+        }
+        if let Some(seed) = seed {
+            options.seed(seed);
+        }
+        if let Some(scale) = voxels_per_unit {
+            options.voxels_per_unit(scale);
+        }
+        PyRenderOptions { options }
+    }
+
+    /// Load render options from the `render` section of an L System
+    /// definition file.
+    #[staticmethod]
+    fn from_file(path: &str) -> PyResult<PyRenderOptions> {
+        Ok(PyRenderOptions {
+            options: RenderOptions::from_file(path)?,
+        })
+    }
+
+    /// Draw modules with `symbol` in `color`.
+    fn symbol_color(&mut self, symbol: char, color: Color) {
+        self.options.symbol_color(symbol, rgba(color));
+    }
+
+    /// Render `l_system` into a new buffer.
+    fn render(&self, py: Python<'_>, l_system: &PyLSystem) -> PyResult<PyBuffer> {
+        let buf = py
+            .detach(|| self.options.render(&l_system.l_system))
+            .map_err(Error::from)?;
+        Ok(PyBuffer { buf })
+    }
+
+    /// Render `l_system` and save it as a MagicaVoxel `.vox` file.
+    fn save(&self, py: Python<'_>, l_system: &PyLSystem, path: &str) -> PyResult<()> {
+        Ok(py.detach(|| self.options.save(&l_system.l_system, path))?)
+    }
+}
+
+/// The `voxgen` Python module.
+#[pymodule]
+fn voxgen(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBuffer>()?;
+    m.add_class::<PyTurtleGraphics>()?;
+    m.add_class::<PyLSystem>()?;
+    m.add_class::<PyRenderOptions>()?;
+    Ok(())
+}
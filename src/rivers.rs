@@ -0,0 +1,467 @@
+use crate::seed::Seed;
+use crate::terrain::WATER;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::seq::SliceRandom;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+const NEIGHBORS: [(i64, i64); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// The drainage of a terrain, where every column drains to one of it's
+/// neighbors until the water reaches the sea or the edge of the terrain.
+///
+/// Directions are found by flooding the terrain up from the outlets, the
+/// columns topped with `WATER` and the columns along the edges, so water
+/// runs across flats and spills out of pits instead of getting stuck in
+/// them.
+#[derive(Clone, Debug)]
+pub struct Drainage {
+    size_x: u32,
+    size_y: u32,
+    tops: Vec<Option<u32>>,
+    downstream: Vec<Option<usize>>,
+    flow: Vec<u32>,
+}
+
+impl Drainage {
+    /// Find the drainage of the terrain in `buf`.
+    pub fn new(buf: &ArrayVoxelBuffer<Rgba>) -> Drainage {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let len = size_x as usize * size_y as usize;
+        let mut tops = Vec::with_capacity(len);
+        let mut heights = Vec::with_capacity(len);
+        let mut outlets = Vec::new();
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let top = (0..size_z).rev().find(|&z| buf.voxel(x, y, z).0[3] != 0);
+                let sea = top.is_some_and(|z| *buf.voxel(x, y, z) == WATER);
+                let edge = x == 0 || y == 0 || x == size_x - 1 || y == size_y - 1;
+                if sea || edge {
+                    outlets.push(tops.len());
+                }
+                tops.push(top.filter(|_| !sea));
+                heights.push(top.map_or(0, |z| z + 1));
+            }
+        }
+        // Flood up from the outlets, lowest first and in order of arrival
+        // among equal heights, pointing every column at the one it was
+        // reached from.
+        let mut downstream = vec![None; len];
+        let mut visited = vec![false; len];
+        let mut order = Vec::with_capacity(len);
+        let mut queue = BinaryHeap::new();
+        for (arrival, &i) in outlets.iter().enumerate() {
+            visited[i] = true;
+            queue.push(Reverse((heights[i], arrival, i)));
+        }
+        let mut arrival = outlets.len();
+        while let Some(Reverse((level, _, i))) = queue.pop() {
+            order.push(i);
+            let (x, y) = ((i % size_x as usize) as i64, (i / size_x as usize) as i64);
+            for (dx, dy) in NEIGHBORS {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= size_x as i64 || ny >= size_y as i64 {
+                    continue;
+                }
+                let j = nx as usize + ny as usize * size_x as usize;
+                if visited[j] {
+                    continue;
+                }
+                visited[j] = true;
+                downstream[j] = Some(i);
+                queue.push(Reverse((heights[j].max(level), arrival, j)));
+                arrival += 1;
+            }
+        }
+        // Every column comes after the one it drains to, so summing in
+        // reverse passes the flow all the way down.
+        let mut flow = vec![1; len];
+        for &i in order.iter().rev() {
+            if let Some(j) = downstream[i] {
+                flow[j] += flow[i];
+            }
+        }
+        Drainage {
+            size_x,
+            size_y,
+            tops,
+            downstream,
+            flow,
+        }
+    }
+
+    /// Get the drainage dimensions as a tuple `(size_x, size_y)`.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.size_x, self.size_y)
+    }
+
+    /// Get the number of columns draining through the column at `(x, y)`,
+    /// including itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside the terrain.
+    pub fn flow(&self, x: u32, y: u32) -> u32 {
+        self.flow[self.index(x, y)]
+    }
+
+    /// Get the column the column at `(x, y)` drains to, or `None` for the
+    /// outlets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside the terrain.
+    pub fn downstream(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        self.downstream[self.index(x, y)].map(|j| self.position(j))
+    }
+
+    /// Check if the column at `(x, y)` is an outlet, at the sea or the edge
+    /// of the terrain.
+    pub fn is_outlet(&self, x: u32, y: u32) -> bool {
+        self.downstream[self.index(x, y)].is_none()
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.size_x && y < self.size_y);
+        x as usize + y as usize * self.size_x as usize
+    }
+
+    fn position(&self, i: usize) -> (u32, u32) {
+        (
+            (i % self.size_x as usize) as u32,
+            (i / self.size_x as usize) as u32,
+        )
+    }
+}
+
+/// A river generator, tracing rivers downhill from sources high up in
+/// terrain and carving water filled channels for them.
+///
+/// Rivers follow the `Drainage` of the terrain to the sea or the edge,
+/// widening as more of the terrain drains into them, and end early where
+/// they join another river. The water surface never rises along a river,
+/// so rivers cut gorges through the rises they spill over.
+///
+/// Rivers are `WATER`, so a `Colorizer` run afterwards leaves them be.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::rivers::Rivers;
+/// # use voxgen::terrain::{Colorizer, DiamondSquare};
+/// let mut buf = DiamondSquare::new().roughness(0.45).seed(6).render(64, 64, 24);
+/// let rivers = Rivers::new().sources(4).seed(6).carve(&mut buf);
+/// assert!(!rivers.is_empty());
+/// Colorizer::new().colorize(&mut buf);
+/// buf.save("test/volumes/rivers.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Rivers {
+    sources: usize,
+    min_source_height: f32,
+    width: f32,
+    depth: u32,
+    seed: u64,
+}
+
+impl Rivers {
+    pub fn new() -> Rivers {
+        Rivers {
+            sources: 3,
+            min_source_height: 0.6,
+            width: 2.0,
+            depth: 1,
+            seed: 0,
+        }
+    }
+
+    /// Set the number of rivers.
+    pub fn sources(&mut self, n: usize) -> &mut Self {
+        self.sources = n;
+        self
+    }
+
+    /// Set the lowest height rivers start at, as a fraction of the height
+    /// of the buffer.
+    pub fn min_source_height(&mut self, height: f32) -> &mut Self {
+        self.min_source_height = height;
+        self
+    }
+
+    /// Set the `width` of rivers at their sources. Rivers are up to three
+    /// times as wide downstream.
+    pub fn width(&mut self, width: f32) -> &mut Self {
+        self.width = width.max(1.0);
+        self
+    }
+
+    /// Set the `depth` of the water below the terrain rivers run over.
+    pub fn depth(&mut self, depth: u32) -> &mut Self {
+        self.depth = depth.max(1);
+        self
+    }
+
+    /// Set the `seed` of the random number generator picking sources.
+    ///
+    /// The same seed always gives the same rivers.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Carve rivers into the terrain in `buf`, returning the columns every
+    /// river runs through from it's source.
+    ///
+    /// Fewer rivers than asked for are returned if the terrain doesn't
+    /// reach the lowest source height often enough.
+    pub fn carve(&self, buf: &mut ArrayVoxelBuffer<Rgba>) -> Vec<Vec<(u32, u32)>> {
+        let mut rng = Seed::new(self.seed).rng();
+        let drainage = Drainage::new(buf);
+        let min_top = (self.min_source_height * buf.dimensions().2 as f32).max(0.0) as u32;
+        let candidates: Vec<usize> = (0..drainage.tops.len())
+            .filter(|&i| drainage.downstream[i].is_some())
+            .filter(|&i| drainage.tops[i].is_some_and(|z| z >= min_top))
+            .collect();
+        let mut wet = HashSet::new();
+        let mut rivers = Vec::new();
+        for &source in candidates.choose_multiple(&mut rng, self.sources) {
+            if wet.contains(&source) {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut level = u32::MAX;
+            let mut current = Some(source);
+            while let Some(i) = current {
+                let (x, y) = drainage.position(i);
+                path.push((x, y));
+                let Some(top) = drainage.tops[i] else {
+                    break;
+                };
+                level = level.min(top + 1);
+                let growth = (drainage.flow[i] as f32 / drainage.flow[source] as f32).log2();
+                let radius = self.width / 2.0 * (1.0 + growth / 4.0).min(3.0);
+                self.carve_channel(buf, (x, y), radius, level);
+                if !wet.insert(i) {
+                    // Joined another river.
+                    break;
+                }
+                current = drainage.downstream[i];
+            }
+            rivers.push(path);
+        }
+        rivers
+    }
+
+    /// Carve a disk of channel of `radius` around `center`, with the water
+    /// surface at `level`, or just above the terrain of columns lower than
+    /// it.
+    fn carve_channel(
+        &self,
+        buf: &mut ArrayVoxelBuffer<Rgba>,
+        center: (u32, u32),
+        radius: f32,
+        level: u32,
+    ) {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let reach = radius.ceil() as i64;
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                if ((dx * dx + dy * dy) as f32) > radius * radius {
+                    continue;
+                }
+                let (x, y) = (center.0 as i64 + dx, center.1 as i64 + dy);
+                if x < 0 || y < 0 || x >= size_x as i64 || y >= size_y as i64 {
+                    continue;
+                }
+                let (x, y) = (x as u32, y as u32);
+                let top = (0..size_z).rev().find(|&z| {
+                    let voxel = *buf.voxel(x, y, z);
+                    voxel.0[3] != 0 && voxel != WATER
+                });
+                let Some(top) = top else {
+                    continue;
+                };
+                let surface = level.min(top + 1);
+                for z in surface.saturating_sub(self.depth)..surface {
+                    *buf.voxel_mut(x, y, z) = WATER;
+                }
+                for z in surface..=top {
+                    *buf.voxel_mut(x, y, z) = Rgba([0, 0, 0, 0]);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Rivers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A settling pass flooding the basins of a terrain, filling every hollow
+/// with water up to where it would spill out, and the lowlands up to the
+/// sea level.
+///
+/// Water levels are found by flooding the terrain up from it's edges, so
+/// every column fills up to the lowest height water would have to rise to
+/// on it's way to the edge. Basins spilling out at the sea level or below
+/// it join the sea, and the others become lakes, filled when they're at
+/// least the minimum depth. Water only spreads between columns sharing a
+/// face, so lakes never leak through diagonal gaps.
+///
+/// Existing `WATER` is flooded over like air, so the pass can run again
+/// after the terrain changes.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::rivers::Lakes;
+/// # use voxgen::terrain::{Colorizer, DiamondSquare};
+/// let mut buf = DiamondSquare::new().roughness(0.6).seed(4).render(64, 64, 24);
+/// let lakes = Lakes::new().sea_level(0.3).min_depth(2).fill(&mut buf);
+/// assert!(lakes > 0);
+/// Colorizer::new().colorize(&mut buf);
+/// buf.save("test/volumes/lakes.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Lakes {
+    sea_level: f32,
+    min_depth: u32,
+    color: Rgba,
+}
+
+impl Lakes {
+    /// Create a pass filling every lake, without a sea.
+    pub fn new() -> Lakes {
+        Lakes {
+            sea_level: 0.0,
+            min_depth: 1,
+            color: WATER,
+        }
+    }
+
+    /// Set the `sea_level`, as a fraction of the height of the buffer.
+    pub fn sea_level(&mut self, sea_level: f32) -> &mut Self {
+        self.sea_level = sea_level.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the depth in voxels shallower lakes are left dry at.
+    pub fn min_depth(&mut self, depth: u32) -> &mut Self {
+        self.min_depth = depth.max(1);
+        self
+    }
+
+    /// Set the `color` of the water of lakes. The sea is always `WATER`.
+    pub fn color(&mut self, color: Rgba) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    /// Flood the basins of the terrain in `buf`, returning the number of
+    /// lakes filled, not counting the sea.
+    pub fn fill(&self, buf: &mut ArrayVoxelBuffer<Rgba>) -> usize {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let (width, len) = (size_x as usize, size_x as usize * size_y as usize);
+        let sea = (self.sea_level * size_z as f32).round() as u32;
+        let mut heights = Vec::with_capacity(len);
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let top = (0..size_z).rev().find(|&z| {
+                    let voxel = *buf.voxel(x, y, z);
+                    voxel.0[3] != 0 && voxel != WATER && voxel != self.color
+                });
+                heights.push(top.map_or(0, |z| z + 1));
+            }
+        }
+        let neighbors = |i: usize| {
+            let (x, y) = (i % width, i / width);
+            [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then_some(i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < size_y as usize).then_some(i + width),
+            ]
+            .into_iter()
+            .flatten()
+        };
+        // Flood up from the edges, lowest first, raising every column to
+        // the level it was reached at.
+        let mut levels = vec![0; len];
+        let mut visited = vec![false; len];
+        let mut queue = BinaryHeap::new();
+        for i in 0..len {
+            let (x, y) = (i % width, i / width);
+            if x == 0 || y == 0 || x == width - 1 || y == size_y as usize - 1 {
+                visited[i] = true;
+                levels[i] = heights[i].max(sea);
+                queue.push(Reverse((levels[i], i)));
+            }
+        }
+        while let Some(Reverse((level, i))) = queue.pop() {
+            for j in neighbors(i) {
+                if !visited[j] {
+                    visited[j] = true;
+                    levels[j] = heights[j].max(level);
+                    queue.push(Reverse((levels[j], j)));
+                }
+            }
+        }
+        // Group the flooded columns into bodies of water of one level, and
+        // fill the sea and the lakes deep enough.
+        let mut grouped = vec![false; len];
+        let mut lakes = 0;
+        for start in 0..len {
+            if grouped[start] || levels[start] <= heights[start] {
+                continue;
+            }
+            let level = levels[start];
+            let mut body = vec![start];
+            grouped[start] = true;
+            let mut next = 0;
+            while next < body.len() {
+                for j in neighbors(body[next]) {
+                    if !grouped[j] && levels[j] == level && heights[j] < level {
+                        grouped[j] = true;
+                        body.push(j);
+                    }
+                }
+                next += 1;
+            }
+            let depth = level - body.iter().map(|&i| heights[i]).min().unwrap();
+            let color = if level <= sea {
+                WATER
+            } else if depth >= self.min_depth {
+                lakes += 1;
+                self.color
+            } else {
+                continue;
+            };
+            for i in body {
+                let (x, y) = ((i % width) as u32, (i / width) as u32);
+                for z in heights[i]..level.min(size_z) {
+                    *buf.voxel_mut(x, y, z) = color;
+                }
+            }
+        }
+        lakes
+    }
+}
+
+impl Default for Lakes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,215 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+
+/// The horizontal directions voxels slide and flow in.
+const SIDES: [[i64; 2]; 4] = [[1, 0], [-1, 0], [0, 1], [0, -1]];
+
+/// How a voxel color moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Material {
+    /// Falls, sinks through liquids and slides down slopes, piling up at 45
+    /// degrees.
+    Sand,
+    /// Falls and slides down slopes like sand, and flows sideways towards
+    /// nearby drops until it finds a level.
+    Water,
+}
+
+/// A falling sand physics simulation, moving voxels of some colors with
+/// gravity to settle generated debris into natural piles and pools.
+///
+/// Voxels of colors given a material move, and all other voxels are
+/// static. Gravity pulls towards -z, the bottom of the buffer is a floor,
+/// and voxels can't leave the buffer. Every step moves every voxel at most
+/// one voxel, to the first place it can go of straight down, diagonally
+/// down to the side, and for water, to the side towards a drop within it's
+/// spread. Sides are tried in a random order every time, so piles spread
+/// evenly.
+///
+/// # Examples
+///
+/// Drop a block of sand and a block of water into a walled yard.
+/// ```
+/// # use voxgen::sand::FallingSand;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let (wall, sand, water) = (
+///     Rgba([120, 110, 100, 255]),
+///     Rgba([220, 200, 140, 255]),
+///     Rgba([60, 110, 200, 255]),
+/// );
+/// let mut buf = ArrayVoxelBuffer::new(32, 32, 24);
+/// for z in 0..24 {
+///     for y in 0..32 {
+///         for x in 0..32 {
+///             let color = if x == 0 || y == 0 || x == 31 || y == 31 {
+///                 if z < 6 { Some(wall) } else { None }
+///             } else if (8..14).contains(&x) && (8..14).contains(&y) {
+///                 Some(sand)
+///             } else if (18..26).contains(&x) && (18..26).contains(&y) && z < 12 {
+///                 Some(water)
+///             } else {
+///                 None
+///             };
+///             if let Some(color) = color {
+///                 *buf.voxel_mut(x, y, z) = color;
+///             }
+///         }
+///     }
+/// }
+/// let steps = FallingSand::new().sand(sand).water(water).seed(1).run(&mut buf, 500);
+/// assert!(steps < 500);
+/// buf.save("test/volumes/sand.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct FallingSand {
+    materials: HashMap<Rgba, Material>,
+    spread: u32,
+    seed: u64,
+}
+
+impl FallingSand {
+    /// Create a simulation where every voxel is static.
+    pub fn new() -> FallingSand {
+        FallingSand {
+            materials: HashMap::new(),
+            spread: 8,
+            seed: 0,
+        }
+    }
+
+    /// Make voxels of `color` behave as `material`.
+    pub fn material(&mut self, color: Rgba, material: Material) -> &mut Self {
+        self.materials.insert(color, material);
+        self
+    }
+
+    /// Make voxels of `color` behave as sand.
+    pub fn sand(&mut self, color: Rgba) -> &mut Self {
+        self.material(color, Material::Sand)
+    }
+
+    /// Make voxels of `color` behave as water.
+    pub fn water(&mut self, color: Rgba) -> &mut Self {
+        self.material(color, Material::Water)
+    }
+
+    /// Set how many voxels to the side water looks for a drop to flow
+    /// towards, where larger spreads level pools further.
+    pub fn spread(&mut self, spread: u32) -> &mut Self {
+        self.spread = spread;
+        self
+    }
+
+    /// Set the `seed` of the random number generator picking the sides
+    /// voxels move to.
+    ///
+    /// The same seed always settles a buffer the same way.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Run the simulation over `buf` for up to `steps` steps, stopping
+    /// early once nothing moves, and return the number of steps that moved
+    /// voxels.
+    pub fn run(&self, buf: &mut ArrayVoxelBuffer<Rgba>, steps: usize) -> usize {
+        let mut rng = Seed::new(self.seed).rng();
+        for step in 0..steps {
+            if !self.step(buf, &mut rng) {
+                return step;
+            }
+        }
+        steps
+    }
+
+    /// Move every voxel of `buf` at most once, returning whether any moved.
+    fn step(&self, buf: &mut ArrayVoxelBuffer<Rgba>, rng: &mut ChaCha8Rng) -> bool {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let index =
+            |p: [i64; 3]| (p[0] + p[1] * size_x as i64 + p[2] * (size_x * size_y) as i64) as usize;
+        let mut moved = vec![false; (size_x * size_y * size_z) as usize];
+        let mut any = false;
+        // Whether the voxel at `p` is in the buffer and `material` can move
+        // into it.
+        let open = |buf: &ArrayVoxelBuffer<Rgba>, p: [i64; 3], material: Material| {
+            let inside = p[0] >= 0
+                && p[1] >= 0
+                && p[2] >= 0
+                && p[0] < size_x as i64
+                && p[1] < size_y as i64
+                && p[2] < size_z as i64;
+            if !inside {
+                return false;
+            }
+            let voxel = buf.voxel(p[0] as u32, p[1] as u32, p[2] as u32);
+            voxel.0[3] == 0
+                || (material == Material::Sand
+                    && self.materials.get(voxel) == Some(&Material::Water))
+        };
+        let mut sides = SIDES;
+        // Going up from the bottom lets whole columns fall together.
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let p = [x as i64, y as i64, z as i64];
+                    if moved[index(p)] {
+                        continue;
+                    }
+                    let Some(&material) = self.materials.get(buf.voxel(x, y, z)) else {
+                        continue;
+                    };
+                    sides.shuffle(rng);
+                    let below = [p[0], p[1], p[2] - 1];
+                    let mut target = open(buf, below, material).then_some(below);
+                    for [dx, dy] in sides {
+                        if target.is_some() {
+                            break;
+                        }
+                        let side = [p[0] + dx, p[1] + dy, p[2]];
+                        let diagonal = [side[0], side[1], p[2] - 1];
+                        if open(buf, side, material) && open(buf, diagonal, material) {
+                            target = Some(diagonal);
+                        }
+                    }
+                    if material == Material::Water {
+                        'sides: for [dx, dy] in sides {
+                            if target.is_some() {
+                                break;
+                            }
+                            for k in 1..=self.spread as i64 {
+                                let ahead = [p[0] + dx * k, p[1] + dy * k, p[2]];
+                                if !open(buf, ahead, material) {
+                                    continue 'sides;
+                                }
+                                if open(buf, [ahead[0], ahead[1], p[2] - 1], material) {
+                                    target = Some([p[0] + dx, p[1] + dy, p[2]]);
+                                    continue 'sides;
+                                }
+                            }
+                        }
+                    }
+                    if let Some(q) = target {
+                        let (qx, qy, qz) = (q[0] as u32, q[1] as u32, q[2] as u32);
+                        let displaced = *buf.voxel(qx, qy, qz);
+                        *buf.voxel_mut(qx, qy, qz) = *buf.voxel(x, y, z);
+                        *buf.voxel_mut(x, y, z) = displaced;
+                        moved[index(q)] = true;
+                        moved[index(p)] = true;
+                        any = true;
+                    }
+                }
+            }
+        }
+        any
+    }
+}
+
+impl Default for FallingSand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
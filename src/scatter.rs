@@ -0,0 +1,197 @@
+use crate::seed::{self, Seed};
+use crate::terrain::WATER;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+
+/// A Poisson disk sampler, scattering points randomly with no two closer
+/// than a minimum distance, for placing trees, rocks and props that
+/// shouldn't overlap or clump.
+///
+/// Uses Bridson's algorithm, growing the sample from a random first point
+/// by trying candidates around the points that still have room around
+/// them.
+///
+/// # Examples
+///
+/// Scatter trees over terrain.
+/// ```
+/// # use voxgen::scatter::PoissonDisk;
+/// # use voxgen::terrain::DiamondSquare;
+/// # use voxgen::voxel_buffer::{Rgba, VoxelBuffer};
+/// let mut buf = DiamondSquare::new().seed(1).render(64, 64, 24);
+/// for (x, y, z) in PoissonDisk::new().min_distance(6.0).seed(1).sample_surface(&buf) {
+///     for trunk in z + 1..(z + 4).min(buf.dimensions().2) {
+///         *buf.voxel_mut(x, y, trunk) = Rgba([101, 67, 33, 255]);
+///     }
+/// }
+/// buf.save("test/volumes/scatter.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct PoissonDisk {
+    min_distance: f32,
+    attempts: usize,
+    seed: u64,
+}
+
+impl PoissonDisk {
+    pub fn new() -> PoissonDisk {
+        PoissonDisk {
+            min_distance: 4.0,
+            attempts: 30,
+            seed: 0,
+        }
+    }
+
+    /// Set the smallest `distance` allowed between two points.
+    pub fn min_distance(&mut self, distance: f32) -> &mut Self {
+        self.min_distance = distance.max(f32::EPSILON);
+        self
+    }
+
+    /// Set the number of candidates tried around a point before giving up
+    /// on it.
+    ///
+    /// More attempts pack the points more densely, but take longer.
+    pub fn attempts(&mut self, n: usize) -> &mut Self {
+        self.attempts = n.max(1);
+        self
+    }
+
+    /// Set the `seed` of the random number generator picking the points.
+    ///
+    /// The same seed always gives the same points.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Scatter points over the area from `[0.0, 0.0]` to `[size_x,
+    /// size_y]`.
+    pub fn sample_area(&self, size_x: f32, size_y: f32) -> Vec<[f32; 2]> {
+        self.sample([size_x, size_y])
+    }
+
+    /// Scatter points through the volume from `[0.0, 0.0, 0.0]` to
+    /// `[size_x, size_y, size_z]`.
+    pub fn sample_volume(&self, size_x: f32, size_y: f32, size_z: f32) -> Vec<[f32; 3]> {
+        self.sample([size_x, size_y, size_z])
+    }
+
+    /// Scatter points over the surface of the terrain in `buf`, returning
+    /// the top voxel of the column under every point.
+    ///
+    /// The minimum distance is kept between columns, ignoring height.
+    /// Columns that are empty or topped with `WATER` are skipped.
+    pub fn sample_surface(&self, buf: &ArrayVoxelBuffer<Rgba>) -> Vec<(u32, u32, u32)> {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let mut surface = Vec::new();
+        for [x, y] in self.sample_area(size_x as f32, size_y as f32) {
+            let (x, y) = (x as u32, y as u32);
+            let top = (0..size_z).rev().find(|&z| buf.voxel(x, y, z).0[3] != 0);
+            if let Some(z) = top {
+                if *buf.voxel(x, y, z) != WATER && !surface.contains(&(x, y, z)) {
+                    surface.push((x, y, z));
+                }
+            }
+        }
+        surface
+    }
+
+    fn sample<const N: usize>(&self, size: [f32; N]) -> Vec<[f32; N]> {
+        if size.iter().any(|&s| s <= 0.0) {
+            return Vec::new();
+        }
+        let mut rng = Seed::new(self.seed).rng();
+        let r = self.min_distance;
+        // Cells small enough to hold at most one point each.
+        let cell = r / (N as f32).sqrt();
+        let cells: [usize; N] = size.map(|s| (s / cell).ceil() as usize);
+        let mut grid: Vec<Option<usize>> = vec![None; cells.iter().product()];
+        let grid_index = |p: &[f32; N]| {
+            (0..N).rev().fold(0, |index, k| {
+                index * cells[k] + ((p[k] / cell) as usize).min(cells[k] - 1)
+            })
+        };
+        let mut points: Vec<[f32; N]> = Vec::new();
+        let mut active = Vec::new();
+        let first = size.map(|s| rng.gen_range(0.0..s));
+        grid[grid_index(&first)] = Some(0);
+        points.push(first);
+        active.push(0);
+        while !active.is_empty() {
+            let a = seed::index(&mut rng, active.len());
+            let center = points[active[a]];
+            let mut found = false;
+            for _ in 0..self.attempts {
+                // A random direction, at a random distance between r and 2r.
+                let direction: [f32; N] = std::array::from_fn(|_| rng.gen_range(-1.0..=1.0));
+                let length = direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+                if !(1e-3..=1.0).contains(&length) {
+                    continue;
+                }
+                let distance = rng.gen_range(r..2.0 * r);
+                let candidate: [f32; N] =
+                    std::array::from_fn(|k| center[k] + direction[k] / length * distance);
+                if (0..N).any(|k| candidate[k] < 0.0 || candidate[k] >= size[k]) {
+                    continue;
+                }
+                if self.is_clear(&candidate, &points, &grid, cells, cell) {
+                    grid[grid_index(&candidate)] = Some(points.len());
+                    active.push(points.len());
+                    points.push(candidate);
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                active.swap_remove(a);
+            }
+        }
+        points
+    }
+
+    /// Check that no point in the cells around `candidate` is closer than
+    /// the minimum distance.
+    fn is_clear<const N: usize>(
+        &self,
+        candidate: &[f32; N],
+        points: &[[f32; N]],
+        grid: &[Option<usize>],
+        cells: [usize; N],
+        cell: f32,
+    ) -> bool {
+        // The minimum distance spans at most two cells in every direction.
+        let home = candidate.map(|c| (c / cell) as i64);
+        for offset in 0..5usize.pow(N as u32) {
+            let mut index = 0;
+            let mut inside = true;
+            let mut rest = offset;
+            for k in (0..N).rev() {
+                let c = home[k] + (rest % 5) as i64 - 2;
+                rest /= 5;
+                if c < 0 || c >= cells[k] as i64 {
+                    inside = false;
+                    break;
+                }
+                index = index * cells[k] + c as usize;
+            }
+            if !inside {
+                continue;
+            }
+            if let Some(i) = grid[index] {
+                let d2: f32 = (0..N).map(|k| (points[i][k] - candidate[k]).powi(2)).sum();
+                if d2 < self.min_distance * self.min_distance {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl Default for PoissonDisk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
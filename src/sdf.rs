@@ -0,0 +1,233 @@
+use crate::progress::{Progress, Stage};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use std::sync::Arc;
+
+/// A point in voxel coordinates.
+pub type Point = [f32; 3];
+
+fn length(p: Point) -> f32 {
+    (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt()
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Point, b: Point) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// A signed distance field, giving the distance from a point to the surface
+/// of a shape, negative inside of it.
+///
+/// Shapes are built from primitives centered on the origin, moved with
+/// transforms and combined with constructive solid geometry.
+///
+/// # Examples
+///
+/// Round a box with a sphere blended into it's top and a hole through it.
+/// ```
+/// # use voxgen::sdf::{rasterize, Sdf};
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+/// let shape = Sdf::cuboid([10.0, 10.0, 6.0])
+///     .smooth_union(&Sdf::sphere(7.0).translate([0.0, 0.0, 6.0]), 3.0)
+///     .difference(&Sdf::capsule([-12.0, 0.0, 0.0], [12.0, 0.0, 0.0], 3.0))
+///     .translate([16.0, 16.0, 8.0]);
+/// let mut buf = ArrayVoxelBuffer::new(32, 32, 24);
+/// rasterize(&shape, &mut buf, |p| Rgba([200, (p[2] * 10.0) as u8, 80, 255]));
+/// buf.save("test/volumes/sdf.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Sdf(Arc<dyn Fn(Point) -> f32 + Send + Sync>);
+
+impl Sdf {
+    /// Create a distance field from a `distance` function.
+    pub fn new<F>(distance: F) -> Sdf
+    where
+        F: Fn(Point) -> f32 + Send + Sync + 'static,
+    {
+        Sdf(Arc::new(distance))
+    }
+
+    /// Get the signed distance from `p` to the surface.
+    pub fn distance(&self, p: Point) -> f32 {
+        (self.0)(p)
+    }
+
+    /// A sphere of `radius`.
+    pub fn sphere(radius: f32) -> Sdf {
+        Sdf::new(move |p| length(p) - radius)
+    }
+
+    /// A box extending `half_extents` from the origin along each axis.
+    pub fn cuboid(half_extents: Point) -> Sdf {
+        Sdf::new(move |p| {
+            let q = [
+                p[0].abs() - half_extents[0],
+                p[1].abs() - half_extents[1],
+                p[2].abs() - half_extents[2],
+            ];
+            let outside = length([q[0].max(0.0), q[1].max(0.0), q[2].max(0.0)]);
+            outside + q[0].max(q[1]).max(q[2]).min(0.0)
+        })
+    }
+
+    /// A torus around the z axis, with a ring of `major_radius` and a tube of
+    /// `minor_radius`.
+    pub fn torus(major_radius: f32, minor_radius: f32) -> Sdf {
+        Sdf::new(move |p| {
+            let ring = (p[0] * p[0] + p[1] * p[1]).sqrt() - major_radius;
+            (ring * ring + p[2] * p[2]).sqrt() - minor_radius
+        })
+    }
+
+    /// A capsule of `radius` around the line segment from `a` to `b`.
+    pub fn capsule(a: Point, b: Point, radius: f32) -> Sdf {
+        Sdf::new(move |p| {
+            let pa = sub(p, a);
+            let ba = sub(b, a);
+            let h = (dot(pa, ba) / dot(ba, ba).max(f32::EPSILON)).clamp(0.0, 1.0);
+            length(sub(pa, [ba[0] * h, ba[1] * h, ba[2] * h])) - radius
+        })
+    }
+
+    /// A cone along the z axis with a base of `radius` at the origin and the
+    /// tip at `height`.
+    pub fn cone(radius: f32, height: f32) -> Sdf {
+        Sdf::new(move |p| {
+            // Work in the plane through the axis and p, as (r, z).
+            let r = (p[0] * p[0] + p[1] * p[1]).sqrt();
+            let z = p[2];
+            let slant = (radius * radius + height * height).sqrt();
+            // Distance to the slanted side, measured along it's normal.
+            let side = (r * height + z * radius - radius * height) / slant;
+            side.max(-z)
+        })
+    }
+
+    /// Move the shape by `offset`.
+    pub fn translate(&self, offset: Point) -> Sdf {
+        let sdf = self.clone();
+        Sdf::new(move |p| sdf.distance(sub(p, offset)))
+    }
+
+    /// Scale the shape uniformly by `factor` around the origin.
+    pub fn scale(&self, factor: f32) -> Sdf {
+        let sdf = self.clone();
+        Sdf::new(move |p| sdf.distance([p[0] / factor, p[1] / factor, p[2] / factor]) * factor)
+    }
+
+    /// Rotate the shape by `angle` radians around the x axis.
+    pub fn rotate_x(&self, angle: f32) -> Sdf {
+        let (sdf, (sin, cos)) = (self.clone(), angle.sin_cos());
+        Sdf::new(move |p| sdf.distance([p[0], cos * p[1] + sin * p[2], cos * p[2] - sin * p[1]]))
+    }
+
+    /// Rotate the shape by `angle` radians around the y axis.
+    pub fn rotate_y(&self, angle: f32) -> Sdf {
+        let (sdf, (sin, cos)) = (self.clone(), angle.sin_cos());
+        Sdf::new(move |p| sdf.distance([cos * p[0] - sin * p[2], p[1], cos * p[2] + sin * p[0]]))
+    }
+
+    /// Rotate the shape by `angle` radians around the z axis.
+    pub fn rotate_z(&self, angle: f32) -> Sdf {
+        let (sdf, (sin, cos)) = (self.clone(), angle.sin_cos());
+        Sdf::new(move |p| sdf.distance([cos * p[0] + sin * p[1], cos * p[1] - sin * p[0], p[2]]))
+    }
+
+    /// Mirror the side of the shape with positive x onto the side with
+    /// negative x, making it symmetric across the yz plane.
+    pub fn mirror_x(&self) -> Sdf {
+        let sdf = self.clone();
+        Sdf::new(move |p| sdf.distance([p[0].abs(), p[1], p[2]]))
+    }
+
+    /// The space inside either shape.
+    pub fn union(&self, other: &Sdf) -> Sdf {
+        let (a, b) = (self.clone(), other.clone());
+        Sdf::new(move |p| a.distance(p).min(b.distance(p)))
+    }
+
+    /// The space inside both shapes.
+    pub fn intersection(&self, other: &Sdf) -> Sdf {
+        let (a, b) = (self.clone(), other.clone());
+        Sdf::new(move |p| a.distance(p).max(b.distance(p)))
+    }
+
+    /// The space inside this shape but not the `other`.
+    pub fn difference(&self, other: &Sdf) -> Sdf {
+        let (a, b) = (self.clone(), other.clone());
+        Sdf::new(move |p| a.distance(p).max(-b.distance(p)))
+    }
+
+    /// The union of both shapes, blended over a distance of about `k`.
+    pub fn smooth_union(&self, other: &Sdf, k: f32) -> Sdf {
+        let (a, b) = (self.clone(), other.clone());
+        Sdf::new(move |p| smooth_min(a.distance(p), b.distance(p), k))
+    }
+
+    /// The intersection of both shapes, blended over a distance of about `k`.
+    pub fn smooth_intersection(&self, other: &Sdf, k: f32) -> Sdf {
+        let (a, b) = (self.clone(), other.clone());
+        Sdf::new(move |p| -smooth_min(-a.distance(p), -b.distance(p), k))
+    }
+
+    /// The difference of both shapes, blended over a distance of about `k`.
+    pub fn smooth_difference(&self, other: &Sdf, k: f32) -> Sdf {
+        let (a, b) = (self.clone(), other.clone());
+        Sdf::new(move |p| -smooth_min(-a.distance(p), b.distance(p), k))
+    }
+}
+
+/// The polynomial smooth minimum of `a` and `b` over a distance of `k`.
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k / 4.0
+}
+
+/// Fill every voxel of `buf` whose center is inside `sdf` with the color
+/// `colorizer` gives for the center.
+///
+/// Voxel `(x, y, z)` has it's center at `[x + 0.5, y + 0.5, z + 0.5]`.
+/// Voxels outside the shape are left unchanged.
+pub fn rasterize<F>(sdf: &Sdf, buf: &mut ArrayVoxelBuffer<Rgba>, colorizer: F)
+where
+    F: Fn(Point) -> Rgba,
+{
+    rasterize_with_progress(sdf, buf, colorizer, |_| ());
+}
+
+/// Fill the voxels of `buf` inside `sdf` like `rasterize`, reporting every
+/// z slice filled to `progress` as `Stage::Rasterization`.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, name = "rasterize"))]
+pub fn rasterize_with_progress<F, P>(
+    sdf: &Sdf,
+    buf: &mut ArrayVoxelBuffer<Rgba>,
+    colorizer: F,
+    progress: P,
+) where
+    F: Fn(Point) -> Rgba,
+    P: Fn(Progress),
+{
+    let (size_x, size_y, size_z) = buf.dimensions();
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let p = [x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
+                if sdf.distance(p) <= 0.0 {
+                    // SAFETY: (x, y, z) is inside the buffer dimensions.
+                    unsafe { *buf.voxel_unchecked_mut(x, y, z) = colorizer(p) };
+                }
+            }
+        }
+        progress(Progress {
+            stage: Stage::Rasterization,
+            done: z as usize + 1,
+            total: Some(size_z as usize),
+        });
+    }
+}
@@ -0,0 +1,318 @@
+use crate::seed::Seed;
+use crate::turtle_graphics::TurtleGraphics;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+use rand::Rng;
+
+type Vector = [f32; 3];
+
+fn distance(a: Vector, b: Vector) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// A node of a growing tree, connected to the node it grew from.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    position: Vector,
+    parent: Option<usize>,
+}
+
+/// A tree generator using the space colonization algorithm of Runions,
+/// Lane and Prusinkiewicz.
+///
+/// Attraction points are scattered in an ellipsoid crown above the root.
+/// On every iteration, each point pulls the nearest branch node within the
+/// influence radius towards it, every pulled node grows a new node one
+/// segment length towards the average direction of it's points, and points
+/// within the kill radius of a node are removed. Branches are drawn with
+/// widths following the pipe model, where the cross section of a branch is
+/// the sum of the cross sections of the branches it carries.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::space_colonization::SpaceColonization;
+/// SpaceColonization::new()
+///     .attraction_points(300)
+///     .seed(2)
+///     .render()
+///     .save("test/volumes/space_colonization.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpaceColonization {
+    attraction_points: usize,
+    crown_height: f32,
+    crown_radius: f32,
+    crown_depth: f32,
+    influence_radius: f32,
+    kill_radius: f32,
+    segment_length: f32,
+    max_iterations: usize,
+    tip_width: f32,
+    pipe_exponent: f32,
+    color: Rgba,
+    leaf_color: Option<Rgba>,
+    leaf_size: f32,
+    seed: u64,
+}
+
+impl SpaceColonization {
+    pub fn new() -> SpaceColonization {
+        SpaceColonization {
+            attraction_points: 400,
+            crown_height: 40.0,
+            crown_radius: 20.0,
+            crown_depth: 15.0,
+            influence_radius: 16.0,
+            kill_radius: 4.0,
+            segment_length: 2.0,
+            max_iterations: 200,
+            tip_width: 1.0,
+            pipe_exponent: 2.5,
+            color: Rgba([101, 67, 33, 255]),
+            leaf_color: Some(Rgba([60, 140, 50, 255])),
+            leaf_size: 3.0,
+            seed: 0,
+        }
+    }
+
+    /// Set the number of attraction points scattered in the crown.
+    pub fn attraction_points(&mut self, n: usize) -> &mut Self {
+        self.attraction_points = n;
+        self
+    }
+
+    /// Set the crown ellipsoid, centered `height` voxels above the root,
+    /// `radius` voxels wide horizontally and `depth` voxels vertically.
+    pub fn crown(&mut self, height: f32, radius: f32, depth: f32) -> &mut Self {
+        self.crown_height = height;
+        self.crown_radius = radius;
+        self.crown_depth = depth;
+        self
+    }
+
+    /// Set the distance within which attraction points pull on nodes.
+    pub fn influence_radius(&mut self, radius: f32) -> &mut Self {
+        self.influence_radius = radius;
+        self
+    }
+
+    /// Set the distance within which a node removes attraction points.
+    pub fn kill_radius(&mut self, radius: f32) -> &mut Self {
+        self.kill_radius = radius;
+        self
+    }
+
+    /// Set the length in voxels of every new branch segment.
+    pub fn segment_length(&mut self, length: f32) -> &mut Self {
+        self.segment_length = length.max(1.0);
+        self
+    }
+
+    pub fn max_iterations(&mut self, n: usize) -> &mut Self {
+        self.max_iterations = n;
+        self
+    }
+
+    /// Set the width of the branch tips, and the exponent of the pipe model
+    /// relating the widths of branches to the branches they carry.
+    ///
+    /// An exponent of 2 keeps the cross section area constant, higher
+    /// exponents give thinner trunks.
+    pub fn thickness(&mut self, tip_width: f32, pipe_exponent: f32) -> &mut Self {
+        self.tip_width = tip_width.max(1.0);
+        self.pipe_exponent = pipe_exponent.max(1.0);
+        self
+    }
+
+    /// Set the `color` of the branches.
+    pub fn color(&mut self, color: Rgba) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the `color` and `size` of the leaves drawn at the branch tips, or
+    /// draw no leaves for a `color` of `None`.
+    pub fn leaves(&mut self, color: Option<Rgba>, size: f32) -> &mut Self {
+        self.leaf_color = color;
+        self.leaf_size = size;
+        self
+    }
+
+    /// Set the `seed` of the random number generator placing the attraction
+    /// points.
+    ///
+    /// The same seed always gives the same tree.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Scatter the attraction points uniformly in the crown, relative to the
+    /// root.
+    fn scatter(&self) -> Vec<Vector> {
+        let mut rng = Seed::new(self.seed).rng();
+        let mut points = Vec::with_capacity(self.attraction_points);
+        while points.len() < self.attraction_points {
+            let p: Vector = [
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+            ];
+            if p.iter().map(|c| c * c).sum::<f32>() <= 1.0 {
+                points.push([
+                    p[0] * self.crown_radius,
+                    p[1] * self.crown_radius,
+                    self.crown_height + p[2] * self.crown_depth,
+                ]);
+            }
+        }
+        points
+    }
+
+    /// Grow the tree, returning it's nodes with parents always before their
+    /// children.
+    fn grow(&self) -> Vec<Node> {
+        let mut points = self.scatter();
+        let mut nodes = vec![Node {
+            position: [0.0, 0.0, 0.0],
+            parent: None,
+        }];
+        for _ in 0..self.max_iterations {
+            if points.is_empty() {
+                break;
+            }
+            // Sum the directions to every point from it's nearest node.
+            let mut pulls: Vec<Option<Vector>> = vec![None; nodes.len()];
+            for point in &points {
+                let nearest = nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, node)| (i, distance(node.position, *point)))
+                    .filter(|(_, d)| *d < self.influence_radius && *d > 0.0)
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                if let Some((i, d)) = nearest {
+                    let p = nodes[i].position;
+                    let pull = pulls[i].get_or_insert([0.0; 3]);
+                    for k in 0..3 {
+                        pull[k] += (point[k] - p[k]) / d;
+                    }
+                }
+            }
+            let grown = nodes.len();
+            if pulls.iter().all(Option::is_none) {
+                // Grow the trunk straight up until it reaches the crown.
+                if grown > 1 && nodes[grown - 1].position[2] > self.crown_height {
+                    break;
+                }
+                pulls[grown - 1] = Some([0.0, 0.0, 1.0]);
+            }
+            for (i, pull) in pulls.into_iter().enumerate() {
+                let pull = match pull {
+                    Some(pull) => pull,
+                    None => continue,
+                };
+                let length = pull.iter().map(|c| c * c).sum::<f32>().sqrt();
+                if length < 1e-6 {
+                    continue;
+                }
+                let p = nodes[i].position;
+                nodes.push(Node {
+                    position: std::array::from_fn(|k| {
+                        p[k] + pull[k] / length * self.segment_length
+                    }),
+                    parent: Some(i),
+                });
+            }
+            let new_nodes = &nodes[grown..];
+            points.retain(|point| {
+                new_nodes
+                    .iter()
+                    .all(|node| distance(node.position, *point) > self.kill_radius)
+            });
+        }
+        nodes
+    }
+
+    /// Grow the tree and draw it into a new buffer fitting it exactly, with
+    /// the root in the bottom layer.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let nodes = self.grow();
+        // Accumulate pipe model cross sections from the tips down.
+        let mut children = vec![0; nodes.len()];
+        let mut sections = vec![0.0f32; nodes.len()];
+        for node in &nodes {
+            if let Some(parent) = node.parent {
+                children[parent] += 1;
+            }
+        }
+        for i in (0..nodes.len()).rev() {
+            if children[i] == 0 {
+                sections[i] = self.tip_width.powf(self.pipe_exponent);
+            }
+            if let Some(parent) = nodes[i].parent {
+                sections[parent] += sections[i];
+            }
+        }
+        let widths: Vec<f32> = sections
+            .iter()
+            .map(|s| s.powf(1.0 / self.pipe_exponent))
+            .collect();
+        let voxel = |p: Vector| p.map(|c| c.round() as i32);
+        let mut turtle = TurtleGraphics::measure();
+        self.draw(&mut turtle, &nodes, &children, &widths, voxel);
+        let (min, max) = turtle.bounds().unwrap_or(((0, 0, 0), (0, 0, 0)));
+        let mut turtle = TurtleGraphics::new(
+            (max.0 - min.0 + 1) as u32,
+            (max.1 - min.1 + 1) as u32,
+            (max.2 - min.2 + 1) as u32,
+        );
+        let offset = |p: Vector| {
+            let [x, y, z] = voxel(p);
+            [x - min.0, y - min.1, z - min.2]
+        };
+        self.draw(&mut turtle, &nodes, &children, &widths, offset);
+        turtle.into_buf()
+    }
+
+    /// Draw every branch segment and leaf with the voxel positions given by
+    /// `voxel`.
+    fn draw<F>(
+        &self,
+        turtle: &mut TurtleGraphics,
+        nodes: &[Node],
+        children: &[usize],
+        widths: &[f32],
+        voxel: F,
+    ) where
+        F: Fn(Vector) -> [i32; 3],
+    {
+        turtle.color(self.color);
+        for (i, node) in nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                let [x, y, z] = voxel(nodes[parent].position);
+                turtle.move_to(x, y, z);
+                turtle.width(widths[i]);
+                let [x, y, z] = voxel(node.position);
+                turtle.draw_to(x, y, z);
+            }
+        }
+        if let Some(leaf_color) = self.leaf_color {
+            turtle.color(leaf_color);
+            turtle.width(self.leaf_size);
+            for (i, node) in nodes.iter().enumerate() {
+                if children[i] == 0 && node.parent.is_some() {
+                    let [x, y, z] = voxel(node.position);
+                    turtle.move_to(x, y, z);
+                    turtle.draw(0.0);
+                }
+            }
+        }
+    }
+}
+
+impl Default for SpaceColonization {
+    fn default() -> Self {
+        Self::new()
+    }
+}
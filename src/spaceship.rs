@@ -0,0 +1,255 @@
+use crate::sdf::{rasterize, Point, Sdf};
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use std::f32::consts::FRAC_PI_2;
+
+/// A spaceship and vehicle generator, building hulls mirrored across their
+/// length from stacked primitives and covering them in greebles.
+///
+/// The hull is a row of boxes and capsules along the y axis blended
+/// together, with a cone for a nose, a cockpit on top of the front, wings
+/// and engines at the back. The seed varies the size and shape of every
+/// part, and where the greebles go. Ships face +y, so the engines are at
+/// the front of MagicaVoxel's view.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::spaceship::Spaceship;
+/// # use voxgen::voxel_buffer::ArrayVoxelBuffer;
+/// let mut fleet = ArrayVoxelBuffer::new(96, 48, 16);
+/// for i in 0..3 {
+///     let ship = Spaceship::new().size(28, 44, 14).greebles(12).seed(i).render();
+///     fleet.stamp(&ship, 2 + 32 * i as i32, 2, 1);
+/// }
+/// fleet.save("test/volumes/spaceships.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Spaceship {
+    width: u32,
+    length: u32,
+    height: u32,
+    segments: u32,
+    wings: bool,
+    greebles: usize,
+    seed: u64,
+    hull_color: Rgba,
+    accent_color: Rgba,
+    cockpit_color: Rgba,
+    engine_color: Rgba,
+}
+
+impl Spaceship {
+    pub fn new() -> Spaceship {
+        Spaceship {
+            width: 24,
+            length: 40,
+            height: 12,
+            segments: 3,
+            wings: true,
+            greebles: 8,
+            seed: 0,
+            hull_color: Rgba([170, 175, 185, 255]),
+            accent_color: Rgba([90, 95, 110, 255]),
+            cockpit_color: Rgba([80, 170, 230, 255]),
+            engine_color: Rgba([255, 140, 40, 255]),
+        }
+    }
+
+    /// Set the `width` along x, `length` along y and `height` along z of the
+    /// buffer the ship fills.
+    pub fn size(&mut self, width: u32, length: u32, height: u32) -> &mut Self {
+        // An even width puts the mirror plane between two voxels.
+        self.width = width.max(8).div_ceil(2) * 2;
+        self.length = length.max(12);
+        self.height = height.max(6);
+        self
+    }
+
+    /// Set the number of primitives stacked along the hull.
+    pub fn segments(&mut self, segments: u32) -> &mut Self {
+        self.segments = segments.max(1);
+        self
+    }
+
+    pub fn wings(&mut self, wings: bool) -> &mut Self {
+        self.wings = wings;
+        self
+    }
+
+    /// Set the number of greeble pairs stamped onto the hull.
+    pub fn greebles(&mut self, n: usize) -> &mut Self {
+        self.greebles = n;
+        self
+    }
+
+    /// Set the `seed` of the random number generator shaping the ship.
+    ///
+    /// The same parameters and seed always give the same ship.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the colors of the `hull`, and of the `accent` on wings and
+    /// greebles.
+    pub fn hull_colors(&mut self, hull: Rgba, accent: Rgba) -> &mut Self {
+        self.hull_color = hull;
+        self.accent_color = accent;
+        self
+    }
+
+    pub fn cockpit_color(&mut self, color: Rgba) -> &mut Self {
+        self.cockpit_color = color;
+        self
+    }
+
+    /// Set the color of the engine exhausts.
+    pub fn engine_color(&mut self, color: Rgba) -> &mut Self {
+        self.engine_color = color;
+        self
+    }
+
+    /// Build the ship into a new buffer of it's size.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let mut rng = Seed::new(self.seed).rng();
+        let (width, length, height) = (self.width as f32, self.length as f32, self.height as f32);
+        let mid_z = height / 2.0;
+        // The hull is built around x = 0, and moved to the middle of the
+        // buffer at the end.
+        let (rear, front) = (0.2 * length, 0.75 * length);
+        let step = (front - rear) / self.segments as f32;
+        let mut hull: Option<Sdf> = None;
+        let mut nose_radius = 0.0;
+        for i in 0..self.segments {
+            let center = [0.0, rear + (i as f32 + 0.5) * step, mid_z];
+            let half_width = rng.gen_range(0.12..0.3) * width;
+            let half_height = rng.gen_range(0.2..0.4) * height;
+            let segment = if rng.gen_bool(0.5) {
+                Sdf::cuboid([half_width, step / 2.0 + 1.0, half_height]).translate(center)
+            } else {
+                let radius = half_width.min(half_height);
+                let a = [0.0, center[1] - step / 2.0, mid_z];
+                let b = [0.0, center[1] + step / 2.0, mid_z];
+                Sdf::capsule(a, b, radius)
+            };
+            nose_radius = half_width.min(half_height);
+            hull = Some(match hull {
+                None => segment,
+                Some(hull) => hull.smooth_union(&segment, 2.0),
+            });
+        }
+        let nose = Sdf::cone(nose_radius, length - 1.0 - front)
+            .rotate_x(-FRAC_PI_2)
+            .translate([0.0, front, mid_z]);
+        let mut hull = hull.unwrap().smooth_union(&nose, 2.0);
+        // The rear of the hull, for engines to sit on.
+        let stern = Sdf::cuboid([
+            rng.gen_range(0.15..0.25) * width,
+            0.1 * length,
+            0.25 * height,
+        ])
+        .translate([0.0, rear, mid_z]);
+        hull = hull.smooth_union(&stern, 2.0);
+        let mut accents: Option<Sdf> = None;
+        if self.wings {
+            let span = rng.gen_range(0.35..0.48) * width;
+            let chord = rng.gen_range(0.12..0.25) * length;
+            let sweep = rng.gen_range(-0.3..0.3);
+            let wing = Sdf::cuboid([span / 2.0, chord / 2.0, 0.75])
+                .rotate_z(sweep)
+                .translate([
+                    span / 2.0,
+                    rear + chord,
+                    mid_z - rng.gen_range(0.0..0.2) * height,
+                ]);
+            let fin = Sdf::cuboid([0.75, chord / 3.0, rng.gen_range(0.1..0.25) * height])
+                .translate([span, rear + chord, mid_z]);
+            accents = Some(wing.union(&fin).mirror_x());
+        }
+        let engine_x = rng.gen_range(0.1..0.2) * width;
+        let engine_radius = rng.gen_range(0.06..0.1) * width.min(height * 2.0);
+        let engine = Sdf::capsule(
+            [engine_x, 1.0 + engine_radius, mid_z],
+            [engine_x, rear + step / 2.0, mid_z],
+            engine_radius.max(1.5),
+        )
+        .mirror_x();
+        let cockpit_center = [
+            0.0,
+            front - rng.gen_range(0.0..0.5) * step,
+            mid_z + nose_radius * 0.6,
+        ];
+        let cockpit =
+            Sdf::sphere(rng.gen_range(0.06..0.1) * width.max(12.0)).translate(cockpit_center);
+        let offset = [width / 2.0, 0.0, 0.0];
+        let mut buf = ArrayVoxelBuffer::new(self.width, self.length, self.height);
+        rasterize(&hull.translate(offset), &mut buf, |_| self.hull_color);
+        if let Some(accents) = accents {
+            rasterize(&accents.translate(offset), &mut buf, |_| self.accent_color);
+        }
+        let exhaust = 1.0 + engine_radius * 0.5;
+        rasterize(&engine.translate(offset), &mut buf, |p: Point| {
+            if p[1] < exhaust {
+                self.engine_color
+            } else {
+                self.accent_color
+            }
+        });
+        rasterize(&cockpit.translate(offset), &mut buf, |_| self.cockpit_color);
+        self.greebles_into(&mut buf, &mut rng);
+        buf
+    }
+
+    /// Stamp pairs of small boxes onto the top and sides of the hull,
+    /// mirrored across it.
+    fn greebles_into(&self, buf: &mut ArrayVoxelBuffer<Rgba>, rng: &mut ChaCha8Rng) {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let solid = |buf: &ArrayVoxelBuffer<Rgba>, x, y, z| *buf.voxel(x, y, z) == self.hull_color;
+        for _ in 0..self.greebles {
+            let (w, d, h) = (
+                rng.gen_range(1..=3),
+                rng.gen_range(1..=4),
+                rng.gen_range(1..=2),
+            );
+            let mut greeble = ArrayVoxelBuffer::new(w, d, h);
+            for z in 0..h {
+                for y in 0..d {
+                    for x in 0..w {
+                        *greeble.voxel_mut(x, y, z) = self.accent_color;
+                    }
+                }
+            }
+            let y = rng.gen_range(0..size_y);
+            let origin = if rng.gen_bool(0.5) {
+                // On top, scanning down a column on the right half.
+                let x = rng.gen_range(size_x / 2..size_x);
+                (0..size_z)
+                    .rev()
+                    .find(|&z| solid(buf, x, y, z))
+                    .map(|z| (x as i32, z as i32 + 1))
+            } else {
+                // On the side, scanning in from the right.
+                let z = rng.gen_range(0..size_z);
+                (size_x / 2..size_x)
+                    .rev()
+                    .find(|&x| solid(buf, x, y, z))
+                    .map(|x| (x as i32 + 1, z as i32))
+            };
+            if let Some((x, z)) = origin {
+                let y = y as i32 - d as i32 / 2;
+                buf.stamp(&greeble, x, y, z);
+                buf.stamp(&greeble, size_x as i32 - x - w as i32, y, z);
+            }
+        }
+    }
+}
+
+impl Default for Spaceship {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,212 @@
+use crate::noise::{Generator, NoiseParams};
+use crate::seed::{self, Seed};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+
+/// The distinct opacities of the fog, keeping under the MagicaVoxel palette
+/// limit.
+const FOG_LEVELS: u32 = 8;
+
+/// The distinct hues of the fog between it's two colors.
+const HUE_LEVELS: u32 = 6;
+
+/// A star field and nebula generator, scattering single voxel stars that
+/// gather in clusters through thin clouds of colored fog, for space
+/// backdrops and skyboxes.
+///
+/// Clustered stars are scattered around a few cluster centers, the others
+/// anywhere in the buffer. The fog is fractal noise thresholded like
+/// `clouds::Clouds`, kept faint and shifting between two colors.
+///
+/// MagicaVoxel lights stars with emissive materials, but saving materials
+/// isn't supported yet, so stars are saved as plain colors. Their voxels
+/// are returned by `star_voxels` for marking them emissive later.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::starfield::StarField;
+/// let field = StarField::new(64, 64, 32);
+/// let stars = field.star_voxels();
+/// assert_eq!(stars.len(), 300);
+/// field.render().save("test/volumes/starfield.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct StarField {
+    size_x: u32,
+    size_y: u32,
+    size_z: u32,
+    stars: usize,
+    clusters: usize,
+    spread: f32,
+    clustering: f64,
+    star_colors: Vec<Rgba>,
+    fog_density: f32,
+    fog_opacity: u8,
+    fog_colors: (Rgba, Rgba),
+    fog_frequency: f64,
+    seed: u64,
+}
+
+impl StarField {
+    /// Create a generator filling a buffer `size_x` by `size_y` by `size_z`.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> StarField {
+        StarField {
+            size_x: size_x.max(1),
+            size_y: size_y.max(1),
+            size_z: size_z.max(1),
+            stars: 300,
+            clusters: 3,
+            spread: 6.0,
+            clustering: 0.5,
+            star_colors: vec![
+                Rgba([255, 255, 255, 255]),
+                Rgba([200, 220, 255, 255]),
+                Rgba([255, 240, 200, 255]),
+                Rgba([255, 190, 140, 255]),
+            ],
+            fog_density: 0.3,
+            fog_opacity: 48,
+            fog_colors: (Rgba([120, 60, 170, 255]), Rgba([40, 140, 170, 255])),
+            fog_frequency: 1.0 / 20.0,
+            seed: 0,
+        }
+    }
+
+    /// Set the number of stars.
+    pub fn stars(&mut self, n: usize) -> &mut Self {
+        self.stars = n;
+        self
+    }
+
+    /// Set the number of star clusters, and the `spread` of the stars
+    /// around their centers in voxels.
+    pub fn clusters(&mut self, n: usize, spread: f32) -> &mut Self {
+        self.clusters = n;
+        self.spread = spread.max(0.0);
+        self
+    }
+
+    /// Set the fraction of stars in clusters, from 0.0 for stars scattered
+    /// evenly to 1.0 for stars only in clusters.
+    pub fn clustering(&mut self, clustering: f64) -> &mut Self {
+        self.clustering = clustering.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the colors stars are picked from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `colors` is empty.
+    pub fn star_colors(&mut self, colors: Vec<Rgba>) -> &mut Self {
+        assert!(!colors.is_empty());
+        self.star_colors = colors;
+        self
+    }
+
+    /// Set how much of the buffer the fog fills, from 0.0 to 1.0, and the
+    /// `opacity` of it's thickest parts.
+    pub fn fog(&mut self, density: f32, opacity: u8) -> &mut Self {
+        self.fog_density = density.clamp(0.0, 1.0);
+        self.fog_opacity = opacity;
+        self
+    }
+
+    /// Set the two colors the fog shifts between.
+    pub fn fog_colors(&mut self, a: Rgba, b: Rgba) -> &mut Self {
+        self.fog_colors = (a, b);
+        self
+    }
+
+    /// Set the frequency of the fog noise, where higher frequencies give
+    /// smaller wisps.
+    pub fn fog_frequency(&mut self, frequency: f64) -> &mut Self {
+        self.fog_frequency = frequency;
+        self
+    }
+
+    /// Set the `seed` of the random number generator scattering the stars,
+    /// and of the fog noise.
+    ///
+    /// The same seed always gives the same star field.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Get the voxels of the stars, with the color of each.
+    ///
+    /// Stars can land on the same voxel, so there can be fewer distinct
+    /// voxels than stars.
+    pub fn star_voxels(&self) -> Vec<((u32, u32, u32), Rgba)> {
+        let mut rng = Seed::new(self.seed).rng();
+        let size = [self.size_x as f32, self.size_y as f32, self.size_z as f32];
+        let centers: Vec<[f32; 3]> = (0..self.clusters)
+            .map(|_| size.map(|s| rng.gen_range(0.0..s)))
+            .collect();
+        let mut stars = Vec::with_capacity(self.stars);
+        while stars.len() < self.stars {
+            let p = if !centers.is_empty() && rng.gen_bool(self.clustering) {
+                let center = centers[seed::index(&mut rng, centers.len())];
+                // Summing uniform offsets crowds stars towards the center.
+                center.map(|c| {
+                    c + (rng.gen_range(-1.0..=1.0) + rng.gen_range(-1.0..=1.0)) * self.spread
+                })
+            } else {
+                size.map(|s| rng.gen_range(0.0..s))
+            };
+            let color = self.star_colors[seed::index(&mut rng, self.star_colors.len())];
+            let inside = p.iter().zip(size).all(|(&c, s)| c >= 0.0 && c < s);
+            if inside {
+                stars.push(((p[0] as u32, p[1] as u32, p[2] as u32), color));
+            }
+        }
+        stars
+    }
+
+    /// Render the fog and stars into a new buffer.
+    pub fn render(&self) -> ArrayVoxelBuffer<Rgba> {
+        let mut buf = ArrayVoxelBuffer::new(self.size_x, self.size_y, self.size_z);
+        let mut params = NoiseParams::new();
+        params
+            .generator(Generator::Fbm)
+            .frequency(self.fog_frequency)
+            .seed(Seed::new(self.seed).noise());
+        let fog = params.sampler();
+        let hue = params
+            .frequency(self.fog_frequency / 2.0)
+            .seed(Seed::new(self.seed).derive("hue").noise())
+            .sampler();
+        // Fractal noise mostly stays within -0.4 to 0.4, so the density
+        // thresholds within that range.
+        let threshold = 0.4 - 0.8 * self.fog_density as f64;
+        let (a, b) = self.fog_colors;
+        for z in 0..self.size_z {
+            for y in 0..self.size_y {
+                for x in 0..self.size_x {
+                    let (fx, fy, fz) = (x as f64, y as f64, z as f64);
+                    let thickness = ((fog(fx, fy, fz) - threshold) / 0.3).min(1.0);
+                    let level = (thickness * FOG_LEVELS as f64).ceil();
+                    if level <= 0.0 {
+                        continue;
+                    }
+                    let t = ((hue(fx, fy, fz) + 0.5).clamp(0.0, 1.0) * (HUE_LEVELS - 1) as f64)
+                        .round()
+                        / (HUE_LEVELS - 1) as f64;
+                    let mut color = Rgba(std::array::from_fn(|c| {
+                        (a.0[c] as f64 + (b.0[c] as f64 - a.0[c] as f64) * t).round() as u8
+                    }));
+                    color.0[3] =
+                        (level / FOG_LEVELS as f64 * self.fog_opacity as f64).round() as u8;
+                    *buf.voxel_mut(x, y, z) = color;
+                }
+            }
+        }
+        for ((x, y, z), color) in self.star_voxels() {
+            *buf.voxel_mut(x, y, z) = color;
+        }
+        buf
+    }
+}
@@ -0,0 +1,817 @@
+use crate::noise::{Generator, NoiseParams};
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use palette::{LinSrgba, Mix, Srgba};
+use rand::Rng;
+
+/// Terrain colors by height, from the bottom of the buffer up: sand, grass,
+/// rock and snow, each starting at the given fraction of the height.
+pub const TERRAIN_BANDS: [(f32, Rgba); 4] = [
+    (0.0, Rgba([194, 178, 128, 255])),
+    (0.35, Rgba([86, 125, 70, 255])),
+    (0.65, Rgba([120, 110, 100, 255])),
+    (0.85, Rgba([240, 240, 245, 255])),
+];
+
+/// The color of water filling terrain up to the sea level.
+pub const WATER: Rgba = Rgba([60, 110, 200, 255]);
+
+/// A grid of terrain heights, from 0.0 at the bottom to 1.0 at the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightmap {
+    size_x: u32,
+    size_y: u32,
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// Create a flat heightmap of `size_x` by `size_y` columns at height 0.
+    pub fn new(size_x: u32, size_y: u32) -> Heightmap {
+        Heightmap {
+            size_x,
+            size_y,
+            heights: vec![0.0; size_x as usize * size_y as usize],
+        }
+    }
+
+    /// Create a heightmap of `size_x` by `size_y` columns from two
+    /// dimensional noise, with noise values from -1.0 to 1.0 mapped to
+    /// heights from 0.0 to 1.0.
+    ///
+    /// Besides terrain, noise heightmaps make good moisture maps for a
+    /// `Colorizer`.
+    pub fn from_noise(size_x: u32, size_y: u32, params: &NoiseParams) -> Heightmap {
+        let sample = params.sampler();
+        let mut heightmap = Heightmap::new(size_x, size_y);
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let value = (sample(x as f64, y as f64, 0.0) + 1.0) / 2.0;
+                heightmap.set_height(x, y, value.clamp(0.0, 1.0) as f32);
+            }
+        }
+        heightmap
+    }
+
+    /// Read the heights of the columns of a terrain `buf`, from the top voxel
+    /// of each column that is neither transparent nor `WATER`.
+    pub fn from_buffer(buf: &ArrayVoxelBuffer<Rgba>) -> Heightmap {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let mut heightmap = Heightmap::new(size_x, size_y);
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let top = (0..size_z).rev().find(|&z| {
+                    let voxel = *buf.voxel(x, y, z);
+                    voxel.0[3] != 0 && voxel != WATER
+                });
+                if let Some(z) = top {
+                    heightmap.set_height(x, y, (z + 1) as f32 / size_z as f32);
+                }
+            }
+        }
+        heightmap
+    }
+
+    /// Get the heightmap dimensions as a tuple `(size_x, size_y)`.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.size_x, self.size_y)
+    }
+
+    /// Get the height of the column at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside the heightmap.
+    pub fn height(&self, x: u32, y: u32) -> f32 {
+        self.heights[self.index(x, y)]
+    }
+
+    /// Set the `height` of the column at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside the heightmap.
+    pub fn set_height(&mut self, x: u32, y: u32, height: f32) -> &mut Self {
+        let i = self.index(x, y);
+        self.heights[i] = height;
+        self
+    }
+
+    /// Rescale the heights to span 0.0 to 1.0 exactly.
+    pub fn normalize(&mut self) -> &mut Self {
+        let min = self.heights.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self
+            .heights
+            .iter()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+        let range = max - min;
+        for height in self.heights.iter_mut() {
+            *height = if range > 0.0 {
+                (*height - min) / range
+            } else {
+                0.0
+            };
+        }
+        self
+    }
+
+    /// Slide material down slopes steeper than `talus` for `iterations`
+    /// passes, as loose rock settles into scree.
+    ///
+    /// The `talus` is the largest stable height difference between
+    /// neighboring columns. Half of the excess over it moves from a column
+    /// to it's lower neighbors on every pass, shared by how much lower they
+    /// are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::terrain::DiamondSquare;
+    /// let mut heightmap = DiamondSquare::new().roughness(0.7).heightmap(64, 64);
+    /// heightmap.thermal_erosion(20, 0.03).hydraulic_erosion(20_000, 0);
+    /// heightmap.to_buffer(24, 0.3).save("test/volumes/eroded.vox")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn thermal_erosion(&mut self, iterations: usize, talus: f32) -> &mut Self {
+        const NEIGHBORS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        for _ in 0..iterations {
+            let mut delta = vec![0.0; self.heights.len()];
+            for y in 0..self.size_y {
+                for x in 0..self.size_x {
+                    let height = self.height(x, y);
+                    let lower: Vec<(usize, f32)> = NEIGHBORS
+                        .iter()
+                        .filter_map(|(dx, dy)| {
+                            let nx = u32::try_from(x as i64 + dx).ok()?;
+                            let ny = u32::try_from(y as i64 + dy).ok()?;
+                            if nx >= self.size_x || ny >= self.size_y {
+                                return None;
+                            }
+                            let difference = height - self.height(nx, ny);
+                            (difference > talus).then(|| (self.index(nx, ny), difference))
+                        })
+                        .collect();
+                    let total: f32 = lower.iter().map(|(_, difference)| difference).sum();
+                    let steepest = lower.iter().map(|(_, d)| *d).fold(0.0, f32::max);
+                    let amount = (steepest - talus) / 2.0;
+                    for (j, difference) in lower {
+                        let share = amount * difference / total;
+                        delta[j] += share;
+                        delta[self.index(x, y)] -= share;
+                    }
+                }
+            }
+            for (height, delta) in self.heights.iter_mut().zip(delta) {
+                *height += delta;
+            }
+        }
+        self
+    }
+
+    /// Simulate `droplets` rain drops running downhill, eroding sediment
+    /// from steep slopes and depositing it where they slow down, carving
+    /// valleys and ridges.
+    ///
+    /// Drops start at random columns picked with a random number generator
+    /// seeded with `seed`, and follow the slope with some inertia until they
+    /// evaporate or leave the heightmap.
+    pub fn hydraulic_erosion(&mut self, droplets: usize, seed: u64) -> &mut Self {
+        const INERTIA: f32 = 0.05;
+        const CAPACITY: f32 = 4.0;
+        const MIN_SLOPE: f32 = 0.01;
+        const DEPOSITION: f32 = 0.3;
+        const EROSION: f32 = 0.3;
+        const EVAPORATION: f32 = 0.02;
+        const GRAVITY: f32 = 4.0;
+        const LIFETIME: usize = 30;
+        if self.size_x < 2 || self.size_y < 2 {
+            return self;
+        }
+        let max_x = (self.size_x - 1) as f32;
+        let max_y = (self.size_y - 1) as f32;
+        let mut rng = Seed::new(seed).rng();
+        for _ in 0..droplets {
+            let mut x = rng.gen_range(0.0..max_x);
+            let mut y = rng.gen_range(0.0..max_y);
+            let (mut dx, mut dy) = (0.0, 0.0);
+            let mut speed = 1.0;
+            let mut water = 1.0;
+            let mut sediment = 0.0;
+            for _ in 0..LIFETIME {
+                let (height, gx, gy) = self.sample(x, y);
+                dx = dx * INERTIA - gx * (1.0 - INERTIA);
+                dy = dy * INERTIA - gy * (1.0 - INERTIA);
+                let length = f32::hypot(dx, dy);
+                if length < f32::EPSILON {
+                    break;
+                }
+                dx /= length;
+                dy /= length;
+                let (old_x, old_y) = (x, y);
+                x += dx;
+                y += dy;
+                if !(0.0..max_x).contains(&x) || !(0.0..max_y).contains(&y) {
+                    break;
+                }
+                let difference = self.sample(x, y).0 - height;
+                let capacity = f32::max(-difference, MIN_SLOPE) * speed * water * CAPACITY;
+                if difference > 0.0 || sediment > capacity {
+                    // Fill the pit the drop runs into, or drop the excess.
+                    let amount = if difference > 0.0 {
+                        difference.min(sediment)
+                    } else {
+                        (sediment - capacity) * DEPOSITION
+                    };
+                    sediment -= amount;
+                    self.spread(old_x, old_y, amount);
+                } else {
+                    // Never erode deeper than the drop falls, to avoid digging
+                    // pits.
+                    let amount = f32::min((capacity - sediment) * EROSION, -difference);
+                    sediment += amount;
+                    self.spread(old_x, old_y, -amount);
+                }
+                speed = f32::sqrt(f32::max(speed * speed - difference * GRAVITY, 0.0));
+                water *= 1.0 - EVAPORATION;
+            }
+        }
+        self
+    }
+
+    /// Get the bilinearly interpolated height and it's gradient at a point
+    /// between columns.
+    fn sample(&self, x: f32, y: f32) -> (f32, f32, f32) {
+        let (cx, cy) = (x as u32, y as u32);
+        let (fx, fy) = (x - cx as f32, y - cy as f32);
+        let nw = self.height(cx, cy);
+        let ne = self.height(cx + 1, cy);
+        let sw = self.height(cx, cy + 1);
+        let se = self.height(cx + 1, cy + 1);
+        let height = nw * (1.0 - fx) * (1.0 - fy)
+            + ne * fx * (1.0 - fy)
+            + sw * (1.0 - fx) * fy
+            + se * fx * fy;
+        let gx = (ne - nw) * (1.0 - fy) + (se - sw) * fy;
+        let gy = (sw - nw) * (1.0 - fx) + (se - ne) * fx;
+        (height, gx, gy)
+    }
+
+    /// Add `amount` to the four columns around a point, weighted by how close
+    /// they are.
+    fn spread(&mut self, x: f32, y: f32, amount: f32) {
+        let (cx, cy) = (x as u32, y as u32);
+        let (fx, fy) = (x - cx as f32, y - cy as f32);
+        for (column_x, column_y, weight) in [
+            (cx, cy, (1.0 - fx) * (1.0 - fy)),
+            (cx + 1, cy, fx * (1.0 - fy)),
+            (cx, cy + 1, (1.0 - fx) * fy),
+            (cx + 1, cy + 1, fx * fy),
+        ] {
+            let i = self.index(column_x, column_y);
+            self.heights[i] += amount * weight;
+        }
+    }
+
+    /// Fill a new buffer `size_z` voxels tall with the columns of the
+    /// heightmap, colored by `TERRAIN_BANDS`.
+    ///
+    /// Columns below `sea_level`, as a fraction of the height, are covered
+    /// with `WATER` up to it.
+    pub fn to_buffer(&self, size_z: u32, sea_level: f32) -> ArrayVoxelBuffer<Rgba> {
+        let mut buf = ArrayVoxelBuffer::new(self.size_x, self.size_y, size_z);
+        let column = |height: f32| (height.clamp(0.0, 1.0) * size_z as f32).round() as u32;
+        let sea = column(sea_level);
+        for y in 0..self.size_y {
+            for x in 0..self.size_x {
+                let top = column(self.height(x, y)).max(1);
+                for z in 0..top.min(size_z) {
+                    let fraction = z as f32 / size_z as f32;
+                    let band = TERRAIN_BANDS
+                        .iter()
+                        .rev()
+                        .find(|(start, _)| fraction >= *start)
+                        .unwrap_or(&TERRAIN_BANDS[0]);
+                    *buf.voxel_mut(x, y, z) = band.1;
+                }
+                for z in top..sea.min(size_z) {
+                    *buf.voxel_mut(x, y, z) = WATER;
+                }
+            }
+        }
+        buf
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(
+            x < self.size_x && y < self.size_y,
+            "Heightmap index {:?} out of bounds {:?}",
+            (x, y),
+            (self.size_x, self.size_y)
+        );
+        x as usize + y as usize * self.size_x as usize
+    }
+}
+
+/// A diamond-square midpoint displacement terrain generator.
+///
+/// # Examples
+///
+/// ```
+/// # use voxgen::terrain::DiamondSquare;
+/// DiamondSquare::new()
+///     .roughness(0.55)
+///     .seed(3)
+///     .render(64, 64, 24)
+///     .save("test/volumes/diamond_square.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiamondSquare {
+    roughness: f32,
+    seed: u64,
+    sea_level: f32,
+}
+
+impl DiamondSquare {
+    pub fn new() -> DiamondSquare {
+        DiamondSquare {
+            roughness: 0.5,
+            seed: 0,
+            sea_level: 0.3,
+        }
+    }
+
+    /// Set how much the random displacement shrinks at every level of
+    /// detail, from smooth hills near 0.0 to jagged peaks near 1.0.
+    pub fn roughness(&mut self, roughness: f32) -> &mut Self {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the `seed` of the random displacements.
+    ///
+    /// The same seed always gives the same terrain.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the height water fills up to, as a fraction of the height.
+    pub fn sea_level(&mut self, sea_level: f32) -> &mut Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Generate a normalized heightmap of `size_x` by `size_y` columns.
+    ///
+    /// The heights are generated on the smallest square grid of `2^n + 1`
+    /// columns covering the size, then cropped.
+    pub fn heightmap(&self, size_x: u32, size_y: u32) -> Heightmap {
+        let mut rng = Seed::new(self.seed).rng();
+        let size = (size_x.max(size_y).max(2) - 1).next_power_of_two() as usize + 1;
+        let mut grid = vec![0.0f32; size * size];
+        for (x, y) in [(0, 0), (size - 1, 0), (0, size - 1), (size - 1, size - 1)] {
+            grid[x + y * size] = rng.gen_range(-1.0..1.0);
+        }
+        let mut amplitude = 1.0;
+        let mut step = size - 1;
+        while step > 1 {
+            let half = step / 2;
+            // Diamond step: the center of every square is the average of it's
+            // corners, displaced.
+            for y in (half..size).step_by(step) {
+                for x in (half..size).step_by(step) {
+                    let average = (grid[(x - half) + (y - half) * size]
+                        + grid[(x + half) + (y - half) * size]
+                        + grid[(x - half) + (y + half) * size]
+                        + grid[(x + half) + (y + half) * size])
+                        / 4.0;
+                    grid[x + y * size] = average + rng.gen_range(-amplitude..amplitude);
+                }
+            }
+            // Square step: the center of every diamond is the average of it's
+            // corners inside the grid, displaced.
+            for y in (0..size).step_by(half) {
+                let start = if (y / half).is_multiple_of(2) {
+                    half
+                } else {
+                    0
+                };
+                for x in (start..size).step_by(step) {
+                    let mut sum = 0.0;
+                    let mut count = 0.0;
+                    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                        let nx = x as isize + dx * half as isize;
+                        let ny = y as isize + dy * half as isize;
+                        if (0..size as isize).contains(&nx) && (0..size as isize).contains(&ny) {
+                            sum += grid[nx as usize + ny as usize * size];
+                            count += 1.0;
+                        }
+                    }
+                    grid[x + y * size] = sum / count + rng.gen_range(-amplitude..amplitude);
+                }
+            }
+            amplitude *= self.roughness.max(f32::EPSILON);
+            step = half;
+        }
+        let mut heightmap = Heightmap::new(size_x, size_y);
+        for y in 0..size_y {
+            for x in 0..size_x {
+                heightmap.set_height(x, y, grid[x as usize + y as usize * size]);
+            }
+        }
+        heightmap.normalize();
+        heightmap
+    }
+
+    /// Generate a heightmap and fill a new `size_x` by `size_y` by `size_z`
+    /// buffer with it, colored by height and covered with water up to the
+    /// sea level.
+    pub fn render(&self, size_x: u32, size_y: u32, size_z: u32) -> ArrayVoxelBuffer<Rgba> {
+        self.heightmap(size_x, size_y)
+            .to_buffer(size_z, self.sea_level)
+    }
+}
+
+impl Default for DiamondSquare {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A color gradient through colors at positions from 0.0 to 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ramp {
+    stops: Vec<(f32, Rgba)>,
+}
+
+impl Ramp {
+    /// Create a ramp through `stops` of positions and colors, blending
+    /// linearly between them.
+    ///
+    /// Positions before the first stop take it's color, and positions after
+    /// the last stop take the color of the last.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stops` is empty.
+    pub fn new(mut stops: Vec<(f32, Rgba)>) -> Ramp {
+        assert!(!stops.is_empty(), "Ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ramp { stops }
+    }
+
+    /// Get the color at position `t`.
+    pub fn color(&self, t: f32) -> Rgba {
+        let next = self.stops.iter().position(|(start, _)| *start > t);
+        let (a, b) = match next {
+            Some(0) => return self.stops[0].1,
+            Some(i) => (self.stops[i - 1], self.stops[i]),
+            None => return self.stops[self.stops.len() - 1].1,
+        };
+        let factor = (t - a.0) / (b.0 - a.0).max(f32::EPSILON);
+        let (from, to): (LinSrgba, LinSrgba) = (
+            Srgba::from(a.1 .0).into_linear(),
+            Srgba::from(b.1 .0).into_linear(),
+        );
+        Rgba(Srgba::from_linear(from.mix(to, factor)).into())
+    }
+}
+
+impl From<&[(f32, Rgba)]> for Ramp {
+    fn from(stops: &[(f32, Rgba)]) -> Ramp {
+        Ramp::new(stops.to_vec())
+    }
+}
+
+/// A colorization pass turning raw terrain into landscapes, picking colors
+/// from gradient ramps by height, slope and moisture.
+///
+/// Every column belongs to the biome with the highest minimum moisture at
+/// or below the moisture of the column, and it's voxels take the colors of
+/// the biome ramp at their height, as a fraction of the buffer height.
+/// Near the surface of slopes steeper than the cliff slope, the cliff ramp
+/// is used instead.
+///
+/// Ramps are sampled at a limited number of levels, keeping terrain under
+/// the MagicaVoxel palette limit.
+///
+/// # Examples
+///
+/// Color dry lowlands as desert and wet highlands as forest.
+/// ```
+/// # use voxgen::noise::NoiseParams;
+/// # use voxgen::terrain::{Colorizer, DiamondSquare, Heightmap, Ramp, TERRAIN_BANDS};
+/// # use voxgen::voxel_buffer::Rgba;
+/// let mut buf = DiamondSquare::new().seed(5).render(64, 64, 24);
+/// let desert = Ramp::new(vec![
+///     (0.0, Rgba([220, 200, 140, 255])),
+///     (0.8, Rgba([200, 120, 70, 255])),
+/// ]);
+/// let forest = Ramp::new(vec![
+///     (0.3, Rgba([40, 100, 40, 255])),
+///     (0.9, Rgba([230, 235, 240, 255])),
+/// ]);
+/// let moisture = Heightmap::from_noise(64, 64, NoiseParams::new().seed(2).frequency(1.0 / 48.0));
+/// Colorizer::new()
+///     .biome(0.0, desert)
+///     .biome(0.5, forest)
+///     .moisture(moisture)
+///     .colorize(&mut buf);
+/// buf.save("test/volumes/biomes.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Colorizer {
+    biomes: Vec<(f32, Ramp)>,
+    cliff_slope: f32,
+    cliff: Option<Ramp>,
+    moisture: Option<Heightmap>,
+    levels: u32,
+}
+
+impl Colorizer {
+    /// Create a colorizer with a single biome of `TERRAIN_BANDS`, and gray
+    /// rock cliffs on slopes steeper than 1.5.
+    pub fn new() -> Colorizer {
+        Colorizer {
+            biomes: Vec::new(),
+            cliff_slope: 1.5,
+            cliff: Some(Ramp::new(vec![
+                (0.0, Rgba([110, 100, 90, 255])),
+                (1.0, Rgba([150, 145, 140, 255])),
+            ])),
+            moisture: None,
+            levels: 16,
+        }
+    }
+
+    /// Add a biome with the colors of `ramp` for columns with a moisture of
+    /// at least `min_moisture`.
+    ///
+    /// The first biome added replaces the default one.
+    pub fn biome(&mut self, min_moisture: f32, ramp: Ramp) -> &mut Self {
+        self.biomes.push((min_moisture, ramp));
+        self.biomes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    /// Color the surface of slopes steeper than `slope`, in voxels of
+    /// height per voxel across, with `ramp`, or leave cliffs to the biomes
+    /// for a `ramp` of `None`.
+    pub fn cliffs(&mut self, slope: f32, ramp: Option<Ramp>) -> &mut Self {
+        self.cliff_slope = slope;
+        self.cliff = ramp;
+        self
+    }
+
+    /// Set the moisture of every column, from 0.0 to 1.0.
+    ///
+    /// Without a moisture map, every column has a moisture of 0.0.
+    pub fn moisture(&mut self, map: Heightmap) -> &mut Self {
+        self.moisture = Some(map);
+        self
+    }
+
+    /// Set the number of evenly spaced `levels` ramps are sampled at.
+    pub fn levels(&mut self, levels: u32) -> &mut Self {
+        self.levels = levels.max(1);
+        self
+    }
+
+    /// Get the color of `ramp` at `t`, rounded to the nearest level.
+    fn sample(&self, ramp: &Ramp, t: f32) -> Rgba {
+        let steps = (self.levels - 1).max(1) as f32;
+        ramp.color((t.clamp(0.0, 1.0) * steps).round() / steps)
+    }
+
+    /// Recolor the terrain voxels of `buf`, leaving transparent voxels and
+    /// `WATER` unchanged.
+    pub fn colorize(&self, buf: &mut ArrayVoxelBuffer<Rgba>) {
+        let default_biome = [(0.0, Ramp::from(&TERRAIN_BANDS[..]))];
+        let biomes = if self.biomes.is_empty() {
+            &default_biome[..]
+        } else {
+            &self.biomes[..]
+        };
+        let heights = Heightmap::from_buffer(buf);
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let height = |x: u32, y: u32| {
+            let x = x.min(size_x - 1);
+            let y = y.min(size_y - 1);
+            heights.height(x, y) * size_z as f32
+        };
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let moisture = match &self.moisture {
+                    Some(map) if x < map.size_x && y < map.size_y => map.height(x, y),
+                    _ => 0.0,
+                };
+                let ramp = biomes
+                    .iter()
+                    .rev()
+                    .find(|(min, _)| moisture >= *min)
+                    .unwrap_or(&biomes[0]);
+                let dx = (height(x + 1, y) - height(x.saturating_sub(1), y)) / 2.0;
+                let dy = (height(x, y + 1) - height(x, y.saturating_sub(1))) / 2.0;
+                let steep = (dx * dx + dy * dy).sqrt() > self.cliff_slope;
+                let top = height(x, y).round() as u32;
+                for z in 0..size_z {
+                    let voxel = *buf.voxel(x, y, z);
+                    if voxel.0[3] == 0 || voxel == WATER {
+                        continue;
+                    }
+                    let t = z as f32 / size_z as f32;
+                    let color = match &self.cliff {
+                        Some(cliff) if steep && z + 3 >= top => self.sample(cliff, t),
+                        _ => self.sample(&ramp.1, t),
+                    };
+                    *buf.voxel_mut(x, y, z) = color;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Colorizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Soil layers by depth below the surface: grass, dirt and stone, each
+/// starting at the given depth in voxels.
+pub const SOIL_LAYERS: [(f32, Rgba); 3] = [
+    (0.0, Rgba([86, 125, 70, 255])),
+    (1.0, Rgba([120, 85, 55, 255])),
+    (4.0, Rgba([120, 110, 100, 255])),
+];
+
+/// A pocket of ore scattered through the deeper layers.
+#[derive(Clone, Debug)]
+struct Ore {
+    color: Rgba,
+    min_depth: f32,
+    abundance: f32,
+    size: f32,
+}
+
+/// A stratification pass layering terrain by depth below it's surface, with
+/// pockets of ore in the deeper layers.
+///
+/// Every voxel takes the color of the deepest layer starting at or above
+/// it's depth below the top of it's column. The depths are perturbed by
+/// noise, so the boundaries between layers wander instead of following the
+/// surface exactly. Ore pockets are blobs of noise above a threshold set
+/// by their abundance, replacing the layers below their minimum depth.
+///
+/// # Examples
+///
+/// Layer terrain with caves cut into it, showing the layers on the cave
+/// walls.
+/// ```
+/// # use voxgen::caves::CaveCarver;
+/// # use voxgen::terrain::{DiamondSquare, Strata};
+/// # use voxgen::voxel_buffer::Rgba;
+/// let mut buf = DiamondSquare::new().seed(8).render(64, 64, 32);
+/// CaveCarver::new().worms(6).entrances(2).seed(8).carve(&mut buf);
+/// Strata::new()
+///     .ore(Rgba([40, 40, 45, 255]), 5.0, 0.08, 3.0)
+///     .ore(Rgba([230, 190, 60, 255]), 12.0, 0.03, 2.0)
+///     .seed(8)
+///     .apply(&mut buf);
+/// buf.save("test/volumes/strata.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Strata {
+    layers: Vec<(f32, Rgba)>,
+    ores: Vec<Ore>,
+    perturbation: f32,
+    frequency: f64,
+    seed: u64,
+}
+
+impl Strata {
+    /// Create a pass with the layers of `SOIL_LAYERS` and no ore, with
+    /// boundaries perturbed by up to 1.5 voxels.
+    pub fn new() -> Strata {
+        Strata {
+            layers: Vec::new(),
+            ores: Vec::new(),
+            perturbation: 1.5,
+            frequency: 1.0 / 12.0,
+            seed: 0,
+        }
+    }
+
+    /// Add a layer of `color` starting `depth` voxels below the surface.
+    ///
+    /// The first layer added replaces the default ones.
+    pub fn layer(&mut self, depth: f32, color: Rgba) -> &mut Self {
+        self.layers.push((depth, color));
+        self.layers.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    /// Add pockets of ore of `color` below `min_depth`, filling about the
+    /// `abundance` fraction of the voxels there, in pockets about `size`
+    /// voxels across.
+    ///
+    /// Ores added later replace earlier ones where they overlap.
+    pub fn ore(&mut self, color: Rgba, min_depth: f32, abundance: f32, size: f32) -> &mut Self {
+        self.ores.push(Ore {
+            color,
+            min_depth,
+            abundance: abundance.clamp(0.0, 1.0),
+            size: size.max(1.0),
+        });
+        self
+    }
+
+    /// Set how many voxels the layer boundaries wander up or down by, and
+    /// the `frequency` of the noise moving them.
+    pub fn perturbation(&mut self, amplitude: f32, frequency: f64) -> &mut Self {
+        self.perturbation = amplitude.max(0.0);
+        self.frequency = frequency;
+        self
+    }
+
+    /// Set the `seed` of the noise perturbing the layers and placing ore.
+    ///
+    /// The same seed always gives the same layers.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Recolor the terrain voxels of `buf`, leaving transparent voxels and
+    /// `WATER` unchanged.
+    pub fn apply(&self, buf: &mut ArrayVoxelBuffer<Rgba>) {
+        let layers = if self.layers.is_empty() {
+            &SOIL_LAYERS[..]
+        } else {
+            &self.layers[..]
+        };
+        let mut params = NoiseParams::new();
+        params
+            .generator(Generator::Perlin)
+            .frequency(self.frequency)
+            .seed(Seed::new(self.seed).noise());
+        let wander = params.sampler();
+        let ores: Vec<_> = self
+            .ores
+            .iter()
+            .enumerate()
+            .map(|(i, ore)| {
+                let sampler = params
+                    .frequency(0.5 / ore.size as f64)
+                    .seed(Seed::new(self.seed).derive("ores").index(i as u64).noise())
+                    .sampler();
+                // Perlin noise mostly stays within -0.5 to 0.5, so the
+                // abundance thresholds within that range.
+                (ore, sampler, 0.5 - ore.abundance as f64)
+            })
+            .collect();
+        let (size_x, size_y, size_z) = buf.dimensions();
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let terrain = |buf: &ArrayVoxelBuffer<Rgba>, z| {
+                    let voxel = *buf.voxel(x, y, z);
+                    voxel.0[3] != 0 && voxel != WATER
+                };
+                let Some(top) = (0..size_z).rev().find(|&z| terrain(buf, z)) else {
+                    continue;
+                };
+                for z in 0..=top {
+                    if !terrain(buf, z) {
+                        continue;
+                    }
+                    let (fx, fy, fz) = (x as f64, y as f64, z as f64);
+                    let mut depth = (top - z) as f32;
+                    if depth > 0.0 {
+                        depth = (depth + wander(fx, fy, fz) as f32 * self.perturbation).max(0.5);
+                    }
+                    let mut color = layers
+                        .iter()
+                        .rev()
+                        .find(|(start, _)| depth >= *start)
+                        .unwrap_or(&layers[0])
+                        .1;
+                    for (ore, sampler, threshold) in &ores {
+                        if depth >= ore.min_depth && sampler(fx, fy, fz) > *threshold {
+                            color = ore.color;
+                        }
+                    }
+                    *buf.voxel_mut(x, y, z) = color;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Strata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,228 @@
+use crate::noise::{Generator, NoiseParams};
+use crate::seed::Seed;
+use crate::voronoi::Voronoi;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+
+/// A pattern shading surface voxels.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// Three dimensional noise, from dark at -1.0 to light at 1.0.
+    Noise(NoiseParams),
+    /// Bricks `length` by `height` voxels in a running bond, projected onto
+    /// the face every voxel is exposed on, with mortar lines between them.
+    Brick {
+        length: u32,
+        height: u32,
+        mortar: Rgba,
+    },
+    /// Irregular stones about `size` voxels across, with mortar in the
+    /// cracks between them.
+    Stone { size: f32, mortar: Rgba },
+    /// Growth rings `spacing` voxels apart around a trunk along the z axis
+    /// through the middle of the buffer, warped by noise.
+    WoodGrain { spacing: f32 },
+}
+
+/// A surface texturing pass, shading only the voxels on the surface of a
+/// buffer with a pattern, so blocky structures get visual detail while their
+/// insides stay untouched.
+///
+/// Surface voxels are the voxels with an empty face neighbor. Every surface
+/// voxel keeps it's color, darkened by up to the strength where the pattern
+/// is darkest, and mortar replaces the color outright.
+///
+/// # Examples
+///
+/// Brick the walls of a house and give it's roof some noise.
+/// ```
+/// # use voxgen::building::BuildingParams;
+/// # use voxgen::noise::NoiseParams;
+/// # use voxgen::texture::{Pattern, SurfaceTexture};
+/// # use voxgen::voxel_buffer::Rgba;
+/// let params = BuildingParams::new();
+/// let mut buf = params.render();
+/// SurfaceTexture::new(Pattern::Brick { length: 4, height: 2, mortar: Rgba([200, 195, 185, 255]) })
+///     .only(Rgba([210, 190, 160, 255]))
+///     .apply(&mut buf);
+/// SurfaceTexture::new(Pattern::Noise(NoiseParams::new().frequency(0.3).clone()))
+///     .only(Rgba([150, 60, 40, 255]))
+///     .strength(0.4)
+///     .apply(&mut buf);
+/// buf.save("test/volumes/texture.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct SurfaceTexture {
+    pattern: Pattern,
+    strength: f32,
+    levels: u32,
+    only: Option<Rgba>,
+    seed: u64,
+}
+
+impl SurfaceTexture {
+    pub fn new(pattern: Pattern) -> SurfaceTexture {
+        SurfaceTexture {
+            pattern,
+            strength: 0.25,
+            levels: 8,
+            only: None,
+            seed: 0,
+        }
+    }
+
+    /// Set how much the darkest parts of the pattern darken voxels, from
+    /// 0.0 to 1.0.
+    pub fn strength(&mut self, strength: f32) -> &mut Self {
+        self.strength = strength.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the number of evenly spaced shades, keeping under the
+    /// MagicaVoxel palette limit.
+    pub fn levels(&mut self, levels: u32) -> &mut Self {
+        self.levels = levels.max(1);
+        self
+    }
+
+    /// Only texture surface voxels of `color`.
+    pub fn only(&mut self, color: Rgba) -> &mut Self {
+        self.only = Some(color);
+        self
+    }
+
+    /// Set the `seed` of the stones and of the wood grain noise, and of the
+    /// shade of every brick and stone.
+    ///
+    /// The same seed always gives the same texture.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Texture the surface voxels of `buf`.
+    pub fn apply(&self, buf: &mut ArrayVoxelBuffer<Rgba>) {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        let empty = |buf: &ArrayVoxelBuffer<Rgba>, x: i64, y: i64, z: i64| {
+            x < 0
+                || y < 0
+                || z < 0
+                || x >= size_x as i64
+                || y >= size_y as i64
+                || z >= size_z as i64
+                || buf.voxel(x as u32, y as u32, z as u32).0[3] == 0
+        };
+        // Find the surface and the axes it faces before recoloring anything.
+        let mut surface = Vec::new();
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let voxel = *buf.voxel(x, y, z);
+                    if voxel.0[3] == 0 || self.only.is_some_and(|only| only != voxel) {
+                        continue;
+                    }
+                    let (x, y, z) = (x as i64, y as i64, z as i64);
+                    let exposed = [
+                        empty(buf, x - 1, y, z) || empty(buf, x + 1, y, z),
+                        empty(buf, x, y - 1, z) || empty(buf, x, y + 1, z),
+                        empty(buf, x, y, z - 1) || empty(buf, x, y, z + 1),
+                    ];
+                    if exposed.iter().any(|&e| e) {
+                        surface.push(([x as u32, y as u32, z as u32], exposed));
+                    }
+                }
+            }
+        }
+        let mut noise = match &self.pattern {
+            Pattern::Noise(params) => params.clone(),
+            _ => NoiseParams::new(),
+        };
+        if let Pattern::WoodGrain { .. } = self.pattern {
+            noise
+                .generator(Generator::Perlin)
+                .frequency(1.0 / 12.0)
+                .seed(Seed::new(self.seed).noise());
+        }
+        let sample = noise.sampler();
+        let stones = match self.pattern {
+            Pattern::Stone { size, .. } => {
+                let volume = size_x as f32 * size_y as f32 * size_z as f32;
+                let count = (volume / size.max(1.0).powi(3)).ceil() as usize;
+                let bounds = [size_x as f32, size_y as f32, size_z as f32];
+                Some(Voronoi::random(count.max(1), bounds, self.seed))
+            }
+            _ => None,
+        };
+        for ([x, y, z], exposed) in surface {
+            let p = [x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
+            // The shade from 0.0 for darkest to 1.0 for unchanged, or the
+            // mortar color.
+            let shade = match self.pattern {
+                Pattern::Noise(_) => Ok((sample(x as f64, y as f64, z as f64) as f32 + 1.0) / 2.0),
+                Pattern::Brick {
+                    length,
+                    height,
+                    mortar,
+                } => {
+                    // Project onto the face exposed, preferring the sides.
+                    let (u, v) = if exposed[0] {
+                        (y, z)
+                    } else if exposed[1] {
+                        (x, z)
+                    } else {
+                        (x, y)
+                    };
+                    let (length, height) = (length.max(2), height.max(2));
+                    let row = v / height;
+                    let shifted = u + row % 2 * length / 2;
+                    if v % height == 0 || shifted % length == 0 {
+                        Err(mortar)
+                    } else {
+                        Ok(hash(self.seed, [shifted / length, row, exposed[2] as u32]))
+                    }
+                }
+                Pattern::Stone { mortar, .. } => {
+                    let nearest = stones
+                        .as_ref()
+                        .and_then(|stones| stones.nearest(p))
+                        .unwrap();
+                    if nearest.second_distance - nearest.distance < 1.0 {
+                        Err(mortar)
+                    } else {
+                        Ok(hash(self.seed, [nearest.site as u32, 0, 0]))
+                    }
+                }
+                Pattern::WoodGrain { spacing } => {
+                    let (dx, dy) = (p[0] - size_x as f32 / 2.0, p[1] - size_y as f32 / 2.0);
+                    let warp = sample(x as f64, y as f64, z as f64) as f32 * spacing;
+                    let ring = ((dx * dx + dy * dy).sqrt() + warp) / spacing.max(1.0);
+                    Ok(1.0 - (ring.fract() * 2.0 - 1.0).abs())
+                }
+            };
+            let voxel = buf.voxel_mut(x, y, z);
+            *voxel = match shade {
+                Err(mortar) => mortar,
+                Ok(shade) => {
+                    let steps = (self.levels - 1).max(1) as f32;
+                    let shade = (shade.clamp(0.0, 1.0) * steps).round() / steps;
+                    let factor = 1.0 - self.strength * (1.0 - shade);
+                    let mut color = *voxel;
+                    for c in &mut color.0[..3] {
+                        *c = (*c as f32 * factor).round() as u8;
+                    }
+                    color
+                }
+            };
+        }
+    }
+}
+
+/// Hash a `seed` and a cell into a shade from 0.0 to 1.0.
+fn hash(seed: u64, cell: [u32; 3]) -> f32 {
+    let mut h = seed ^ 0x9e37_79b9_7f4a_7c15;
+    for c in cell {
+        h = (h ^ c as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h ^= h >> 31;
+    }
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
@@ -0,0 +1,216 @@
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// An error returned when a tile can't be added or a map can't be
+/// assembled.
+#[derive(Debug)]
+pub enum TileError {
+    /// A tile file couldn't be loaded.
+    Io(io::Error),
+    /// A tile isn't the size of the tile set, given as `(expected, found)`.
+    Size((u32, u32, u32), (u32, u32, u32)),
+    /// A layout cell names a tile that isn't in the tile set, given as
+    /// `(cell, tile)`.
+    Unknown((u32, u32, u32), String),
+}
+
+impl std::fmt::Display for TileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TileError::Io(error) => write!(f, "can't load tile: {}", error),
+            TileError::Size(expected, found) => {
+                write!(
+                    f,
+                    "tile size {:?} isn't the tile set size {:?}",
+                    found, expected
+                )
+            }
+            TileError::Unknown(cell, tile) => write!(f, "unknown tile {} at cell {:?}", tile, cell),
+        }
+    }
+}
+
+impl std::error::Error for TileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TileError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TileError {
+    fn from(error: io::Error) -> Self {
+        TileError::Io(error)
+    }
+}
+
+/// A library of equally sized tiles, for assembling maps by stamping them
+/// onto a grid.
+///
+/// Layouts name the tile of every cell either by it's index, in the order
+/// tiles were added, or by a color keyed to it, so the output of a
+/// `wfc::Model` or a small hand painted .vox file can lay out a map.
+///
+/// # Examples
+///
+/// Assemble a path through grass from a painted layout.
+/// ```
+/// # use voxgen::tiles::TileSet;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// let (grass, path) = (Rgba([80, 160, 60, 255]), Rgba([190, 170, 120, 255]));
+/// let mut tiles = TileSet::new(4, 4, 2);
+/// for (color, height) in [(grass, 2), (path, 1)] {
+///     let mut tile = ArrayVoxelBuffer::new(4, 4, 2);
+///     for z in 0..height {
+///         for y in 0..4 {
+///             for x in 0..4 {
+///                 *tile.voxel_mut(x, y, z) = color;
+///             }
+///         }
+///     }
+///     let index = tiles.add(tile)?;
+///     tiles.key(color, index);
+/// }
+/// let mut layout = ArrayVoxelBuffer::new(8, 8, 1);
+/// for y in 0..8 {
+///     for x in 0..8 {
+///         *layout.voxel_mut(x, y, 0) = if x == y { path } else { grass };
+///     }
+/// }
+/// let map = tiles.assemble_buffer(&layout)?;
+/// assert_eq!(map.dimensions(), (32, 32, 2));
+/// map.save("test/volumes/tiles.vox")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct TileSet {
+    size: (u32, u32, u32),
+    tiles: Vec<ArrayVoxelBuffer<Rgba>>,
+    keys: HashMap<Rgba, usize>,
+}
+
+impl TileSet {
+    /// Create an empty tile set of tiles `size_x` by `size_y` by `size_z`.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> TileSet {
+        TileSet {
+            size: (size_x.max(1), size_y.max(1), size_z.max(1)),
+            tiles: Vec::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Get the tile size as a tuple `(size_x, size_y, size_z)`.
+    pub fn tile_size(&self) -> (u32, u32, u32) {
+        self.size
+    }
+
+    /// Get the number of tiles.
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Get the tile of `index`.
+    pub fn tile(&self, index: usize) -> Option<&ArrayVoxelBuffer<Rgba>> {
+        self.tiles.get(index)
+    }
+
+    /// Add a `tile`, returning it's index.
+    pub fn add(&mut self, tile: ArrayVoxelBuffer<Rgba>) -> Result<usize, TileError> {
+        if tile.dimensions() != self.size {
+            return Err(TileError::Size(self.size, tile.dimensions()));
+        }
+        self.tiles.push(tile);
+        Ok(self.tiles.len() - 1)
+    }
+
+    /// Load a tile from the MagicaVoxel .vox file at `path` and add it,
+    /// returning it's index.
+    pub fn load<P>(&mut self, path: P) -> Result<usize, TileError>
+    where
+        P: AsRef<Path>,
+    {
+        self.add(ArrayVoxelBuffer::load(path)?)
+    }
+
+    /// Key the tile of `index` to `color`, for layouts given as buffers.
+    pub fn key(&mut self, color: Rgba, index: usize) -> &mut Self {
+        self.keys.insert(color, index);
+        self
+    }
+
+    /// Assemble a map from a grid of cells `size_x` by `size_y` by
+    /// `size_z`, with the tile index of every cell in `layout`, x first,
+    /// then y, then z. Cells of `None` are left empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layout` doesn't have a tile index for every cell.
+    pub fn assemble(
+        &self,
+        size_x: u32,
+        size_y: u32,
+        size_z: u32,
+        layout: &[Option<usize>],
+    ) -> Result<ArrayVoxelBuffer<Rgba>, TileError> {
+        assert_eq!(layout.len(), (size_x * size_y * size_z) as usize);
+        self.assemble_with((size_x, size_y, size_z), |x, y, z| {
+            let index = layout[(x + y * size_x + z * size_x * size_y) as usize]?;
+            Some(
+                self.tiles
+                    .get(index)
+                    .ok_or_else(|| TileError::Unknown((x, y, z), index.to_string())),
+            )
+        })
+    }
+
+    /// Assemble a map from a `layout` buffer, with every voxel colored with
+    /// the key of the tile of it's cell, such as the output of a
+    /// `wfc::Model` over keys. Transparent voxels are left empty.
+    pub fn assemble_buffer(
+        &self,
+        layout: &ArrayVoxelBuffer<Rgba>,
+    ) -> Result<ArrayVoxelBuffer<Rgba>, TileError> {
+        self.assemble_with(layout.dimensions(), |x, y, z| {
+            let color = *layout.voxel(x, y, z);
+            if color.0[3] == 0 {
+                return None;
+            }
+            let tile = self
+                .keys
+                .get(&color)
+                .and_then(|&index| self.tiles.get(index));
+            Some(tile.ok_or_else(|| TileError::Unknown((x, y, z), format!("{:?}", color))))
+        })
+    }
+
+    /// Stamp the tile `tile` picks for every cell of a grid of `cells`.
+    fn assemble_with<'a, F>(
+        &'a self,
+        cells: (u32, u32, u32),
+        tile: F,
+    ) -> Result<ArrayVoxelBuffer<Rgba>, TileError>
+    where
+        F: Fn(u32, u32, u32) -> Option<Result<&'a ArrayVoxelBuffer<Rgba>, TileError>>,
+    {
+        let (tile_x, tile_y, tile_z) = self.size;
+        let mut buf = ArrayVoxelBuffer::new(cells.0 * tile_x, cells.1 * tile_y, cells.2 * tile_z);
+        for z in 0..cells.2 {
+            for y in 0..cells.1 {
+                for x in 0..cells.0 {
+                    if let Some(tile) = tile(x, y, z) {
+                        let (ox, oy, oz) = (x * tile_x, y * tile_y, z * tile_z);
+                        buf.stamp(tile?, ox as i32, oy as i32, oz as i32);
+                    }
+                }
+            }
+        }
+        Ok(buf)
+    }
+}
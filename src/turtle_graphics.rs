@@ -1,57 +1,691 @@
-use line_drawing::Bresenham;
+use crate::compat::HashMap;
+use serde::{Deserialize, Serialize};
 
-use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::voxel_buffer::{
+    Anchor, ArrayVoxelBuffer, BlendMode, CapacityError, Rgba, Segment, VoxelBuffer,
+};
 
 /// The drawing turtle.
-#[derive(Copy, Clone, Debug)]
+///
+/// The orientation of the turtle is given by the unit vectors `heading`,
+/// `left` and `up`, as described in The Algorithmic Beauty of Plants section
+/// 1.5.
+///
+/// Turtle states serialize with serde, to save a turtle and carry on
+/// drawing from it later with `TurtleGraphics::set_state`.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 pub struct Turtle {
     x: i32,
     y: i32,
-    heading: f32,
+    z: i32,
+    heading: [f32; 3],
+    left: [f32; 3],
+    up: [f32; 3],
     color: Rgba,
+    stroke_style: StrokeStyle,
+    stroke_phase: u32,
+    width: f32,
+    step_scale: f32,
+}
+
+/// The pattern of voxels drawn along a turtle stroke.
+///
+/// Patterns are measured in voxels along the path and continue across
+/// consecutive segments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum StrokeStyle {
+    /// Draw every voxel.
+    #[default]
+    Solid,
+    /// Alternate `on` drawn voxels with `off` skipped voxels.
+    Dashed { on: u32, off: u32 },
+    /// Draw single voxels separated by `gap` skipped voxels.
+    Dotted { gap: u32 },
+}
+
+impl StrokeStyle {
+    fn is_on(self, position: u32) -> bool {
+        match self {
+            StrokeStyle::Solid => true,
+            StrokeStyle::Dashed { on, off } => position % on.saturating_add(off).max(1) < on,
+            StrokeStyle::Dotted { gap } => position.is_multiple_of(gap.saturating_add(1)),
+        }
+    }
+}
+
+/// How the turtle reacts when a drawn segment would intersect non-empty voxels.
+///
+/// A voxel is non-empty when its transparency channel is greater than 0. The
+/// voxel the turtle is currently standing on is never considered a collision.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CollisionMode {
+    /// Draw through existing voxels.
+    #[default]
+    Ignore,
+    /// Skip the whole segment and leave the turtle where it is.
+    Stop,
+    /// Draw the segment up to the voxel before the collision and move the
+    /// turtle there.
+    Truncate,
+}
+
+/// The smallest and largest `(x, y, z)` locations of a drawing.
+pub type Bounds = ((i32, i32, i32), (i32, i32, i32));
+
+/// Options for interpreting turtle command strings with
+/// `TurtleGraphics::interpret`.
+pub struct InterpretOptions {
+    step_size: f32,
+    angle_increment: f32,
+}
+
+impl Default for InterpretOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InterpretOptions {
+    /// Create new options with a `step_size` of `2.0` voxels and an
+    /// `angle_increment` of pi/2 radians.
+    pub fn new() -> InterpretOptions {
+        InterpretOptions {
+            step_size: 2.0,
+            angle_increment: core::f32::consts::FRAC_PI_2,
+        }
+    }
+
+    /// Set the number of voxels moved by `F` and `f`.
+    pub fn step_size(&mut self, d: f32) -> &mut Self {
+        self.step_size = d;
+        self
+    }
+
+    /// Set the radians turned by `+` and `-`.
+    pub fn angle_increment(&mut self, delta: f32) -> &mut Self {
+        self.angle_increment = delta;
+        self
+    }
 }
 
 /// Draw an `ArrayVoxelBuffer` using LOGO-style turtle graphics commands.
 pub struct TurtleGraphics {
     buf: ArrayVoxelBuffer<Rgba>,
     state: Turtle,
+    stack: Vec<Turtle>,
+    collision_mode: CollisionMode,
+    blend_mode: BlendMode,
+    segments: Option<ArrayVoxelBuffer<Segment>>,
+    segment_count: u32,
+    supersampling: u32,
+    drawn_voxels: usize,
+    bounds: Option<Bounds>,
+    clipped_segments: usize,
+    measuring: bool,
+    projection: Option<HashMap<(i32, i32), (i32, Rgba)>>,
+    voxels_per_unit: f32,
 }
 
 impl TurtleGraphics {
     /// Create a new `TurtleGraphics` object of the given dimensions.
     ///
     /// The `ArrayVoxelBuffer` is initially empty. The turtle starts at position
-    /// `(0, 0, 0)` with a heading of `0.0` radians (facing east) with RGBA
-    /// drawing color `[0, 0, 0, 255]`.
+    /// `(0, 0, 0)` facing east (along `x`) with it's left towards north (along
+    /// `y`) and it's up along `z`, with RGBA drawing color `[0, 0, 0, 255]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the buffer can't be created, see `ArrayVoxelBuffer::new`.
+    /// Use `try_new` to handle these cases.
     pub fn new(size_x: u32, size_y: u32, size_z: u32) -> TurtleGraphics {
+        match Self::try_new(size_x, size_y, size_z) {
+            Ok(turtle) => turtle,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// Create a new `TurtleGraphics` object like `new`, returning an error
+    /// instead of panicking when the buffer is too large or over the memory
+    /// budget.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::turtle_graphics::TurtleGraphics;
+    /// # use voxgen::voxel_buffer::CapacityError;
+    /// let turtle = TurtleGraphics::try_new(u32::MAX, u32::MAX, u32::MAX);
+    /// assert_eq!(turtle.err(), Some(CapacityError::Overflow));
+    /// ```
+    pub fn try_new(size_x: u32, size_y: u32, size_z: u32) -> Result<TurtleGraphics, CapacityError> {
+        Ok(TurtleGraphics::from_buf(ArrayVoxelBuffer::try_new(
+            size_x, size_y, size_z,
+        )?))
+    }
+
+    /// Create a `TurtleGraphics` object drawing over the existing `buf`, with
+    /// the turtle starting like `new`.
+    pub fn from_buf(buf: ArrayVoxelBuffer<Rgba>) -> TurtleGraphics {
         TurtleGraphics {
-            buf: ArrayVoxelBuffer::new(size_x, size_y, size_z),
+            buf,
             state: Turtle {
                 x: 0,
                 y: 0,
-                heading: 0.0,
-                color: Rgba([0, 0, 0, 255])
+                z: 0,
+                heading: [1.0, 0.0, 0.0],
+                left: [0.0, 1.0, 0.0],
+                up: [0.0, 0.0, 1.0],
+                color: Rgba([0, 0, 0, 255]),
+                stroke_style: StrokeStyle::Solid,
+                stroke_phase: 0,
+                width: 1.0,
+                step_scale: 1.0,
             },
+            stack: Vec::new(),
+            collision_mode: CollisionMode::Ignore,
+            blend_mode: BlendMode::Replace,
+            segments: None,
+            segment_count: 0,
+            supersampling: 1,
+            drawn_voxels: 0,
+            bounds: None,
+            clipped_segments: 0,
+            measuring: false,
+            projection: None,
+            voxels_per_unit: 1.0,
+        }
+    }
+
+    /// Create a `TurtleGraphics` object without a buffer that only measures
+    /// what it would draw.
+    ///
+    /// The turtle is unbounded and may move to negative locations. Use
+    /// `bounds` and `drawn_voxels` to get the extent and amount of the
+    /// drawing. Nothing is ever hit in the `Stop` and `Truncate` collision
+    /// modes.
+    pub fn measure() -> TurtleGraphics {
+        TurtleGraphics {
+            measuring: true,
+            ..TurtleGraphics::new(0, 0, 0)
+        }
+    }
+
+    /// Create a measuring `TurtleGraphics` object, like `measure`, that also
+    /// records a top-down view of what it would draw.
+    ///
+    /// Use `projection` to get the view.
+    pub fn sketch() -> TurtleGraphics {
+        TurtleGraphics {
+            projection: Some(HashMap::new()),
+            ..TurtleGraphics::measure()
+        }
+    }
+
+    /// Get the `(x, y, z)` location of the turtle.
+    pub fn position(&self) -> (i32, i32, i32) {
+        (self.state.x, self.state.y, self.state.z)
+    }
+
+    /// Get the length in voxels of a move of `step_size` units.
+    fn length(&self, step_size: f32) -> f32 {
+        step_size * self.state.step_scale * self.voxels_per_unit
+    }
+
+    /// Get the position `length` voxels ahead of the turtle.
+    fn target(&self, length: f32) -> (i32, i32, i32) {
+        let [hx, hy, hz] = self.state.heading;
+        (
+            self.state.x + (length * hx) as i32,
+            self.state.y + (length * hy) as i32,
+            self.state.z + (length * hz) as i32,
+        )
+    }
+
+    /// Get the voxels covered by the next segment of `length` voxels.
+    ///
+    /// Returns each voxel `(x, y, z)` in drawing order along with the
+    /// fraction of it covered by the segment.
+    fn stroke(&self, length: f32) -> Vec<((i32, i32, i32), f32)> {
+        let start = self.position();
+        if self.supersampling <= 1 {
+            return Bresenham3d::new(start, self.target(length))
+                .map(|p| (p, 1.0))
+                .collect();
+        }
+        // Rasterize from the center of the current voxel to the exact end
+        // point on a finer grid, then count the samples landing in each voxel.
+        let scale = self.supersampling as i32;
+        let factor = self.supersampling as f32;
+        let sample = |p: i32, h: f32| ((p as f32 + 0.5 + length * h) * factor).floor() as i32;
+        let [hx, hy, hz] = self.state.heading;
+        let start_sample = (
+            start.0 * scale + scale / 2,
+            start.1 * scale + scale / 2,
+            start.2 * scale + scale / 2,
+        );
+        let end_sample = (
+            sample(start.0, hx),
+            sample(start.1, hy),
+            sample(start.2, hz),
+        );
+        let mut cells: Vec<((i32, i32, i32), f32)> = Vec::new();
+        for (x, y, z) in Bresenham3d::new(start_sample, end_sample) {
+            let cell = (
+                x.div_euclid(scale),
+                y.div_euclid(scale),
+                z.div_euclid(scale),
+            );
+            match cells.last_mut() {
+                Some((last, coverage)) if *last == cell => *coverage += 1.0 / factor,
+                _ => cells.push((cell, 1.0 / factor)),
+            }
+        }
+        for (_, coverage) in &mut cells {
+            *coverage = coverage.min(1.0);
+        }
+        cells
+    }
+
+    /// Move the turtle to the location `(x, y, z)` without drawing a line.
+    pub fn move_to(&mut self, x: i32, y: i32, z: i32) {
+        (self.state.x, self.state.y, self.state.z) = (x, y, z);
+    }
+
+    /// Draw a line from the turtle to the location `(x, y, z)` and move
+    /// there, facing along the line.
+    ///
+    /// The line is drawn like `draw`, with the current width, color and
+    /// stroke style, but `(x, y, z)` is in voxels, so it's length is not
+    /// scaled by `scale_step` or `voxels_per_unit`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::turtle_graphics::TurtleGraphics;
+    /// # use voxgen::voxel_buffer::VoxelBuffer;
+    /// let mut turtle = TurtleGraphics::new(16, 1, 1);
+    /// turtle.voxels_per_unit(2.0);
+    /// turtle.scale_step(0.0);
+    /// turtle.draw_to(4, 0, 0);
+    /// assert_eq!(turtle.position(), (4, 0, 0));
+    /// assert_eq!(turtle.into_buf().enumerate_occupied().count(), 5);
+    /// ```
+    pub fn draw_to(&mut self, x: i32, y: i32, z: i32) {
+        let (px, py, pz) = self.position();
+        let direction = [(x - px) as f32, (y - py) as f32, (z - pz) as f32];
+        let length = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+        if length > 0.0 {
+            self.face(direction);
+            self.draw_length(length);
+        }
+        self.move_to(x, y, z);
+    }
+
+    /// Turn the turtle to face `direction`, keeping it's up vector as close
+    /// to the previous one as possible.
+    ///
+    /// Does nothing if `direction` is zero.
+    pub fn face(&mut self, direction: [f32; 3]) {
+        let heading = match normalize(direction) {
+            Some(heading) => heading,
+            None => return,
+        };
+        // The heading, left and up vectors form a right handed basis, so
+        // left is up cross heading. Fall back to the old left vector when
+        // facing straight up or down.
+        let left = normalize(cross(self.state.up, heading))
+            .or_else(|| normalize(cross(heading, cross(self.state.left, heading))))
+            .unwrap_or(self.state.left);
+        self.state.heading = heading;
+        self.state.left = left;
+        self.state.up = cross(heading, left);
+    }
+
+    /// Mark the turtle's position and heading as an anchor of the drawing
+    /// named `name`, to attach other parts to with `ArrayVoxelBuffer::attach`.
+    ///
+    /// Names in use get a numbered suffix, so every anchor marked as
+    /// `"branch_tip"` is kept as `"branch_tip"`, `"branch_tip_1"`,
+    /// `"branch_tip_2"` and so on.
+    ///
+    /// # Examples
+    ///
+    /// Mark the tips of a tree's branches.
+    /// ```
+    /// # use voxgen::l_system::{LSystem, RenderOptions};
+    /// let tree = LSystem::try_new("tree", "FFT", vec!["T → [+FT][-FT]"])?;
+    /// let buf = RenderOptions::new()
+    ///     .derivation_length(2)
+    ///     .callback('T', |turtle, _| turtle.anchor("branch_tip"))
+    ///     .render(&tree)?;
+    /// assert_eq!(buf.anchors().count(), 4);
+    /// assert!(buf.anchor("branch_tip_3").is_some());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn anchor(&mut self, name: &str) {
+        let mut unique = name.to_string();
+        let mut i = 0;
+        while self.buf.anchor(&unique).is_some() {
+            i += 1;
+            unique = format!("{}_{}", name, i);
         }
+        let anchor = Anchor::new(self.position(), self.state.heading);
+        self.buf.set_anchor(&unique, anchor);
     }
 
     /// Move the turtle without drawing a line.
     pub fn step(&mut self, step_size: f32) {
-        self.state.x = self.state.x + (step_size * self.state.heading.cos()) as i32;
-        self.state.y = self.state.y + (step_size * self.state.heading.sin()) as i32;
+        (self.state.x, self.state.y, self.state.z) = self.target(self.length(step_size));
     }
 
     /// Move the turtle and draw a line along it's path.
     ///
-    /// The turtle moves `step_size` voxels in the direction of it's current
-    /// `heading`.
+    /// The turtle moves `step_size` units, see `voxels_per_unit`, in the
+    /// direction of it's current `heading`. If the segment would intersect
+    /// non-empty voxels, the current `CollisionMode` decides whether it is
+    /// drawn in full, skipped or truncated.
+    ///
+    /// The turtle may leave the buffer. Voxels outside of it are skipped and
+    /// the segment counts as clipped, see `clipped_segments`.
     pub fn draw(&mut self, step_size: f32) {
-        let (x0, y0) = (self.state.x, self.state.y);
-        self.step(step_size);
-        let (x1, y1) = (self.state.x, self.state.y);
-        for (x, y) in Bresenham::new((x0, y0), (x1, y1)) {
-            *self.buf.voxel_mut(x as u32, y as u32, 0) = self.state.color;
+        self.draw_length(self.length(step_size));
+    }
+
+    /// Draw a segment of `length` voxels, see `draw`.
+    fn draw_length(&mut self, length: f32) {
+        let start = self.position();
+        let mut end = self.target(length);
+        let mut points = self.stroke(length);
+        if self.collision_mode != CollisionMode::Ignore {
+            if let Some((x, y, z)) = self.first_collision(length) {
+                self.clipped_segments += 1;
+                if self.collision_mode == CollisionMode::Stop {
+                    return;
+                }
+                let hit = (x as i32, y as i32, z as i32);
+                points.truncate(
+                    points
+                        .iter()
+                        .position(|&(p, _)| p == hit)
+                        .unwrap_or(points.len()),
+                );
+                end = points.last().map_or(start, |&(p, _)| p);
+            }
+        }
+        self.segment_count += 1;
+        let segment = Segment::new(self.stack.len() as u32, self.segment_count);
+        let brush = self.brush();
+        let mut clipped = false;
+        for (i, &((x, y, z), coverage)) in points.iter().enumerate() {
+            if !self
+                .state
+                .stroke_style
+                .is_on(self.state.stroke_phase + i as u32)
+            {
+                continue;
+            }
+            for &(dx, dy, dz) in &brush {
+                let (x, y, z) = (x + dx, y + dy, z + dz);
+                if self.measuring {
+                    self.drawn_voxels += 1;
+                    self.extend_bounds((x, y, z));
+                    if let Some(projection) = &mut self.projection {
+                        let top = projection.entry((x, y)).or_insert((z, self.state.color));
+                        if z >= top.0 {
+                            *top = (z, self.state.color);
+                        }
+                    }
+                    continue;
+                }
+                // Voxels outside of the buffer are clipped.
+                if !self.contains((x, y, z)) {
+                    clipped = true;
+                    continue;
+                }
+                let voxel = self.buf.voxel_mut(x as u32, y as u32, z as u32);
+                let kept = self.blend_mode == BlendMode::SkipOccupied && voxel.0[3] > 0;
+                let mut color = self.state.color;
+                color.0[3] = (color.0[3] as f32 * coverage).round() as u8;
+                // The partly covered start of a segment joining the end of the
+                // one before keeps the larger coverage.
+                if i == 0 && coverage < 1.0 && voxel.0[3] >= color.0[3] {
+                    continue;
+                }
+                *voxel = self.blend_mode.blend(*voxel, color);
+                self.drawn_voxels += 1;
+                self.extend_bounds((x, y, z));
+                if let (Some(segments), false) = (&mut self.segments, kept) {
+                    *segments.voxel_mut(x as u32, y as u32, z as u32) = segment;
+                }
+            }
+        }
+        if clipped {
+            self.clipped_segments += 1;
         }
+        // The end of this segment is the start of the next one.
+        self.state.stroke_phase += points.len().saturating_sub(1) as u32;
+        (self.state.x, self.state.y, self.state.z) = end;
+    }
+
+    /// Get the number of voxels drawn so far, counting voxels drawn over
+    /// more than once each time.
+    pub fn drawn_voxels(&self) -> usize {
+        self.drawn_voxels
+    }
+
+    /// Get the color of the topmost voxel drawn so far in each `(x, y)`
+    /// column, if this is a sketching turtle from `sketch`.
+    pub fn projection(&self) -> Option<HashMap<(i32, i32), Rgba>> {
+        self.projection.as_ref().map(|projection| {
+            projection
+                .iter()
+                .map(|(&column, &(_, color))| (column, color))
+                .collect()
+        })
+    }
+
+    /// Get the number of segments cut short so far, either by a collision or
+    /// by clipping them to the edges of the buffer.
+    pub fn clipped_segments(&self) -> usize {
+        self.clipped_segments
+    }
+
+    /// Get the smallest and largest `(x, y, z)` locations drawn so far, or
+    /// `None` if nothing has been drawn.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    fn extend_bounds(&mut self, (x, y, z): (i32, i32, i32)) {
+        self.bounds = Some(match self.bounds {
+            None => ((x, y, z), (x, y, z)),
+            Some((min, max)) => (
+                (min.0.min(x), min.1.min(y), min.2.min(z)),
+                (max.0.max(x), max.1.max(y), max.2.max(z)),
+            ),
+        });
+    }
+
+    /// Check whether the location `(x, y, z)` is inside the buffer.
+    fn contains(&self, (x, y, z): (i32, i32, i32)) -> bool {
+        let (size_x, size_y, size_z) = self.buf.dimensions();
+        x >= 0
+            && y >= 0
+            && z >= 0
+            && (x as u32) < size_x
+            && (y as u32) < size_y
+            && (z as u32) < size_z
+    }
+
+    /// Get the voxel offsets covered by a stroke of the current width around
+    /// each voxel of it's path.
+    fn brush(&self) -> Vec<(i32, i32, i32)> {
+        let radius = (self.state.width * self.voxels_per_unit - 1.0).max(0.0) / 2.0;
+        let r = radius.floor() as i32;
+        let mut offsets = Vec::new();
+        for dx in -r..=r {
+            for dy in -r..=r {
+                for dz in -r..=r {
+                    if ((dx * dx + dy * dy + dz * dz) as f32) <= radius * radius {
+                        offsets.push((dx, dy, dz));
+                    }
+                }
+            }
+        }
+        offsets
+    }
+
+    /// Find the first non-empty voxel the next `draw(step_size)` would hit.
+    ///
+    /// Returns the `(x, y, z)` location of the collision, or `None` if the
+    /// segment only crosses empty voxels. Locations outside of the buffer are
+    /// never collisions.
+    pub fn collision(&self, step_size: f32) -> Option<(u32, u32, u32)> {
+        self.first_collision(self.length(step_size))
+    }
+
+    /// Find the first non-empty voxel a segment of `length` voxels would hit.
+    fn first_collision(&self, length: f32) -> Option<(u32, u32, u32)> {
+        let start = self.position();
+        self.stroke(length)
+            .into_iter()
+            .map(|(p, _)| p)
+            .filter(|&p| p != start)
+            .filter(|&p| self.contains(p))
+            .map(|(x, y, z)| (x as u32, y as u32, z as u32))
+            .find(|&(x, y, z)| self.buf.voxel(x, y, z).0[3] > 0)
+    }
+
+    /// Set how `draw` handles segments that intersect non-empty voxels.
+    pub fn collision_mode(&mut self, mode: CollisionMode) {
+        self.collision_mode = mode;
+    }
+
+    /// Set the supersampling factor used to rasterize strokes.
+    ///
+    /// With a `factor` of 2 to 4, each segment is rasterized on a grid
+    /// `factor` times finer than the voxel grid, from the center of the
+    /// current voxel to the exact end point, and the transparency channel of
+    /// each drawn voxel is scaled by how much of it the segment covers. This
+    /// gives smoother diagonals and curves, especially combined with
+    /// `BlendMode::Alpha`. A `factor` of 1 disables supersampling. Values are
+    /// clamped to `1..=4`.
+    ///
+    /// The partly covered start of a segment never makes the end of the one
+    /// before more transparent, so the joints of connected segments stay
+    /// opaque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::turtle_graphics::TurtleGraphics;
+    /// # use voxgen::voxel_buffer::{Rgba, VoxelBuffer};
+    /// let mut turtle = TurtleGraphics::new(16, 1, 1);
+    /// turtle.supersampling(2);
+    /// turtle.color(Rgba([255, 0, 0, 255]));
+    /// turtle.draw(4.0);
+    /// turtle.draw(4.0);
+    /// let buf = turtle.into_buf();
+    /// let alphas: Vec<u8> = (0..9).map(|x| buf.voxel(x, 0, 0).0[3]).collect();
+    /// assert_eq!(alphas, [128, 255, 255, 255, 255, 255, 255, 255, 255]);
+    /// ```
+    pub fn supersampling(&mut self, factor: u32) {
+        self.supersampling = factor.clamp(1, 4);
+    }
+
+    /// Set how `draw` combines the drawing color with existing voxels.
+    pub fn blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    /// Set the pattern of voxels drawn by `draw` and restart the pattern.
+    pub fn stroke_style(&mut self, style: StrokeStyle) {
+        self.state.stroke_style = style;
+        self.state.stroke_phase = 0;
+    }
+
+    /// Enable or disable recording structural metadata while drawing.
+    ///
+    /// When enabled, every voxel written by `draw` also records the current
+    /// branch depth (the number of pushed turtle states) and the id of the
+    /// segment being drawn into a companion buffer of the same dimensions.
+    /// Disabling recording discards the companion buffer.
+    pub fn record_segments(&mut self, enabled: bool) {
+        if !enabled {
+            self.segments = None;
+        } else if self.segments.is_none() {
+            let (size_x, size_y, size_z) = self.buf.dimensions();
+            self.segments = Some(ArrayVoxelBuffer::new(size_x, size_y, size_z));
+        }
+    }
+
+    /// Get the recorded segment metadata, if recording is enabled.
+    pub fn segments(&self) -> Option<&ArrayVoxelBuffer<Segment>> {
+        self.segments.as_ref()
+    }
+
+    /// Set the resolution of the drawing, the number of voxels in one unit
+    /// of `step`, `draw` and `width`. Defaults to 1.
+    ///
+    /// Rules written in abstract units render at any resolution by only
+    /// changing the scale, such as a coarse preview at 0.5 and a detailed
+    /// final render at 4. Positions given to `move_to` and `draw_to` are
+    /// still in voxels. Resolutions that aren't positive are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::turtle_graphics::{InterpretOptions, TurtleGraphics};
+    /// let mut turtle = TurtleGraphics::new(64, 64, 64);
+    /// turtle.voxels_per_unit(4.0);
+    /// turtle.interpret("F+F", &InterpretOptions::new().step_size(5.0));
+    /// assert_eq!(turtle.position(), (20, 20, 0));
+    /// turtle.buf().save("test/volumes/voxels_per_unit.vox")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn voxels_per_unit(&mut self, voxels_per_unit: f32) {
+        if voxels_per_unit > 0.0 && voxels_per_unit.is_finite() {
+            self.voxels_per_unit = voxels_per_unit;
+        }
+    }
+
+    /// Set the width of the lines drawn by `draw`, in voxels at the default
+    /// `voxels_per_unit` of 1.
+    ///
+    /// Lines are drawn with a ball of diameter `width` around each voxel of
+    /// their path, so widths below 3 draw single voxel lines.
+    pub fn width(&mut self, width: f32) {
+        self.state.width = width.max(1.0);
+    }
+
+    /// Get the width of the lines drawn by `draw`.
+    pub fn stroke_width(&self) -> f32 {
+        self.state.width
+    }
+
+    /// Make the lines drawn by `draw` `decrement` voxels thinner, down to a
+    /// width of 1.
+    pub fn decrement_width(&mut self, decrement: f32) {
+        self.width(self.state.width - decrement);
+    }
+
+    /// Multiply the width of the lines drawn by `draw` by `factor`, down to a
+    /// width of 1.
+    pub fn scale_width(&mut self, factor: f32) {
+        self.width(self.state.width * factor);
+    }
+
+    /// Multiply the length of all further `step` and `draw` moves by
+    /// `factor`.
+    pub fn scale_step(&mut self, factor: f32) {
+        self.state.step_scale *= factor;
+    }
+
+    /// Get the factor the length of `step` and `draw` moves is currently
+    /// multiplied by.
+    pub fn scale(&self) -> f32 {
+        self.state.step_scale
     }
 
     /// Set the turtle drawing color to the RGBA value of `color`.
@@ -59,14 +693,97 @@ impl TurtleGraphics {
         self.state.color = color;
     }
 
-    /// Rotate the turtle `angle_increment` radians to the left.
+    /// Rotate the turtle `angle_increment` radians to the right, around it's
+    /// up vector.
     pub fn right(&mut self, angle_increment: f32) {
-        self.state.heading -= angle_increment;
+        self.left(-angle_increment);
     }
 
-    /// Rotate the turtle `angle_increment` radians to the right.
+    /// Rotate the turtle `angle_increment` radians to the left, around it's
+    /// up vector.
     pub fn left(&mut self, angle_increment: f32) {
-        self.state.heading += angle_increment;
+        let state = &mut self.state;
+        (state.heading, state.left) = rotate(state.heading, state.left, angle_increment);
+    }
+
+    /// Pitch the turtle `angle_increment` radians down, around it's left
+    /// vector.
+    pub fn pitch_down(&mut self, angle_increment: f32) {
+        self.pitch_up(-angle_increment);
+    }
+
+    /// Pitch the turtle `angle_increment` radians up, around it's left
+    /// vector.
+    pub fn pitch_up(&mut self, angle_increment: f32) {
+        let state = &mut self.state;
+        (state.heading, state.up) = rotate(state.heading, state.up, angle_increment);
+    }
+
+    /// Roll the turtle `angle_increment` radians to the left, around it's
+    /// heading vector.
+    pub fn roll_left(&mut self, angle_increment: f32) {
+        self.roll_right(-angle_increment);
+    }
+
+    /// Roll the turtle `angle_increment` radians to the right, around it's
+    /// heading vector.
+    pub fn roll_right(&mut self, angle_increment: f32) {
+        let state = &mut self.state;
+        (state.left, state.up) = rotate(state.left, state.up, angle_increment);
+    }
+
+    /// Turn the turtle around to face the opposite direction.
+    pub fn turn_around(&mut self) {
+        self.left(core::f32::consts::PI);
+    }
+
+    /// Save the current state of the turtle on the stack.
+    pub fn push(&mut self) {
+        self.stack.push(self.state);
+    }
+
+    /// Restore the most recently pushed state of the turtle.
+    ///
+    /// Does nothing if the stack is empty.
+    pub fn pop(&mut self) {
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+    }
+
+    /// Execute the turtle commands in `commands` directly.
+    ///
+    /// Supports the standard turtle interpretation symbols: `F` draws a step,
+    /// `f` moves a step without drawing, `+` turns left, `-` turns right, `&`
+    /// pitches down, `^` pitches up, `\` rolls left, `/` rolls right, `|`
+    /// turns around, `[` pushes the turtle state and `]` pops it. All other
+    /// characters are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::turtle_graphics::{InterpretOptions, TurtleGraphics};
+    /// let mut turtle = TurtleGraphics::new(16, 16, 1);
+    /// turtle.step(4.0);
+    /// turtle.interpret("F+F-F[+F]F", InterpretOptions::new().step_size(2.0));
+    /// ```
+    pub fn interpret(&mut self, commands: &str, options: &InterpretOptions) {
+        for c in commands.chars() {
+            match c {
+                'F' => self.draw(options.step_size),
+                'f' => self.step(options.step_size),
+                '+' => self.left(options.angle_increment),
+                '-' => self.right(options.angle_increment),
+                '&' => self.pitch_down(options.angle_increment),
+                '^' => self.pitch_up(options.angle_increment),
+                '\\' => self.roll_left(options.angle_increment),
+                '/' => self.roll_right(options.angle_increment),
+                '|' => self.turn_around(),
+                '[' => self.push(),
+                ']' => self.pop(),
+                _ => (),
+            }
+        }
     }
 
     /// Get the current state of the turtle.
@@ -74,8 +791,121 @@ impl TurtleGraphics {
         self.state
     }
 
+    /// Replace the current state of the turtle with `state`, such as one
+    /// saved with `TurtleGraphics::state`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::turtle_graphics::{InterpretOptions, Turtle, TurtleGraphics};
+    /// let mut turtle = TurtleGraphics::new(16, 16, 16);
+    /// turtle.interpret("f+f^f", &InterpretOptions::new());
+    /// let saved = serde_json::to_string(&turtle.state())?;
+    /// let mut replay = TurtleGraphics::new(16, 16, 16);
+    /// replay.set_state(serde_json::from_str::<Turtle>(&saved)?);
+    /// assert_eq!(replay.position(), turtle.position());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_state(&mut self, state: Turtle) {
+        self.state = state;
+    }
+
     /// Get the current state of the turtle.
     pub fn buf(&mut self) -> &ArrayVoxelBuffer<Rgba> {
         &self.buf
     }
+
+    /// Get the drawing for direct editing.
+    pub fn buf_mut(&mut self) -> &mut ArrayVoxelBuffer<Rgba> {
+        &mut self.buf
+    }
+
+    /// Consume the `TurtleGraphics` object and get it's drawing.
+    pub fn into_buf(self) -> ArrayVoxelBuffer<Rgba> {
+        self.buf
+    }
+}
+
+/// Rotate the orthogonal unit vectors `a` and `b` by `angle` radians in their
+/// plane, turning `a` towards `b`.
+fn rotate(a: [f32; 3], b: [f32; 3], angle: f32) -> ([f32; 3], [f32; 3]) {
+    let (sin, cos) = angle.sin_cos();
+    let mut a_rotated = [0.0; 3];
+    let mut b_rotated = [0.0; 3];
+    for i in 0..3 {
+        a_rotated[i] = a[i] * cos + b[i] * sin;
+        b_rotated[i] = b[i] * cos - a[i] * sin;
+    }
+    (a_rotated, b_rotated)
+}
+
+/// Get the cross product `a × b`.
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Scale `v` to unit length, or get `None` if it's too short to have a
+/// direction.
+fn normalize(v: [f32; 3]) -> Option<[f32; 3]> {
+    let length = v.iter().map(|c| c * c).sum::<f32>().sqrt();
+    (length > 1e-6).then(|| v.map(|c| c / length))
+}
+
+/// The voxels of a line from `start` to `end`, both included, with the 3D
+/// Bresenham algorithm.
+struct Bresenham3d {
+    voxel: (i32, i32, i32),
+    sign: (i32, i32, i32),
+    len: (i32, i32, i32),
+    err: (i32, i32, i32),
+    longest: i32,
+    count: i32,
+}
+
+impl Bresenham3d {
+    fn new(start: (i32, i32, i32), end: (i32, i32, i32)) -> Bresenham3d {
+        let delta = (end.0 - start.0, end.1 - start.1, end.2 - start.2);
+        let len = (delta.0.abs(), delta.1.abs(), delta.2.abs());
+        let longest = len.0.max(len.1).max(len.2);
+        Bresenham3d {
+            voxel: start,
+            sign: (delta.0.signum(), delta.1.signum(), delta.2.signum()),
+            len,
+            err: (longest / 2, longest / 2, longest / 2),
+            longest,
+            count: longest,
+        }
+    }
+}
+
+impl Iterator for Bresenham3d {
+    type Item = (i32, i32, i32);
+
+    fn next(&mut self) -> Option<(i32, i32, i32)> {
+        if self.count < 0 {
+            return None;
+        }
+        self.count -= 1;
+        let voxel = self.voxel;
+        self.err.0 -= self.len.0;
+        self.err.1 -= self.len.1;
+        self.err.2 -= self.len.2;
+        if self.err.0 < 0 {
+            self.err.0 += self.longest;
+            self.voxel.0 += self.sign.0;
+        }
+        if self.err.1 < 0 {
+            self.err.1 += self.longest;
+            self.voxel.1 += self.sign.1;
+        }
+        if self.err.2 < 0 {
+            self.err.2 += self.longest;
+            self.voxel.2 += self.sign.2;
+        }
+        Some(voxel)
+    }
 }
@@ -0,0 +1,579 @@
+use crate::l_system::watch::Watch;
+use crate::mesh::SurfaceMesh;
+use crate::preview::{add, cross, direction, normalize, scale};
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_6};
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+use winit::application::ApplicationHandler;
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, NamedKey};
+use winit::window::{Window, WindowId};
+
+/// The shader drawing the faces of the voxels, colored and shaded on the
+/// CPU.
+const SHADER: &str = r#"
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct Fragment {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vertex(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> Fragment {
+    var out: Fragment;
+    out.position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fragment(in: Fragment) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+/// The vertical field of view of the camera in radians.
+const FOV: f32 = 0.8;
+
+/// The brightness of faces turned away from the light, like
+/// `preview::Preview::ambient`.
+const AMBIENT: f32 = 0.35;
+
+/// The format of the depth buffer.
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// A corner of a voxel face, laid out like the vertex inputs of `SHADER`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 3],
+    color: [u8; 4],
+}
+
+/// Open a window showing `buf`, and block until it's closed.
+///
+/// Drag with the left mouse button to orbit the camera around the middle
+/// of the buffer, scroll to zoom, and press escape to close the window.
+/// Only faces between visible and empty voxels are drawn, shaded by a
+/// light over the camera's starting position like `preview::Preview`.
+///
+/// Most platforms only allow one event loop per process, and only on the
+/// main thread, so call this once, from `main`.
+///
+/// # Errors
+///
+/// Returns an error if there's no display to open a window on, or no GPU
+/// to draw with.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use voxgen::l_system::{presets, RenderOptions};
+/// let buf = RenderOptions::new()
+///     .derivation_length(3)
+///     .step_size(1.0)
+///     .auto_fit(true)
+///     .render(&presets::plant_a())?;
+/// voxgen::viewer::show(&buf)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn show(buf: &ArrayVoxelBuffer<Rgba>) -> io::Result<()> {
+    let mut viewer = Viewer::new("voxgen".to_string(), None);
+    viewer.set_buffer(buf);
+    run(viewer)
+}
+
+/// Open a window showing the renders of `watch`, re-rendering the watched
+/// definition file whenever it changes, and block until it's closed.
+///
+/// The camera keeps it's position between renders of the same size. Errors
+/// are shown in the window title, keeping the last good render in view
+/// until they're fixed. See `show` for the controls.
+///
+/// # Errors
+///
+/// Returns an error if there's no display to open a window on, or no GPU
+/// to draw with.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use voxgen::l_system::watch::Watch;
+/// voxgen::viewer::watch(Watch::new("test/systems/koch.toml"))?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn watch(mut watch: Watch) -> io::Result<()> {
+    let title = watch.path().display().to_string();
+    let result = watch.poll();
+    let mut viewer = Viewer::new(title, Some(watch));
+    if let Some(result) = result {
+        viewer.set_result(result);
+    }
+    run(viewer)
+}
+
+/// Run the event loop of `viewer` until it's window closes.
+fn run(mut viewer: Viewer) -> io::Result<()> {
+    let event_loop = EventLoop::new().map_err(io::Error::other)?;
+    event_loop.run_app(&mut viewer).map_err(io::Error::other)?;
+    match viewer.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Build the faces between the visible and empty voxels of `buf`, as
+/// vertices and the indices of their triangles.
+fn mesh(buf: &ArrayVoxelBuffer<Rgba>) -> (Vec<Vertex>, Vec<u32>) {
+    let light = direction(FRAC_PI_4 + 0.6, 1.0);
+    let surface = SurfaceMesh::from_buffer(buf);
+    let vertices = surface
+        .positions()
+        .iter()
+        .zip(surface.normals())
+        .zip(surface.colors())
+        .map(|((&position, n), voxel)| {
+            let dot: f32 = (0..3).map(|i| n[i] * light[i]).sum();
+            let factor = AMBIENT + (1.0 - AMBIENT) * dot.max(0.0);
+            let mut color = voxel.0;
+            for c in &mut color[..3] {
+                *c = (*c as f32 * factor).round() as u8;
+            }
+            color[3] = 255;
+            Vertex { position, color }
+        })
+        .collect();
+    (vertices, surface.indices().to_vec())
+}
+
+/// The window, orbit camera and mesh of a running viewer.
+struct Viewer {
+    name: String,
+    title: String,
+    watch: Option<Watch>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    dimensions: Option<(u32, u32, u32)>,
+    center: [f32; 3],
+    azimuth: f32,
+    elevation: f32,
+    distance: f32,
+    dragging: bool,
+    cursor: Option<PhysicalPosition<f64>>,
+    state: Option<State>,
+    error: Option<io::Error>,
+}
+
+/// The window and GPU resources of a viewer, created once the event loop
+/// starts.
+struct State {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    vertices: wgpu::Buffer,
+    indices: wgpu::Buffer,
+    index_count: u32,
+    camera: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    depth: wgpu::TextureView,
+}
+
+impl Viewer {
+    /// Create a viewer titled `name` with an empty mesh, re-rendering
+    /// `watch` if there is one.
+    fn new(name: String, watch: Option<Watch>) -> Viewer {
+        Viewer {
+            title: name.clone(),
+            name,
+            watch,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            dimensions: None,
+            center: [0.0; 3],
+            azimuth: FRAC_PI_4,
+            elevation: FRAC_PI_6,
+            distance: 1.0,
+            dragging: false,
+            cursor: None,
+            state: None,
+            error: None,
+        }
+    }
+
+    /// Show `buf`, framing the camera on it if it's a different size from
+    /// the last buffer.
+    fn set_buffer(&mut self, buf: &ArrayVoxelBuffer<Rgba>) {
+        let dimensions = buf.dimensions();
+        if self.dimensions != Some(dimensions) {
+            let (size_x, size_y, size_z) = dimensions;
+            let size = [size_x as f32, size_y as f32, size_z as f32];
+            let radius = (size.iter().map(|s| s * s).sum::<f32>()).sqrt() / 2.0;
+            self.center = size.map(|s| s / 2.0);
+            self.distance = radius.max(1.0) / (FOV / 2.0).tan();
+            self.dimensions = Some(dimensions);
+        }
+        (self.vertices, self.indices) = mesh(buf);
+        if let Some(state) = &mut self.state {
+            (state.vertices, state.indices) =
+                mesh_buffers(&state.device, &self.vertices, &self.indices);
+            state.index_count = self.indices.len() as u32;
+            state.window.request_redraw();
+        }
+    }
+
+    /// Show a render of the watched file, or it's error in the title.
+    fn set_result(&mut self, result: crate::Result<ArrayVoxelBuffer<Rgba>>) {
+        self.title = match result {
+            Ok(buf) => {
+                self.set_buffer(&buf);
+                self.name.clone()
+            }
+            Err(e) => format!("{}: {}", self.name, e),
+        };
+        if let Some(state) = &self.state {
+            state.window.set_title(&self.title);
+        }
+    }
+
+    /// Open the window and set up drawing to it.
+    fn create_state(&self, event_loop: &ActiveEventLoop) -> io::Result<State> {
+        let attributes = Window::default_attributes().with_title(&self.title);
+        let window = Arc::new(
+            event_loop
+                .create_window(attributes)
+                .map_err(io::Error::other)?,
+        );
+        let instance = wgpu::Instance::default();
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(io::Error::other)?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .map_err(io::Error::other)?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))
+                .map_err(io::Error::other)?;
+        let size = window.inner_size();
+        let mut config = surface
+            .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+            .ok_or_else(|| io::Error::other("the surface isn't supported by the GPU"))?;
+        // Voxel colors are already in sRGB, so write them as they are.
+        let formats = surface.get_capabilities(&adapter).formats;
+        if let Some(&format) = formats.iter().find(|f| !f.is_srgb()) {
+            config.format = format;
+        }
+        surface.configure(&device, &config);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("viewer"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("viewer"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vertex"),
+                compilation_options: Default::default(),
+                buffers: &[Some(wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Unorm8x4],
+                })],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: Some(true),
+                depth_compare: Some(wgpu::CompareFunction::Less),
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fragment"),
+                compilation_options: Default::default(),
+                targets: &[Some(config.format.into())],
+            }),
+            multiview_mask: None,
+            cache: None,
+        });
+        let (vertices, indices) = mesh_buffers(&device, &self.vertices, &self.indices);
+        let camera = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("viewer camera"),
+            contents: bytemuck::cast_slice(&self.view_proj(1.0)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("viewer camera"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            }],
+        });
+        let depth = depth_view(&device, &config);
+        Ok(State {
+            window,
+            surface,
+            device,
+            queue,
+            config,
+            pipeline,
+            vertices,
+            indices,
+            index_count: self.indices.len() as u32,
+            camera,
+            bind_group,
+            depth,
+        })
+    }
+
+    /// Get the view and projection matrix of the camera for an image of
+    /// `aspect` width over height, in column major order.
+    fn view_proj(&self, aspect: f32) -> [[f32; 4]; 4] {
+        let back = direction(self.azimuth, self.elevation);
+        let eye = add(self.center, scale(back, self.distance));
+        let forward = back.map(|c| -c);
+        let right = normalize(cross(forward, [0.0, 0.0, 1.0]));
+        let up = cross(right, forward);
+        let dot = |a: [f32; 3], b: [f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+        // The rows of the view matrix, moving the eye to the origin looking
+        // down -z.
+        let view = [
+            [right[0], right[1], right[2], -dot(right, eye)],
+            [up[0], up[1], up[2], -dot(up, eye)],
+            [back[0], back[1], back[2], -dot(back, eye)],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        // The rows of the projection matrix, mapping depths from `near` to
+        // `far` to 0.0 to 1.0.
+        let (near, far) = (self.distance / 100.0, self.distance * 4.0);
+        let f = 1.0 / (FOV / 2.0).tan();
+        let proj = [
+            [f / aspect, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0, far / (near - far), near * far / (near - far)],
+            [0.0, 0.0, -1.0, 0.0],
+        ];
+        let mut columns = [[0.0; 4]; 4];
+        for (column, out) in columns.iter_mut().enumerate() {
+            for (row, c) in out.iter_mut().enumerate() {
+                *c = (0..4).map(|k| proj[row][k] * view[k][column]).sum();
+            }
+        }
+        columns
+    }
+
+    /// Draw a frame, reconfiguring the surface if it's out of date.
+    fn redraw(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let aspect = state.config.width as f32 / state.config.height as f32;
+        let view_proj = self.view_proj(aspect);
+        let state = self.state.as_mut().unwrap();
+        state
+            .queue
+            .write_buffer(&state.camera, 0, bytemuck::cast_slice(&view_proj));
+        let frame = match state.surface.get_current_texture() {
+            wgpu::CurrentSurfaceTexture::Success(frame)
+            | wgpu::CurrentSurfaceTexture::Suboptimal(frame) => frame,
+            wgpu::CurrentSurfaceTexture::Outdated | wgpu::CurrentSurfaceTexture::Lost => {
+                state.surface.configure(&state.device, &state.config);
+                state.window.request_redraw();
+                return;
+            }
+            _ => return,
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("viewer"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.08,
+                            g: 0.08,
+                            b: 0.1,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &state.depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+            pass.set_pipeline(&state.pipeline);
+            pass.set_bind_group(0, &state.bind_group, &[]);
+            pass.set_vertex_buffer(0, state.vertices.slice(..));
+            pass.set_index_buffer(state.indices.slice(..), wgpu::IndexFormat::Uint32);
+            pass.draw_indexed(0..state.index_count, 0, 0..1);
+        }
+        state.queue.submit([encoder.finish()]);
+        state.queue.present(frame);
+    }
+
+    /// Ask for a new frame after the camera moves.
+    fn request_redraw(&self) {
+        if let Some(state) = &self.state {
+            state.window.request_redraw();
+        }
+    }
+}
+
+impl ApplicationHandler for Viewer {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.state.is_some() {
+            return;
+        }
+        match self.create_state(event_loop) {
+            Ok(state) => self.state = Some(state),
+            Err(error) => {
+                self.error = Some(error);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::Escape),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => event_loop.exit(),
+            WindowEvent::Resized(size) => {
+                if let Some(state) = &mut self.state {
+                    state.config.width = size.width.max(1);
+                    state.config.height = size.height.max(1);
+                    state.surface.configure(&state.device, &state.config);
+                    state.depth = depth_view(&state.device, &state.config);
+                    state.window.request_redraw();
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => self.dragging = state == ElementState::Pressed,
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some(cursor)) = (self.dragging, self.cursor) {
+                    let limit = FRAC_PI_2 - 0.001;
+                    self.azimuth -= (position.x - cursor.x) as f32 * 0.01;
+                    self.elevation = (self.elevation + (position.y - cursor.y) as f32 * 0.01)
+                        .clamp(-limit, limit);
+                    self.request_redraw();
+                }
+                self.cursor = Some(position);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0,
+                };
+                self.distance *= 0.9f32.powf(lines);
+                self.request_redraw();
+            }
+            WindowEvent::RedrawRequested => self.redraw(),
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(watch) = &mut self.watch else {
+            return;
+        };
+        let interval = watch.poll_interval();
+        if let Some(result) = watch.poll() {
+            self.set_result(result);
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + interval));
+    }
+}
+
+/// Upload a mesh to vertex and index buffers.
+fn mesh_buffers(
+    device: &wgpu::Device,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> (wgpu::Buffer, wgpu::Buffer) {
+    // Buffers can't be empty, so an empty mesh gets one degenerate
+    // triangle.
+    let (vertices, indices) = if indices.is_empty() {
+        (
+            &[<Vertex as bytemuck::Zeroable>::zeroed()][..],
+            &[0, 0, 0][..],
+        )
+    } else {
+        (vertices, indices)
+    };
+    let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("viewer vertices"),
+        contents: bytemuck::cast_slice(vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("viewer indices"),
+        contents: bytemuck::cast_slice(indices),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+    (vertices, indices)
+}
+
+/// Create a depth buffer the size of the surface.
+fn depth_view(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("viewer depth"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
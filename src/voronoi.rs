@@ -0,0 +1,119 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+
+/// A point in voxel coordinates.
+pub type Point = [f32; 3];
+
+/// The nearest sites of a Voronoi diagram to a point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Nearest {
+    /// The index of the nearest site, naming the cell containing the point.
+    pub site: usize,
+    /// The distance to the nearest site.
+    pub distance: f32,
+    /// The distance to the second nearest site, or infinity if there is
+    /// only one.
+    ///
+    /// It's equal to `distance` on the border between two cells, so the
+    /// difference of the two grows with the distance from the border.
+    pub second_distance: f32,
+}
+
+/// A Voronoi diagram, partitioning space into cells of the points closest
+/// to each of a set of sites.
+///
+/// # Examples
+///
+/// Shatter a block of stone, leaving cracks between the pieces.
+/// ```
+/// # use voxgen::voronoi::Voronoi;
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+/// let voronoi = Voronoi::random(24, [32.0, 32.0, 32.0], 9);
+/// let mut buf = ArrayVoxelBuffer::new(32, 32, 32);
+/// voronoi.partition(&mut buf, |_, nearest| {
+///     let shade = 100 + 20 * (nearest.site % 5) as u8;
+///     let cracked = nearest.second_distance - nearest.distance < 1.0;
+///     (!cracked).then_some(Rgba([shade, shade, shade + 10, 255]))
+/// });
+/// buf.save("test/volumes/voronoi.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Voronoi {
+    sites: Vec<Point>,
+}
+
+impl Voronoi {
+    /// Create a Voronoi diagram of `sites`.
+    ///
+    /// Sites scattered with `scatter::PoissonDisk` give cells of similar
+    /// size.
+    pub fn new(sites: Vec<Point>) -> Voronoi {
+        Voronoi { sites }
+    }
+
+    /// Create a Voronoi diagram of `count` sites placed uniformly at random
+    /// in the volume from the origin to `size`, with a random number
+    /// generator seeded with `seed`.
+    pub fn random(count: usize, size: Point, seed: u64) -> Voronoi {
+        let mut rng = Seed::new(seed).rng();
+        let sites = (0..count)
+            .map(|_| size.map(|s| rng.gen::<f32>() * s))
+            .collect();
+        Voronoi { sites }
+    }
+
+    pub fn sites(&self) -> &[Point] {
+        &self.sites
+    }
+
+    /// Find the nearest sites to `p`, or `None` if there are no sites.
+    pub fn nearest(&self, p: Point) -> Option<Nearest> {
+        let mut nearest: Option<Nearest> = None;
+        for (site, s) in self.sites.iter().enumerate() {
+            let distance =
+                ((p[0] - s[0]).powi(2) + (p[1] - s[1]).powi(2) + (p[2] - s[2]).powi(2)).sqrt();
+            nearest = Some(match nearest {
+                None => Nearest {
+                    site,
+                    distance,
+                    second_distance: f32::INFINITY,
+                },
+                Some(n) if distance < n.distance => Nearest {
+                    site,
+                    distance,
+                    second_distance: n.distance,
+                },
+                Some(n) => Nearest {
+                    second_distance: n.second_distance.min(distance),
+                    ..n
+                },
+            });
+        }
+        nearest
+    }
+
+    /// Color every voxel of `buf` by the cell containing it's center, with
+    /// the color `colorizer` gives for the center and it's nearest sites.
+    ///
+    /// Voxels the colorizer gives `None` for are left unchanged, so cells
+    /// can be assigned biomes, or cracks left along their borders.
+    pub fn partition<F>(&self, buf: &mut ArrayVoxelBuffer<Rgba>, colorizer: F)
+    where
+        F: Fn(Point, &Nearest) -> Option<Rgba>,
+    {
+        let (size_x, size_y, size_z) = buf.dimensions();
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let p = [x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
+                    let color = self.nearest(p).and_then(|nearest| colorizer(p, &nearest));
+                    if let Some(color) = color {
+                        *buf.voxel_mut(x, y, z) = color;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,27 @@
-use std::collections::HashMap;
-use std::fs::write;
-use std::io::Write;
-use std::marker::PhantomData;
-use std::ops::Range;
+use crate::color::Palette;
+#[cfg(feature = "std")]
+use crate::color::MAGICAVOXEL_PALETTE;
+#[cfg(not(feature = "std"))]
+use crate::compat::prelude::*;
+use crate::compat::HashMap;
+#[cfg(feature = "std")]
+use crate::progress::{Progress, Stage};
+use alloc::collections::BTreeMap;
+use core::hash::{BuildHasherDefault, Hasher};
+use core::marker::PhantomData;
+use core::ops::{Add, AddAssign, Mul, Range};
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use image::RgbaImage;
+use palette::{LinSrgb, Srgb};
+#[cfg(feature = "std")]
+use rayon::prelude::*;
+use serde::Serialize;
+#[cfg(feature = "std")]
+use std::fs::{read, File};
+#[cfg(feature = "std")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "std")]
 use std::path::Path;
 
 /// A generic voxel buffer.
@@ -31,6 +50,99 @@ pub trait VoxelBuffer {
     fn voxel_mut(&mut self, x: u32, y: u32, z: u32) -> &mut Self::Voxel;
 }
 
+/// A read-only source of voxels, computed or stored.
+///
+/// Sources can be saved, composited into buffers and copied into new
+/// buffers directly, so procedural content such as noise or signed
+/// distance fields can be exported without a dense buffer of it's own. Make
+/// a source from a function with `from_fn`.
+pub trait VoxelSource {
+    type Voxel;
+
+    /// Get the source dimensions.
+    ///
+    /// Returns a tuple `(size_x, size_y, size_z)`.
+    fn source_dimensions(&self) -> (u32, u32, u32);
+
+    /// Get the voxel at location (`x`, `y`, `z`).
+    ///
+    /// Only called with locations inside the source dimensions.
+    fn sample(&self, x: u32, y: u32, z: u32) -> Self::Voxel;
+}
+
+/// A voxel source computing every voxel with a function, made with
+/// `from_fn`.
+#[derive(Clone, Debug)]
+pub struct FnSource<F> {
+    dimensions: (u32, u32, u32),
+    f: F,
+}
+
+impl<F, T> VoxelSource for FnSource<F>
+where
+    F: Fn(u32, u32, u32) -> T,
+{
+    type Voxel = T;
+
+    fn source_dimensions(&self) -> (u32, u32, u32) {
+        self.dimensions
+    }
+
+    fn sample(&self, x: u32, y: u32, z: u32) -> T {
+        (self.f)(x, y, z)
+    }
+}
+
+/// Make a voxel source of `dimensions` whose voxel at (`x`, `y`, `z`) is
+/// `f(x, y, z)`.
+///
+/// # Examples
+///
+/// Save a sphere without filling a buffer.
+/// ```
+/// # use voxgen::voxel_buffer::{from_fn, save_source, Rgba};
+/// let sphere = from_fn((32, 32, 32), |x, y, z| {
+///     let d = [x, y, z].map(|c| c as f32 - 15.5);
+///     if d[0] * d[0] + d[1] * d[1] + d[2] * d[2] < 14.0 * 14.0 {
+///         Rgba([200, 80, 60, 255])
+///     } else {
+///         Rgba([0, 0, 0, 0])
+///     }
+/// });
+/// save_source(&sphere, "test/volumes/source_sphere.vox")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn from_fn<F, T>(dimensions: (u32, u32, u32), f: F) -> FnSource<F>
+where
+    F: Fn(u32, u32, u32) -> T,
+{
+    FnSource { dimensions, f }
+}
+
+/// Save the voxels of `source` as a MagicaVoxel .vox file to `path`, like
+/// `ArrayVoxelBuffer::save`.
+///
+/// Every voxel is computed once, one z slice per task.
+#[cfg(feature = "std")]
+pub fn save_source<S, P>(source: &S, path: P) -> std::io::Result<()>
+where
+    S: VoxelSource<Voxel = Rgba> + Sync,
+    P: AsRef<Path>,
+{
+    let (size_x, size_y, _) = source.source_dimensions();
+    write_vox(
+        path,
+        source.source_dimensions(),
+        |z| {
+            (0..size_y)
+                .flat_map(move |y| (0..size_x).map(move |x| ((x, y, z), source.sample(x, y, z))))
+                .filter(|(_, rgba)| rgba.0[3] != 0)
+        },
+        None,
+        |_| (),
+    )
+}
+
 /// A generic view of a voxel byte array.
 pub trait Voxel {
     const SIZE: u8;
@@ -49,7 +161,10 @@ pub trait Voxel {
 pub const CHANNEL_COUNT_RGBA: usize = 4;
 
 /// An RGBA voxel.
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+///
+/// Parse hex colors and CSS color names with `Rgba::from_hex`,
+/// `Rgba::named` or `str::parse`, which serde deserializes too.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize)]
 pub struct Rgba(pub [u8; CHANNEL_COUNT_RGBA]);
 
 impl Voxel for Rgba {
@@ -71,6 +186,431 @@ impl Voxel for Rgba {
     }
 }
 
+/// A hasher for palette lookups, mixing the few bytes of a color with a
+/// single multiply instead of SipHash's rounds.
+///
+/// It isn't resistant to collision attacks, which doesn't matter for
+/// colors of our own buffers.
+#[derive(Default)]
+struct ColorHasher(u64);
+
+impl Hasher for ColorHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.0 = (self.0.rotate_left(5) ^ u64::from_le_bytes(word))
+                .wrapping_mul(0x517c_c1b7_2722_0a95);
+        }
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A map from colors to palette indices, hashed with `ColorHasher`.
+type ColorMap<I> = HashMap<Rgba, I, BuildHasherDefault<ColorHasher>>;
+
+/// A structural metadata voxel recorded alongside a drawing.
+///
+/// Stores the branch depth and segment id of the stroke that last drew a
+/// voxel as two little-endian `u32` values. Segment ids start at 1, so a
+/// segment id of 0 marks a voxel that was never drawn.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Segment(pub [u8; 8]);
+
+impl Segment {
+    /// Create a metadata voxel for the given branch `depth` and `segment` id.
+    pub fn new(depth: u32, segment: u32) -> Segment {
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&depth.to_le_bytes());
+        bytes[4..].copy_from_slice(&segment.to_le_bytes());
+        Segment(bytes)
+    }
+
+    /// Get the branch depth of the stroke that drew this voxel.
+    pub fn depth(&self) -> u32 {
+        u32::from_le_bytes([self.0[0], self.0[1], self.0[2], self.0[3]])
+    }
+
+    /// Get the id of the segment that drew this voxel.
+    pub fn segment(&self) -> u32 {
+        u32::from_le_bytes([self.0[4], self.0[5], self.0[6], self.0[7]])
+    }
+}
+
+impl Voxel for Segment {
+    const SIZE: u8 = 8;
+
+    #[inline(always)]
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn from_slice(slice: &[u8]) -> &Segment {
+        assert_eq!(slice.len(), Self::SIZE as usize);
+        unsafe { &*(slice.as_ptr() as *const Segment) }
+    }
+
+    fn from_slice_mut(slice: &mut [u8]) -> &mut Segment {
+        assert_eq!(slice.len(), Self::SIZE as usize);
+        unsafe { &mut *(slice.as_mut_ptr() as *mut Segment) }
+    }
+}
+
+/// A high dynamic range RGBA voxel of linear light, for accumulating light
+/// bakes and densities over many passes without clipping at 8 bits.
+///
+/// Stores the red, green, blue and transparency channels as four
+/// little-endian `f32` values. Color channels may go over 1.0, and are
+/// brought back into range with a `Tonemap` when converting to `Rgba` for
+/// export, see `ArrayVoxelBuffer::tonemap`.
+///
+/// Adding voxels adds their light and keeps the larger transparency, and
+/// multiplying a voxel by a factor scales it's light.
+#[derive(Clone, Copy, PartialEq)]
+pub struct RgbaF32(pub [u8; 16]);
+
+impl RgbaF32 {
+    /// Create a voxel from linear `[r, g, b, a]` channels.
+    pub fn new(channels: [f32; 4]) -> RgbaF32 {
+        let mut bytes = [0; 16];
+        for (chunk, channel) in bytes.chunks_exact_mut(4).zip(channels) {
+            chunk.copy_from_slice(&channel.to_le_bytes());
+        }
+        RgbaF32(bytes)
+    }
+
+    /// Get the linear `[r, g, b, a]` channels.
+    pub fn channels(&self) -> [f32; 4] {
+        core::array::from_fn(|i| f32::from_le_bytes(self.0[i * 4..i * 4 + 4].try_into().unwrap()))
+    }
+}
+
+/// Decodes the sRGB colors of `Rgba` to linear light.
+impl From<Rgba> for RgbaF32 {
+    fn from(Rgba([r, g, b, a]): Rgba) -> RgbaF32 {
+        let linear: LinSrgb = Srgb::new(r, g, b).into_format::<f32>().into_linear();
+        RgbaF32::new([linear.red, linear.green, linear.blue, a as f32 / 255.0])
+    }
+}
+
+impl Add for RgbaF32 {
+    type Output = RgbaF32;
+
+    fn add(self, other: RgbaF32) -> RgbaF32 {
+        let ([r, g, b, a], [or, og, ob, oa]) = (self.channels(), other.channels());
+        RgbaF32::new([r + or, g + og, b + ob, a.max(oa)])
+    }
+}
+
+impl AddAssign for RgbaF32 {
+    fn add_assign(&mut self, other: RgbaF32) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<f32> for RgbaF32 {
+    type Output = RgbaF32;
+
+    fn mul(self, factor: f32) -> RgbaF32 {
+        let [r, g, b, a] = self.channels();
+        RgbaF32::new([r * factor, g * factor, b * factor, a])
+    }
+}
+
+impl core::fmt::Debug for RgbaF32 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("RgbaF32").field(&self.channels()).finish()
+    }
+}
+
+impl Voxel for RgbaF32 {
+    const SIZE: u8 = 16;
+
+    #[inline(always)]
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn from_slice(slice: &[u8]) -> &RgbaF32 {
+        assert_eq!(slice.len(), Self::SIZE as usize);
+        unsafe { &*(slice.as_ptr() as *const RgbaF32) }
+    }
+
+    fn from_slice_mut(slice: &mut [u8]) -> &mut RgbaF32 {
+        assert_eq!(slice.len(), Self::SIZE as usize);
+        unsafe { &mut *(slice.as_mut_ptr() as *mut RgbaF32) }
+    }
+}
+
+/// How high dynamic range light is mapped into the range of 8 bit colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tonemap {
+    /// Clip light over 1.0.
+    Clamp,
+    /// Compress light with `x / (1 + x)`, keeping detail in highlights.
+    #[default]
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, with more contrast than
+    /// `Reinhard`.
+    Aces,
+}
+
+impl Tonemap {
+    /// Map the linear light `value` to the range from 0.0 to 1.0.
+    pub fn apply(self, value: f32) -> f32 {
+        let value = value.max(0.0);
+        let mapped = match self {
+            Tonemap::Clamp => value,
+            Tonemap::Reinhard => value / (1.0 + value),
+            Tonemap::Aces => {
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/// A named attachment point of a buffer, such as a door or a branch tip,
+/// for snapping parts together with `ArrayVoxelBuffer::attach`.
+///
+/// An anchor is a voxel on the edge of a part and the direction out of the
+/// part there. It may lie outside of the buffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Anchor {
+    position: (i32, i32, i32),
+    direction: [f32; 3],
+}
+
+impl Anchor {
+    /// Create an anchor at voxel `position`, facing out of it's part along
+    /// `direction`.
+    pub fn new(position: (i32, i32, i32), direction: [f32; 3]) -> Anchor {
+        Anchor {
+            position,
+            direction,
+        }
+    }
+
+    pub fn position(&self) -> (i32, i32, i32) {
+        self.position
+    }
+
+    pub fn direction(&self) -> [f32; 3] {
+        self.direction
+    }
+
+    /// Get the voxel next to the anchor along the largest component of it's
+    /// direction, where the anchor of an attached part goes.
+    pub fn next(&self) -> (i32, i32, i32) {
+        let (x, y, z) = self.position;
+        let [dx, dy, dz] = self.direction;
+        let step = |d: f32| d.signum() as i32;
+        let (ax, ay, az) = (dx.abs(), dy.abs(), dz.abs());
+        if ax >= ay && ax >= az {
+            (x + step(dx), y, z)
+        } else if ay >= az {
+            (x, y + step(dy), z)
+        } else {
+            (x, y, z + step(dz))
+        }
+    }
+
+    /// Get the anchor moved by `offset`.
+    fn translated(&self, (x, y, z): (i32, i32, i32)) -> Anchor {
+        let (px, py, pz) = self.position;
+        Anchor::new((px + x, py + y, pz + z), self.direction)
+    }
+}
+
+/// A plane of symmetry through the center of a buffer, named by the axis
+/// it's perpendicular to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Plane {
+    /// The plane perpendicular to the x axis, mirroring west and east.
+    X,
+    /// The plane perpendicular to the y axis, mirroring south and north.
+    Y,
+    /// The plane perpendicular to the z axis, mirroring bottom and top.
+    Z,
+}
+
+/// How a new voxel value is combined with the value already in a buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrite the existing voxel.
+    #[default]
+    Replace,
+    /// Only write to empty voxels, keeping any existing non-empty voxel.
+    SkipOccupied,
+    /// Composite the new voxel over the existing voxel by its transparency
+    /// channel.
+    Alpha,
+    /// Keep the maximum of each channel.
+    Max,
+}
+
+impl BlendMode {
+    /// Combine the new voxel `src` with the existing voxel `dst`.
+    pub fn blend(self, dst: Rgba, src: Rgba) -> Rgba {
+        match self {
+            BlendMode::Replace => src,
+            BlendMode::SkipOccupied => {
+                if dst.0[3] > 0 {
+                    dst
+                } else {
+                    src
+                }
+            }
+            BlendMode::Alpha => {
+                let src_a = src.0[3] as f32 / 255.0;
+                let dst_a = dst.0[3] as f32 / 255.0 * (1.0 - src_a);
+                let a = src_a + dst_a;
+                if a == 0.0 {
+                    return Rgba([0; CHANNEL_COUNT_RGBA]);
+                }
+                let mut rgba = [0; CHANNEL_COUNT_RGBA];
+                for (c, value) in rgba.iter_mut().enumerate().take(3) {
+                    *value =
+                        ((src.0[c] as f32 * src_a + dst.0[c] as f32 * dst_a) / a).round() as u8;
+                }
+                rgba[3] = (a * 255.0).round() as u8;
+                Rgba(rgba)
+            }
+            BlendMode::Max => {
+                let mut rgba = dst.0;
+                for (d, s) in rgba.iter_mut().zip(src.0) {
+                    *d = (*d).max(s);
+                }
+                Rgba(rgba)
+            }
+        }
+    }
+}
+
+/// The most bytes the storage array of a buffer may use, or `usize::MAX`
+/// for no limit.
+static MEMORY_BUDGET: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Set the most bytes the storage array of any new buffer may use, or
+/// `None` for no limit, the default.
+///
+/// Buffers created with `ArrayVoxelBuffer::try_new` over the budget return
+/// an error, and buffers created with `ArrayVoxelBuffer::new` panic. Every
+/// buffer is checked alone, so this doesn't bound the total memory used by
+/// several buffers.
+pub fn set_memory_budget(bytes: Option<usize>) {
+    MEMORY_BUDGET.store(bytes.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Get the most bytes the storage array of any new buffer may use, or
+/// `None` if there's no limit.
+pub fn memory_budget() -> Option<usize> {
+    match MEMORY_BUDGET.load(Ordering::Relaxed) {
+        usize::MAX => None,
+        bytes => Some(bytes),
+    }
+}
+
+/// An error returned when the storage array of a buffer can't be created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapacityError {
+    /// The storage array is larger than the maximum size of a vector.
+    Overflow,
+    /// The storage array of `bytes` bytes is over the memory `budget`.
+    OverBudget { bytes: usize, budget: usize },
+}
+
+impl core::fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CapacityError::Overflow => write!(f, "buffer size overflows usize"),
+            CapacityError::OverBudget { bytes, budget } => {
+                write!(
+                    f,
+                    "buffer of {} bytes is over the {} byte budget",
+                    bytes, budget
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for CapacityError {}
+
+/// The largest side of a MagicaVoxel model, in voxels.
+const VOX_MAX_SIDE: u32 = 256;
+
+/// The most colors of a MagicaVoxel palette, since index 0 is empty.
+const VOX_MAX_COLORS: usize = 255;
+
+/// A limit of MagicaVoxel .vox files a buffer breaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxViolation {
+    /// A side of the buffer is over `max` voxels.
+    Dimensions {
+        dimensions: (u32, u32, u32),
+        max: u32,
+    },
+    /// The buffer has `count` visible voxels, more than the `max` of a
+    /// model.
+    Voxels { count: usize, max: usize },
+    /// The visible voxels have `count` colors, more than the `max` of a
+    /// palette.
+    Colors { count: usize, max: usize },
+}
+
+impl core::fmt::Display for VoxViolation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VoxViolation::Dimensions { dimensions, max } => write!(
+                f,
+                "dimensions {:?} are over {} voxels on a side",
+                dimensions, max
+            ),
+            VoxViolation::Voxels { count, max } => {
+                write!(f, "{} visible voxels are over {} voxels", count, max)
+            }
+            VoxViolation::Colors { count, max } => {
+                write!(f, "{} colors are over {} palette colors", count, max)
+            }
+        }
+    }
+}
+
+/// An error returned when a buffer breaks the limits of MagicaVoxel .vox
+/// files, with every limit it breaks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoxError {
+    violations: Vec<VoxViolation>,
+}
+
+impl VoxError {
+    /// Get the limits the buffer breaks.
+    pub fn violations(&self) -> &[VoxViolation] {
+        &self.violations
+    }
+}
+
+impl core::fmt::Display for VoxError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "buffer can't be saved as a MagicaVoxel model: ")?;
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for VoxError {}
+
 /// A generic array-based voxel buffer.
 ///
 /// Array-based voxel buffers are dense. Every voxel in the image has data
@@ -79,13 +619,54 @@ impl Voxel for Rgba {
 /// MagicaVoxel conventions, where voxel position `(0, 0, 0)` is in the bottom
 /// left corner closest to the camera. Increasing `x` moves to the right,
 /// increasing `y` moves away from the camera, and increasing `z` moves up.
+#[derive(Clone)]
 pub struct ArrayVoxelBuffer<T> {
     size_x: u32,
     size_y: u32,
     size_z: u32,
+    /// The bytes between voxels one apart along y.
+    stride_y: usize,
+    /// The bytes between voxels one apart along z.
+    stride_z: usize,
     data: Vec<u8>,
+    anchors: BTreeMap<String, Anchor>,
     _phantom: PhantomData<T>,
 }
+
+/// Buffers are equal when they have the same dimensions and voxels.
+/// Anchors aren't compared.
+impl<T> PartialEq for ArrayVoxelBuffer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.size_x, self.size_y, self.size_z) == (other.size_x, other.size_y, other.size_z)
+            && self.data == other.data
+    }
+}
+
+impl<T> Eq for ArrayVoxelBuffer<T> {}
+
+/// Summarizes the buffer by it's dimensions, voxel type and size in bytes,
+/// instead of listing every voxel.
+impl<T> core::fmt::Debug for ArrayVoxelBuffer<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ArrayVoxelBuffer")
+            .field("dimensions", &(self.size_x, self.size_y, self.size_z))
+            .field("voxel", &core::any::type_name::<T>())
+            .field("bytes", &self.data.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An empty buffer of 0 by 0 by 0 voxels, as a placeholder until a
+/// generator fills it in.
+impl<T> Default for ArrayVoxelBuffer<T>
+where
+    T: Voxel + Copy,
+{
+    fn default() -> Self {
+        Self::new(0, 0, 0)
+    }
+}
+
 impl<T> ArrayVoxelBuffer<T>
 where
     T: Voxel + Copy,
@@ -101,17 +682,313 @@ where
     /// # Panics
     ///
     /// Panics when the storage array is larger than the maximum size of a
-    /// vector.
+    /// vector, or than the global memory budget. Use `try_new` to handle
+    /// these cases.
     pub fn new(size_x: u32, size_y: u32, size_z: u32) -> ArrayVoxelBuffer<T> {
-        match Self::len(size_x, size_y, size_z) {
-            None => panic!("ArrayVoxelBuffer len overflows usize"),
-            Some(len) => Self {
-                size_x: size_x,
-                size_y: size_y,
-                size_z: size_z,
-                data: vec![0; len],
-                _phantom: PhantomData,
-            },
+        match Self::try_new(size_x, size_y, size_z) {
+            Err(CapacityError::Overflow) => panic!("ArrayVoxelBuffer len overflows usize"),
+            Err(error) => panic!("{}", error),
+            Ok(buf) => buf,
+        }
+    }
+
+    /// Create a new empty generic array-based voxel buffer like `new`,
+    /// returning an error instead of panicking when the storage array is
+    /// too large.
+    ///
+    /// The storage array must fit the global memory budget set with
+    /// `set_memory_budget`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, CapacityError, Rgba};
+    /// let buf = ArrayVoxelBuffer::<Rgba>::try_new(u32::MAX, u32::MAX, u32::MAX);
+    /// assert!(buf.is_err());
+    /// ```
+    pub fn try_new(size_x: u32, size_y: u32, size_z: u32) -> Result<Self, CapacityError> {
+        Self::try_new_with_budget(
+            size_x,
+            size_y,
+            size_z,
+            MEMORY_BUDGET.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Create a new empty generic array-based voxel buffer like `try_new`,
+    /// with a storage array of at most `budget` bytes instead of the global
+    /// memory budget.
+    ///
+    /// # Examples
+    ///
+    /// Reject a request for a buffer over 64 MiB.
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, CapacityError, Rgba};
+    /// let budget = 64 << 20;
+    /// let buf = ArrayVoxelBuffer::<Rgba>::try_new_with_budget(1024, 1024, 1024, budget);
+    /// assert_eq!(
+    ///     buf.err(),
+    ///     Some(CapacityError::OverBudget { bytes: 4 << 30, budget })
+    /// );
+    /// ```
+    pub fn try_new_with_budget(
+        size_x: u32,
+        size_y: u32,
+        size_z: u32,
+        budget: usize,
+    ) -> Result<Self, CapacityError> {
+        let len = Self::len(size_x, size_y, size_z)
+            .filter(|&len| len <= isize::MAX as usize)
+            .ok_or(CapacityError::Overflow)?;
+        if len > budget {
+            return Err(CapacityError::OverBudget { bytes: len, budget });
+        }
+        Ok(Self {
+            size_x,
+            size_y,
+            size_z,
+            stride_y: <T>::SIZE as usize * size_x as usize,
+            stride_z: <T>::SIZE as usize * size_x as usize * size_y as usize,
+            data: vec![0; len],
+            anchors: BTreeMap::new(),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Create a buffer of (`size_x`, `size_y`, `size_z`) voxels, all set to
+    /// `voxel`.
+    ///
+    /// # Panics
+    ///
+    /// Panics like `new` when the storage array is too large.
+    ///
+    /// # Examples
+    ///
+    /// Duplicate a buffer for a variant, and compare them.
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let stone = ArrayVoxelBuffer::filled(4, 4, 4, Rgba([128, 128, 128, 255]));
+    /// let mut mossy = stone.clone();
+    /// assert_eq!(stone, mossy);
+    /// *mossy.voxel_mut(0, 0, 3) = Rgba([60, 140, 50, 255]);
+    /// assert_ne!(stone, mossy);
+    /// assert!(format!("{:?}", mossy).contains("dimensions: (4, 4, 4)"));
+    /// ```
+    pub fn filled(size_x: u32, size_y: u32, size_z: u32, voxel: T) -> ArrayVoxelBuffer<T> {
+        let mut buf = Self::new(size_x, size_y, size_z);
+        buf.fill(voxel);
+        buf
+    }
+
+    /// Set every voxel to `voxel`.
+    pub fn fill(&mut self, voxel: T) {
+        Self::fill_bytes(&mut self.data, &voxel);
+    }
+
+    /// Set every byte of every voxel to 0, emptying RGBA buffers.
+    pub fn clear(&mut self) {
+        self.data.fill(0);
+    }
+
+    /// Set the voxels from `min` up to but not including `max` to `voxel`.
+    ///
+    /// The region is clipped to the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut buf = ArrayVoxelBuffer::new(8, 8, 8);
+    /// buf.fill(Rgba([120, 110, 100, 255]));
+    /// buf.fill_region((2, 2, 2), (6, 6, 10), Rgba([0, 0, 0, 0]));
+    /// assert_eq!(*buf.voxel(3, 3, 7), Rgba([0, 0, 0, 0]));
+    /// assert_eq!(*buf.voxel(1, 3, 7), Rgba([120, 110, 100, 255]));
+    /// ```
+    pub fn fill_region(&mut self, min: (u32, u32, u32), max: (u32, u32, u32), voxel: T) {
+        let max = (
+            max.0.min(self.size_x),
+            max.1.min(self.size_y),
+            max.2.min(self.size_z),
+        );
+        if min.0 >= max.0 || min.1 >= max.1 || min.2 >= max.2 {
+            return;
+        }
+        // Every row of the region is contiguous, so rows are filled whole.
+        let row_len = (max.0 - min.0) as usize * <T>::SIZE as usize;
+        for z in min.2..max.2 {
+            for y in min.1..max.1 {
+                let start = self.voxel_indices_unchecked(min.0, y, z).start;
+                Self::fill_bytes(&mut self.data[start..start + row_len], &voxel);
+            }
+        }
+    }
+
+    /// Create a buffer holding the voxels of `source`.
+    pub fn from_source<S>(source: &S) -> ArrayVoxelBuffer<T>
+    where
+        S: VoxelSource<Voxel = T>,
+    {
+        let (size_x, size_y, size_z) = source.source_dimensions();
+        let mut buf = Self::new(size_x, size_y, size_z);
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    // SAFETY: (x, y, z) is inside the buffer dimensions.
+                    unsafe { *buf.voxel_unchecked_mut(x, y, z) = source.sample(x, y, z) };
+                }
+            }
+        }
+        buf
+    }
+
+    /// Iterate over the positions of every voxel, x first, then y, then z.
+    pub fn coordinates(&self) -> impl Iterator<Item = (u32, u32, u32)> {
+        let (size_x, size_y, size_z) = (self.size_x, self.size_y, self.size_z);
+        (0..size_z)
+            .flat_map(move |z| (0..size_y).flat_map(move |y| (0..size_x).map(move |x| (x, y, z))))
+    }
+
+    /// Iterate over every voxel with it's position, x first, then y, then
+    /// z.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut buf = ArrayVoxelBuffer::new(4, 4, 4);
+    /// for ((x, y, z), voxel) in buf.enumerate_mut() {
+    ///     *voxel = Rgba([x as u8 * 60, y as u8 * 60, z as u8 * 60, 255]);
+    /// }
+    /// let (position, voxel) = buf.enumerate().nth(5).unwrap();
+    /// assert_eq!(position, (1, 1, 0));
+    /// assert_eq!(*voxel, Rgba([60, 60, 0, 255]));
+    /// ```
+    pub fn enumerate(&self) -> impl Iterator<Item = ((u32, u32, u32), &T)> + '_ {
+        self.coordinates().zip(
+            self.data
+                .chunks_exact(<T>::SIZE as usize)
+                .map(T::from_slice),
+        )
+    }
+
+    /// Iterate over every voxel mutably with it's position, x first, then
+    /// y, then z.
+    pub fn enumerate_mut(&mut self) -> impl Iterator<Item = ((u32, u32, u32), &mut T)> + '_ {
+        self.coordinates().zip(
+            self.data
+                .chunks_exact_mut(<T>::SIZE as usize)
+                .map(T::from_slice_mut),
+        )
+    }
+
+    /// Copy every voxel of `other` into `self`, empty voxels included, with
+    /// the origin of `other` at `origin`.
+    ///
+    /// Voxels falling outside of `self` are clipped.
+    pub fn paste(&mut self, other: &ArrayVoxelBuffer<T>, origin: (u32, u32, u32)) {
+        let max = (
+            origin.0.saturating_add(other.size_x).min(self.size_x),
+            origin.1.saturating_add(other.size_y).min(self.size_y),
+            origin.2.saturating_add(other.size_z).min(self.size_z),
+        );
+        if origin.0 >= max.0 || origin.1 >= max.1 || origin.2 >= max.2 {
+            return;
+        }
+        // Rows are contiguous in both buffers, so rows are copied whole.
+        let row_len = (max.0 - origin.0) as usize * <T>::SIZE as usize;
+        for z in origin.2..max.2 {
+            for y in origin.1..max.1 {
+                let start = self.voxel_indices_unchecked(origin.0, y, z).start;
+                let from = other
+                    .voxel_indices_unchecked(0, y - origin.1, z - origin.2)
+                    .start;
+                self.data[start..start + row_len]
+                    .copy_from_slice(&other.data[from..from + row_len]);
+            }
+        }
+    }
+
+    /// Name `anchor` as `name`, replacing the anchor named `name` before if
+    /// any.
+    pub fn set_anchor(&mut self, name: &str, anchor: Anchor) {
+        self.anchors.insert(name.to_string(), anchor);
+    }
+
+    /// Get the anchor named `name`.
+    pub fn anchor(&self, name: &str) -> Option<Anchor> {
+        self.anchors.get(name).copied()
+    }
+
+    /// Remove the anchor named `name`, returning it.
+    pub fn remove_anchor(&mut self, name: &str) -> Option<Anchor> {
+        self.anchors.remove(name)
+    }
+
+    /// Iterate over the anchors with their names, in name order.
+    pub fn anchors(&self) -> impl Iterator<Item = (&str, Anchor)> + '_ {
+        self.anchors
+            .iter()
+            .map(|(name, &anchor)| (name.as_str(), anchor))
+    }
+
+    /// Make `self` symmetric about `plane`, copying the half below the plane
+    /// mirrored over the half above it, empty voxels included.
+    ///
+    /// For odd sizes, the middle slice lies on the plane and is kept.
+    ///
+    /// # Examples
+    ///
+    /// Build the left wing of a ship and mirror it to the right.
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Plane, Rgba, VoxelBuffer};
+    /// let hull = Rgba([140, 150, 160, 255]);
+    /// let mut ship = ArrayVoxelBuffer::new(16, 9, 3);
+    /// ship.fill_region((0, 0, 1), (16, 5, 2), hull);
+    /// ship.fill_region((6, 0, 1), (10, 2, 2), Rgba([200, 60, 40, 255]));
+    /// ship.symmetrize(Plane::Y);
+    /// assert_eq!(*ship.voxel(7, 8, 1), Rgba([200, 60, 40, 255]));
+    /// assert_eq!(*ship.voxel(0, 6, 1), hull);
+    /// ship.save("test/volumes/symmetrize.vox")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn symmetrize(&mut self, plane: Plane) {
+        let (size_x, size_y, size_z) = (self.size_x, self.size_y, self.size_z);
+        let (half_x, half_y, half_z) = match plane {
+            Plane::X => (size_x / 2, size_y, size_z),
+            Plane::Y => (size_x, size_y / 2, size_z),
+            Plane::Z => (size_x, size_y, size_z / 2),
+        };
+        for z in 0..half_z {
+            for y in 0..half_y {
+                for x in 0..half_x {
+                    let (mx, my, mz) = match plane {
+                        Plane::X => (size_x - 1 - x, y, z),
+                        Plane::Y => (x, size_y - 1 - y, z),
+                        Plane::Z => (x, y, size_z - 1 - z),
+                    };
+                    // SAFETY: both positions are inside the buffer dimensions.
+                    unsafe {
+                        *self.voxel_unchecked_mut(mx, my, mz) = *self.voxel_unchecked(x, y, z)
+                    };
+                }
+            }
+        }
+    }
+
+    /// Fill `bytes` with copies of the bytes of `voxel`, doubling the copied
+    /// bytes every time so the copies run at memory speed.
+    fn fill_bytes(bytes: &mut [u8], voxel: &T) {
+        let pattern = voxel.as_slice();
+        if pattern.iter().all(|&b| b == pattern[0]) {
+            bytes.fill(pattern[0]);
+            return;
+        }
+        let mut filled = pattern.len().min(bytes.len());
+        bytes[..filled].copy_from_slice(&pattern[..filled]);
+        while filled < bytes.len() {
+            let n = filled.min(bytes.len() - filled);
+            bytes.copy_within(..n, filled);
+            filled += n;
         }
     }
 
@@ -133,12 +1010,77 @@ where
 
     #[inline(always)]
     fn voxel_indices_unchecked(&self, x: u32, y: u32, z: u32) -> Range<usize> {
-        let min_index_unsized = x as usize
-            + (y as usize * self.size_x as usize)
-            + (z as usize * self.size_x as usize * self.size_y as usize);
-        let min_index = min_index_unsized * <T>::SIZE as usize;
+        let min_index = x as usize * <T>::SIZE as usize
+            + y as usize * self.stride_y
+            + z as usize * self.stride_z;
         min_index..min_index + <T>::SIZE as usize
     }
+
+    /// Get a reference to the voxel at location (`x`, `y`, `z`) without
+    /// checking it's inside the buffer, for tight loops over known ranges.
+    ///
+    /// # Safety
+    ///
+    /// (`x`, `y`, `z`) must be inside the range of the volumetric image
+    /// dimensions (`size_x`, `size_y`, `size_z`). Reading outside it is
+    /// undefined behavior, even if the resulting location is inside the
+    /// storage array.
+    #[inline(always)]
+    pub unsafe fn voxel_unchecked(&self, x: u32, y: u32, z: u32) -> &T {
+        let indices = self.voxel_indices_unchecked(x, y, z);
+        <T>::from_slice(self.data.get_unchecked(indices))
+    }
+
+    /// Get a mutable reference to the voxel at location (`x`, `y`, `z`)
+    /// without checking it's inside the buffer, for tight loops over known
+    /// ranges.
+    ///
+    /// # Safety
+    ///
+    /// (`x`, `y`, `z`) must be inside the range of the volumetric image
+    /// dimensions (`size_x`, `size_y`, `size_z`). Writing outside it is
+    /// undefined behavior, even if the resulting location is inside the
+    /// storage array.
+    #[inline(always)]
+    pub unsafe fn voxel_unchecked_mut(&mut self, x: u32, y: u32, z: u32) -> &mut T {
+        let indices = self.voxel_indices_unchecked(x, y, z);
+        <T>::from_slice_mut(self.data.get_unchecked_mut(indices))
+    }
+
+    /// Get a reference to the voxel at location (`x`, `y`, `z`), or an
+    /// `Error::OutOfBounds` if it's outside the buffer.
+    pub fn try_voxel(&self, x: u32, y: u32, z: u32) -> crate::Result<&T> {
+        match self.voxel_indices(x, y, z) {
+            Some(indices) => Ok(<T>::from_slice(&self.data[indices])),
+            None => Err(self.out_of_bounds(x, y, z)),
+        }
+    }
+
+    /// Get a mutable reference to the voxel at location (`x`, `y`, `z`), or
+    /// an `Error::OutOfBounds` if it's outside the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba};
+    /// let mut buf = ArrayVoxelBuffer::new(4, 4, 4);
+    /// *buf.try_voxel_mut(3, 3, 3)? = Rgba([10, 20, 30, 255]);
+    /// assert!(buf.try_voxel_mut(4, 0, 0).is_err());
+    /// # Ok::<(), voxgen::Error>(())
+    /// ```
+    pub fn try_voxel_mut(&mut self, x: u32, y: u32, z: u32) -> crate::Result<&mut T> {
+        match self.voxel_indices(x, y, z) {
+            Some(indices) => Ok(<T>::from_slice_mut(&mut self.data[indices])),
+            None => Err(self.out_of_bounds(x, y, z)),
+        }
+    }
+
+    fn out_of_bounds(&self, x: u32, y: u32, z: u32) -> crate::Error {
+        crate::Error::OutOfBounds {
+            position: (x, y, z),
+            dimensions: (self.size_x, self.size_y, self.size_z),
+        }
+    }
 }
 
 impl<V> VoxelBuffer for ArrayVoxelBuffer<V>
@@ -174,92 +1116,861 @@ where
     }
 }
 
+impl<V> VoxelSource for ArrayVoxelBuffer<V>
+where
+    V: Voxel + Copy,
+{
+    type Voxel = V;
+
+    fn source_dimensions(&self) -> (u32, u32, u32) {
+        (self.size_x, self.size_y, self.size_z)
+    }
+
+    fn sample(&self, x: u32, y: u32, z: u32) -> V {
+        *self.voxel(x, y, z)
+    }
+}
+
+/// A fill function of a `BufferBuilder`, taking world coordinates.
+type FillFn<T> = Box<dyn Fn(i32, i32, i32) -> Option<T>>;
+
+/// A builder of array-based voxel buffers, setting up the dimensions,
+/// background and initial contents of a buffer in one expression.
+///
+/// Every voxel starts as the background voxel, then gets the voxel the fill
+/// function returns for it, if any. The fill function takes world
+/// coordinates, the buffer coordinates plus the origin, so a generator can
+/// fill any window of a larger world, like one chunk of terrain.
+///
+/// # Examples
+///
+/// Fill the ground of a window into the world centered on the world
+/// origin.
+/// ```
+/// # use voxgen::prelude::*;
+/// let buf = BufferBuilder::new(32, 32, 16)
+///     .background(Rgba([120, 180, 230, 40]))
+///     .origin(-16, -16, -8)
+///     .fill_with(|x, y, z| {
+///         let height = (x * x + y * y) / 64 - 4;
+///         (z < height.min(6)).then_some(Rgba([90, 140, 80, 255]))
+///     })
+///     .build()?;
+/// assert_eq!(*buf.voxel(16, 16, 0), Rgba([90, 140, 80, 255]));
+/// assert_eq!(*buf.voxel(16, 16, 15), Rgba([120, 180, 230, 40]));
+/// buf.save("test/volumes/buffer_builder.vox")?;
+/// # Ok::<(), voxgen::Error>(())
+/// ```
+pub struct BufferBuilder<T> {
+    dimensions: (u32, u32, u32),
+    background: Option<T>,
+    origin: (i32, i32, i32),
+    fill: Option<FillFn<T>>,
+}
+
+impl<T> BufferBuilder<T>
+where
+    T: Voxel + Copy,
+{
+    /// Create a builder of buffers of (`size_x`, `size_y`, `size_z`)
+    /// voxels, with an empty background, the origin at (0, 0, 0), and no
+    /// fill function.
+    pub fn new(size_x: u32, size_y: u32, size_z: u32) -> BufferBuilder<T> {
+        BufferBuilder {
+            dimensions: (size_x, size_y, size_z),
+            background: None,
+            origin: (0, 0, 0),
+            fill: None,
+        }
+    }
+
+    /// Set the `voxel` every voxel starts as.
+    pub fn background(&mut self, voxel: T) -> &mut Self {
+        self.background = Some(voxel);
+        self
+    }
+
+    /// Set the world coordinates of buffer voxel (0, 0, 0).
+    pub fn origin(&mut self, x: i32, y: i32, z: i32) -> &mut Self {
+        self.origin = (x, y, z);
+        self
+    }
+
+    /// Set the voxel at world coordinates (`x`, `y`, `z`) to `f(x, y, z)`,
+    /// keeping the background where it returns `None`.
+    pub fn fill_with<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(i32, i32, i32) -> Option<T> + 'static,
+    {
+        self.fill = Some(Box::new(f));
+        self
+    }
+
+    /// Create the buffer.
+    ///
+    /// Returns an error if the storage array is too large, like
+    /// `ArrayVoxelBuffer::try_new`.
+    pub fn build(&self) -> Result<ArrayVoxelBuffer<T>, CapacityError> {
+        let (size_x, size_y, size_z) = self.dimensions;
+        let mut buf = ArrayVoxelBuffer::try_new(size_x, size_y, size_z)?;
+        if let Some(background) = self.background {
+            buf.fill(background);
+        }
+        if let Some(fill) = &self.fill {
+            let (ox, oy, oz) = self.origin;
+            for z in 0..size_z {
+                for y in 0..size_y {
+                    for x in 0..size_x {
+                        let world = (ox + x as i32, oy + y as i32, oz + z as i32);
+                        if let Some(voxel) = fill(world.0, world.1, world.2) {
+                            // SAFETY: (x, y, z) is inside the buffer dimensions.
+                            unsafe { *buf.voxel_unchecked_mut(x, y, z) = voxel };
+                        }
+                    }
+                }
+            }
+        }
+        Ok(buf)
+    }
+}
+
+impl ArrayVoxelBuffer<RgbaF32> {
+    /// Convert to 8 bit colors for export, scaling the light by `exposure`,
+    /// mapping it into range with `tonemap` and encoding it as sRGB.
+    ///
+    /// Transparency is clamped to the range from 0.0 to 1.0, and voxels with
+    /// a transparency that rounds to 0 are empty.
+    ///
+    /// # Examples
+    ///
+    /// Light a wall with two lamps whose light overlaps, without clipping.
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, RgbaF32, Tonemap, VoxelBuffer};
+    /// let wall = ArrayVoxelBuffer::filled(32, 1, 8, Rgba([200, 180, 160, 255]));
+    /// let mut light = wall.to_hdr();
+    /// let albedo = light.clone();
+    /// for lamp in [10.0, 20.0] {
+    ///     for ((x, y, z), voxel) in light.enumerate_mut() {
+    ///         let d2 = (x as f32 - lamp).powi(2) + (z as f32 - 4.0).powi(2) + 1.0;
+    ///         *voxel += *albedo.voxel(x, y, z) * (40.0 / d2);
+    ///     }
+    /// }
+    /// assert!(light.voxel(15, 0, 4).channels()[0] > 1.0);
+    /// let lit = light.tonemap(Tonemap::Aces, 0.5);
+    /// assert!(lit.voxel(15, 0, 4).0[0] < 255);
+    /// assert!(lit.voxel(10, 0, 4).0[0] > lit.voxel(0, 0, 4).0[0]);
+    /// lit.save("test/volumes/tonemap.vox")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn tonemap(&self, tonemap: Tonemap, exposure: f32) -> ArrayVoxelBuffer<Rgba> {
+        let (size_x, size_y, size_z) = self.dimensions();
+        let mut buf = ArrayVoxelBuffer::new(size_x, size_y, size_z);
+        for ((_, voxel), (_, hdr)) in buf.enumerate_mut().zip(self.enumerate()) {
+            let [r, g, b, a] = hdr.channels();
+            let a = (a.clamp(0.0, 1.0) * 255.0).round() as u8;
+            if a == 0 {
+                continue;
+            }
+            let [r, g, b] = [r, g, b].map(|c| tonemap.apply(c * exposure));
+            let srgb: Srgb<u8> = Srgb::from_linear(LinSrgb::new(r, g, b));
+            *voxel = Rgba([srgb.red, srgb.green, srgb.blue, a]);
+        }
+        buf
+    }
+}
+
 /// An `ArrayVoxelBuffer` with RGBA voxels.
 impl ArrayVoxelBuffer<Rgba> {
+    /// Convert to a high dynamic range buffer of linear light, to accumulate
+    /// lighting passes in and `tonemap` back for export.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, Tonemap};
+    /// let mut buf = ArrayVoxelBuffer::filled(2, 2, 2, Rgba([200, 100, 50, 255]));
+    /// buf.fill_region((0, 0, 0), (1, 2, 2), Rgba([0, 0, 0, 0]));
+    /// assert_eq!(buf.to_hdr().tonemap(Tonemap::Clamp, 1.0), buf);
+    /// ```
+    pub fn to_hdr(&self) -> ArrayVoxelBuffer<RgbaF32> {
+        let (size_x, size_y, size_z) = self.dimensions();
+        let mut buf = ArrayVoxelBuffer::new(size_x, size_y, size_z);
+        for ((_, hdr), (_, voxel)) in buf.enumerate_mut().zip(self.enumerate()) {
+            *hdr = RgbaF32::from(*voxel);
+        }
+        buf
+    }
+
+    /// Copy the visible voxels of `other` into `self`, with the origin of
+    /// `other` at (`x`, `y`, `z`).
+    ///
+    /// Voxels of `other` with a transparency channel of 0 are skipped, and
+    /// voxels falling outside of `self` are clipped.
+    pub fn stamp(&mut self, other: &ArrayVoxelBuffer<Rgba>, x: i32, y: i32, z: i32) {
+        let (size_x, size_y, size_z) = self.dimensions();
+        for ((ox, oy, oz), voxel) in other.enumerate_occupied() {
+            let (tx, ty, tz) = (x + ox as i32, y + oy as i32, z + oz as i32);
+            if tx < 0 || ty < 0 || tz < 0 {
+                continue;
+            }
+            let (tx, ty, tz) = (tx as u32, ty as u32, tz as u32);
+            if tx < size_x && ty < size_y && tz < size_z {
+                *self.voxel_mut(tx, ty, tz) = *voxel;
+            }
+        }
+    }
+
+    /// Repeat the visible voxels of `pattern` across the region from `min`
+    /// up to but excluding `max`, like a 3D texture, with a copy of
+    /// `pattern` starting at `min`.
+    ///
+    /// Voxels of `pattern` with a transparency channel of 0 are skipped, like
+    /// `stamp`, and the region is clipped to the buffer. Does nothing if
+    /// `pattern` is empty.
+    ///
+    /// # Examples
+    ///
+    /// Cover a wall with a brick pattern.
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let brick = Rgba([170, 70, 50, 255]);
+    /// let mortar = Rgba([200, 200, 190, 255]);
+    /// let mut pattern = ArrayVoxelBuffer::filled(4, 1, 2, brick);
+    /// pattern.fill_region((0, 0, 1), (4, 1, 2), mortar);
+    /// *pattern.voxel_mut(3, 0, 0) = mortar;
+    /// let mut wall = ArrayVoxelBuffer::new(32, 4, 16);
+    /// wall.stamp_tiled(&pattern, (0, 1, 0), (32, 2, 16));
+    /// assert_eq!(*wall.voxel(5, 1, 2), brick);
+    /// assert_eq!(*wall.voxel(7, 1, 2), mortar);
+    /// assert_eq!(*wall.voxel(5, 1, 3), mortar);
+    /// assert_eq!(*wall.voxel(5, 0, 2), Rgba([0, 0, 0, 0]));
+    /// wall.save("test/volumes/stamp_tiled.vox")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn stamp_tiled(
+        &mut self,
+        pattern: &ArrayVoxelBuffer<Rgba>,
+        min: (u32, u32, u32),
+        max: (u32, u32, u32),
+    ) {
+        let (size_x, size_y, size_z) = self.dimensions();
+        let (pattern_x, pattern_y, pattern_z) = pattern.dimensions();
+        if pattern_x == 0 || pattern_y == 0 || pattern_z == 0 {
+            return;
+        }
+        let max = (max.0.min(size_x), max.1.min(size_y), max.2.min(size_z));
+        for z in min.2..max.2 {
+            for y in min.1..max.1 {
+                for x in min.0..max.0 {
+                    let voxel = *pattern.voxel(
+                        (x - min.0) % pattern_x,
+                        (y - min.1) % pattern_y,
+                        (z - min.2) % pattern_z,
+                    );
+                    if voxel.0[3] != 0 {
+                        *self.voxel_mut(x, y, z) = voxel;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Snap `part` onto `self` by it's anchors, stamping it so the anchor
+    /// `part_anchor` of `part` lands next to the anchor `anchor` of `self`,
+    /// at `Anchor::next`.
+    ///
+    /// Parts are only moved, not rotated, so give joined anchors opposite
+    /// directions. The anchor `anchor` is used up and removed, and the other
+    /// anchors of `part` are added to `self` where they landed, so more parts
+    /// can be attached to them. Returns the position the origin of `part`
+    /// was stamped at, or `None` if either anchor doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// Join rooms with a corridor, door to door.
+    /// ```
+    /// # use voxgen::voxel_buffer::{Anchor, ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut room = ArrayVoxelBuffer::filled(8, 8, 4, Rgba([150, 140, 130, 255]));
+    /// room.set_anchor("door", Anchor::new((7, 4, 0), [1.0, 0.0, 0.0]));
+    /// let mut corridor = ArrayVoxelBuffer::filled(6, 2, 3, Rgba([90, 90, 100, 255]));
+    /// corridor.set_anchor("west", Anchor::new((0, 0, 0), [-1.0, 0.0, 0.0]));
+    /// corridor.set_anchor("east", Anchor::new((5, 0, 0), [1.0, 0.0, 0.0]));
+    ///
+    /// let mut level = ArrayVoxelBuffer::new(32, 16, 4);
+    /// level.stamp(&room, 0, 0, 0);
+    /// level.set_anchor("door", room.anchor("door").unwrap());
+    /// assert_eq!(level.attach("door", &corridor, "west"), Some((8, 4, 0)));
+    /// assert_eq!(level.anchor("east"), Some(Anchor::new((13, 4, 0), [1.0, 0.0, 0.0])));
+    /// room.set_anchor("door", Anchor::new((0, 4, 0), [-1.0, 0.0, 0.0]));
+    /// assert_eq!(level.attach("east", &room, "door"), Some((14, 0, 0)));
+    /// assert_eq!(*level.voxel(20, 3, 2), Rgba([150, 140, 130, 255]));
+    /// level.save("test/volumes/attach.vox")?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn attach(
+        &mut self,
+        anchor: &str,
+        part: &ArrayVoxelBuffer<Rgba>,
+        part_anchor: &str,
+    ) -> Option<(i32, i32, i32)> {
+        let (x, y, z) = self.anchor(anchor)?.next();
+        let (px, py, pz) = part.anchor(part_anchor)?.position();
+        let origin = (x - px, y - py, z - pz);
+        self.stamp(part, origin.0, origin.1, origin.2);
+        self.remove_anchor(anchor);
+        for (name, other) in part.anchors().filter(|&(name, _)| name != part_anchor) {
+            self.set_anchor(name, other.translated(origin));
+        }
+        Some(origin)
+    }
+
+    /// Blend the voxels of `source` into `self` with `mode`, with the origin
+    /// of `source` at (`x`, `y`, `z`).
+    ///
+    /// Voxels of `source` with a transparency channel of 0 are skipped, and
+    /// voxels falling outside of `self` are clipped, like `stamp`.
+    pub fn composite<S>(&mut self, source: &S, x: i32, y: i32, z: i32, mode: BlendMode)
+    where
+        S: VoxelSource<Voxel = Rgba>,
+    {
+        let (size_x, size_y, size_z) = self.dimensions();
+        let (source_x, source_y, source_z) = source.source_dimensions();
+        // Only visit the part of the source landing inside `self`.
+        let range = |offset: i32, size: u32, source_size: u32| {
+            let min = (-(offset as i64)).clamp(0, source_size as i64) as u32;
+            let max = (size as i64 - offset as i64).clamp(0, source_size as i64) as u32;
+            min..max
+        };
+        for sz in range(z, size_z, source_z) {
+            for sy in range(y, size_y, source_y) {
+                for sx in range(x, size_x, source_x) {
+                    let voxel = source.sample(sx, sy, sz);
+                    if voxel.0[3] == 0 {
+                        continue;
+                    }
+                    let (tx, ty, tz) = (
+                        (x as i64 + sx as i64) as u32,
+                        (y as i64 + sy as i64) as u32,
+                        (z as i64 + sz as i64) as u32,
+                    );
+                    let dst = self.voxel_mut(tx, ty, tz);
+                    *dst = mode.blend(*dst, voxel);
+                }
+            }
+        }
+    }
+
+    /// Get the z slice `z` as an image, with y increasing up the image, like
+    /// the top view of MagicaVoxel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `z` is outside the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::terrain::{Colorizer, DiamondSquare};
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut buf = DiamondSquare::new().seed(3).render(32, 32, 16);
+    /// Colorizer::new().colorize(&mut buf);
+    /// let slice = buf.to_image(4);
+    /// assert_eq!(slice.dimensions(), (32, 32));
+    /// let mut copy = ArrayVoxelBuffer::new(32, 32, 1);
+    /// copy.from_image(&slice, 0);
+    /// assert_eq!(copy.voxel(5, 7, 0), buf.voxel(5, 7, 4));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn to_image(&self, z: u32) -> RgbaImage {
+        assert!(z < self.size_z, "slice {} is outside the buffer", z);
+        RgbaImage::from_fn(self.size_x, self.size_y, |x, row| {
+            image::Rgba(self.voxel(x, self.size_y - 1 - row, z).0)
+        })
+    }
+
+    /// Set the z slice `z` to the pixels of `image`, with y increasing up
+    /// the image, like `to_image`.
+    ///
+    /// Pixels falling outside of `self` are clipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `z` is outside the buffer.
+    #[cfg(feature = "std")]
+    pub fn from_image(&mut self, image: &RgbaImage, z: u32) {
+        assert!(z < self.size_z, "slice {} is outside the buffer", z);
+        let (width, height) = image.dimensions();
+        for y in 0..self.size_y.min(height) {
+            let row = height - 1 - y;
+            for x in 0..self.size_x.min(width) {
+                *self.voxel_mut(x, y, z) = Rgba(image.get_pixel(x, row).0);
+            }
+        }
+    }
+
+    /// Project the buffer from above into a minimap image, with y
+    /// increasing up the image.
+    ///
+    /// Every pixel is the color of the topmost visible voxel of it's
+    /// column, darkened the lower it is so that heights read at a glance.
+    /// Empty columns are transparent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::terrain::{Colorizer, DiamondSquare};
+    /// let mut buf = DiamondSquare::new().seed(3).render(64, 48, 24);
+    /// Colorizer::new().colorize(&mut buf);
+    /// let minimap = buf.project_top_down();
+    /// assert_eq!(minimap.dimensions(), (64, 48));
+    /// assert!(minimap.pixels().all(|pixel| pixel.0[3] == 255));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn project_top_down(&self) -> RgbaImage {
+        let (size_x, size_y, size_z) = self.dimensions();
+        RgbaImage::from_fn(size_x, size_y, |x, row| {
+            let y = size_y - 1 - row;
+            let top = (0..size_z)
+                .rev()
+                .map(|z| (z, self.voxel(x, y, z)))
+                .find(|(_, voxel)| voxel.0[3] != 0);
+            match top {
+                None => image::Rgba([0; 4]),
+                Some((z, voxel)) => {
+                    let factor = 0.5 + 0.5 * (z + 1) as f32 / size_z as f32;
+                    let mut pixel = voxel.0;
+                    for c in &mut pixel[..3] {
+                        *c = (*c as f32 * factor).round() as u8;
+                    }
+                    image::Rgba(pixel)
+                }
+            }
+        })
+    }
+
+    /// Iterate over the visible voxels of `self`, the voxels with a
+    /// transparency channel above 0, with their positions, x first, then y,
+    /// then z.
+    ///
+    /// Every voxel is still checked, but only by it's transparency byte, so
+    /// passes over sparse scenes spend their time on the visible voxels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut buf = ArrayVoxelBuffer::new(64, 64, 64);
+    /// *buf.voxel_mut(1, 2, 3) = Rgba([10, 20, 30, 255]);
+    /// let occupied: Vec<_> = buf.enumerate_occupied().collect();
+    /// assert_eq!(occupied, vec![((1, 2, 3), &Rgba([10, 20, 30, 255]))]);
+    /// ```
+    pub fn enumerate_occupied(&self) -> impl Iterator<Item = ((u32, u32, u32), &Rgba)> + '_ {
+        (0..self.size_z).flat_map(move |z| self.occupied_in_slice(z))
+    }
+
+    /// Iterate over the visible voxels of the z slice `z`, like
+    /// `enumerate_occupied`.
+    fn occupied_in_slice(&self, z: u32) -> impl Iterator<Item = ((u32, u32, u32), &Rgba)> + '_ {
+        let size_x = self.size_x as usize;
+        let slice_len = size_x * self.size_y as usize * Rgba::SIZE as usize;
+        let start = z as usize * slice_len;
+        self.data[start..start + slice_len]
+            .chunks_exact(Rgba::SIZE as usize)
+            .enumerate()
+            .filter(|(_, voxel)| voxel[3] != 0)
+            .map(move |(i, voxel)| {
+                let (x, y) = ((i % size_x) as u32, (i / size_x) as u32);
+                ((x, y, z), Rgba::from_slice(voxel))
+            })
+    }
+
     /// Save the contents of `self` as a MagicaVoxel .vox file to `path`.
     ///
     /// MagicaVoxel does not support rendering the transparency channel of RGBA
     /// values. Set the transparency channel to 0 to remove it from the
     /// resulting MagicaVoxel .vox entirely.
+    ///
+    /// Returns an `InvalidInput` error if any dimension is over 256, the
+    /// largest MagicaVoxel model, or the visible voxels have more than 255
+    /// colors, the largest MagicaVoxel palette. Check for both up front with
+    /// `validate_for_vox`.
+    #[cfg(feature = "std")]
     pub fn save<P>(&self, path: P) -> std::io::Result<()>
     where
         P: AsRef<Path>,
     {
-        // Calculate vox data
-        let mut color_indices = HashMap::new();
-        let mut index = 1;
-        let mut xyzis = Vec::new();
-        let (size_x, size_y, size_z) = self.dimensions();
-        for z in 0..size_z {
-            for y in 0..size_y {
-                for x in 0..size_x {
-                    let mut xyzi = [0; 4];
-                    xyzi[0] = x as u8;
-                    xyzi[1] = y as u8;
-                    xyzi[2] = z as u8;
-                    let rgba = self.voxel(x, y, z);
-                    match color_indices.get(rgba) {
-                        None => {
-                            color_indices.insert(rgba, index);
-                            xyzi[3] = index;
-                            index += 1;
-                        }
-                        Some(i) => {
-                            xyzi[3] = *i as u8;
-                        }
-                    }
-                    if rgba.0[3] > 0 {
-                        xyzis.push(xyzi);
+        self.save_with_progress(path, |_| ())
+    }
+
+    /// Check that `self` can be saved as a MagicaVoxel .vox file, returning
+    /// every limit it breaks with the offending values.
+    ///
+    /// Models are at most 256 voxels on a side, and palettes hold at most
+    /// 255 colors. Only visible voxels count towards the voxels and colors.
+    ///
+    /// # Examples
+    ///
+    /// Fail fast on a buffer with too many colors.
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxViolation, VoxelBuffer};
+    /// let mut buf = ArrayVoxelBuffer::new(300, 2, 1);
+    /// for x in 0..300 {
+    ///     *buf.voxel_mut(x, 0, 0) = Rgba([x as u8, (x / 256) as u8, 0, 255]);
+    /// }
+    /// let error = buf.validate_for_vox().unwrap_err();
+    /// assert_eq!(
+    ///     error.violations(),
+    ///     [
+    ///         VoxViolation::Dimensions { dimensions: (300, 2, 1), max: 256 },
+    ///         VoxViolation::Colors { count: 300, max: 255 },
+    ///     ]
+    /// );
+    /// assert!(buf.save("test/volumes/validate_for_vox.vox").is_err());
+    /// ```
+    pub fn validate_for_vox(&self) -> Result<(), VoxError> {
+        let mut violations = Vec::new();
+        let dimensions = self.dimensions();
+        let (size_x, size_y, size_z) = dimensions;
+        if size_x > VOX_MAX_SIDE || size_y > VOX_MAX_SIDE || size_z > VOX_MAX_SIDE {
+            violations.push(VoxViolation::Dimensions {
+                dimensions,
+                max: VOX_MAX_SIDE,
+            });
+        }
+        let mut count = 0;
+        let mut colors = ColorMap::<()>::default();
+        for (_, &rgba) in self.enumerate_occupied() {
+            count += 1;
+            colors.insert(rgba, ());
+        }
+        let max = (VOX_MAX_SIDE as usize).pow(3);
+        if count > max {
+            violations.push(VoxViolation::Voxels { count, max });
+        }
+        if colors.len() > VOX_MAX_COLORS {
+            violations.push(VoxViolation::Colors {
+                count: colors.len(),
+                max: VOX_MAX_COLORS,
+            });
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(VoxError { violations })
+        }
+    }
+
+    /// Save the contents of `self` like `save`, reporting every z slice
+    /// encoded to `progress` as `Stage::Export`.
+    ///
+    /// Slices are encoded in parallel and reported in order as they're
+    /// merged, from the calling thread.
+    #[cfg(feature = "std")]
+    pub fn save_with_progress<P, F>(&self, path: P, progress: F) -> std::io::Result<()>
+    where
+        P: AsRef<Path>,
+        F: Fn(Progress),
+    {
+        write_vox(
+            path,
+            self.dimensions(),
+            |z| self.occupied_in_slice(z).map(|(p, &rgba)| (p, rgba)),
+            None,
+            progress,
+        )
+    }
+
+    /// Save the contents of `self` like `save`, with the colors and indices
+    /// of `palette` as the MagicaVoxel palette.
+    ///
+    /// Every visible voxel takes the index of the nearest color of
+    /// `palette`, so files saved with the same palette share indices and
+    /// any number of colors can be saved.
+    ///
+    /// Returns an `InvalidInput` error if any dimension is over 256, or
+    /// `palette` is empty and `self` has visible voxels.
+    #[cfg(feature = "std")]
+    pub fn save_with_palette<P>(&self, path: P, palette: &Palette) -> std::io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        write_vox(
+            path,
+            self.dimensions(),
+            |z| self.occupied_in_slice(z).map(|(p, &rgba)| (p, rgba)),
+            Some(palette),
+            |_| (),
+        )
+    }
+
+    /// Snap the color of every visible voxel to the nearest color of the
+    /// default MagicaVoxel palette, keeping it's transparency.
+    ///
+    /// Save the result with `save_with_palette` and `Palette::magicavoxel`
+    /// so the file uses the default palette indices, and matches hand-made
+    /// assets edited with the default palette.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::color::{Palette, MAGICAVOXEL_PALETTE};
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut buf = ArrayVoxelBuffer::new(2, 1, 1);
+    /// *buf.voxel_mut(0, 0, 0) = Rgba([250, 5, 5, 255]);
+    /// *buf.voxel_mut(1, 0, 0) = Rgba([240, 240, 250, 255]);
+    /// buf.map_to_default_palette();
+    /// assert_eq!(*buf.voxel(0, 0, 0), Rgba([255, 0, 0, 255]));
+    /// assert!(MAGICAVOXEL_PALETTE.contains(buf.voxel(1, 0, 0)));
+    /// buf.save_with_palette("test/volumes/default_palette.vox", &Palette::magicavoxel())?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn map_to_default_palette(&mut self) {
+        Palette::magicavoxel().quantize(self);
+    }
+
+    /// Load the first model of the MagicaVoxel .vox file at `path`.
+    ///
+    /// Files without a palette chunk use the default MagicaVoxel palette.
+    /// Malformed files, models over 256 voxels on a side and models over the
+    /// memory budget, see `set_memory_budget`, return an `InvalidData` error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+    /// let mut buf = ArrayVoxelBuffer::new(2, 3, 4);
+    /// *buf.voxel_mut(1, 2, 3) = Rgba([10, 20, 30, 255]);
+    /// buf.save("test/volumes/load.vox")?;
+    /// let loaded = ArrayVoxelBuffer::load("test/volumes/load.vox")?;
+    /// assert_eq!(loaded.dimensions(), (2, 3, 4));
+    /// assert_eq!(*loaded.voxel(1, 2, 3), Rgba([10, 20, 30, 255]));
+    /// assert_eq!(*loaded.voxel(0, 0, 0), Rgba([0, 0, 0, 0]));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn load<P>(path: P) -> std::io::Result<ArrayVoxelBuffer<Rgba>>
+    where
+        P: AsRef<Path>,
+    {
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let bytes = read(path)?;
+        let int = |offset: usize| -> std::io::Result<u32> {
+            let slice = bytes
+                .get(offset..offset + 4)
+                .ok_or_else(|| invalid("unexpected end of file"))?;
+            Ok(u32::from_le_bytes(slice.try_into().unwrap()))
+        };
+        if bytes.get(0..4) != Some(b"VOX ") {
+            return Err(invalid("missing VOX header"));
+        }
+        let (mut size, mut xyzis, mut palette) = (None, None, None);
+        // Skip the header and the MAIN chunk header, reading it's children.
+        let mut offset = 20;
+        while offset + 12 <= bytes.len() {
+            let id = &bytes[offset..offset + 4];
+            let content_size = int(offset + 4)? as usize;
+            let content = offset + 12;
+            let end = content
+                .checked_add(content_size)
+                .filter(|&end| end <= bytes.len())
+                .ok_or_else(|| invalid("chunk runs past the end of file"))?;
+            match id {
+                b"SIZE" if size.is_none() => {
+                    size = Some((int(content)?, int(content + 4)?, int(content + 8)?));
+                }
+                b"XYZI" if xyzis.is_none() => {
+                    let count = int(content)? as usize;
+                    let xyzi_end = count
+                        .checked_mul(4)
+                        .and_then(|len| len.checked_add(content + 4))
+                        .filter(|&xyzi_end| xyzi_end <= bytes.len())
+                        .ok_or_else(|| invalid("XYZI runs past the end of file"))?;
+                    xyzis = Some(&bytes[content + 4..xyzi_end]);
+                }
+                b"RGBA" => palette = bytes.get(content..content + 256 * 4),
+                _ => {}
+            }
+            offset = end + int(offset + 8)? as usize;
+        }
+        let (size_x, size_y, size_z) = size.ok_or_else(|| invalid("missing SIZE chunk"))?;
+        if size_x > VOX_MAX_SIDE || size_y > VOX_MAX_SIDE || size_z > VOX_MAX_SIDE {
+            return Err(invalid("model size over 256 voxels on a side"));
+        }
+        let xyzis = xyzis.ok_or_else(|| invalid("missing XYZI chunk"))?;
+        let mut buf = ArrayVoxelBuffer::try_new(size_x, size_y, size_z)
+            .map_err(|error| invalid(&error.to_string()))?;
+        for xyzi in xyzis.chunks_exact(4) {
+            let (x, y, z) = (xyzi[0] as u32, xyzi[1] as u32, xyzi[2] as u32);
+            if x >= size_x || y >= size_y || z >= size_z {
+                return Err(invalid("voxel outside the model size"));
+            }
+            // Color indices start at 1, for the first palette entry.
+            let i = (xyzi[3] as usize).max(1) - 1;
+            *buf.voxel_mut(x, y, z) = match palette {
+                Some(palette) => Rgba(palette[i * 4..i * 4 + 4].try_into().unwrap()),
+                None => MAGICAVOXEL_PALETTE[i],
+            };
+        }
+        Ok(buf)
+    }
+}
+
+/// Save the visible voxels of a buffer of `dimensions` as a MagicaVoxel .vox
+/// file to `path`, where `occupied_in_slice` iterates over the visible
+/// voxels of a z slice, x first, then y, reporting every slice encoded to
+/// `progress`.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, name = "save"))]
+#[cfg(feature = "std")]
+fn write_vox<P, F, I, R>(
+    path: P,
+    dimensions: (u32, u32, u32),
+    occupied_in_slice: F,
+    palette: Option<&Palette>,
+    progress: R,
+) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+    F: Fn(u32) -> I + Sync,
+    I: Iterator<Item = ((u32, u32, u32), Rgba)>,
+    R: Fn(Progress),
+{
+    let (size_x, size_y, size_z) = dimensions;
+    // Positions are saved as bytes, which also keeps voxel counts and chunk
+    // sizes within u32s.
+    if size_x > VOX_MAX_SIDE || size_y > VOX_MAX_SIDE || size_z > VOX_MAX_SIDE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "MagicaVoxel models are at most 256 voxels on a side",
+        ));
+    }
+    // Calculate vox data, one z slice per task. Every slice numbers the
+    // colors it finds in the order it finds them, and merging the
+    // slices in order numbers them the same as a single pass would.
+    // Only visible voxels are saved, so transparent colors don't take up
+    // palette entries.
+    //
+    // The colors of a slice, and it's visible voxels with the index of
+    // their color among them.
+    type Slice = (Vec<Rgba>, Vec<([u8; 3], usize)>);
+    let slices: Vec<Slice> = (0..size_z)
+        .into_par_iter()
+        .map(|z| {
+            let mut colors = Vec::new();
+            let mut local_indices = ColorMap::<usize>::default();
+            let mut xyzis = Vec::new();
+            // Neighboring voxels are mostly the same color, so the last
+            // color looked up is checked before hashing.
+            let mut last: Option<(Rgba, usize)> = None;
+            for ((x, y, z), rgba) in occupied_in_slice(z) {
+                let local = match last {
+                    Some((color, local)) if color == rgba => local,
+                    _ => {
+                        let local = *local_indices.entry(rgba).or_insert_with(|| {
+                            colors.push(rgba);
+                            colors.len() - 1
+                        });
+                        last = Some((rgba, local));
+                        local
                     }
+                };
+                xyzis.push(([x as u8, y as u8, z as u8], local));
+            }
+            (colors, xyzis)
+        })
+        .collect();
+    // Colors are numbered in the order they're found, unless the palette
+    // numbers them.
+    let mut color_indices = ColorMap::<u8>::default();
+    let mut xyzis = Vec::new();
+    for (z, (colors, slice_xyzis)) in slices.into_iter().enumerate() {
+        let mut global = Vec::with_capacity(colors.len());
+        for rgba in colors {
+            if let Some(&index) = color_indices.get(&rgba) {
+                global.push(index);
+                continue;
+            }
+            let index = match palette {
+                Some(palette) => {
+                    palette.nearest(rgba).ok_or_else(|| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty palette")
+                    })? + 1
+                }
+                None if color_indices.len() < VOX_MAX_COLORS => color_indices.len() + 1,
+                None => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "MagicaVoxel palettes hold at most 255 colors",
+                    ))
                 }
+            };
+            color_indices.insert(rgba, index as u8);
+            global.push(index as u8);
+        }
+        xyzis.extend(
+            slice_xyzis
+                .into_iter()
+                .map(|([x, y, z], local)| [x, y, z, global[local]]),
+        );
+        progress(Progress {
+            stage: Stage::Export,
+            done: z + 1,
+            total: Some(size_z as usize),
+        });
+    }
+    // Vox spec: https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox.txt
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"VOX ")?;
+    file.write_all(&u32::to_le_bytes(150))?;
+
+    const INT_SIZE: u32 = 4;
+    const ZERO: [u8; 4] = [0; 4];
+    let size_chunk_size = INT_SIZE * 3;
+    let voxel_count = xyzis.len() as u32;
+    let xyzi_chunk_size = INT_SIZE + (voxel_count * INT_SIZE);
+    const PALETTE_COUNT: u32 = 256;
+    let rgba_chunk_size = PALETTE_COUNT * INT_SIZE;
+    let chunk_header_size = INT_SIZE * 3;
+    let chunk_count = 3;
+    let main_child_chunks_size =
+        (chunk_header_size * chunk_count) + size_chunk_size + xyzi_chunk_size + rgba_chunk_size;
+    file.write_all(b"MAIN")?;
+    file.write_all(&ZERO)?; // MAIN has no content
+    file.write_all(&u32::to_le_bytes(main_child_chunks_size))?;
+
+    file.write_all(b"SIZE")?;
+    file.write_all(&u32::to_le_bytes(size_chunk_size))?;
+    file.write_all(&ZERO)?; // SIZE has no children
+    file.write_all(&u32::to_le_bytes(size_x))?;
+    file.write_all(&u32::to_le_bytes(size_y))?;
+    file.write_all(&u32::to_le_bytes(size_z))?;
+
+    file.write_all(b"XYZI")?;
+    file.write_all(&u32::to_le_bytes(xyzi_chunk_size))?;
+    file.write_all(&ZERO)?; // XYZI has no children
+    file.write_all(&u32::to_le_bytes(voxel_count))?;
+    for xyzi in &xyzis {
+        file.write_all(xyzi)?;
+    }
+
+    file.write_all(b"RGBA")?;
+    file.write_all(&u32::to_le_bytes(rgba_chunk_size))?;
+    file.write_all(&ZERO)?; // RGBA has no children
+    let mut entries = [[0; 4]; PALETTE_COUNT as usize];
+    match palette {
+        Some(palette) => {
+            for (i, rgba) in palette.colors().iter().enumerate() {
+                entries[i] = rgba.0;
             }
         }
-        // Vox spec: https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox.txt
-        let mut bytes = Vec::new();
-        bytes.write(b"VOX ")?;
-        bytes.write(&u32::to_le_bytes(150))?;
-
-        const INT_SIZE: u32 = 4;
-        const ZERO: [u8; 4] = [0; 4];
-        let size_chunk_size = INT_SIZE * 3;
-        // TODO: Handle cases where voxel count exeeds u32 bounds
-        let voxel_count = xyzis.len() as u32;
-        let xyzi_chunk_size = INT_SIZE + (voxel_count * INT_SIZE);
-        const PALETTE_COUNT: u32 = 256;
-        let rgba_chunk_size = PALETTE_COUNT * INT_SIZE;
-        let chunk_header_size = INT_SIZE * 3;
-        let chunk_count = 3;
-        let main_child_chunks_size =
-            (chunk_header_size * chunk_count) + size_chunk_size + xyzi_chunk_size + rgba_chunk_size;
-        bytes.write(b"MAIN")?;
-        bytes.write(&ZERO)?; // MAIN has no content
-        bytes.write(&u32::to_le_bytes(main_child_chunks_size))?;
-
-        bytes.write(b"SIZE")?;
-        bytes.write(&u32::to_le_bytes(size_chunk_size))?;
-        bytes.write(&ZERO)?; // SIZE has no children
-        bytes.write(&u32::to_le_bytes(size_x))?;
-        bytes.write(&u32::to_le_bytes(size_y))?;
-        bytes.write(&u32::to_le_bytes(size_z))?;
-
-        bytes.write(b"XYZI")?;
-        bytes.write(&u32::to_le_bytes(xyzi_chunk_size))?;
-        bytes.write(&ZERO)?; // XYZI has no children
-        bytes.write(&u32::to_le_bytes(voxel_count))?;
-        // TODO: Handle cases where xyzi exceeds u8 bounds
-        for xyzi in &xyzis {
-            bytes.write(xyzi)?;
-        }
-
-        bytes.write(b"RGBA")?;
-        bytes.write(&u32::to_le_bytes(rgba_chunk_size))?;
-        bytes.write(&ZERO)?; // RGBA has no children
-        let mut palette = [[0; 4]; PALETTE_COUNT as usize];
-        for (rgba, i) in color_indices {
-            palette[i as usize - 1] = rgba.0;
-        }
-        bytes.write(&palette.concat())?;
-        write(path, &bytes)?;
-        Ok(())
+        None => {
+            for (rgba, i) in color_indices {
+                entries[i as usize - 1] = rgba.0;
+            }
+        }
+    }
+    for rgba in &entries {
+        file.write_all(rgba)?;
     }
+    // Flush explicitly, since dropping the writer ignores errors.
+    file.flush()
 }
@@ -0,0 +1,345 @@
+use crate::seed::Seed;
+use crate::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// The offsets of the six face neighbors of a cell.
+const DIRECTIONS: [(i32, i32, i32); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// An error returned when a model can't be satisfied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Contradiction {
+    /// The adjacency rules can't be satisfied for the output size at all.
+    Unsatisfiable,
+    /// Backtracking gave up after the given number of backtracks.
+    Backtracks(usize),
+}
+
+impl std::fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Contradiction::Unsatisfiable => write!(f, "adjacency rules can't be satisfied"),
+            Contradiction::Backtracks(max) => write!(f, "contradiction after {} backtracks", max),
+        }
+    }
+}
+
+impl std::error::Error for Contradiction {}
+
+/// A Wave Function Collapse model of which voxels may be next to each other.
+///
+/// Every distinct voxel of the examples, including empty ones, is a tile.
+/// Two tiles may be neighbors in a direction of the output only if they are
+/// neighbors in that direction somewhere in an example, and tiles are
+/// picked as often as they appear in the examples. A tile set is learned by
+/// adding a small example for every allowed combination.
+///
+/// # Examples
+///
+/// Grow a larger version of a striped example.
+/// ```
+/// # use voxgen::voxel_buffer::{ArrayVoxelBuffer, Rgba, VoxelBuffer};
+/// # use voxgen::wfc::Model;
+/// let mut example = ArrayVoxelBuffer::new(4, 4, 4);
+/// for x in 0..4 {
+///     for y in 0..4 {
+///         *example.voxel_mut(x, y, 0) = Rgba([0, 160, 0, 255]);
+///         *example.voxel_mut(x, y, 1) = Rgba([120, 80, 40, 255]);
+///     }
+/// }
+/// let buf = Model::learn(&example).generate(16, 16, 4, 0)?;
+/// assert_eq!(buf.dimensions(), (16, 16, 4));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Model {
+    tiles: Vec<Rgba>,
+    weights: Vec<f64>,
+    /// Whether tile `b` may follow tile `a` in direction `d`, indexed as
+    /// `adjacency[d][a][b]`.
+    adjacency: [Vec<Vec<bool>>; 6],
+    max_backtracks: usize,
+}
+
+impl Model {
+    /// Learn the tiles and adjacency rules of an `example`.
+    pub fn learn(example: &ArrayVoxelBuffer<Rgba>) -> Model {
+        let mut model = Model {
+            tiles: Vec::new(),
+            weights: Vec::new(),
+            adjacency: Default::default(),
+            max_backtracks: 1000,
+        };
+        model.add_example(example);
+        model
+    }
+
+    /// Learn the tiles and adjacency rules of another `example`.
+    pub fn add_example(&mut self, example: &ArrayVoxelBuffer<Rgba>) -> &mut Self {
+        let mut indices: HashMap<Rgba, usize> = self
+            .tiles
+            .iter()
+            .enumerate()
+            .map(|(i, tile)| (*tile, i))
+            .collect();
+        let (size_x, size_y, size_z) = example.dimensions();
+        let mut tile = |voxel: Rgba| -> usize {
+            *indices.entry(voxel).or_insert_with(|| {
+                self.tiles.push(voxel);
+                self.weights.push(0.0);
+                self.tiles.len() - 1
+            })
+        };
+        let mut cells = Vec::new();
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    cells.push(tile(*example.voxel(x, y, z)));
+                }
+            }
+        }
+        let count = self.tiles.len();
+        for rules in self.adjacency.iter_mut() {
+            rules.resize(count, Vec::new());
+            for rule in rules.iter_mut() {
+                rule.resize(count, false);
+            }
+        }
+        let size = (size_x as usize, size_y as usize, size_z as usize);
+        for (i, &a) in cells.iter().enumerate() {
+            self.weights[a] += 1.0;
+            for (d, offset) in DIRECTIONS.iter().enumerate() {
+                if let Some(j) = neighbor(size, i, *offset) {
+                    self.adjacency[d][a][cells[j]] = true;
+                }
+            }
+        }
+        self
+    }
+
+    /// Set the most times generation may undo a choice that led to a
+    /// contradiction before giving up.
+    pub fn max_backtracks(&mut self, max: usize) -> &mut Self {
+        self.max_backtracks = max;
+        self
+    }
+
+    /// Get the distinct voxels of the examples.
+    pub fn tiles(&self) -> &[Rgba] {
+        &self.tiles
+    }
+
+    /// Synthesize a `size_x` by `size_y` by `size_z` buffer following the
+    /// adjacency rules.
+    ///
+    /// The cell with the fewest possible tiles is collapsed to a random tile
+    /// picked with a random number generator seeded with `seed`, and the
+    /// choice is propagated to the neighbors, until every cell has one tile.
+    /// Choices that lead to a cell without any possible tile are undone and
+    /// excluded. The same seed always gives the same buffer.
+    ///
+    /// Returns an error if the rules can't be satisfied within the maximum
+    /// number of backtracks, or if the model has no tiles to fill a
+    /// non-empty buffer with.
+    pub fn generate(
+        &self,
+        size_x: u32,
+        size_y: u32,
+        size_z: u32,
+        seed: u64,
+    ) -> Result<ArrayVoxelBuffer<Rgba>, Contradiction> {
+        let size = (size_x as usize, size_y as usize, size_z as usize);
+        if self.tiles.is_empty() && size.0 * size.1 * size.2 > 0 {
+            return Err(Contradiction::Unsatisfiable);
+        }
+        let mut wave = Wave::new(self, size, Seed::new(seed).rng());
+        if !wave.propagate((0..wave.counts.len()).collect()) {
+            return Err(Contradiction::Unsatisfiable);
+        }
+        // The trail length, cell and tile of every choice made.
+        let mut choices = Vec::new();
+        let mut backtracks = 0;
+        while let Some(cell) = wave.lowest_entropy_cell() {
+            let tile = wave.choose(cell);
+            choices.push((wave.trail.len(), cell, tile));
+            let mut consistent = wave.collapse(cell, tile);
+            while !consistent {
+                let (len, cell, tile) = choices.pop().ok_or(Contradiction::Unsatisfiable)?;
+                if backtracks == self.max_backtracks {
+                    return Err(Contradiction::Backtracks(backtracks));
+                }
+                backtracks += 1;
+                wave.undo(len);
+                consistent = wave.remove(cell, tile) && wave.propagate(vec![cell]);
+            }
+        }
+        let mut buf = ArrayVoxelBuffer::new(size_x, size_y, size_z);
+        for (i, domain) in wave.domains.iter().enumerate() {
+            let tile = domain.iter().position(|&possible| possible).unwrap_or(0);
+            let (x, y, z) = coordinates(size, i);
+            *buf.voxel_mut(x as u32, y as u32, z as u32) = self.tiles[tile];
+        }
+        Ok(buf)
+    }
+}
+
+/// Get the index of the neighbor of cell `i` at `offset`, if it's inside a
+/// grid of `size`.
+fn neighbor(size: (usize, usize, usize), i: usize, offset: (i32, i32, i32)) -> Option<usize> {
+    let (x, y, z) = coordinates(size, i);
+    let x = x
+        .checked_add_signed(offset.0 as isize)
+        .filter(|&x| x < size.0)?;
+    let y = y
+        .checked_add_signed(offset.1 as isize)
+        .filter(|&y| y < size.1)?;
+    let z = z
+        .checked_add_signed(offset.2 as isize)
+        .filter(|&z| z < size.2)?;
+    Some(x + y * size.0 + z * size.0 * size.1)
+}
+
+/// Get the coordinates of cell `i` in a grid of `size`.
+fn coordinates(size: (usize, usize, usize), i: usize) -> (usize, usize, usize) {
+    (i % size.0, i / size.0 % size.1, i / (size.0 * size.1))
+}
+
+/// The tiles still possible in every cell of the output.
+struct Wave<'a> {
+    model: &'a Model,
+    size: (usize, usize, usize),
+    domains: Vec<Vec<bool>>,
+    counts: Vec<usize>,
+    /// Every removed cell and tile, in order, to undo removals when
+    /// backtracking.
+    trail: Vec<(usize, usize)>,
+    /// Undecided cells by tile count, with random tie breakers. Entries with
+    /// an outdated count are skipped.
+    queue: BinaryHeap<Reverse<(usize, u32, usize)>>,
+    rng: ChaCha8Rng,
+}
+
+impl<'a> Wave<'a> {
+    fn new(model: &'a Model, size: (usize, usize, usize), rng: ChaCha8Rng) -> Wave<'a> {
+        let cells = size.0 * size.1 * size.2;
+        let tiles = model.tiles.len();
+        let mut wave = Wave {
+            model,
+            size,
+            domains: vec![vec![true; tiles]; cells],
+            counts: vec![tiles; cells],
+            trail: Vec::new(),
+            queue: BinaryHeap::new(),
+            rng,
+        };
+        for cell in 0..cells {
+            wave.enqueue(cell);
+        }
+        wave
+    }
+
+    fn enqueue(&mut self, cell: usize) {
+        if self.counts[cell] > 1 {
+            let tie = self.rng.gen();
+            self.queue.push(Reverse((self.counts[cell], tie, cell)));
+        }
+    }
+
+    /// Pop the undecided cell with the fewest possible tiles.
+    fn lowest_entropy_cell(&mut self) -> Option<usize> {
+        while let Some(Reverse((count, _, cell))) = self.queue.pop() {
+            if self.counts[cell] == count && count > 1 {
+                return Some(cell);
+            }
+        }
+        None
+    }
+
+    /// Pick one of the possible tiles of `cell`, weighted by frequency.
+    fn choose(&mut self, cell: usize) -> usize {
+        let possible: Vec<usize> = (0..self.model.tiles.len())
+            .filter(|&tile| self.domains[cell][tile])
+            .collect();
+        let total: f64 = possible.iter().map(|&tile| self.model.weights[tile]).sum();
+        let mut choice = self.rng.gen::<f64>() * total;
+        for &tile in &possible {
+            choice -= self.model.weights[tile];
+            if choice < 0.0 {
+                return tile;
+            }
+        }
+        possible[possible.len() - 1]
+    }
+
+    /// Remove every tile of `cell` but `tile` and propagate the result.
+    ///
+    /// Returns false on a contradiction.
+    fn collapse(&mut self, cell: usize, tile: usize) -> bool {
+        for other in 0..self.model.tiles.len() {
+            if other != tile && self.domains[cell][other] && !self.remove(cell, other) {
+                return false;
+            }
+        }
+        self.propagate(vec![cell])
+    }
+
+    /// Remove `tile` from the possible tiles of `cell`.
+    ///
+    /// Returns false if no tile is left.
+    fn remove(&mut self, cell: usize, tile: usize) -> bool {
+        self.domains[cell][tile] = false;
+        self.counts[cell] -= 1;
+        self.trail.push((cell, tile));
+        self.enqueue(cell);
+        self.counts[cell] > 0
+    }
+
+    /// Restore every removal after the first `len` ones of the trail.
+    fn undo(&mut self, len: usize) {
+        while self.trail.len() > len {
+            let (cell, tile) = self.trail.pop().unwrap_or_default();
+            self.domains[cell][tile] = true;
+            self.counts[cell] += 1;
+            self.enqueue(cell);
+        }
+    }
+
+    /// Remove the tiles of neighboring cells that no possible tile of a
+    /// changed cell allows, until nothing changes.
+    ///
+    /// Returns false on a contradiction.
+    fn propagate(&mut self, mut changed: Vec<usize>) -> bool {
+        let tiles = self.model.tiles.len();
+        while let Some(cell) = changed.pop() {
+            for (d, offset) in DIRECTIONS.iter().enumerate() {
+                let next = match neighbor(self.size, cell, *offset) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                let rules = &self.model.adjacency[d];
+                let unsupported: Vec<usize> = (0..tiles)
+                    .filter(|&b| self.domains[next][b])
+                    .filter(|&b| !(0..tiles).any(|a| self.domains[cell][a] && rules[a][b]))
+                    .collect();
+                for &b in &unsupported {
+                    if !self.remove(next, b) {
+                        return false;
+                    }
+                }
+                if !unsupported.is_empty() {
+                    changed.push(next);
+                }
+            }
+        }
+        true
+    }
+}